@@ -0,0 +1,310 @@
+use crate::av_sync::AvSyncReport;
+use crate::config::AudioConfig;
+use crate::emulation::SharedState;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{BufferSize, FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const CHANNELS: usize = 2;
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AudioStatus {
+    pub device: Option<String>,
+    pub sample_rate: u32,
+    pub buffer_frames: u32,
+    pub queued_frames: usize,
+    pub underruns: u64,
+    pub dropped_frames: u64,
+    pub error: Option<String>,
+    pub av_sync: Option<AvSyncReport>,
+}
+
+/// Stereo sample FIFO between the emulation thread and the audio device callback.
+#[derive(Debug, Default)]
+pub struct AudioQueue {
+    samples: VecDeque<f32>,
+    sample_rate: u32,
+    max_frames: usize,
+    underruns: u64,
+    dropped_frames: u64,
+}
+
+impl AudioQueue {
+    pub fn configure(&mut self, sample_rate: u32, buffer_frames: u32) {
+        self.samples.clear();
+        self.sample_rate = sample_rate;
+        self.max_frames = buffer_frames as usize * 2;
+        self.underruns = 0;
+        self.dropped_frames = 0;
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Queues interleaved left/right samples from the APU. Returns the number of stereo
+    /// frames queued.
+    pub fn push_samples(&mut self, samples: &[f32]) -> usize {
+        let frames = samples.chunks_exact(CHANNELS);
+        let count = frames.len();
+        for frame in frames {
+            self.push_frame(frame[0], frame[1]);
+        }
+        count
+    }
+
+    pub fn push_frame(&mut self, left: f32, right: f32) {
+        if self.frames() >= self.max_frames {
+            self.samples.drain(..CHANNELS);
+            self.dropped_frames += 1;
+        }
+        self.samples.push_back(left);
+        self.samples.push_back(right);
+    }
+
+    /// Fills an interleaved device buffer, padding with silence and counting an underrun when
+    /// the emulator has not produced enough samples.
+    pub fn fill(&mut self, output: &mut [f32], channels: usize) {
+        let mut starved = false;
+        for frame in output.chunks_mut(channels.max(1)) {
+            let (left, right) = if self.samples.len() >= CHANNELS {
+                let left = self.samples.pop_front().unwrap_or_default();
+                let right = self.samples.pop_front().unwrap_or_default();
+                (left, right)
+            } else {
+                starved = true;
+                (0.0, 0.0)
+            };
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                *sample = if channel % CHANNELS == 0 { left } else { right };
+            }
+        }
+        if starved {
+            self.underruns += 1;
+        }
+    }
+
+    pub fn frames(&self) -> usize {
+        self.samples.len() / CHANNELS
+    }
+}
+
+pub fn buffer_frames(sample_rate: u32, latency_ms: u32) -> u32 {
+    (u64::from(sample_rate) * u64::from(latency_ms) / 1000).max(1) as u32
+}
+
+pub fn list_output_devices() -> Result<Vec<String>, String> {
+    let host = cpal::default_host();
+    let devices = host
+        .output_devices()
+        .map_err(|err| format!("failed to enumerate audio devices: {err}"))?;
+    Ok(devices.filter_map(|device| device.name().ok()).collect())
+}
+
+pub fn audio_status(shared: &SharedState) -> AudioStatus {
+    let mut status = shared
+        .audio_status
+        .lock()
+        .map(|status| status.clone())
+        .unwrap_or_default();
+    if let Ok(queue) = shared.audio_queue.lock() {
+        status.queued_frames = queue.frames();
+        status.underruns = queue.underruns;
+        status.dropped_frames = queue.dropped_frames;
+    }
+    if let Ok(monitor) = shared.av_sync.lock() {
+        status.av_sync = monitor.latest().cloned();
+    }
+    status
+}
+
+/// Owns the output stream on a dedicated thread (cpal streams are not `Send` on every
+/// platform) and rebuilds it whenever the audio section of the config changes.
+pub fn spawn_audio_thread(shared: Arc<SharedState>) {
+    thread::spawn(move || {
+        let mut active: Option<(AudioConfig, Option<Stream>)> = None;
+        loop {
+            let config = shared
+                .config
+                .lock()
+                .map(|config| config.audio.clone())
+                .unwrap_or_default();
+            if active.as_ref().map(|(current, _)| current) != Some(&config) {
+                // Drop the previous stream before opening the device again.
+                drop(active.take());
+                let stream = match open_stream(&config, &shared) {
+                    Ok((stream, status)) => {
+                        set_status(&shared, status);
+                        Some(stream)
+                    }
+                    Err(error) => {
+                        set_status(
+                            &shared,
+                            AudioStatus {
+                                error: Some(error),
+                                ..AudioStatus::default()
+                            },
+                        );
+                        None
+                    }
+                };
+                active = Some((config, stream));
+            }
+            thread::sleep(CONFIG_POLL_INTERVAL);
+        }
+    });
+}
+
+fn set_status(shared: &SharedState, status: AudioStatus) {
+    if let Ok(mut current) = shared.audio_status.lock() {
+        *current = status;
+    }
+}
+
+fn open_stream(
+    config: &AudioConfig,
+    shared: &Arc<SharedState>,
+) -> Result<(Stream, AudioStatus), String> {
+    let host = cpal::default_host();
+    let device = match &config.output_device {
+        Some(name) => host
+            .output_devices()
+            .map_err(|err| format!("failed to enumerate audio devices: {err}"))?
+            .find(|device| device.name().ok().as_deref() == Some(name.as_str()))
+            .ok_or_else(|| format!("audio device '{name}' not found"))?,
+        None => host
+            .default_output_device()
+            .ok_or_else(|| "no default audio output device".to_string())?,
+    };
+    let device_name = device.name().ok();
+    let supported = device
+        .default_output_config()
+        .map_err(|err| format!("failed to query audio device config: {err}"))?;
+
+    let sample_rate = supported.sample_rate().0;
+    let buffer_frames = buffer_frames(sample_rate, config.latency_ms);
+    let stream_config = StreamConfig {
+        channels: supported.channels(),
+        sample_rate: supported.sample_rate(),
+        buffer_size: BufferSize::Fixed(buffer_frames),
+    };
+    if let Ok(mut queue) = shared.audio_queue.lock() {
+        queue.configure(sample_rate, buffer_frames);
+    }
+
+    let stream = match supported.sample_format() {
+        SampleFormat::F32 => build_stream::<f32>(&device, &stream_config, shared),
+        SampleFormat::I16 => build_stream::<i16>(&device, &stream_config, shared),
+        SampleFormat::U16 => build_stream::<u16>(&device, &stream_config, shared),
+        other => Err(format!("unsupported audio sample format: {other}")),
+    }?;
+    stream
+        .play()
+        .map_err(|err| format!("failed to start audio stream: {err}"))?;
+
+    Ok((
+        stream,
+        AudioStatus {
+            device: device_name,
+            sample_rate,
+            buffer_frames,
+            ..AudioStatus::default()
+        },
+    ))
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    shared: &Arc<SharedState>,
+) -> Result<Stream, String>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = usize::from(config.channels);
+    let callback_shared = Arc::clone(shared);
+    let error_shared = Arc::clone(shared);
+    let mut scratch = Vec::new();
+    device
+        .build_output_stream(
+            config,
+            move |output: &mut [T], _| {
+                scratch.resize(output.len(), 0.0f32);
+                if let Ok(mut queue) = callback_shared.audio_queue.lock() {
+                    queue.fill(&mut scratch, channels);
+                } else {
+                    scratch.fill(0.0);
+                }
+                for (sample, value) in output.iter_mut().zip(&scratch) {
+                    *sample = T::from_sample(*value);
+                }
+            },
+            move |err| {
+                if let Ok(mut status) = error_shared.audio_status.lock() {
+                    status.error = Some(format!("audio stream error: {err}"));
+                }
+            },
+            None,
+        )
+        .map_err(|err| format!("failed to open audio stream: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_latency_to_buffer_frames() {
+        assert_eq!(buffer_frames(48_000, 50), 2_400);
+        assert_eq!(buffer_frames(44_100, 0), 1);
+    }
+
+    #[test]
+    fn queues_whole_stereo_frames_from_the_apu() {
+        let mut queue = AudioQueue::default();
+        queue.configure(48_000, 4_800);
+
+        assert_eq!(queue.push_samples(&[0.5, -0.5, 0.25, -0.25, 1.0]), 2);
+        assert_eq!(queue.frames(), 2);
+        let mut output = [0.0f32; 4];
+        queue.fill(&mut output, 2);
+        assert_eq!(output, [0.5, -0.5, 0.25, -0.25]);
+    }
+
+    #[test]
+    fn fill_counts_underruns_and_pads_with_silence() {
+        let mut queue = AudioQueue::default();
+        queue.configure(48_000, 64);
+        queue.push_frame(0.5, -0.5);
+
+        let mut output = [1.0f32; 4];
+        queue.fill(&mut output, 2);
+        assert_eq!(output, [0.5, -0.5, 0.0, 0.0]);
+        assert_eq!(queue.underruns, 1);
+
+        queue.push_frame(0.25, 0.25);
+        let mut output = [1.0f32; 2];
+        queue.fill(&mut output, 2);
+        assert_eq!(queue.underruns, 1);
+    }
+
+    #[test]
+    fn overflow_drops_oldest_frames() {
+        let mut queue = AudioQueue::default();
+        queue.configure(48_000, 2);
+        for index in 0..6 {
+            queue.push_frame(index as f32, index as f32);
+        }
+        assert_eq!(queue.frames(), 4);
+        assert_eq!(queue.dropped_frames, 2);
+
+        let mut output = [0.0f32; 2];
+        queue.fill(&mut output, 2);
+        assert_eq!(output, [2.0, 2.0]);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::session::{CPU_CLOCK_HZ, CYCLES_PER_FRAME};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+pub const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+/// Largest resampling ratio change (0.5%) that stays inaudible; beyond it the producer and
+/// the device clock disagree by more than a resampler should be hiding.
+pub const CORRECTION_WARN_THRESHOLD: f64 = 0.005;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AvSyncReport {
+    pub video_frames: u64,
+    pub audio_frames: u64,
+    /// Emulated audio time minus emulated video time; nonzero growth means the sample-rate
+    /// math and the frame scheduler disagree about how long a frame is.
+    pub drift_ms: f64,
+    pub queue_ms: f64,
+    /// Playback-rate adjustment needed to bring the queue back to its target within one
+    /// report interval (positive = play faster).
+    pub correction: f64,
+    pub warning: bool,
+}
+
+/// Tracks produced audio against produced video frames for the A/V sync diagnostics mode.
+#[derive(Debug, Default)]
+pub struct AvSyncMonitor {
+    sample_rate: u32,
+    target_queue_frames: usize,
+    video_frames: u64,
+    audio_frames: u64,
+    last_report: Option<Instant>,
+    latest: Option<AvSyncReport>,
+}
+
+impl AvSyncMonitor {
+    pub fn reset(&mut self, sample_rate: u32, target_queue_frames: usize) {
+        *self = Self {
+            sample_rate,
+            target_queue_frames,
+            ..Self::default()
+        };
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn latest(&self) -> Option<&AvSyncReport> {
+        self.latest.as_ref()
+    }
+
+    /// Records one emulated video frame and the audio frames it produced; returns a report
+    /// once per `REPORT_INTERVAL`.
+    pub fn record_frame(
+        &mut self,
+        now: Instant,
+        produced_audio_frames: usize,
+        queued_frames: usize,
+    ) -> Option<AvSyncReport> {
+        self.video_frames += 1;
+        self.audio_frames += produced_audio_frames as u64;
+        if self.sample_rate == 0 {
+            return None;
+        }
+        let last = *self.last_report.get_or_insert(now);
+        if now.duration_since(last) < REPORT_INTERVAL {
+            return None;
+        }
+        self.last_report = Some(now);
+
+        let rate = f64::from(self.sample_rate);
+        let frame_ms = 1000.0 * CYCLES_PER_FRAME as f64 / CPU_CLOCK_HZ;
+        let audio_ms = self.audio_frames as f64 * 1000.0 / rate;
+        let correction = (queued_frames as f64 - self.target_queue_frames as f64)
+            / (rate * REPORT_INTERVAL.as_secs_f64());
+        let report = AvSyncReport {
+            video_frames: self.video_frames,
+            audio_frames: self.audio_frames,
+            drift_ms: audio_ms - self.video_frames as f64 * frame_ms,
+            queue_ms: queued_frames as f64 * 1000.0 / rate,
+            correction,
+            warning: correction.abs() > CORRECTION_WARN_THRESHOLD,
+        };
+        self.latest = Some(report.clone());
+        Some(report)
+    }
+}
+
+pub fn log_report(report: &AvSyncReport) {
+    eprintln!(
+        "av-sync: video_frames={} audio_frames={} drift={:+.3}ms queue={:.1}ms correction={:+.3}%",
+        report.video_frames,
+        report.audio_frames,
+        report.drift_ms,
+        report.queue_ms,
+        report.correction * 100.0
+    );
+    if report.warning {
+        eprintln!(
+            "av-sync warning: resampling correction {:+.3}% exceeds {:.1}%",
+            report.correction * 100.0,
+            CORRECTION_WARN_THRESHOLD * 100.0
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching_production_reports_no_drift() {
+        let mut monitor = AvSyncMonitor::default();
+        monitor.reset(48_000, 2_880);
+        let start = Instant::now();
+        let per_frame = 48_000.0 * CYCLES_PER_FRAME as f64 / CPU_CLOCK_HZ;
+        let mut produced = 0.0f64;
+        let mut report = None;
+        for frame in 0..=60u32 {
+            let before = produced.floor();
+            produced += per_frame;
+            let now = start + REPORT_INTERVAL * frame / 60;
+            report = monitor
+                .record_frame(now, (produced.floor() - before) as usize, 2_880)
+                .or(report);
+        }
+
+        let report = report.expect("a report after one interval");
+        assert_eq!(report.video_frames, 61);
+        assert!(report.drift_ms.abs() < 0.05, "drift {}", report.drift_ms);
+        assert_eq!(report.correction, 0.0);
+        assert!(!report.warning);
+    }
+
+    #[test]
+    fn warns_when_queue_needs_large_correction() {
+        let mut monitor = AvSyncMonitor::default();
+        monitor.reset(48_000, 2_880);
+        let start = Instant::now();
+        assert_eq!(monitor.record_frame(start, 800, 2_880), None);
+
+        let report = monitor
+            .record_frame(start + REPORT_INTERVAL, 800, 2_880 + 960)
+            .expect("interval elapsed");
+        assert!((report.correction - 0.02).abs() < 1e-9);
+        assert!(report.warning);
+        assert_eq!(monitor.latest(), Some(&report));
+    }
+}
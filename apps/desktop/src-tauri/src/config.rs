@@ -0,0 +1,214 @@
+use crate::pause::FocusLossBehavior;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+pub const CONFIG_FILE_NAME: &str = "config.json";
+
+const DEFAULT_GAMEPAD_DEADZONE: f32 = 0.25;
+const MAX_GAMEPAD_DEADZONE: f32 = 0.95;
+const DEFAULT_AUDIO_LATENCY_MS: u32 = 60;
+const MIN_AUDIO_LATENCY_MS: u32 = 10;
+const MAX_AUDIO_LATENCY_MS: u32 = 500;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DesktopConfig {
+    pub input: InputConfig,
+    pub audio: AudioConfig,
+    pub window: WindowConfig,
+    pub metrics: MetricsConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputConfig {
+    pub gamepad_deadzone: f32,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            gamepad_deadzone: DEFAULT_GAMEPAD_DEADZONE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// Output device name as reported by the host; `None` follows the system default.
+    pub output_device: Option<String>,
+    pub latency_ms: u32,
+    /// Logs produced-audio vs video drift and warns on large resampling corrections.
+    pub av_sync_diagnostics: bool,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            output_device: None,
+            latency_ms: DEFAULT_AUDIO_LATENCY_MS,
+            av_sync_diagnostics: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    /// What emulation does while the main window is unfocused or minimized.
+    pub focus_loss: FocusLossBehavior,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Address to serve Prometheus metrics on (e.g. `127.0.0.1:9477`); read at startup.
+    pub listen: Option<String>,
+}
+
+impl DesktopConfig {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("failed to read config '{}': {err}", path.display()))?;
+        let config: Self = serde_json::from_str(&text)
+            .map_err(|err| format!("invalid config '{}': {err}", path.display()))?;
+        Ok(config.normalized())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|err| {
+                format!(
+                    "failed to create config directory '{}': {err}",
+                    parent.display()
+                )
+            })?;
+        }
+        let text = serde_json::to_string_pretty(self)
+            .map_err(|err| format!("failed to serialize config: {err}"))?;
+        fs::write(path, text)
+            .map_err(|err| format!("failed to write config '{}': {err}", path.display()))
+    }
+
+    pub fn normalized(mut self) -> Self {
+        let deadzone = self.input.gamepad_deadzone;
+        self.input.gamepad_deadzone = if deadzone.is_finite() {
+            deadzone.clamp(0.0, MAX_GAMEPAD_DEADZONE)
+        } else {
+            DEFAULT_GAMEPAD_DEADZONE
+        };
+        self.audio.latency_ms = self
+            .audio
+            .latency_ms
+            .clamp(MIN_AUDIO_LATENCY_MS, MAX_AUDIO_LATENCY_MS);
+        if self
+            .audio
+            .output_device
+            .as_deref()
+            .is_some_and(|name| name.trim().is_empty())
+        {
+            self.audio.output_device = None;
+        }
+        if self
+            .metrics
+            .listen
+            .as_deref()
+            .is_some_and(|listen| listen.trim().is_empty())
+        {
+            self.metrics.listen = None;
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn missing_fields_fall_back_to_defaults() {
+        let config: DesktopConfig = serde_json::from_str("{}").expect("empty config should parse");
+        assert_eq!(config, DesktopConfig::default());
+        assert_eq!(config.input.gamepad_deadzone, DEFAULT_GAMEPAD_DEADZONE);
+        assert_eq!(config.window.focus_loss, FocusLossBehavior::Pause);
+    }
+
+    #[test]
+    fn parses_focus_loss_behavior() {
+        let config: DesktopConfig = serde_json::from_str(r#"{"window":{"focus_loss":"throttle"}}"#)
+            .expect("focus loss behavior should parse");
+        assert_eq!(config.window.focus_loss, FocusLossBehavior::Throttle);
+        assert!(
+            serde_json::from_str::<DesktopConfig>(r#"{"window":{"focus_loss":"sleep"}}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn normalizes_out_of_range_deadzone() {
+        let mut config = DesktopConfig::default();
+        config.input.gamepad_deadzone = 4.0;
+        assert_eq!(
+            config.normalized().input.gamepad_deadzone,
+            MAX_GAMEPAD_DEADZONE
+        );
+
+        let mut config = DesktopConfig::default();
+        config.input.gamepad_deadzone = f32::NAN;
+        assert_eq!(
+            config.normalized().input.gamepad_deadzone,
+            DEFAULT_GAMEPAD_DEADZONE
+        );
+    }
+
+    #[test]
+    fn normalizes_audio_latency_and_blank_device() {
+        let mut config = DesktopConfig::default();
+        config.audio.latency_ms = 0;
+        config.audio.output_device = Some("  ".to_string());
+        let config = config.normalized();
+        assert_eq!(config.audio.latency_ms, MIN_AUDIO_LATENCY_MS);
+        assert_eq!(config.audio.output_device, None);
+
+        let mut config = DesktopConfig::default();
+        config.audio.latency_ms = 10_000;
+        assert_eq!(config.normalized().audio.latency_ms, MAX_AUDIO_LATENCY_MS);
+    }
+
+    #[test]
+    fn blank_metrics_listen_address_disables_metrics() {
+        let config: DesktopConfig =
+            serde_json::from_str(r#"{"metrics":{"listen":" "}}"#).expect("config should parse");
+        assert_eq!(config.normalized().metrics.listen, None);
+    }
+
+    #[test]
+    fn round_trips_through_disk() {
+        let dir = std::env::temp_dir().join(format!(
+            "vibegb-desktop-config-{}",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("system clock should be after unix epoch")
+                .as_nanos()
+        ));
+        let path = dir.join(CONFIG_FILE_NAME);
+
+        let mut config = DesktopConfig::default();
+        config.input.gamepad_deadzone = 0.4;
+        config.audio.output_device = Some("Speakers".to_string());
+        config.audio.latency_ms = 120;
+        config.window.focus_loss = FocusLossBehavior::Run;
+        config.save(&path).expect("config should save");
+        assert_eq!(
+            DesktopConfig::load(&path).expect("config should load"),
+            config
+        );
+
+        fs::remove_dir_all(dir).expect("config dir should be removable");
+    }
+}
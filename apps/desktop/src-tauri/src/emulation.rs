@@ -0,0 +1,129 @@
+use crate::audio::{AudioQueue, AudioStatus};
+use crate::av_sync::{self, AvSyncMonitor};
+use crate::config::DesktopConfig;
+use crate::input::GamepadInput;
+use crate::pause::{FocusLossBehavior, PauseControl};
+use crate::session::{Session, CPU_CLOCK_HZ, CYCLES_PER_FRAME};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+pub struct SharedState {
+    pub session: Mutex<Option<Session>>,
+    pub config: Mutex<DesktopConfig>,
+    pub gamepads: Mutex<Vec<String>>,
+    pub audio_queue: Mutex<AudioQueue>,
+    pub audio_status: Mutex<AudioStatus>,
+    pub av_sync: Mutex<AvSyncMonitor>,
+    pub pause: Mutex<PauseControl>,
+}
+
+pub fn spawn_emulation_thread(shared: Arc<SharedState>) {
+    let frame_duration = Duration::from_secs_f64(CYCLES_PER_FRAME as f64 / CPU_CLOCK_HZ);
+    thread::spawn(move || {
+        let deadzone = read_deadzone(&shared);
+        let mut gamepad = GamepadInput::new(deadzone);
+        let mut next_frame = Instant::now();
+        loop {
+            gamepad.set_deadzone(read_deadzone(&shared));
+            let changes = gamepad.poll();
+            if let Ok(mut gamepads) = shared.gamepads.lock() {
+                if gamepads.as_slice() != gamepad.connected() {
+                    *gamepads = gamepad.connected().to_vec();
+                }
+            }
+
+            let focus_loss = read_focus_loss(&shared);
+            let run = shared
+                .pause
+                .lock()
+                .map(|mut pause| pause.should_run_frame(focus_loss))
+                .unwrap_or(true);
+            let sample_rate = shared
+                .audio_queue
+                .lock()
+                .map(|queue| queue.sample_rate())
+                .unwrap_or_default();
+            let mut cycles = 0;
+            let mut samples = Vec::new();
+            let mut rumble = false;
+            if let Ok(mut guard) = shared.session.lock() {
+                if let Some(session) = guard.as_mut() {
+                    for change in changes {
+                        session.set_button(change.button, change.pressed);
+                    }
+                    if run {
+                        cycles = session.run_frame(Instant::now());
+                        if sample_rate > 0 {
+                            samples = session.take_audio_samples(sample_rate);
+                        }
+                    }
+                    // A paused game leaves the motor where it was but the pad goes quiet.
+                    rumble = run && session.rumble();
+                }
+            }
+            gamepad.set_rumble(rumble);
+            if cycles > 0 {
+                let audio = shared.audio_queue.lock().ok().map(|mut queue| {
+                    let produced = queue.push_samples(&samples);
+                    (produced, queue.frames())
+                });
+                if let Some((produced, queued)) = audio {
+                    if read_av_sync_diagnostics(&shared) {
+                        record_av_sync(&shared, produced, queued);
+                    }
+                }
+            }
+
+            next_frame += frame_duration;
+            let now = Instant::now();
+            if next_frame > now {
+                thread::sleep(next_frame - now);
+            } else {
+                next_frame = now;
+            }
+        }
+    });
+}
+
+fn record_av_sync(shared: &SharedState, produced: usize, queued: usize) {
+    let (sample_rate, buffer_frames) = shared
+        .audio_status
+        .lock()
+        .map(|status| (status.sample_rate, status.buffer_frames))
+        .unwrap_or_default();
+    let Ok(mut monitor) = shared.av_sync.lock() else {
+        return;
+    };
+    if monitor.sample_rate() != sample_rate {
+        monitor.reset(sample_rate, buffer_frames as usize);
+    }
+    if let Some(report) = monitor.record_frame(Instant::now(), produced, queued) {
+        av_sync::log_report(&report);
+    }
+}
+
+fn read_av_sync_diagnostics(shared: &SharedState) -> bool {
+    shared
+        .config
+        .lock()
+        .map(|config| config.audio.av_sync_diagnostics)
+        .unwrap_or_default()
+}
+
+fn read_deadzone(shared: &SharedState) -> f32 {
+    shared
+        .config
+        .lock()
+        .map(|config| config.input.gamepad_deadzone)
+        .unwrap_or_else(|_| DesktopConfig::default().input.gamepad_deadzone)
+}
+
+pub fn read_focus_loss(shared: &SharedState) -> FocusLossBehavior {
+    shared
+        .config
+        .lock()
+        .map(|config| config.window.focus_loss)
+        .unwrap_or_default()
+}
@@ -0,0 +1,283 @@
+use gilrs::ff::{BaseEffect, BaseEffectType, Effect, EffectBuilder};
+use gilrs::{Axis, Button as PadButton, EventType, Gilrs};
+use vibegb_core::Button;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ButtonChange {
+    pub button: Button,
+    pub pressed: bool,
+}
+
+pub struct GamepadInput {
+    gilrs: Option<Gilrs>,
+    deadzone: f32,
+    stick_x: f32,
+    stick_y: f32,
+    digital: u8,
+    pressed: u8,
+    connected: Vec<String>,
+    // Built on first use for the force-feedback pads connected then; dropped on hotplug.
+    rumble: Option<Effect>,
+    rumbling: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RumbleCommand {
+    Play,
+    Stop,
+}
+
+impl GamepadInput {
+    pub fn new(deadzone: f32) -> Self {
+        let gilrs = Gilrs::new().ok();
+        let mut input = Self {
+            gilrs,
+            deadzone,
+            stick_x: 0.0,
+            stick_y: 0.0,
+            digital: 0,
+            pressed: 0,
+            connected: Vec::new(),
+            rumble: None,
+            rumbling: false,
+        };
+        input.refresh_connected();
+        input
+    }
+
+    pub fn connected(&self) -> &[String] {
+        &self.connected
+    }
+
+    pub fn set_deadzone(&mut self, deadzone: f32) {
+        self.deadzone = deadzone;
+    }
+
+    /// Drains pending gamepad events and returns the resulting Game Boy button transitions.
+    pub fn poll(&mut self) -> Vec<ButtonChange> {
+        let mut hotplug = false;
+        while let Some(event) = self.gilrs.as_mut().and_then(Gilrs::next_event) {
+            match event.event {
+                EventType::ButtonPressed(button, _) => {
+                    if let Some(button) = map_button(button) {
+                        self.digital |= button_bit(button);
+                    }
+                }
+                EventType::ButtonReleased(button, _) => {
+                    if let Some(button) = map_button(button) {
+                        self.digital &= !button_bit(button);
+                    }
+                }
+                EventType::AxisChanged(Axis::LeftStickX, value, _) => self.stick_x = value,
+                EventType::AxisChanged(Axis::LeftStickY, value, _) => self.stick_y = value,
+                EventType::Connected | EventType::Disconnected => hotplug = true,
+                _ => {}
+            }
+        }
+        if hotplug {
+            self.rumble = None;
+            if std::mem::take(&mut self.rumbling) {
+                self.set_rumble(true);
+            }
+            self.refresh_connected();
+            if self.connected.is_empty() {
+                self.digital = 0;
+                self.stick_x = 0.0;
+                self.stick_y = 0.0;
+            }
+        }
+
+        let next = self.digital | stick_mask(self.stick_x, self.stick_y, self.deadzone);
+        let changes = diff_buttons(self.pressed, next);
+        self.pressed = next;
+        changes
+    }
+
+    /// Follows the cartridge's rumble motor on every connected force-feedback gamepad.
+    pub fn set_rumble(&mut self, motor_on: bool) {
+        let Some(command) = rumble_command(self.rumbling, motor_on) else {
+            return;
+        };
+        self.rumbling = motor_on;
+        if command == RumbleCommand::Play && self.rumble.is_none() {
+            self.rumble = self.gilrs.as_mut().and_then(build_rumble);
+        }
+        if let Some(effect) = &self.rumble {
+            // A pad that went away mid-effect is picked up again on the next hotplug.
+            let _ = match command {
+                RumbleCommand::Play => effect.play(),
+                RumbleCommand::Stop => effect.stop(),
+            };
+        }
+    }
+
+    fn refresh_connected(&mut self) {
+        self.connected = self
+            .gilrs
+            .as_ref()
+            .map(|gilrs| {
+                gilrs
+                    .gamepads()
+                    .map(|(_, gamepad)| gamepad.name().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+}
+
+pub fn map_button(button: PadButton) -> Option<Button> {
+    match button {
+        PadButton::South => Some(Button::A),
+        PadButton::East => Some(Button::B),
+        PadButton::Select => Some(Button::Select),
+        PadButton::Start => Some(Button::Start),
+        PadButton::DPadUp => Some(Button::Up),
+        PadButton::DPadDown => Some(Button::Down),
+        PadButton::DPadLeft => Some(Button::Left),
+        PadButton::DPadRight => Some(Button::Right),
+        _ => None,
+    }
+}
+
+fn rumble_command(rumbling: bool, motor_on: bool) -> Option<RumbleCommand> {
+    match (rumbling, motor_on) {
+        (false, true) => Some(RumbleCommand::Play),
+        (true, false) => Some(RumbleCommand::Stop),
+        _ => None,
+    }
+}
+
+// The cartridge motor is either off or fully on, so both pad motors run flat out; the
+// default repeat keeps the effect going until it is stopped.
+fn rumble_effects() -> [BaseEffect; 2] {
+    [
+        BaseEffect {
+            kind: BaseEffectType::Strong {
+                magnitude: u16::MAX,
+            },
+            ..BaseEffect::default()
+        },
+        BaseEffect {
+            kind: BaseEffectType::Weak {
+                magnitude: u16::MAX,
+            },
+            ..BaseEffect::default()
+        },
+    ]
+}
+
+fn build_rumble(gilrs: &mut Gilrs) -> Option<Effect> {
+    let pads: Vec<_> = gilrs
+        .gamepads()
+        .filter(|(_, gamepad)| gamepad.is_ff_supported())
+        .map(|(id, _)| id)
+        .collect();
+    if pads.is_empty() {
+        return None;
+    }
+    let mut builder = EffectBuilder::new();
+    for effect in rumble_effects() {
+        builder.add_effect(effect);
+    }
+    builder.gamepads(&pads).finish(gilrs).ok()
+}
+
+fn stick_mask(x: f32, y: f32, deadzone: f32) -> u8 {
+    let mut mask = 0;
+    if x > deadzone {
+        mask |= button_bit(Button::Right);
+    } else if x < -deadzone {
+        mask |= button_bit(Button::Left);
+    }
+    if y > deadzone {
+        mask |= button_bit(Button::Up);
+    } else if y < -deadzone {
+        mask |= button_bit(Button::Down);
+    }
+    mask
+}
+
+fn button_bit(button: Button) -> u8 {
+    let index = Button::ALL
+        .iter()
+        .position(|candidate| *candidate == button)
+        .expect("button should be listed in Button::ALL");
+    1 << index
+}
+
+fn diff_buttons(previous: u8, next: u8) -> Vec<ButtonChange> {
+    Button::ALL
+        .iter()
+        .filter(|button| (previous ^ next) & button_bit(**button) != 0)
+        .map(|button| ButtonChange {
+            button: *button,
+            pressed: next & button_bit(*button) != 0,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_face_and_dpad_buttons() {
+        assert_eq!(map_button(PadButton::South), Some(Button::A));
+        assert_eq!(map_button(PadButton::East), Some(Button::B));
+        assert_eq!(map_button(PadButton::DPadLeft), Some(Button::Left));
+        assert_eq!(map_button(PadButton::LeftTrigger), None);
+    }
+
+    #[test]
+    fn motor_changes_start_and_stop_full_strength_rumble() {
+        assert_eq!(rumble_command(false, true), Some(RumbleCommand::Play));
+        assert_eq!(rumble_command(true, false), Some(RumbleCommand::Stop));
+        assert_eq!(rumble_command(true, true), None);
+        assert_eq!(rumble_command(false, false), None);
+
+        let kinds = rumble_effects().map(|effect| effect.kind);
+        assert_eq!(
+            kinds,
+            [
+                BaseEffectType::Strong {
+                    magnitude: u16::MAX
+                },
+                BaseEffectType::Weak {
+                    magnitude: u16::MAX
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn stick_respects_deadzone() {
+        assert_eq!(stick_mask(0.2, -0.2, 0.25), 0);
+        assert_eq!(
+            stick_mask(0.6, -0.3, 0.25),
+            button_bit(Button::Right) | button_bit(Button::Down)
+        );
+        assert_eq!(
+            stick_mask(-0.6, 0.9, 0.25),
+            button_bit(Button::Left) | button_bit(Button::Up)
+        );
+    }
+
+    #[test]
+    fn diff_reports_presses_and_releases() {
+        let previous = button_bit(Button::A) | button_bit(Button::Left);
+        let next = button_bit(Button::A) | button_bit(Button::Start);
+        assert_eq!(
+            diff_buttons(previous, next),
+            vec![
+                ButtonChange {
+                    button: Button::Left,
+                    pressed: false,
+                },
+                ButtonChange {
+                    button: Button::Start,
+                    pressed: true,
+                },
+            ]
+        );
+    }
+}
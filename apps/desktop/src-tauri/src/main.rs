@@ -1,7 +1,184 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod audio;
+mod av_sync;
+mod config;
+mod emulation;
+mod input;
+mod metrics;
+mod pause;
+mod session;
+
+use audio::{spawn_audio_thread, AudioStatus};
+use config::{DesktopConfig, CONFIG_FILE_NAME};
+use emulation::{read_focus_loss, spawn_emulation_thread, SharedState};
+use metrics::spawn_metrics_server;
+use pause::PauseStatus;
+use session::{window_title, Session, SessionStatus};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tauri::Manager;
+use vibegb_core::Rom;
+
+struct AppState {
+    shared: Arc<SharedState>,
+    config_path: Mutex<Option<PathBuf>>,
+}
+
+#[tauri::command]
+fn load_rom(
+    path: String,
+    window: tauri::WebviewWindow,
+    state: tauri::State<'_, AppState>,
+) -> Result<SessionStatus, String> {
+    let rom = Rom::from_file(&path).map_err(|err| format!("ROM load failed: {err}"))?;
+    let session = Session::new(rom);
+    let status = session.status();
+    window
+        .set_title(&window_title(session.header()))
+        .map_err(|err| format!("failed to update window title: {err}"))?;
+
+    let mut guard = state
+        .shared
+        .session
+        .lock()
+        .map_err(|_| "emulator session lock poisoned".to_string())?;
+    *guard = Some(session);
+    Ok(status)
+}
+
+#[tauri::command]
+fn emulator_status(state: tauri::State<'_, AppState>) -> Result<Option<SessionStatus>, String> {
+    let guard = state
+        .shared
+        .session
+        .lock()
+        .map_err(|_| "emulator session lock poisoned".to_string())?;
+    Ok(guard.as_ref().map(Session::status))
+}
+
+#[tauri::command]
+fn connected_gamepads(state: tauri::State<'_, AppState>) -> Result<Vec<String>, String> {
+    state
+        .shared
+        .gamepads
+        .lock()
+        .map(|gamepads| gamepads.clone())
+        .map_err(|_| "gamepad list lock poisoned".to_string())
+}
+
+#[tauri::command]
+fn list_audio_devices() -> Result<Vec<String>, String> {
+    audio::list_output_devices()
+}
+
+#[tauri::command]
+fn audio_status(state: tauri::State<'_, AppState>) -> AudioStatus {
+    audio::audio_status(&state.shared)
+}
+
+#[tauri::command]
+fn pause_status(state: tauri::State<'_, AppState>) -> Result<PauseStatus, String> {
+    let focus_loss = read_focus_loss(&state.shared);
+    state
+        .shared
+        .pause
+        .lock()
+        .map(|pause| pause.status(focus_loss))
+        .map_err(|_| "pause state lock poisoned".to_string())
+}
+
+#[tauri::command]
+fn set_paused(paused: bool, state: tauri::State<'_, AppState>) -> Result<PauseStatus, String> {
+    let focus_loss = read_focus_loss(&state.shared);
+    let mut pause = state
+        .shared
+        .pause
+        .lock()
+        .map_err(|_| "pause state lock poisoned".to_string())?;
+    pause.set_user_paused(paused);
+    Ok(pause.status(focus_loss))
+}
+
+#[tauri::command]
+fn get_config(state: tauri::State<'_, AppState>) -> Result<DesktopConfig, String> {
+    state
+        .shared
+        .config
+        .lock()
+        .map(|config| config.clone())
+        .map_err(|_| "config lock poisoned".to_string())
+}
+
+#[tauri::command]
+fn update_config(
+    config: DesktopConfig,
+    state: tauri::State<'_, AppState>,
+) -> Result<DesktopConfig, String> {
+    let config = config.normalized();
+    if let Some(path) = state
+        .config_path
+        .lock()
+        .map_err(|_| "config path lock poisoned".to_string())?
+        .as_ref()
+    {
+        config.save(path)?;
+    }
+    *state
+        .shared
+        .config
+        .lock()
+        .map_err(|_| "config lock poisoned".to_string())? = config.clone();
+    Ok(config)
+}
+
 fn main() {
+    let shared = Arc::new(SharedState::default());
+    spawn_emulation_thread(Arc::clone(&shared));
+    spawn_audio_thread(Arc::clone(&shared));
+
     tauri::Builder::default()
+        .manage(AppState {
+            shared,
+            config_path: Mutex::new(None),
+        })
+        .setup(|app| {
+            let config_path = app.path().app_config_dir()?.join(CONFIG_FILE_NAME);
+            let config = DesktopConfig::load(&config_path).unwrap_or_else(|err| {
+                eprintln!("{err}; using default config");
+                DesktopConfig::default()
+            });
+            let state = app.state::<AppState>();
+            if let Some(listen) = config.metrics.listen.clone() {
+                spawn_metrics_server(Arc::clone(&state.shared), listen);
+            }
+            if let Ok(mut current) = state.shared.config.lock() {
+                *current = config;
+            }
+            if let Ok(mut path) = state.config_path.lock() {
+                *path = Some(config_path);
+            }
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Focused(focused) = event {
+                let state = window.state::<AppState>();
+                if let Ok(mut pause) = state.shared.pause.lock() {
+                    pause.set_focused(*focused);
+                };
+            }
+        })
+        .invoke_handler(tauri::generate_handler![
+            load_rom,
+            emulator_status,
+            connected_gamepads,
+            list_audio_devices,
+            audio_status,
+            pause_status,
+            set_paused,
+            get_config,
+            update_config
+        ])
         .run(tauri::generate_context!())
         .expect("failed to run VibeGB desktop shell");
 }
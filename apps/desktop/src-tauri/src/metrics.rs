@@ -0,0 +1,104 @@
+use crate::audio::audio_status;
+use crate::emulation::SharedState;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use vibegb_core::{write_prometheus_counter, RunCounters};
+
+/// Core counters for the loaded session (zero when none is loaded) plus audio queue health.
+pub fn metrics_text(shared: &SharedState) -> String {
+    let counters = shared
+        .session
+        .lock()
+        .ok()
+        .and_then(|session| session.as_ref().map(|session| session.counters()))
+        .unwrap_or_default();
+    let audio = audio_status(shared);
+    render_metrics(counters, audio.underruns, audio.dropped_frames)
+}
+
+fn render_metrics(counters: RunCounters, underruns: u64, dropped_frames: u64) -> String {
+    let mut text = counters.prometheus_text();
+    write_prometheus_counter(
+        &mut text,
+        "audio_underruns",
+        "Audio device callbacks padded with silence.",
+        underruns,
+    );
+    write_prometheus_counter(
+        &mut text,
+        "audio_dropped_frames",
+        "Audio frames dropped because the queue was full.",
+        dropped_frames,
+    );
+    text
+}
+
+/// Serves `GET /metrics` on `listen` from a background thread for the life of the app.
+pub fn spawn_metrics_server(shared: Arc<SharedState>, listen: String) {
+    let listener = match TcpListener::bind(&listen) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("failed to serve metrics on '{listen}': {err}");
+            return;
+        }
+    };
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Err(err) = respond(&shared, stream) {
+                eprintln!("metrics request failed: {err}");
+            }
+        }
+    });
+}
+
+fn respond(shared: &SharedState, stream: TcpStream) -> std::io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim_end().is_empty() {
+            break;
+        }
+    }
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", metrics_text(shared)),
+        _ => ("404 Not Found", "not found\n".to_string()),
+    };
+    let mut writer = &stream;
+    write!(
+        writer,
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appends_audio_counters_to_core_counters() {
+        let counters = RunCounters {
+            frames: 60,
+            ..RunCounters::default()
+        };
+        let text = render_metrics(counters, 3, 7);
+        assert!(text.starts_with("# HELP vibegb_frames_total"));
+        assert!(text.contains("vibegb_frames_total 60\n"));
+        assert!(text.contains("# TYPE vibegb_audio_underruns_total counter\n"));
+        assert!(text.contains("vibegb_audio_underruns_total 3\n"));
+        assert!(text.ends_with("vibegb_audio_dropped_frames_total 7\n"));
+    }
+
+    #[test]
+    fn reports_zero_counters_without_a_session() {
+        let text = metrics_text(&SharedState::default());
+        assert!(text.contains("vibegb_instructions_total 0\n"));
+        assert!(text.contains("vibegb_audio_underruns_total 0\n"));
+    }
+}
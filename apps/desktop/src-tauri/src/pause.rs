@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+
+/// While throttled in the background, only one host frame in this many runs emulation.
+pub const BACKGROUND_FRAME_DIVISOR: u32 = 4;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FocusLossBehavior {
+    #[default]
+    Pause,
+    Throttle,
+    Run,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PauseStatus {
+    /// True when no emulation is running, either by request or because focus was lost.
+    pub paused: bool,
+    pub user_paused: bool,
+    pub focused: bool,
+    pub throttled: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct PauseControl {
+    user_paused: bool,
+    focused: bool,
+    background_frames: u32,
+}
+
+impl Default for PauseControl {
+    fn default() -> Self {
+        Self {
+            user_paused: false,
+            focused: true,
+            background_frames: 0,
+        }
+    }
+}
+
+impl PauseControl {
+    pub fn set_user_paused(&mut self, paused: bool) {
+        self.user_paused = paused;
+    }
+
+    pub fn set_focused(&mut self, focused: bool) {
+        if focused != self.focused {
+            self.background_frames = 0;
+        }
+        self.focused = focused;
+    }
+
+    /// Decides whether the emulation thread should run a frame on this host tick.
+    pub fn should_run_frame(&mut self, behavior: FocusLossBehavior) -> bool {
+        if self.user_paused {
+            return false;
+        }
+        if self.focused {
+            return true;
+        }
+        match behavior {
+            FocusLossBehavior::Run => true,
+            FocusLossBehavior::Pause => false,
+            FocusLossBehavior::Throttle => {
+                let run = self.background_frames == 0;
+                self.background_frames = (self.background_frames + 1) % BACKGROUND_FRAME_DIVISOR;
+                run
+            }
+        }
+    }
+
+    pub fn status(&self, behavior: FocusLossBehavior) -> PauseStatus {
+        let background = !self.focused;
+        PauseStatus {
+            paused: self.user_paused || (background && behavior == FocusLossBehavior::Pause),
+            user_paused: self.user_paused,
+            focused: self.focused,
+            throttled: !self.user_paused && background && behavior == FocusLossBehavior::Throttle,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_ticks(control: &mut PauseControl, behavior: FocusLossBehavior, ticks: u32) -> u32 {
+        (0..ticks)
+            .filter(|_| control.should_run_frame(behavior))
+            .count() as u32
+    }
+
+    #[test]
+    fn focus_loss_pauses_or_throttles_by_behavior() {
+        let mut control = PauseControl::default();
+        assert_eq!(run_ticks(&mut control, FocusLossBehavior::Pause, 8), 8);
+
+        control.set_focused(false);
+        assert_eq!(run_ticks(&mut control, FocusLossBehavior::Pause, 8), 0);
+        assert!(control.status(FocusLossBehavior::Pause).paused);
+
+        assert_eq!(
+            run_ticks(&mut control, FocusLossBehavior::Throttle, 8),
+            8 / BACKGROUND_FRAME_DIVISOR
+        );
+        let status = control.status(FocusLossBehavior::Throttle);
+        assert!(status.throttled && !status.paused);
+
+        assert_eq!(run_ticks(&mut control, FocusLossBehavior::Run, 8), 8);
+
+        control.set_focused(true);
+        assert_eq!(
+            control.status(FocusLossBehavior::Pause),
+            PauseStatus {
+                paused: false,
+                user_paused: false,
+                focused: true,
+                throttled: false,
+            }
+        );
+    }
+
+    #[test]
+    fn user_pause_overrides_focus_state() {
+        let mut control = PauseControl::default();
+        control.set_user_paused(true);
+        assert_eq!(run_ticks(&mut control, FocusLossBehavior::Run, 4), 0);
+        control.set_focused(false);
+        let status = control.status(FocusLossBehavior::Throttle);
+        assert!(status.paused && status.user_paused && !status.throttled);
+
+        control.set_user_paused(false);
+        assert_eq!(run_ticks(&mut control, FocusLossBehavior::Throttle, 4), 1);
+    }
+}
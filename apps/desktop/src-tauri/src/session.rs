@@ -0,0 +1,222 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use vibegb_core::{Button, GameBoy, GameQuirk, Rom, RomHeader, RtcMode, RunCounters};
+
+pub const CYCLES_PER_FRAME: u64 = vibegb_core::CYCLES_PER_FRAME as u64;
+pub const CPU_CLOCK_HZ: f64 = vibegb_core::CPU_CLOCK_HZ as f64;
+
+const STATS_WINDOW: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionStatus {
+    pub title: String,
+    pub mapper: String,
+    /// Known-games table settings applied at load, e.g. "pixel-fifo-ppu".
+    pub quirks: Vec<String>,
+    pub fps: f64,
+    pub speed: f64,
+    pub running: bool,
+    pub error: Option<String>,
+}
+
+pub struct Session {
+    gb: GameBoy,
+    header: RomHeader,
+    quirks: &'static [GameQuirk],
+    cycle_debt: u64,
+    running: bool,
+    error: Option<String>,
+    stats: SpeedStats,
+    rumble: Arc<AtomicBool>,
+}
+
+impl Session {
+    pub fn new(rom: Rom) -> Self {
+        let mut gb = GameBoy::new();
+        gb.load_rom(&rom.data);
+        // Players expect cartridge clocks to follow real time; the runner keeps them emulated.
+        gb.set_rtc_mode(RtcMode::WallClock);
+        let quirks = gb.apply_game_quirks(&rom.header);
+        let rumble = Arc::new(AtomicBool::new(false));
+        let motor = Arc::clone(&rumble);
+        gb.set_rumble_callback(move |on| motor.store(on, Ordering::Relaxed));
+        Self {
+            gb,
+            header: rom.header,
+            quirks,
+            cycle_debt: 0,
+            running: true,
+            error: None,
+            stats: SpeedStats::new(Instant::now()),
+            rumble,
+        }
+    }
+
+    /// Whether a rumble cartridge has its motor running.
+    pub fn rumble(&self) -> bool {
+        self.running && self.rumble.load(Ordering::Relaxed)
+    }
+
+    pub fn header(&self) -> &RomHeader {
+        &self.header
+    }
+
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        self.gb.set_button(button, pressed);
+    }
+
+    pub fn run_frame(&mut self, now: Instant) -> u64 {
+        if !self.running {
+            return 0;
+        }
+
+        let mut cycles = 0u64;
+        while self.cycle_debt + cycles < CYCLES_PER_FRAME {
+            match self.gb.step() {
+                Ok(step_cycles) => cycles += u64::from(step_cycles),
+                Err(err) => {
+                    self.error = Some(format!("emulation stopped: {err}"));
+                    self.running = false;
+                    break;
+                }
+            }
+        }
+        self.cycle_debt = (self.cycle_debt + cycles).saturating_sub(CYCLES_PER_FRAME);
+        self.stats.record_frame(cycles, now);
+        cycles
+    }
+
+    /// Sound produced since the last call as interleaved stereo samples at `sample_rate`.
+    pub fn take_audio_samples(&mut self, sample_rate: u32) -> Vec<f32> {
+        if self.gb.bus.apu().sample_rate() != sample_rate {
+            self.gb.set_audio_sample_rate(sample_rate);
+        }
+        self.gb.take_audio_samples()
+    }
+
+    pub fn counters(&self) -> RunCounters {
+        self.gb.counters()
+    }
+
+    pub fn status(&self) -> SessionStatus {
+        SessionStatus {
+            title: display_title(&self.header),
+            mapper: self.header.cartridge_type_name().to_string(),
+            quirks: self
+                .quirks
+                .iter()
+                .map(|quirk| quirk.name().to_string())
+                .collect(),
+            fps: self.stats.fps,
+            speed: self.stats.speed,
+            running: self.running,
+            error: self.error.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SpeedStats {
+    window_start: Instant,
+    frames: u32,
+    cycles: u64,
+    fps: f64,
+    speed: f64,
+}
+
+impl SpeedStats {
+    fn new(now: Instant) -> Self {
+        Self {
+            window_start: now,
+            frames: 0,
+            cycles: 0,
+            fps: 0.0,
+            speed: 0.0,
+        }
+    }
+
+    fn record_frame(&mut self, cycles: u64, now: Instant) {
+        self.frames += 1;
+        self.cycles += cycles;
+
+        let elapsed = now.saturating_duration_since(self.window_start);
+        if elapsed < STATS_WINDOW {
+            return;
+        }
+
+        let seconds = elapsed.as_secs_f64();
+        self.fps = f64::from(self.frames) / seconds;
+        self.speed = (self.cycles as f64 / CPU_CLOCK_HZ) / seconds;
+        self.window_start = now;
+        self.frames = 0;
+        self.cycles = 0;
+    }
+}
+
+pub fn window_title(header: &RomHeader) -> String {
+    format!(
+        "VibeGB - {} [{}]",
+        display_title(header),
+        header.cartridge_type_name()
+    )
+}
+
+fn display_title(header: &RomHeader) -> String {
+    if header.title.is_empty() {
+        "Untitled".to_string()
+    } else {
+        header.title.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vibegb_core::TEST_LOGO;
+
+    #[test]
+    fn speed_stats_report_fps_and_speed_per_window() {
+        let start = Instant::now();
+        let mut stats = SpeedStats::new(start);
+
+        for frame in 1..=30u64 {
+            stats.record_frame(CYCLES_PER_FRAME, start + Duration::from_millis(frame * 16));
+        }
+        assert_eq!(stats.fps, 0.0, "window has not elapsed yet");
+
+        stats.record_frame(CYCLES_PER_FRAME, start + Duration::from_millis(500));
+        assert!((stats.fps - 62.0).abs() < 0.01);
+        let expected_speed = (31.0 * CYCLES_PER_FRAME as f64 / CPU_CLOCK_HZ) / 0.5;
+        assert!((stats.speed - expected_speed).abs() < 0.0001);
+        assert_eq!(stats.frames, 0);
+    }
+
+    #[test]
+    fn window_title_includes_rom_title_and_mapper() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x104..0x134].copy_from_slice(&TEST_LOGO);
+        rom[0x134..0x13A].copy_from_slice(b"TETRIS");
+        rom[0x147] = 0x01;
+        rom[0x14D] = header_checksum(&rom);
+        let rom = Rom::from_bytes(rom).expect("test ROM should parse");
+
+        assert_eq!(window_title(&rom.header), "VibeGB - TETRIS [MBC1]");
+
+        let session = Session::new(rom);
+        let status = session.status();
+        assert_eq!(status.title, "TETRIS");
+        assert_eq!(status.mapper, "MBC1");
+        assert!(status.quirks.is_empty());
+        assert!(status.running);
+    }
+
+    fn header_checksum(data: &[u8]) -> u8 {
+        let mut checksum = 0u8;
+        for byte in &data[0x134..=0x14C] {
+            checksum = checksum.wrapping_sub(*byte).wrapping_sub(1);
+        }
+        checksum
+    }
+}
@@ -0,0 +1,64 @@
+//! Times per-frame framebuffer export and fails if it touches the heap.
+//! Run with `cargo bench -p vibegb-core --bench framebuffer_export`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use vibegb_core::{GameBoy, Palette, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+const FRAMES: u32 = 2_000;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn bench(name: &str, mut export: impl FnMut()) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        export();
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    println!(
+        "{name}: {:.2} us/frame, {allocations} allocations over {FRAMES} frames",
+        elapsed.as_secs_f64() * 1e6 / f64::from(FRAMES)
+    );
+    assert_eq!(allocations, 0, "{name} allocated on the per-frame path");
+}
+
+fn main() {
+    let mut gb = GameBoy::new();
+    gb.set_palette(Palette::DMG_GREEN);
+    let mut rgba = vec![0u8; SCREEN_WIDTH * SCREEN_HEIGHT * 4];
+    let mut rgb565 = vec![0u16; SCREEN_WIDTH * SCREEN_HEIGHT];
+
+    bench("rgba8888", || {
+        gb.write_framebuffer_rgba(black_box(&mut rgba))
+            .expect("buffer holds one frame");
+    });
+    bench("rgb565", || {
+        gb.write_framebuffer_rgb565(black_box(&mut rgb565))
+            .expect("buffer holds one frame");
+    });
+}
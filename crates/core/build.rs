@@ -0,0 +1,35 @@
+use std::env;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=VIBEGB_GIT_HASH");
+    println!("cargo:rerun-if-changed=../../.git/HEAD");
+    println!("cargo:rerun-if-changed=../../.git/refs");
+
+    let git_hash = env::var("VIBEGB_GIT_HASH")
+        .ok()
+        .filter(|hash| !hash.is_empty())
+        .or_else(git_short_hash)
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=VIBEGB_GIT_HASH={git_hash}");
+
+    let mut features = env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(str::to_string))
+        .map(|feature| feature.to_ascii_lowercase().replace('_', "-"))
+        .collect::<Vec<_>>();
+    features.sort();
+    println!("cargo:rustc-env=VIBEGB_FEATURES={}", features.join(","));
+}
+
+fn git_short_hash() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let hash = String::from_utf8(output.stdout).ok()?;
+    let hash = hash.trim();
+    (!hash.is_empty()).then(|| hash.to_string())
+}
@@ -0,0 +1,176 @@
+use std::cell::Cell;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryRegion {
+    Cartridge,
+    Vram,
+    Wram,
+    Oam,
+    Io,
+    Hram,
+}
+
+impl MemoryRegion {
+    pub const ALL: [Self; 6] = [
+        Self::Cartridge,
+        Self::Vram,
+        Self::Wram,
+        Self::Oam,
+        Self::Io,
+        Self::Hram,
+    ];
+
+    /// Device an address decodes to; echo RAM counts as WRAM and the unusable area as OAM.
+    pub fn of(address: u16) -> Self {
+        match address {
+            0x0000..=0x7FFF | 0xA000..=0xBFFF => Self::Cartridge,
+            0x8000..=0x9FFF => Self::Vram,
+            0xC000..=0xFDFF => Self::Wram,
+            0xFE00..=0xFEFF => Self::Oam,
+            0xFF80..=0xFFFE => Self::Hram,
+            0xFF00..=0xFF7F | 0xFFFF => Self::Io,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl Display for MemoryRegion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Cartridge => "cartridge",
+            Self::Vram => "vram",
+            Self::Wram => "wram",
+            Self::Oam => "oam",
+            Self::Io => "io",
+            Self::Hram => "hram",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AccessCounts {
+    pub reads: [u64; MemoryRegion::ALL.len()],
+    pub writes: [u64; MemoryRegion::ALL.len()],
+}
+
+impl AccessCounts {
+    pub fn reads(&self, region: MemoryRegion) -> u64 {
+        self.reads[region.index()]
+    }
+
+    pub fn writes(&self, region: MemoryRegion) -> u64 {
+        self.writes[region.index()]
+    }
+
+    pub fn total(&self) -> u64 {
+        self.reads.iter().chain(&self.writes).sum()
+    }
+
+    fn add(&mut self, other: &Self) {
+        for (total, count) in self.reads.iter_mut().zip(other.reads) {
+            *total += count;
+        }
+        for (total, count) in self.writes.iter_mut().zip(other.writes) {
+            *total += count;
+        }
+    }
+}
+
+/// Per-device CPU access counts, for deciding how to order or table-drive `Bus` dispatch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BusAccessStats {
+    /// Completed frames folded into `total`.
+    pub frames: u64,
+    pub total: AccessCounts,
+    pub last_frame: AccessCounts,
+}
+
+/// Counting is off by default so the hot read path only pays for a branch.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AccessRecorder {
+    enabled: bool,
+    reads: [Cell<u64>; MemoryRegion::ALL.len()],
+    writes: [u64; MemoryRegion::ALL.len()],
+    stats: BusAccessStats,
+}
+
+impl AccessRecorder {
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub(crate) fn record_read(&self, address: u16) {
+        if self.enabled {
+            let count = &self.reads[MemoryRegion::of(address).index()];
+            count.set(count.get() + 1);
+        }
+    }
+
+    pub(crate) fn record_write(&mut self, address: u16) {
+        if self.enabled {
+            self.writes[MemoryRegion::of(address).index()] += 1;
+        }
+    }
+
+    pub(crate) fn finish_frame(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        let frame = AccessCounts {
+            reads: self.reads.each_ref().map(Cell::take),
+            writes: std::mem::take(&mut self.writes),
+        };
+        self.stats.frames += 1;
+        self.stats.total.add(&frame);
+        self.stats.last_frame = frame;
+    }
+
+    pub(crate) fn stats(&self) -> BusAccessStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_addresses_by_device() {
+        assert_eq!(MemoryRegion::of(0x0150), MemoryRegion::Cartridge);
+        assert_eq!(MemoryRegion::of(0xA123), MemoryRegion::Cartridge);
+        assert_eq!(MemoryRegion::of(0x9800), MemoryRegion::Vram);
+        assert_eq!(MemoryRegion::of(0xE000), MemoryRegion::Wram);
+        assert_eq!(MemoryRegion::of(0xFEA0), MemoryRegion::Oam);
+        assert_eq!(MemoryRegion::of(0xFF44), MemoryRegion::Io);
+        assert_eq!(MemoryRegion::of(0xFFFF), MemoryRegion::Io);
+        assert_eq!(MemoryRegion::of(0xFF80), MemoryRegion::Hram);
+    }
+
+    #[test]
+    fn frames_roll_counts_into_totals() {
+        let mut recorder = AccessRecorder::default();
+        recorder.record_read(0x0100);
+        recorder.finish_frame();
+        assert_eq!(recorder.stats(), BusAccessStats::default());
+
+        recorder.set_enabled(true);
+        recorder.record_read(0x0100);
+        recorder.record_read(0xFF80);
+        recorder.record_write(0xC000);
+        recorder.finish_frame();
+        recorder.record_write(0xC001);
+        recorder.finish_frame();
+
+        let stats = recorder.stats();
+        assert_eq!(stats.frames, 2);
+        assert_eq!(stats.total.reads(MemoryRegion::Cartridge), 1);
+        assert_eq!(stats.total.writes(MemoryRegion::Wram), 2);
+        assert_eq!(stats.total.total(), 4);
+        assert_eq!(stats.last_frame.total(), 1);
+    }
+}
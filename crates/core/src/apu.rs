@@ -0,0 +1,1471 @@
+use crate::state::{StateError, StateReader, StateWriter, Stateful};
+use crate::{Model, CPU_CLOCK_HZ};
+use std::collections::VecDeque;
+
+pub const NR10_ADDR: u16 = 0xFF10;
+pub const NR11_ADDR: u16 = 0xFF11;
+pub const NR12_ADDR: u16 = 0xFF12;
+pub const NR13_ADDR: u16 = 0xFF13;
+pub const NR14_ADDR: u16 = 0xFF14;
+pub const NR21_ADDR: u16 = 0xFF16;
+pub const NR22_ADDR: u16 = 0xFF17;
+pub const NR23_ADDR: u16 = 0xFF18;
+pub const NR24_ADDR: u16 = 0xFF19;
+pub const NR30_ADDR: u16 = 0xFF1A;
+pub const NR31_ADDR: u16 = 0xFF1B;
+pub const NR32_ADDR: u16 = 0xFF1C;
+pub const NR33_ADDR: u16 = 0xFF1D;
+pub const NR34_ADDR: u16 = 0xFF1E;
+pub const NR41_ADDR: u16 = 0xFF20;
+pub const NR42_ADDR: u16 = 0xFF21;
+pub const NR43_ADDR: u16 = 0xFF22;
+pub const NR44_ADDR: u16 = 0xFF23;
+pub const NR50_ADDR: u16 = 0xFF24;
+pub const NR51_ADDR: u16 = 0xFF25;
+pub const NR52_ADDR: u16 = 0xFF26;
+pub const WAVE_RAM_ADDR: u16 = 0xFF30;
+pub const WAVE_RAM_END: u16 = 0xFF3F;
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+
+// The channels and mixer run once per M-cycle.
+const M_CYCLE: u32 = 4;
+const M_CYCLE_HZ: u32 = CPU_CLOCK_HZ / M_CYCLE;
+// The frame sequencer steps at 512 Hz, when this bit of the timer's divider (DIV bit 4)
+// falls, so DIV writes can step it early.
+const SEQUENCER_DIVIDER_BIT: u16 = 1 << 12;
+// Stereo frames buffered before the oldest are dropped; about 170 ms at 48 kHz.
+const SAMPLE_BUFFER_FRAMES: usize = 8192;
+// Per-cycle charge kept by the DMG output capacitor, which removes the DACs' DC offset.
+const HIGH_PASS_CHARGE: f64 = 0.999958;
+
+const NR52_POWER: u8 = 0x80;
+const TRIGGER: u8 = 0x80;
+const LENGTH_ENABLE: u8 = 0x40;
+
+const DUTY_PATTERNS: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+const NOISE_DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+// Bits of NR10..NR52 that always read back as 1.
+const READ_MASKS: [u8; 23] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, 0xFF, 0x3F, 0x00, 0xFF, 0xBF, 0x7F, 0xFF, 0x9F, 0xFF, 0xBF, 0xFF,
+    0xFF, 0x00, 0x00, 0xBF, 0x00, 0x00, 0x70,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Square1,
+    Square2,
+    Wave,
+    Noise,
+}
+
+impl Channel {
+    pub const ALL: [Self; 4] = [Self::Square1, Self::Square2, Self::Wave, Self::Noise];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// How the analog end of the sound hardware is modeled in the mixed output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioConfig {
+    /// Pass the output through the DMG's DC-blocking capacitor.
+    pub high_pass_filter: bool,
+    /// Let a channel whose DAC is on but which is not playing add its DAC's DC level, so
+    /// switching DACs and channels on and off clicks as on hardware. When off, only
+    /// playing channels reach the mixer.
+    pub dac_clicks: bool,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            high_pass_filter: true,
+            dac_clicks: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct LengthCounter {
+    remaining: u16,
+    enabled: bool,
+}
+
+impl LengthCounter {
+    fn load(&mut self, max: u16, value: u16) {
+        self.remaining = max - value;
+    }
+
+    /// Applies NRx4's length-enable and trigger bits. While the frame sequencer's next step
+    /// does not clock length, enabling the counter clocks it once straight away and a
+    /// trigger reloads an expired counter one short. Returns true when that extra clock
+    /// expires the counter with no trigger to restart the channel.
+    fn write_control(&mut self, max: u16, nrx4: u8, next_step_skips_length: bool) -> bool {
+        let was_enabled = self.enabled;
+        self.enabled = nrx4 & LENGTH_ENABLE != 0;
+        let triggered = nrx4 & TRIGGER != 0;
+        let mut expired = false;
+        if next_step_skips_length && !was_enabled && self.enabled && self.remaining > 0 {
+            self.remaining -= 1;
+            expired = self.remaining == 0 && !triggered;
+        }
+        if triggered && self.remaining == 0 {
+            self.remaining = if next_step_skips_length && self.enabled {
+                max - 1
+            } else {
+                max
+            };
+        }
+        expired
+    }
+
+    /// Returns true when the counter runs out and silences its channel.
+    fn clock(&mut self) -> bool {
+        if !self.enabled || self.remaining == 0 {
+            return false;
+        }
+        self.remaining -= 1;
+        self.remaining == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Envelope {
+    volume: u8,
+    increase: bool,
+    period: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    fn trigger(&mut self, nrx2: u8) {
+        self.volume = nrx2 >> 4;
+        self.increase = nrx2 & 0x08 != 0;
+        self.period = nrx2 & 0x07;
+        self.timer = self.period;
+    }
+
+    fn clock(&mut self) {
+        if self.period == 0 {
+            return;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer == 0 {
+            self.timer = self.period;
+            if self.increase && self.volume < 15 {
+                self.volume += 1;
+            } else if !self.increase && self.volume > 0 {
+                self.volume -= 1;
+            }
+        }
+    }
+}
+
+/// Counts `timer` down by `cycles`, reloading it with `period` each time it expires, and
+/// returns how many times it did.
+fn run_timer(timer: &mut u32, period: u32, cycles: u32) -> u32 {
+    if *timer == 0 {
+        *timer = period;
+    }
+    let mut remaining = cycles;
+    let mut expired = 0;
+    while remaining >= *timer {
+        remaining -= *timer;
+        *timer = period;
+        expired += 1;
+    }
+    *timer -= remaining;
+    expired
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Square {
+    enabled: bool,
+    dac: bool,
+    duty: u8,
+    duty_step: u8,
+    frequency: u16,
+    timer: u32,
+    length: LengthCounter,
+    envelope: Envelope,
+}
+
+impl Square {
+    fn period(&self) -> u32 {
+        (2048 - u32::from(self.frequency)) * 4
+    }
+
+    fn trigger(&mut self, nrx2: u8) {
+        self.enabled = self.dac;
+        self.timer = self.period();
+        self.envelope.trigger(nrx2);
+    }
+
+    fn run(&mut self, cycles: u32) {
+        let period = self.period();
+        let steps = run_timer(&mut self.timer, period, cycles);
+        self.duty_step = ((u32::from(self.duty_step) + steps) % 8) as u8;
+    }
+
+    fn output(&self) -> u8 {
+        let high = DUTY_PATTERNS[usize::from(self.duty)] >> (7 - self.duty_step) & 1 != 0;
+        if self.enabled && high {
+            self.envelope.volume
+        } else {
+            0
+        }
+    }
+}
+
+/// Channel 1's frequency sweep.
+#[derive(Debug, Clone, Copy, Default)]
+struct Sweep {
+    enabled: bool,
+    shadow: u16,
+    timer: u8,
+    period: u8,
+    negate: bool,
+    shift: u8,
+    // Set once a calculation since the last trigger subtracted.
+    negated: bool,
+}
+
+impl Sweep {
+    /// Returns false when clearing negate after a subtracting calculation disables the
+    /// channel.
+    fn write(&mut self, nr10: u8) -> bool {
+        self.period = (nr10 >> 4) & 0x07;
+        self.negate = nr10 & 0x08 != 0;
+        self.shift = nr10 & 0x07;
+        self.negate || !self.negated
+    }
+
+    fn reload_timer(&mut self) {
+        // A period of 0 counts as 8.
+        self.timer = if self.period == 0 { 8 } else { self.period };
+    }
+
+    /// The next frequency, or `None` when it would overflow 11 bits.
+    fn next_frequency(&mut self) -> Option<u16> {
+        let delta = self.shadow >> self.shift;
+        self.negated |= self.negate;
+        let next = if self.negate {
+            self.shadow - delta
+        } else {
+            self.shadow + delta
+        };
+        (next <= 2047).then_some(next)
+    }
+
+    /// Returns false when the overflow check disables the channel.
+    fn trigger(&mut self, frequency: u16) -> bool {
+        self.shadow = frequency;
+        self.negated = false;
+        self.reload_timer();
+        self.enabled = self.period != 0 || self.shift != 0;
+        self.shift == 0 || self.next_frequency().is_some()
+    }
+
+    /// Returns the new channel frequency, if any, and false when an overflow check disables
+    /// the channel. A frequency that passes the first check is written back even when the
+    /// second check then disables the channel.
+    fn clock(&mut self) -> (Option<u16>, bool) {
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer != 0 {
+            return (None, true);
+        }
+        self.reload_timer();
+        if !self.enabled || self.period == 0 {
+            return (None, true);
+        }
+        let Some(next) = self.next_frequency() else {
+            return (None, false);
+        };
+        if self.shift == 0 {
+            return (None, true);
+        }
+        self.shadow = next;
+        (Some(next), self.next_frequency().is_some())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Wave {
+    enabled: bool,
+    dac: bool,
+    volume_code: u8,
+    frequency: u16,
+    timer: u32,
+    position: u8,
+    sample: u8,
+    // Whether the channel fetched a wave RAM byte in the last M-cycle.
+    just_read: bool,
+    length: LengthCounter,
+}
+
+impl Wave {
+    fn period(&self) -> u32 {
+        (2048 - u32::from(self.frequency)) * 2
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac;
+        self.timer = self.period();
+        self.position = 0;
+    }
+
+    fn run(&mut self, cycles: u32, wave_ram: &[u8]) {
+        let period = self.period();
+        let steps = run_timer(&mut self.timer, period, cycles);
+        self.just_read = steps > 0;
+        if steps == 0 {
+            return;
+        }
+        self.position = ((u32::from(self.position) + steps) % 32) as u8;
+        let byte = wave_ram[self.ram_index()];
+        self.sample = if self.position.is_multiple_of(2) {
+            byte >> 4
+        } else {
+            byte & 0x0F
+        };
+    }
+
+    fn ram_index(&self) -> usize {
+        usize::from(self.position / 2)
+    }
+
+    /// Where a CPU access to wave RAM lands. While playing, the DMG only lets it through in
+    /// the cycle the channel fetches a byte, and then it hits that byte whatever the
+    /// address; at any other time reads see 0xFF and writes are lost.
+    fn cpu_access(&self, address: u16) -> Option<usize> {
+        match (self.enabled, self.just_read) {
+            (false, _) => Some(usize::from(address - WAVE_RAM_ADDR)),
+            (true, true) => Some(self.ram_index()),
+            (true, false) => None,
+        }
+    }
+
+    fn output(&self) -> u8 {
+        match (self.enabled, self.volume_code) {
+            (false, _) | (true, 0) => 0,
+            (true, code) => self.sample >> (code - 1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Noise {
+    enabled: bool,
+    dac: bool,
+    shift: u8,
+    narrow: bool,
+    divisor: u8,
+    timer: u32,
+    lfsr: u16,
+    length: LengthCounter,
+    envelope: Envelope,
+}
+
+impl Noise {
+    fn period(&self) -> u32 {
+        NOISE_DIVISORS[usize::from(self.divisor)] << self.shift
+    }
+
+    fn trigger(&mut self, nr42: u8) {
+        self.enabled = self.dac;
+        self.timer = self.period();
+        self.envelope.trigger(nr42);
+        self.lfsr = 0x7FFF;
+    }
+
+    fn run(&mut self, cycles: u32) {
+        // Shifts 14 and 15 stop the LFSR.
+        if self.shift >= 14 {
+            return;
+        }
+        let period = self.period();
+        for _ in 0..run_timer(&mut self.timer, period, cycles) {
+            let feedback = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+            self.lfsr = (self.lfsr >> 1) | (feedback << 14);
+            if self.narrow {
+                self.lfsr = (self.lfsr & !(1 << 6)) | (feedback << 6);
+            }
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.enabled && self.lfsr & 1 == 0 {
+            self.envelope.volume
+        } else {
+            0
+        }
+    }
+}
+
+/// The four sound channels, frame sequencer and mixer. Output is resampled from the
+/// M-cycle rate to `sample_rate` by averaging, high-pass filtered like the DMG's output
+/// capacitor, and kept as interleaved left/right `f32` samples in a bounded ring buffer
+/// that drops its oldest frames when the frontend falls behind.
+#[derive(Debug, Clone)]
+pub struct Apu {
+    registers: [u8; READ_MASKS.len()],
+    powered: bool,
+    model: Model,
+    square1: Square,
+    sweep: Sweep,
+    square2: Square,
+    wave: Wave,
+    noise: Noise,
+    // Mirror of the timer's divider, reset with it by `reset_divider`.
+    divider: u16,
+    sequencer_step: u8,
+    // Powering on with the divider's sequencer bit set swallows the next falling edge.
+    skip_sequencer_edge: bool,
+    pending_cycles: u32,
+    sample_rate: u32,
+    sample_phase: u32,
+    accumulated: (f64, f64),
+    accumulated_cycles: u32,
+    capacitor: (f64, f64),
+    high_pass_charge: f64,
+    config: AudioConfig,
+    // Debug mute overrides; a muted channel still runs, it just never reaches the mixer.
+    channels_enabled: [bool; 4],
+    output_enabled: bool,
+    samples: VecDeque<f32>,
+    dropped_frames: u64,
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self {
+            registers: [0; READ_MASKS.len()],
+            powered: false,
+            model: Model::Dmg,
+            square1: Square::default(),
+            sweep: Sweep::default(),
+            square2: Square::default(),
+            wave: Wave::default(),
+            noise: Noise::default(),
+            divider: 0,
+            sequencer_step: 0,
+            skip_sequencer_edge: false,
+            pending_cycles: 0,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            sample_phase: 0,
+            accumulated: (0.0, 0.0),
+            accumulated_cycles: 0,
+            capacitor: (0.0, 0.0),
+            high_pass_charge: high_pass_charge(DEFAULT_SAMPLE_RATE),
+            config: AudioConfig::default(),
+            channels_enabled: [true; 4],
+            output_enabled: true,
+            samples: VecDeque::with_capacity(SAMPLE_BUFFER_FRAMES * 2),
+            dropped_frames: 0,
+        }
+    }
+}
+
+fn high_pass_charge(sample_rate: u32) -> f64 {
+    HIGH_PASS_CHARGE.powf(f64::from(CPU_CLOCK_HZ) / f64::from(sample_rate))
+}
+
+impl Apu {
+    pub fn read_register(&self, address: u16) -> u8 {
+        let index = usize::from(address - NR10_ADDR);
+        if address == NR52_ADDR {
+            let power = if self.powered { NR52_POWER } else { 0 };
+            return READ_MASKS[index] | power | self.channel_status();
+        }
+        self.registers[index] | READ_MASKS[index]
+    }
+
+    /// `wave_ram` is the 16 bytes at 0xFF30-0xFF3F.
+    pub fn read_wave_ram(&self, address: u16, wave_ram: &[u8]) -> u8 {
+        self.wave
+            .cpu_access(address)
+            .map_or(0xFF, |index| wave_ram[index])
+    }
+
+    pub fn write_wave_ram(&self, address: u16, value: u8, wave_ram: &mut [u8]) {
+        if let Some(index) = self.wave.cpu_access(address) {
+            wave_ram[index] = value;
+        }
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        if address == NR52_ADDR {
+            self.set_power(value & NR52_POWER != 0);
+            return;
+        }
+        if !self.powered {
+            if self.model == Model::Cgb {
+                return;
+            }
+            // DMG length counters stay powered, so their loads still land.
+            match address {
+                NR11_ADDR => self.square1.length.load(64, u16::from(value & 0x3F)),
+                NR21_ADDR => self.square2.length.load(64, u16::from(value & 0x3F)),
+                NR31_ADDR => self.wave.length.load(256, u16::from(value)),
+                NR41_ADDR => self.noise.length.load(64, u16::from(value & 0x3F)),
+                _ => {}
+            }
+            return;
+        }
+        self.registers[usize::from(address - NR10_ADDR)] = value;
+        let skips_length = !self.sequencer_step.is_multiple_of(2);
+        match address {
+            NR10_ADDR => self.square1.enabled &= self.sweep.write(value),
+            NR11_ADDR => {
+                self.square1.duty = value >> 6;
+                self.square1.length.load(64, u16::from(value & 0x3F));
+            }
+            NR12_ADDR => {
+                self.square1.dac = value & 0xF8 != 0;
+                self.square1.enabled &= self.square1.dac;
+            }
+            NR13_ADDR => self.square1.frequency = self.square1.frequency & 0x700 | u16::from(value),
+            NR14_ADDR => {
+                self.square1.frequency = frequency_high(self.square1.frequency, value);
+                if self.square1.length.write_control(64, value, skips_length) {
+                    self.square1.enabled = false;
+                }
+                if value & TRIGGER != 0 {
+                    self.square1.trigger(self.registers[2]);
+                    if !self.sweep.trigger(self.square1.frequency) {
+                        self.square1.enabled = false;
+                    }
+                }
+            }
+            NR21_ADDR => {
+                self.square2.duty = value >> 6;
+                self.square2.length.load(64, u16::from(value & 0x3F));
+            }
+            NR22_ADDR => {
+                self.square2.dac = value & 0xF8 != 0;
+                self.square2.enabled &= self.square2.dac;
+            }
+            NR23_ADDR => self.square2.frequency = self.square2.frequency & 0x700 | u16::from(value),
+            NR24_ADDR => {
+                self.square2.frequency = frequency_high(self.square2.frequency, value);
+                if self.square2.length.write_control(64, value, skips_length) {
+                    self.square2.enabled = false;
+                }
+                if value & TRIGGER != 0 {
+                    self.square2.trigger(self.registers[7]);
+                }
+            }
+            NR30_ADDR => {
+                self.wave.dac = value & 0x80 != 0;
+                self.wave.enabled &= self.wave.dac;
+            }
+            NR31_ADDR => self.wave.length.load(256, u16::from(value)),
+            NR32_ADDR => self.wave.volume_code = (value >> 5) & 0x03,
+            NR33_ADDR => self.wave.frequency = self.wave.frequency & 0x700 | u16::from(value),
+            NR34_ADDR => {
+                self.wave.frequency = frequency_high(self.wave.frequency, value);
+                if self.wave.length.write_control(256, value, skips_length) {
+                    self.wave.enabled = false;
+                }
+                if value & TRIGGER != 0 {
+                    self.wave.trigger();
+                }
+            }
+            NR41_ADDR => self.noise.length.load(64, u16::from(value & 0x3F)),
+            NR42_ADDR => {
+                self.noise.dac = value & 0xF8 != 0;
+                self.noise.enabled &= self.noise.dac;
+            }
+            NR43_ADDR => {
+                self.noise.shift = value >> 4;
+                self.noise.narrow = value & 0x08 != 0;
+                self.noise.divisor = value & 0x07;
+            }
+            NR44_ADDR => {
+                if self.noise.length.write_control(64, value, skips_length) {
+                    self.noise.enabled = false;
+                }
+                if value & TRIGGER != 0 {
+                    self.noise.trigger(self.registers[0x11]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Powering off clears every sound register and silences the channels; wave RAM,
+    /// which lives on the bus, keeps its contents, and so do the length counters except on
+    /// the CGB.
+    /// Powering on restarts the frame sequencer at step 0; if the divider's sequencer bit is
+    /// already set, its next falling edge does not clock it.
+    fn set_power(&mut self, on: bool) {
+        if on == self.powered {
+            return;
+        }
+        if !on {
+            let lengths = [
+                self.square1.length.remaining,
+                self.square2.length.remaining,
+                self.wave.length.remaining,
+                self.noise.length.remaining,
+            ];
+            self.registers = [0; READ_MASKS.len()];
+            self.square1 = Square::default();
+            self.sweep = Sweep::default();
+            self.square2 = Square::default();
+            self.wave = Wave::default();
+            self.noise = Noise::default();
+            if self.model != Model::Cgb {
+                self.square1.length.remaining = lengths[0];
+                self.square2.length.remaining = lengths[1];
+                self.wave.length.remaining = lengths[2];
+                self.noise.length.remaining = lengths[3];
+            }
+        }
+        self.sequencer_step = 0;
+        self.skip_sequencer_edge = on && self.divider & SEQUENCER_DIVIDER_BIT != 0;
+        self.powered = on;
+    }
+
+    /// Whether the length counters follow the DMG (powered through NR52 power-off) or
+    /// the CGB (cleared with the rest).
+    pub(crate) fn set_model(&mut self, model: Model) {
+        self.model = model;
+    }
+
+    /// Called on DIV writes: zeroing the divider while its sequencer bit is set is a
+    /// falling edge, which steps the frame sequencer early.
+    pub(crate) fn reset_divider(&mut self) {
+        if self.powered && self.divider & SEQUENCER_DIVIDER_BIT != 0 {
+            self.sequencer_edge();
+        }
+        self.divider = 0;
+    }
+
+    fn sequencer_edge(&mut self) {
+        if self.skip_sequencer_edge {
+            self.skip_sequencer_edge = false;
+        } else {
+            self.clock_frame_sequencer();
+        }
+    }
+
+    pub fn powered(&self) -> bool {
+        self.powered
+    }
+
+    /// NR52 bits 0-3: which channels are currently playing.
+    pub fn channel_status(&self) -> u8 {
+        u8::from(self.square1.enabled)
+            | u8::from(self.square2.enabled) << 1
+            | u8::from(self.wave.enabled) << 2
+            | u8::from(self.noise.enabled) << 3
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Output rate in stereo frames per second, clamped to 1..=1 MiHz. Buffered samples
+    /// at the old rate are discarded.
+    pub fn set_sample_rate(&mut self, rate: u32) {
+        self.sample_rate = rate.clamp(1, M_CYCLE_HZ);
+        self.high_pass_charge = high_pass_charge(self.sample_rate);
+        self.sample_phase = 0;
+        self.accumulated = (0.0, 0.0);
+        self.accumulated_cycles = 0;
+        self.samples.clear();
+    }
+
+    pub fn config(&self) -> AudioConfig {
+        self.config
+    }
+
+    /// Changing the filter setting discharges the capacitor.
+    pub fn set_config(&mut self, config: AudioConfig) {
+        if config.high_pass_filter != self.config.high_pass_filter {
+            self.capacitor = (0.0, 0.0);
+        }
+        self.config = config;
+    }
+
+    /// Mutes or unmutes one channel in the mix, e.g. to solo a channel while composing. The
+    /// channel keeps running, so NR52 and length timing are unaffected.
+    pub fn set_channel_enabled(&mut self, channel: Channel, enabled: bool) {
+        self.channels_enabled[channel.index()] = enabled;
+    }
+
+    pub fn channel_enabled(&self, channel: Channel) -> bool {
+        self.channels_enabled[channel.index()]
+    }
+
+    /// Turning output off skips mixing and resampling entirely, for runs that never listen;
+    /// the channels still run. Buffered samples are discarded.
+    pub fn set_output_enabled(&mut self, enabled: bool) {
+        self.output_enabled = enabled;
+        self.samples.clear();
+    }
+
+    pub fn output_enabled(&self) -> bool {
+        self.output_enabled
+    }
+
+    /// Stereo frames waiting in the buffer.
+    pub fn buffered_frames(&self) -> usize {
+        self.samples.len() / 2
+    }
+
+    /// Stereo frames thrown away because the buffer was full.
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped_frames
+    }
+
+    /// Moves up to `out.len()` buffered samples (whole left/right pairs) into `out` and
+    /// returns how many were written.
+    pub fn read_samples(&mut self, out: &mut [f32]) -> usize {
+        let count = self.samples.len().min(out.len() / 2 * 2);
+        for (slot, sample) in out.iter_mut().zip(self.samples.drain(..count)) {
+            *slot = sample;
+        }
+        count
+    }
+
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        self.samples.drain(..).collect()
+    }
+
+    pub fn tick(&mut self, cycles: u32, wave_ram: &[u8]) {
+        self.pending_cycles += cycles;
+        while self.pending_cycles >= M_CYCLE {
+            self.pending_cycles -= M_CYCLE;
+            let previous = self.divider;
+            self.divider = self.divider.wrapping_add(M_CYCLE as u16);
+            if self.powered {
+                if previous & !self.divider & SEQUENCER_DIVIDER_BIT != 0 {
+                    self.sequencer_edge();
+                }
+                self.run_channels(wave_ram);
+            }
+            if self.output_enabled {
+                self.mix();
+            }
+        }
+    }
+
+    fn run_channels(&mut self, wave_ram: &[u8]) {
+        if self.square1.enabled {
+            self.square1.run(M_CYCLE);
+        }
+        if self.square2.enabled {
+            self.square2.run(M_CYCLE);
+        }
+        if self.wave.enabled {
+            self.wave.run(M_CYCLE, wave_ram);
+        }
+        if self.noise.enabled {
+            self.noise.run(M_CYCLE);
+        }
+    }
+
+    /// Steps 0, 2, 4 and 6 clock the length counters, 2 and 6 the sweep, 7 the envelopes.
+    fn clock_frame_sequencer(&mut self) {
+        let step = self.sequencer_step;
+        self.sequencer_step = (step + 1) % 8;
+        if step.is_multiple_of(2) {
+            if self.square1.length.clock() {
+                self.square1.enabled = false;
+            }
+            if self.square2.length.clock() {
+                self.square2.enabled = false;
+            }
+            if self.wave.length.clock() {
+                self.wave.enabled = false;
+            }
+            if self.noise.length.clock() {
+                self.noise.enabled = false;
+            }
+        }
+        if step == 2 || step == 6 {
+            let (frequency, keep_enabled) = self.sweep.clock();
+            if let Some(frequency) = frequency {
+                self.square1.frequency = frequency;
+                self.registers[3] = frequency as u8;
+                self.registers[4] = self.registers[4] & !0x07 | (frequency >> 8) as u8;
+            }
+            self.square1.enabled &= keep_enabled;
+        }
+        if step == 7 {
+            self.square1.envelope.clock();
+            self.square2.envelope.clock();
+            self.noise.envelope.clock();
+        }
+    }
+
+    fn mix(&mut self) {
+        let (mut left, mut right) = (0.0, 0.0);
+        if self.powered {
+            let panning = self.registers[0x15];
+            let playing = self.channel_status();
+            let outputs = [
+                (self.square1.dac, self.square1.output()),
+                (self.square2.dac, self.square2.output()),
+                (self.wave.dac, self.wave.output()),
+                (self.noise.dac, self.noise.output()),
+            ];
+            for (channel, (dac, digital)) in outputs.into_iter().enumerate() {
+                if !dac
+                    || !self.channels_enabled[channel]
+                    || (!self.config.dac_clicks && playing & (1 << channel) == 0)
+                {
+                    continue;
+                }
+                // The DACs map 0..=15 onto 1.0..=-1.0.
+                let analog = 1.0 - f64::from(digital) / 7.5;
+                if panning & (0x10 << channel) != 0 {
+                    left += analog;
+                }
+                if panning & (0x01 << channel) != 0 {
+                    right += analog;
+                }
+            }
+            let volume = self.registers[0x14];
+            left *= f64::from((volume >> 4) & 0x07) + 1.0;
+            right *= f64::from(volume & 0x07) + 1.0;
+        }
+        self.accumulated.0 += left;
+        self.accumulated.1 += right;
+        self.accumulated_cycles += 1;
+
+        self.sample_phase += self.sample_rate;
+        if self.sample_phase >= M_CYCLE_HZ {
+            self.sample_phase -= M_CYCLE_HZ;
+            // Four channels at up to 8x master volume scale to -1.0..=1.0.
+            let scale = 1.0 / (32.0 * f64::from(self.accumulated_cycles));
+            let (mut left, mut right) = (self.accumulated.0 * scale, self.accumulated.1 * scale);
+            if self.config.high_pass_filter {
+                left = self.high_pass(0, left);
+                right = self.high_pass(1, right);
+            }
+            self.push_frame(left as f32, right as f32);
+            self.accumulated = (0.0, 0.0);
+            self.accumulated_cycles = 0;
+        }
+    }
+
+    fn high_pass(&mut self, side: usize, input: f64) -> f64 {
+        let capacitor = if side == 0 {
+            &mut self.capacitor.0
+        } else {
+            &mut self.capacitor.1
+        };
+        let output = input - *capacitor;
+        *capacitor = input - output * self.high_pass_charge;
+        output
+    }
+
+    fn push_frame(&mut self, left: f32, right: f32) {
+        if self.samples.len() >= SAMPLE_BUFFER_FRAMES * 2 {
+            self.samples.drain(..2);
+            self.dropped_frames += 1;
+        }
+        self.samples.push_back(left);
+        self.samples.push_back(right);
+    }
+}
+
+fn frequency_high(frequency: u16, nrx4: u8) -> u16 {
+    frequency & 0xFF | u16::from(nrx4 & 0x07) << 8
+}
+
+impl Stateful for LengthCounter {
+    fn save_state(&self, out: &mut StateWriter) {
+        out.u16(self.remaining);
+        out.bool(self.enabled);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader<'_>) -> Result<(), StateError> {
+        self.remaining = input.u16()?;
+        self.enabled = input.bool()?;
+        Ok(())
+    }
+}
+
+impl Stateful for Envelope {
+    fn save_state(&self, out: &mut StateWriter) {
+        out.u8(self.volume);
+        out.bool(self.increase);
+        out.u8(self.period);
+        out.u8(self.timer);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader<'_>) -> Result<(), StateError> {
+        self.volume = input.u8()?;
+        self.increase = input.bool()?;
+        self.period = input.u8()?;
+        self.timer = input.u8()?;
+        if self.volume > 15 {
+            return Err(StateError::Invalid("envelope volume"));
+        }
+        Ok(())
+    }
+}
+
+impl Stateful for Square {
+    fn save_state(&self, out: &mut StateWriter) {
+        out.bool(self.enabled);
+        out.bool(self.dac);
+        out.u8(self.duty);
+        out.u8(self.duty_step);
+        out.u16(self.frequency);
+        out.u32(self.timer);
+        self.length.save_state(out);
+        self.envelope.save_state(out);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader<'_>) -> Result<(), StateError> {
+        self.enabled = input.bool()?;
+        self.dac = input.bool()?;
+        self.duty = input.u8()?;
+        self.duty_step = input.u8()?;
+        self.frequency = input.u16()?;
+        self.timer = input.u32()?;
+        self.length.load_state(input)?;
+        self.envelope.load_state(input)?;
+        if self.duty > 3 || self.duty_step > 7 || self.frequency > 0x7FF {
+            return Err(StateError::Invalid("square channel"));
+        }
+        Ok(())
+    }
+}
+
+impl Stateful for Sweep {
+    fn save_state(&self, out: &mut StateWriter) {
+        out.bool(self.enabled);
+        out.u16(self.shadow);
+        out.u8(self.timer);
+        out.u8(self.period);
+        out.bool(self.negate);
+        out.u8(self.shift);
+        out.bool(self.negated);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader<'_>) -> Result<(), StateError> {
+        self.enabled = input.bool()?;
+        self.shadow = input.u16()?;
+        self.timer = input.u8()?;
+        self.period = input.u8()?;
+        self.negate = input.bool()?;
+        self.shift = input.u8()?;
+        self.negated = input.bool()?;
+        Ok(())
+    }
+}
+
+impl Stateful for Wave {
+    fn save_state(&self, out: &mut StateWriter) {
+        out.bool(self.enabled);
+        out.bool(self.dac);
+        out.u8(self.volume_code);
+        out.u16(self.frequency);
+        out.u32(self.timer);
+        out.u8(self.position);
+        out.u8(self.sample);
+        out.bool(self.just_read);
+        self.length.save_state(out);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader<'_>) -> Result<(), StateError> {
+        self.enabled = input.bool()?;
+        self.dac = input.bool()?;
+        self.volume_code = input.u8()?;
+        self.frequency = input.u16()?;
+        self.timer = input.u32()?;
+        self.position = input.u8()?;
+        self.sample = input.u8()?;
+        self.just_read = input.bool()?;
+        self.length.load_state(input)?;
+        if self.volume_code > 3 || self.frequency > 0x7FF || self.position > 31 {
+            return Err(StateError::Invalid("wave channel"));
+        }
+        Ok(())
+    }
+}
+
+impl Stateful for Noise {
+    fn save_state(&self, out: &mut StateWriter) {
+        out.bool(self.enabled);
+        out.bool(self.dac);
+        out.u8(self.shift);
+        out.bool(self.narrow);
+        out.u8(self.divisor);
+        out.u32(self.timer);
+        out.u16(self.lfsr);
+        self.length.save_state(out);
+        self.envelope.save_state(out);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader<'_>) -> Result<(), StateError> {
+        self.enabled = input.bool()?;
+        self.dac = input.bool()?;
+        self.shift = input.u8()?;
+        self.narrow = input.bool()?;
+        self.divisor = input.u8()?;
+        self.timer = input.u32()?;
+        self.lfsr = input.u16()?;
+        self.length.load_state(input)?;
+        self.envelope.load_state(input)?;
+        if self.shift > 15 || usize::from(self.divisor) >= NOISE_DIVISORS.len() {
+            return Err(StateError::Invalid("noise channel"));
+        }
+        Ok(())
+    }
+}
+
+/// Buffered output samples and the sample rate belong to the frontend and are not saved;
+/// the resampler and filter phases are, so loading continues the same waveform.
+impl Stateful for Apu {
+    fn save_state(&self, out: &mut StateWriter) {
+        out.bytes(&self.registers);
+        out.bool(self.powered);
+        self.square1.save_state(out);
+        self.sweep.save_state(out);
+        self.square2.save_state(out);
+        self.wave.save_state(out);
+        self.noise.save_state(out);
+        out.u16(self.divider);
+        out.u8(self.sequencer_step);
+        out.bool(self.skip_sequencer_edge);
+        out.u32(self.pending_cycles);
+        out.u32(self.sample_phase);
+        out.f64(self.accumulated.0);
+        out.f64(self.accumulated.1);
+        out.u32(self.accumulated_cycles);
+        out.f64(self.capacitor.0);
+        out.f64(self.capacitor.1);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader<'_>) -> Result<(), StateError> {
+        input.bytes_into(&mut self.registers)?;
+        self.powered = input.bool()?;
+        self.square1.load_state(input)?;
+        self.sweep.load_state(input)?;
+        self.square2.load_state(input)?;
+        self.wave.load_state(input)?;
+        self.noise.load_state(input)?;
+        self.divider = input.u16()?;
+        self.sequencer_step = input.u8()?;
+        self.skip_sequencer_edge = input.bool()?;
+        self.pending_cycles = input.u32()?;
+        self.sample_phase = input.u32()?;
+        self.accumulated = (input.f64()?, input.f64()?);
+        self.accumulated_cycles = input.u32()?;
+        self.capacitor = (input.f64()?, input.f64()?);
+        if self.sequencer_step > 7 {
+            return Err(StateError::Invalid("frame sequencer step"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GameBoy, CYCLES_PER_FRAME, DIV_ADDR};
+
+    const SEQUENCER_PERIOD: u32 = 8192;
+
+    fn powered_apu() -> Apu {
+        let mut apu = Apu::default();
+        apu.write_register(NR52_ADDR, 0x80);
+        apu.write_register(NR50_ADDR, 0x77);
+        apu.write_register(NR51_ADDR, 0xFF);
+        apu
+    }
+
+    #[test]
+    fn square_channel_produces_a_resampled_stereo_tone() {
+        let wave_ram = [0; 16];
+        let mut apu = powered_apu();
+        apu.write_register(NR11_ADDR, 0x80);
+        apu.write_register(NR12_ADDR, 0xF0);
+        // 1 kHz-ish: 131072 / (2048 - 1917).
+        apu.write_register(NR13_ADDR, 0x7D);
+        apu.write_register(NR14_ADDR, 0x87);
+        assert_eq!(apu.read_register(NR52_ADDR), 0xF1);
+
+        apu.tick(CPU_CLOCK_HZ / 8, &wave_ram);
+        assert_eq!(apu.buffered_frames(), 6000);
+        let samples = apu.take_samples();
+        assert_eq!(samples.len(), 12_000);
+        assert!(samples.iter().all(|sample| (-1.0..=1.0).contains(sample)));
+        assert!(samples.iter().any(|&sample| sample > 0.2));
+        assert!(samples.iter().any(|&sample| sample < -0.2));
+        assert!(samples.chunks(2).all(|frame| frame[0] == frame[1]));
+        assert_eq!(apu.buffered_frames(), 0);
+    }
+
+    #[test]
+    fn audio_config_toggles_the_high_pass_filter_and_dac_clicks() {
+        let wave_ram = [0; 16];
+        // Channel 2's DAC on but the channel never triggered: a pure DC level.
+        let silent_dac = |config: AudioConfig| {
+            let mut apu = powered_apu();
+            apu.set_config(config);
+            apu.write_register(NR22_ADDR, 0xF0);
+            apu.tick(CPU_CLOCK_HZ / 4, &wave_ram);
+            apu.take_samples()
+        };
+
+        let raw = silent_dac(AudioConfig {
+            high_pass_filter: false,
+            dac_clicks: true,
+        });
+        assert!(raw.iter().all(|&sample| (sample - 0.25).abs() < 1e-6));
+
+        let filtered = silent_dac(AudioConfig::default());
+        assert!(filtered[filtered.len() - 1].abs() < 0.01, "decayed");
+        assert!(filtered[filtered.len() - 1].abs() < 0.01, "decayed");
+
+        let quiet = silent_dac(AudioConfig {
+            high_pass_filter: false,
+            dac_clicks: false,
+        });
+        assert!(quiet.iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn muted_channels_and_disabled_output_stay_silent() {
+        let wave_ram = [0; 16];
+        let mut apu = powered_apu();
+        apu.set_config(AudioConfig {
+            high_pass_filter: false,
+            dac_clicks: true,
+        });
+        apu.write_register(NR22_ADDR, 0xF0);
+        apu.write_register(NR42_ADDR, 0xF0);
+        for channel in Channel::ALL {
+            apu.set_channel_enabled(channel, channel == Channel::Noise);
+        }
+        assert!(!apu.channel_enabled(Channel::Square2));
+        apu.tick(CPU_CLOCK_HZ / 100, &wave_ram);
+        let solo = apu.take_samples();
+        assert!(
+            solo.iter().all(|&sample| (sample - 0.25).abs() < 1e-6),
+            "only the noise DAC's level"
+        );
+
+        apu.set_channel_enabled(Channel::Noise, false);
+        apu.tick(CPU_CLOCK_HZ / 100, &wave_ram);
+        // The first frame still averages in the noise DAC from before the mute.
+        let muted = apu.take_samples();
+        assert!(muted[2..].iter().all(|&sample| sample == 0.0));
+
+        apu.set_output_enabled(false);
+        apu.write_register(NR44_ADDR, 0x80);
+        apu.tick(CPU_CLOCK_HZ / 100, &wave_ram);
+        assert_eq!(apu.buffered_frames(), 0);
+        assert_eq!(apu.channel_status(), 0x08, "channels still run");
+    }
+
+    #[test]
+    fn panning_routes_channels_to_one_side() {
+        let wave_ram = [0; 16];
+        let mut apu = powered_apu();
+        apu.write_register(NR51_ADDR, 0x02);
+        apu.write_register(NR21_ADDR, 0x80);
+        apu.write_register(NR22_ADDR, 0xF0);
+        apu.write_register(NR24_ADDR, 0x87);
+        apu.tick(CYCLES_PER_FRAME, &wave_ram);
+
+        let samples = apu.take_samples();
+        assert!(samples.chunks(2).all(|frame| frame[0] == 0.0));
+        assert!(samples.chunks(2).any(|frame| frame[1] != 0.0));
+    }
+
+    #[test]
+    fn length_counter_stops_the_channel() {
+        let wave_ram = [0; 16];
+        let mut apu = powered_apu();
+        apu.write_register(NR41_ADDR, 0x3E);
+        apu.write_register(NR42_ADDR, 0xF0);
+        apu.write_register(NR44_ADDR, TRIGGER | LENGTH_ENABLE);
+        assert_eq!(apu.channel_status(), 0x08);
+
+        apu.tick(SEQUENCER_PERIOD, &wave_ram);
+        assert_eq!(apu.channel_status(), 0x08, "one length clock left");
+        apu.tick(SEQUENCER_PERIOD * 2, &wave_ram);
+        assert_eq!(apu.channel_status(), 0x00);
+    }
+
+    #[test]
+    fn enabling_length_in_the_first_half_clocks_it_early() {
+        let wave_ram = [0; 16];
+        let mut apu = powered_apu();
+        apu.tick(SEQUENCER_PERIOD, &wave_ram);
+        assert_eq!(apu.sequencer_step, 1, "next step skips length");
+
+        apu.write_register(NR42_ADDR, 0xF0);
+        apu.write_register(NR41_ADDR, 0x3E);
+        apu.write_register(NR44_ADDR, TRIGGER);
+        apu.write_register(NR44_ADDR, LENGTH_ENABLE);
+        assert_eq!(apu.noise.length.remaining, 1);
+        assert_eq!(apu.channel_status(), 0x08);
+        apu.write_register(NR44_ADDR, 0x00);
+        apu.write_register(NR44_ADDR, LENGTH_ENABLE);
+        assert_eq!(apu.channel_status(), 0x00, "the extra clock expired it");
+
+        apu.write_register(NR44_ADDR, TRIGGER | LENGTH_ENABLE);
+        assert_eq!(apu.channel_status(), 0x08);
+        assert_eq!(apu.noise.length.remaining, 63, "reloaded one short");
+
+        apu.tick(SEQUENCER_PERIOD, &wave_ram);
+        apu.write_register(NR41_ADDR, 0x3F);
+        apu.write_register(NR44_ADDR, 0x00);
+        apu.write_register(NR44_ADDR, LENGTH_ENABLE);
+        assert_eq!(
+            apu.noise.length.remaining, 1,
+            "no extra clock before a length step"
+        );
+    }
+
+    #[test]
+    fn div_writes_step_the_frame_sequencer() {
+        let mut gb = GameBoy::new();
+        gb.bus.write_byte(NR52_ADDR, 0x80);
+        gb.bus.tick(SEQUENCER_PERIOD / 2 - 4);
+        gb.bus.write_byte(DIV_ADDR, 0x00);
+        assert_eq!(
+            gb.bus.apu().sequencer_step,
+            0,
+            "sequencer bit was still clear"
+        );
+
+        gb.bus.tick(SEQUENCER_PERIOD / 2);
+        gb.bus.write_byte(DIV_ADDR, 0x00);
+        assert_eq!(gb.bus.apu().sequencer_step, 1);
+        gb.bus.tick(SEQUENCER_PERIOD - 4);
+        assert_eq!(gb.bus.apu().sequencer_step, 1);
+        gb.bus.tick(4);
+        assert_eq!(gb.bus.apu().sequencer_step, 2);
+    }
+
+    #[test]
+    fn powering_on_with_the_sequencer_bit_set_skips_its_next_edge() {
+        let mut gb = GameBoy::new();
+        gb.bus.tick(SEQUENCER_PERIOD / 2);
+        gb.bus.write_byte(NR52_ADDR, 0x80);
+        gb.bus.tick(SEQUENCER_PERIOD / 2);
+        assert_eq!(gb.bus.apu().sequencer_step, 0, "first edge swallowed");
+        gb.bus.tick(SEQUENCER_PERIOD);
+        assert_eq!(gb.bus.apu().sequencer_step, 1);
+
+        gb.bus.write_byte(NR52_ADDR, 0x00);
+        gb.bus.tick(SEQUENCER_PERIOD);
+        gb.bus.write_byte(NR52_ADDR, 0x80);
+        gb.bus.tick(SEQUENCER_PERIOD);
+        assert_eq!(gb.bus.apu().sequencer_step, 1, "bit was clear at power-on");
+    }
+
+    #[test]
+    fn clearing_negate_after_a_subtraction_disables_channel_one() {
+        let mut apu = powered_apu();
+        apu.write_register(NR10_ADDR, 0x19);
+        apu.write_register(NR12_ADDR, 0xF0);
+        apu.write_register(NR14_ADDR, 0x84);
+        assert_eq!(apu.channel_status(), 0x01);
+        apu.write_register(NR10_ADDR, 0x11);
+        assert_eq!(apu.channel_status(), 0x00);
+
+        apu.write_register(NR14_ADDR, 0x84);
+        apu.write_register(NR10_ADDR, 0x11);
+        assert_eq!(
+            apu.channel_status(),
+            0x01,
+            "no subtraction since the trigger"
+        );
+    }
+
+    fn clock_sweep(apu: &mut Apu, times: usize) {
+        for _ in 0..times * 4 {
+            apu.clock_frame_sequencer();
+        }
+    }
+
+    #[test]
+    fn sweep_steps_the_shadow_frequency_not_later_nr13_writes() {
+        let mut apu = powered_apu();
+        apu.write_register(NR10_ADDR, 0x11);
+        apu.write_register(NR12_ADDR, 0xF0);
+        apu.write_register(NR13_ADDR, 0x00);
+        apu.write_register(NR14_ADDR, 0x82);
+        apu.write_register(NR13_ADDR, 0xFF);
+        apu.write_register(NR14_ADDR, 0x07);
+
+        clock_sweep(&mut apu, 1);
+        assert_eq!(apu.square1.frequency, 0x300);
+        assert_eq!(apu.read_register(NR13_ADDR), 0xFF, "NR13 is write-only");
+        clock_sweep(&mut apu, 1);
+        assert_eq!(apu.square1.frequency, 0x480);
+        assert_eq!(apu.channel_status(), 0x01);
+
+        // 0x6C0 fits, but the second overflow check (0xA20) disables the channel.
+        clock_sweep(&mut apu, 1);
+        assert_eq!(apu.square1.frequency, 0x6C0);
+        assert_eq!(apu.channel_status(), 0x00);
+    }
+
+    #[test]
+    fn sweep_period_zero_counts_as_eight_until_the_next_reload() {
+        let mut apu = powered_apu();
+        apu.write_register(NR10_ADDR, 0x01);
+        apu.write_register(NR12_ADDR, 0xF0);
+        apu.write_register(NR14_ADDR, 0x82);
+        apu.write_register(NR10_ADDR, 0x11);
+
+        clock_sweep(&mut apu, 7);
+        assert_eq!(apu.square1.frequency, 0x200);
+        clock_sweep(&mut apu, 1);
+        assert_eq!(apu.square1.frequency, 0x300);
+        clock_sweep(&mut apu, 1);
+        assert_eq!(apu.square1.frequency, 0x480, "new period after the reload");
+
+        apu.write_register(NR10_ADDR, 0x00);
+        apu.write_register(NR13_ADDR, 0x00);
+        apu.write_register(NR14_ADDR, 0x82);
+        apu.write_register(NR10_ADDR, 0x11);
+        clock_sweep(&mut apu, 16);
+        assert_eq!(
+            apu.square1.frequency, 0x200,
+            "period and shift of 0 at trigger leave sweep off"
+        );
+    }
+
+    #[test]
+    fn dmg_length_counters_survive_power_off() {
+        let mut apu = powered_apu();
+        apu.write_register(NR21_ADDR, 0x30);
+        apu.write_register(NR52_ADDR, 0x00);
+        assert_eq!(apu.square2.length.remaining, 16);
+        apu.write_register(NR41_ADDR, 0x3C);
+        apu.write_register(NR52_ADDR, 0x80);
+        assert_eq!(apu.noise.length.remaining, 4);
+        assert_eq!(apu.square2.length.remaining, 16);
+        assert_eq!(apu.read_register(NR21_ADDR), 0x3F);
+    }
+
+    #[test]
+    fn cgb_length_counters_clear_on_power_off() {
+        let mut apu = powered_apu();
+        apu.set_model(Model::Cgb);
+        apu.write_register(NR21_ADDR, 0x30);
+        apu.write_register(NR52_ADDR, 0x00);
+        assert_eq!(apu.square2.length.remaining, 0);
+        apu.write_register(NR41_ADDR, 0x3C);
+        apu.write_register(NR52_ADDR, 0x80);
+        assert_eq!(
+            apu.noise.length.remaining, 0,
+            "the load is ignored while off"
+        );
+    }
+
+    #[test]
+    fn power_off_clears_registers_and_ignores_writes() {
+        let mut apu = powered_apu();
+        apu.write_register(NR12_ADDR, 0xF3);
+        apu.write_register(NR14_ADDR, 0x80);
+        assert_eq!(apu.read_register(NR52_ADDR), 0xF1);
+
+        apu.write_register(NR52_ADDR, 0x00);
+        assert_eq!(apu.read_register(NR52_ADDR), 0x70);
+        assert_eq!(apu.read_register(NR12_ADDR), 0x00);
+        apu.write_register(NR50_ADDR, 0x77);
+        assert_eq!(apu.read_register(NR50_ADDR), 0x00);
+        assert_eq!(apu.read_register(NR10_ADDR), 0x80);
+    }
+
+    #[test]
+    fn sweep_overflow_on_trigger_disables_channel_one() {
+        let mut apu = powered_apu();
+        apu.write_register(NR10_ADDR, 0x11);
+        apu.write_register(NR12_ADDR, 0xF0);
+        apu.write_register(NR13_ADDR, 0xFF);
+        apu.write_register(NR14_ADDR, 0x87);
+        assert_eq!(apu.channel_status(), 0x00);
+    }
+
+    #[test]
+    fn wave_ram_is_only_reachable_when_the_playing_channel_fetches() {
+        let mut wave_ram = [0; 16];
+        let mut apu = powered_apu();
+        for (offset, address) in (WAVE_RAM_ADDR..=WAVE_RAM_END).enumerate() {
+            apu.write_wave_ram(address, offset as u8 * 0x11, &mut wave_ram);
+        }
+        assert_eq!(apu.read_wave_ram(0xFF35, &wave_ram), 0x55);
+
+        // Period 8: a fetch every other M-cycle.
+        apu.write_register(NR30_ADDR, 0x80);
+        apu.write_register(NR33_ADDR, 0xFC);
+        apu.write_register(NR34_ADDR, 0x87);
+        apu.tick(4, &wave_ram);
+        if !apu.wave.just_read {
+            apu.tick(4, &wave_ram);
+        }
+        let current = apu.wave.ram_index();
+        assert_ne!(current, 15);
+        assert_eq!(apu.read_wave_ram(0xFF3F, &wave_ram), wave_ram[current]);
+        apu.write_wave_ram(0xFF3F, 0xAB, &mut wave_ram);
+        assert_eq!(wave_ram[current], 0xAB);
+        assert_eq!(wave_ram[15], 0xFF);
+
+        apu.tick(4, &wave_ram);
+        assert!(!apu.wave.just_read);
+        assert_eq!(apu.read_wave_ram(0xFF30, &wave_ram), 0xFF);
+        apu.write_wave_ram(0xFF30, 0x12, &mut wave_ram);
+        assert!(!wave_ram.contains(&0x12));
+
+        apu.write_register(NR30_ADDR, 0x00);
+        assert_eq!(apu.read_wave_ram(0xFF3F, &wave_ram), 0xFF);
+        assert_eq!(apu.read_wave_ram(0xFF31, &wave_ram), 0x11);
+    }
+
+    #[test]
+    fn full_buffer_drops_the_oldest_frames() {
+        let wave_ram = [0; 16];
+        let mut apu = powered_apu();
+        apu.tick(CPU_CLOCK_HZ, &wave_ram);
+        assert_eq!(apu.buffered_frames(), SAMPLE_BUFFER_FRAMES);
+        assert_eq!(
+            apu.dropped_frames(),
+            u64::from(DEFAULT_SAMPLE_RATE) - SAMPLE_BUFFER_FRAMES as u64
+        );
+
+        let mut out = [0.0; 5];
+        assert_eq!(apu.read_samples(&mut out), 4);
+        assert_eq!(apu.buffered_frames(), SAMPLE_BUFFER_FRAMES - 2);
+    }
+
+    #[test]
+    fn game_boy_exposes_samples_from_bus_writes() {
+        // JR -2
+        let mut gb = GameBoy::with_program(0x0000, &[0x18, 0xFE]);
+        gb.set_audio_sample_rate(32_000);
+        gb.bus.write_byte(NR52_ADDR, 0x80);
+        gb.bus.write_byte(NR51_ADDR, 0x44);
+        gb.bus.write_byte(NR50_ADDR, 0x77);
+        gb.bus.write_byte(0xFF30, 0xF0);
+        gb.bus.write_byte(NR30_ADDR, 0x80);
+        gb.bus.write_byte(NR32_ADDR, 0x20);
+        gb.bus.write_byte(NR34_ADDR, 0x87);
+        assert_eq!(gb.bus.read_byte(NR52_ADDR), 0xF4);
+
+        gb.run_steps(10_000).expect("loop should run");
+        let frames = gb.audio_buffered_frames();
+        let expected = gb.cycle_count() * 32_000 / u64::from(CPU_CLOCK_HZ);
+        assert!(frames.abs_diff(expected as usize) <= 1);
+        let samples = gb.take_audio_samples();
+        assert_eq!(samples.len(), frames * 2);
+        assert!(samples.iter().any(|&sample| sample.abs() > 0.1));
+    }
+}
@@ -0,0 +1,147 @@
+use crate::cartridge::ROM_BANK_SIZE;
+use std::cell::Cell;
+use std::fmt::{Display, Formatter};
+
+const RAM_BANK_SIZE: usize = 0x2000;
+
+/// Which cartridge banks the CPU has read, for profiling what a game actually uses.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BankStats {
+    /// CPU reads per ROM bank, indexed by bank number. Bank 0 counts reads of
+    /// 0x0000-0x3FFF as well as of bank 0 mapped at 0x4000.
+    pub rom_reads: Vec<u64>,
+    /// CPU reads per 8 KiB RAM bank; MBC2's built-in RAM is one bank.
+    pub ram_reads: Vec<u64>,
+    /// Writes that switched the RAM gate on, and off. Rewriting the current state does
+    /// not count.
+    pub ram_enables: u64,
+    pub ram_disables: u64,
+}
+
+impl BankStats {
+    /// ROM banks read at least once, in bank order.
+    pub fn rom_banks_used(&self) -> Vec<usize> {
+        used(&self.rom_reads)
+    }
+
+    pub fn ram_banks_used(&self) -> Vec<usize> {
+        used(&self.ram_reads)
+    }
+}
+
+fn used(reads: &[u64]) -> Vec<usize> {
+    (0..reads.len()).filter(|&bank| reads[bank] > 0).collect()
+}
+
+impl Display for BankStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "rom_banks_used={}/{} ram_banks_used={}/{} ram_enables={} ram_disables={}",
+            self.rom_banks_used().len(),
+            self.rom_reads.len(),
+            self.ram_banks_used().len(),
+            self.ram_reads.len(),
+            self.ram_enables,
+            self.ram_disables
+        )
+    }
+}
+
+/// Kept by each mapper, which alone knows the bank behind an address. Reads are counted
+/// through `Cell`s because the bus reads cartridges by shared reference.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BankRecorder {
+    rom_reads: Box<[Cell<u64>]>,
+    ram_reads: Box<[Cell<u64>]>,
+    ram_enables: u64,
+    ram_disables: u64,
+}
+
+impl BankRecorder {
+    pub(crate) fn new(rom_banks: usize, ram_size: usize) -> Self {
+        let counters = |len: usize| (0..len).map(|_| Cell::new(0)).collect();
+        Self {
+            rom_reads: counters(rom_banks),
+            ram_reads: counters(ram_size.div_ceil(RAM_BANK_SIZE)),
+            ram_enables: 0,
+            ram_disables: 0,
+        }
+    }
+
+    /// A read at `offset` into the ROM image.
+    pub(crate) fn record_rom(&self, offset: usize) {
+        increment(&self.rom_reads, offset / ROM_BANK_SIZE);
+    }
+
+    /// A read at `index` into external RAM.
+    pub(crate) fn record_ram(&self, index: usize) {
+        increment(&self.ram_reads, index / RAM_BANK_SIZE);
+    }
+
+    pub(crate) fn record_ram_gate(&mut self, was_enabled: bool, enabled: bool) {
+        match (was_enabled, enabled) {
+            (false, true) => self.ram_enables += 1,
+            (true, false) => self.ram_disables += 1,
+            _ => {}
+        }
+    }
+
+    pub(crate) fn stats(&self) -> BankStats {
+        let counts = |reads: &[Cell<u64>]| reads.iter().map(Cell::get).collect();
+        BankStats {
+            rom_reads: counts(&self.rom_reads),
+            ram_reads: counts(&self.ram_reads),
+            ram_enables: self.ram_enables,
+            ram_disables: self.ram_disables,
+        }
+    }
+}
+
+fn increment(counts: &[Cell<u64>], bank: usize) {
+    if let Some(count) = counts.get(bank) {
+        count.set(count.get() + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GameBoy;
+
+    #[test]
+    fn counts_cpu_reads_per_bank_and_ram_gate_switches() {
+        let mut rom = vec![0; 8 * ROM_BANK_SIZE];
+        rom[0x147] = 0x03; // MBC1+RAM+BATTERY
+        rom[0x149] = 0x03;
+        rom[0x100..0x113].copy_from_slice(&[
+            0x3E, 0x05, 0xEA, 0x00, 0x20, // select ROM bank 5
+            0xFA, 0x00, 0x40, // LD A,($4000)
+            0x3E, 0x0A, 0xEA, 0x00, 0x00, // enable RAM
+            0xEA, 0x00, 0x00, // and again
+            0xFA, 0x00, 0xA0, // LD A,($A000)
+        ]);
+        let mut gb = GameBoy::new();
+        gb.load_rom(&rom);
+        gb.cpu.pc = 0x0100;
+        gb.run_steps(7).expect("program should run");
+        gb.bus.write_byte(0x0000, 0x00);
+
+        let stats = gb.bus.cartridge().expect("cartridge").stats();
+        assert_eq!(stats.rom_banks_used(), [0, 5]);
+        assert_eq!(stats.rom_reads[5], 1);
+        assert_eq!(stats.ram_reads, [1, 0, 0, 0]);
+        assert_eq!((stats.ram_enables, stats.ram_disables), (1, 1));
+        assert_eq!(
+            stats.to_string(),
+            "rom_banks_used=2/8 ram_banks_used=1/4 ram_enables=1 ram_disables=1"
+        );
+
+        let _ = gb.bus.peek(0x4000);
+        assert_eq!(
+            gb.bus.cartridge().expect("cartridge").stats(),
+            stats,
+            "debugger peeks do not count"
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{GameBoy, RTC_DAY_HIGH, RTC_SECONDS};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The RTC footer BGB, VBA-M and SameBoy append to MBC3 clock saves: the running and
+/// latched registers as ten little-endian u32s, then the UNIX time the file was written
+/// as a little-endian u64.
+pub const RTC_FOOTER_LEN: usize = 48;
+// Older emulators write the timestamp as a u32.
+const RTC_FOOTER_LEN_32: usize = 44;
+const RTC_REGISTERS: usize = (RTC_DAY_HIGH - RTC_SECONDS + 1) as usize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SaveFileError {
+    NoCartridge,
+    /// The file is neither the cartridge RAM size nor, on clock carts, that plus a footer.
+    Size {
+        ram: usize,
+        actual: usize,
+    },
+}
+
+impl Display for SaveFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoCartridge => f.write_str("no cartridge inserted"),
+            Self::Size { ram, actual } => write!(
+                f,
+                "save file is {actual} bytes but the cartridge has {ram} bytes of RAM"
+            ),
+        }
+    }
+}
+
+impl Error for SaveFileError {}
+
+impl GameBoy {
+    /// The cartridge's battery-backed state as a `.sav` file: its external RAM, plus the
+    /// RTC footer on clock carts. `None` without a cartridge.
+    pub fn save_file(&self) -> Option<Vec<u8>> {
+        self.save_file_at(unix_time())
+    }
+
+    /// `save_file` with the footer stamped as written at `unix_time`.
+    pub fn save_file_at(&self, unix_time: u64) -> Option<Vec<u8>> {
+        let cartridge = self.bus.cartridge()?;
+        let mut data = cartridge.ram().to_vec();
+        if cartridge.has_rtc() {
+            let rtc = self.bus.rtc();
+            for register in rtc.registers().into_iter().chain(rtc.latched()) {
+                data.extend_from_slice(&u32::from(register).to_le_bytes());
+            }
+            data.extend_from_slice(&unix_time.to_le_bytes());
+        }
+        Some(data)
+    }
+
+    /// Restores a `.sav` file from this or another emulator. A clock cart's RTC footer is
+    /// optional; when present the clock catches up on the time since the file was written.
+    pub fn load_save_file(&mut self, data: &[u8]) -> Result<(), SaveFileError> {
+        self.load_save_file_at(data, unix_time())
+    }
+
+    /// `load_save_file` as if loaded at `unix_time`.
+    pub fn load_save_file_at(&mut self, data: &[u8], unix_time: u64) -> Result<(), SaveFileError> {
+        let cartridge = self.bus.cartridge_mut().ok_or(SaveFileError::NoCartridge)?;
+        let ram = cartridge.ram().len();
+        let footer = &data[ram.min(data.len())..];
+        let footer_fits = matches!(footer.len(), 0 | RTC_FOOTER_LEN | RTC_FOOTER_LEN_32);
+        if data.len() < ram || !footer_fits || (!footer.is_empty() && !cartridge.has_rtc()) {
+            return Err(SaveFileError::Size {
+                ram,
+                actual: data.len(),
+            });
+        }
+        cartridge.ram_mut().copy_from_slice(&data[..ram]);
+        if footer.is_empty() {
+            return Ok(());
+        }
+
+        let word = |index: usize| {
+            let bytes = [0, 1, 2, 3].map(|offset| footer[index * 4 + offset]);
+            u32::from_le_bytes(bytes)
+        };
+        let register = |index: usize| word(index) as u8;
+        let current: [u8; RTC_REGISTERS] = std::array::from_fn(register);
+        let latched: [u8; RTC_REGISTERS] =
+            std::array::from_fn(|index| register(RTC_REGISTERS + index));
+        let saved_at = match footer.len() {
+            RTC_FOOTER_LEN => {
+                u64::from(word(2 * RTC_REGISTERS)) | u64::from(word(2 * RTC_REGISTERS + 1)) << 32
+            }
+            _ => u64::from(word(2 * RTC_REGISTERS)),
+        };
+        let rtc = self.bus.rtc_mut();
+        rtc.set_registers(current, latched);
+        rtc.advance(unix_time.saturating_sub(saved_at));
+        Ok(())
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RTC_HOURS, RTC_MINUTES};
+
+    fn clock_cart() -> GameBoy {
+        let mut rom = vec![0; 0x8000];
+        rom[0x147] = 0x10; // MBC3+TIMER+RAM+BATTERY
+        rom[0x149] = 0x02;
+        let mut gb = GameBoy::new();
+        gb.load_rom(&rom);
+        gb
+    }
+
+    #[test]
+    fn clock_saves_carry_a_footer_and_catch_up_on_load() {
+        let mut gb = clock_cart();
+        gb.bus.write_byte(0x0000, 0x0A);
+        gb.bus.write_byte(0xA010, 0x5A);
+        gb.bus.rtc_mut().write_register(RTC_MINUTES, 59);
+        gb.bus.rtc_mut().write_register(RTC_HOURS, 1);
+        gb.bus.rtc_mut().latch();
+
+        let save = gb.save_file_at(1_000).expect("a cartridge is inserted");
+        assert_eq!(save.len(), 0x2000 + RTC_FOOTER_LEN);
+        assert_eq!(save[0x10], 0x5A);
+        assert_eq!(&save[0x2000 + 4..0x2000 + 12], &[59, 0, 0, 0, 1, 0, 0, 0]);
+        assert_eq!(&save[0x2000 + 40..], &1_000u64.to_le_bytes());
+
+        let mut restored = clock_cart();
+        assert_eq!(restored.load_save_file_at(&save, 1_000 + 61), Ok(()));
+        assert_eq!(restored.bus.rtc().registers()[..3], [1, 0, 2]);
+        assert_eq!(restored.bus.rtc().latched()[..3], [0, 59, 1]);
+        assert_eq!(restored.save_file_at(0).expect("cartridge")[0x10], 0x5A);
+
+        // A 44-byte footer from an older emulator, and a save with no footer at all.
+        let mut short = save[..0x2000 + 40].to_vec();
+        short.extend_from_slice(&1_000u32.to_le_bytes());
+        let mut older = clock_cart();
+        assert_eq!(older.load_save_file_at(&short, 1_000), Ok(()));
+        assert_eq!(older.bus.rtc().registers()[..3], [0, 59, 1]);
+        assert_eq!(older.load_save_file_at(&save[..0x2000], 1_000), Ok(()));
+    }
+
+    #[test]
+    fn rejects_files_that_do_not_fit_the_cartridge() {
+        let mut gb = clock_cart();
+        assert_eq!(
+            gb.load_save_file(&[0; 0x2000 + 12]),
+            Err(SaveFileError::Size {
+                ram: 0x2000,
+                actual: 0x2000 + 12
+            })
+        );
+
+        let mut plain = GameBoy::new();
+        assert_eq!(
+            plain.load_save_file(&[0; 0x2000 + RTC_FOOTER_LEN]),
+            Err(SaveFileError::Size {
+                ram: 0x2000,
+                actual: 0x2000 + RTC_FOOTER_LEN
+            }),
+            "only clock carts take a footer"
+        );
+        assert_eq!(plain.save_file().map(|save| save.len()), Some(0x2000));
+        assert_eq!(
+            plain
+                .load_save_file(&[0; 0x10])
+                .map_err(|err| err.to_string()),
+            Err("save file is 16 bytes but the cartridge has 8192 bytes of RAM".to_string())
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use crate::{Button, CgbMode, GameBoy, Model, Rom};
+
+const TITLE_CHECKSUM_RANGE: std::ops::RangeInclusive<usize> = 0x134..=0x143;
+const WAVE_RAM_START: u16 = 0xFF30;
+const CGB_WAVE_RAM: [u8; 16] = [
+    0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF,
+];
+
+// IO registers as every boot ROM leaves them (sound, LCD, timer), then the ones that differ.
+const POST_BOOT_IO: [(u16, u8); 25] = [
+    (0xFF07, 0xF8),
+    (0xFF0F, 0xE1),
+    // Sound power first: the other sound registers ignore writes while it is off.
+    (0xFF26, 0xF1),
+    (0xFF10, 0x80),
+    (0xFF11, 0xBF),
+    (0xFF12, 0xF3),
+    (0xFF13, 0xFF),
+    (0xFF14, 0xBF),
+    (0xFF16, 0x3F),
+    (0xFF17, 0x00),
+    (0xFF18, 0xFF),
+    (0xFF19, 0xBF),
+    (0xFF1A, 0x7F),
+    (0xFF1B, 0xFF),
+    (0xFF1C, 0x9F),
+    (0xFF1D, 0xFF),
+    (0xFF1E, 0xBF),
+    (0xFF20, 0xFF),
+    (0xFF21, 0x00),
+    (0xFF22, 0x00),
+    (0xFF23, 0xBF),
+    (0xFF24, 0x77),
+    (0xFF25, 0xF3),
+    (0xFF40, 0x91),
+    (0xFF47, 0xFC),
+];
+const DMG_POST_BOOT_IO: [(u16, u8); 1] = [(0xFF02, 0x7E)];
+const CGB_POST_BOOT_IO: [(u16, u8); 2] = [(0xFF02, 0x7F), (0xFF70, 0xF8)];
+// The DMG and MGB boot ROMs always take as long, so DIV reads 0xAB at 0x0100. The CGB one
+// runs longer for DMG games, so DIV is left alone there.
+const DMG_POST_BOOT_DIVIDER: u16 = 0xABCC;
+const KEY0_ADDR: u16 = 0xFF4C;
+const KEY0_DMG_COMPAT: u8 = 0x04;
+const OPRI_ADDR: u16 = 0xFF6C;
+const OPRI_COORDINATE_PRIORITY: u8 = 0x01;
+
+const WHITE: u32 = 0xFFFFFF;
+const BLACK: u32 = 0x000000;
+const RED: [u32; 4] = [WHITE, 0xFF8484, 0x943A3A, BLACK];
+const BLUE: [u32; 4] = [WHITE, 0x63A5FF, 0x0000FF, BLACK];
+const GREEN: [u32; 4] = [WHITE, 0x7BFF31, 0x008400, BLACK];
+const BROWN: [u32; 4] = [WHITE, 0xFFAD63, 0x843100, BLACK];
+
+/// Background and object palettes (24-bit RGB) the CGB boot ROM assigns to DMG games.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompatPalettes {
+    pub bg: [u32; 4],
+    pub obj0: [u32; 4],
+    pub obj1: [u32; 4],
+}
+
+impl CompatPalettes {
+    const fn uniform(colors: [u32; 4]) -> Self {
+        Self {
+            bg: colors,
+            obj0: colors,
+            obj1: colors,
+        }
+    }
+
+    /// Palette used when no title match or button combination applies (same as Right+A).
+    pub const DEFAULT: Self = Self {
+        bg: [WHITE, 0x7BFF31, 0x0063C5, BLACK],
+        obj0: RED,
+        obj1: RED,
+    };
+}
+
+/// Palette picked by holding a direction (optionally with A or B) while the CGB logo shows.
+pub fn manual_compat_palettes(held: &[Button]) -> Option<CompatPalettes> {
+    let a = held.contains(&Button::A);
+    let b = held.contains(&Button::B);
+    let palettes = if held.contains(&Button::Up) {
+        match (a, b) {
+            (true, _) => CompatPalettes::uniform(RED),
+            (_, true) => CompatPalettes::uniform([0xFFE7C5, 0xCE9C84, 0x846B29, 0x5A3108]),
+            _ => CompatPalettes::uniform(BROWN),
+        }
+    } else if held.contains(&Button::Down) {
+        match (a, b) {
+            (true, _) => CompatPalettes::uniform([WHITE, 0xFFFF00, 0xFF0000, BLACK]),
+            (_, true) => CompatPalettes {
+                bg: [WHITE, 0xFFFF00, 0x7B4A00, BLACK],
+                obj0: BLUE,
+                obj1: GREEN,
+            },
+            _ => CompatPalettes::uniform([0xFFFFA5, 0xFF9494, 0x9494FF, BLACK]),
+        }
+    } else if held.contains(&Button::Left) {
+        match (a, b) {
+            (true, _) => CompatPalettes {
+                bg: [WHITE, 0x8C8CDE, 0x52528C, BLACK],
+                obj0: RED,
+                obj1: BROWN,
+            },
+            (_, true) => CompatPalettes::uniform([WHITE, 0xA5A5A5, 0x525252, BLACK]),
+            _ => CompatPalettes {
+                bg: BLUE,
+                obj0: RED,
+                obj1: BLUE,
+            },
+        }
+    } else if held.contains(&Button::Right) {
+        match (a, b) {
+            (true, _) => CompatPalettes::DEFAULT,
+            (_, true) => CompatPalettes::uniform([BLACK, 0x008484, 0xFFDE00, WHITE]),
+            _ => CompatPalettes::uniform([WHITE, 0x52FF00, 0xFF4200, BLACK]),
+        }
+    } else {
+        return None;
+    };
+    Some(palettes)
+}
+
+/// Sum of the title bytes, which the boot ROM uses to key its per-title palette table.
+pub fn title_checksum(rom_data: &[u8]) -> u8 {
+    rom_data
+        .get(TITLE_CHECKSUM_RANGE)
+        .unwrap_or_default()
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// Picks the compatibility palettes for a DMG game, or `None` for CGB-aware titles, which
+/// load their own palettes.
+///
+/// The real boot ROM also recognises a list of Nintendo-published titles by
+/// `title_checksum`; that table is not reproduced, so those titles get the default too.
+pub fn select_compat_palettes(rom: &Rom, held: &[Button]) -> Option<CompatPalettes> {
+    if matches!(rom.header.cgb_mode, CgbMode::CgbEnhanced | CgbMode::CgbOnly) {
+        return None;
+    }
+    Some(manual_compat_palettes(held).unwrap_or(CompatPalettes::DEFAULT))
+}
+
+impl GameBoy {
+    /// A `model` console as its boot ROM hands over at 0x0100, without running one; see
+    /// `apply_post_boot_state`. `new` leaves every register zeroed instead.
+    pub fn new_with_model(model: Model) -> Self {
+        let mut gb = Self::new();
+        gb.apply_post_boot_state(model);
+        gb
+    }
+
+    /// Switches to `model` and sets the CPU registers, DIV and the IO registers to what its
+    /// boot ROM leaves, for test ROMs that check them. The DMG and MGB flags are those for
+    /// a nonzero header checksum, as on every licensed cartridge; the CGB values are those
+    /// for a CGB title, see `apply_cgb_boot_hle` for DMG games.
+    pub fn apply_post_boot_state(&mut self, model: Model) {
+        self.set_model(model);
+        self.write_post_boot_state(model);
+    }
+
+    fn write_post_boot_state(&mut self, model: Model) {
+        let regs = &mut self.cpu.regs;
+        if model == Model::Cgb {
+            (regs.a, regs.f) = (0x11, 0x80);
+            regs.set_bc(0x0000);
+            regs.set_de(0xFF56);
+            regs.set_hl(0x000D);
+        } else {
+            // The Pocket's boot ROM differs only in A, which games read to detect it.
+            regs.a = if model == Model::Mgb { 0xFF } else { 0x01 };
+            regs.f = 0xB0;
+            regs.set_bc(0x0013);
+            regs.set_de(0x00D8);
+            regs.set_hl(0x014D);
+        }
+        self.cpu.pc = 0x0100;
+        self.cpu.sp = 0xFFFE;
+
+        let model_io: &[(u16, u8)] = match model {
+            Model::Dmg | Model::Mgb => &DMG_POST_BOOT_IO,
+            Model::Cgb => &CGB_POST_BOOT_IO,
+        };
+        for &(address, value) in POST_BOOT_IO.iter().chain(model_io) {
+            self.bus.write_byte(address, value);
+        }
+        match model {
+            Model::Dmg | Model::Mgb => self.bus.set_divider(DMG_POST_BOOT_DIVIDER),
+            Model::Cgb => self.bus.load_bytes(WAVE_RAM_START, &CGB_WAVE_RAM),
+        }
+    }
+
+    /// Reproduces the observable effects of the CGB boot ROM without running it: CPU and IO
+    /// register values, wave RAM, and the DMG compatibility palettes for older games.
+    pub fn apply_cgb_boot_hle(&mut self, rom: &Rom, held: &[Button]) {
+        let palettes = select_compat_palettes(rom, held);
+
+        self.write_post_boot_state(Model::Cgb);
+        if palettes.is_some() {
+            self.cpu.regs.set_de(0x0008);
+            self.cpu.regs.set_hl(0x007C);
+            self.bus.write_byte(KEY0_ADDR, KEY0_DMG_COMPAT);
+            self.bus.write_byte(OPRI_ADDR, OPRI_COORDINATE_PRIORITY);
+        }
+        self.bus.set_compat_palettes(palettes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom_with(cgb_flag: u8, title: &[u8]) -> Rom {
+        let mut data = vec![0u8; 0x8000];
+        data[0x134..0x134 + title.len()].copy_from_slice(title);
+        data[0x143] = cgb_flag;
+        data[0x104..0x134].copy_from_slice(&crate::TEST_LOGO);
+        data[0x14D] = crate::calculate_header_checksum(&data);
+        Rom::from_bytes(data).expect("test ROM header should parse")
+    }
+
+    #[test]
+    fn manual_combinations_override_default() {
+        assert_eq!(manual_compat_palettes(&[]), None);
+        assert_eq!(
+            manual_compat_palettes(&[Button::Right, Button::A]),
+            Some(CompatPalettes::DEFAULT)
+        );
+        assert_eq!(
+            manual_compat_palettes(&[Button::Left, Button::B]).map(|p| p.bg),
+            Some([WHITE, 0xA5A5A5, 0x525252, BLACK])
+        );
+    }
+
+    #[test]
+    fn title_checksum_sums_title_bytes() {
+        let mut data = vec![0u8; 0x150];
+        data[0x134..0x138].copy_from_slice(b"ABCD");
+        assert_eq!(title_checksum(&data), (65 + 66 + 67 + 68) as u8);
+    }
+
+    #[test]
+    fn models_start_from_their_post_boot_state() {
+        let mut dmg = GameBoy::new_with_model(Model::Dmg);
+        dmg.load_rom(&rom_with(0x00, b"DMG TEST").data);
+        let regs = dmg.cpu.regs;
+        assert_eq!((regs.a, regs.f), (0x01, 0xB0), "loading a ROM keeps them");
+        assert_eq!((regs.bc(), regs.de(), regs.hl()), (0x0013, 0x00D8, 0x014D));
+        assert_eq!((dmg.cpu.pc, dmg.cpu.sp), (0x0100, 0xFFFE));
+        assert_eq!(dmg.bus.read_byte(0xFF04), 0xAB);
+        assert_eq!(dmg.bus.read_byte(0xFF40), 0x91);
+        assert_eq!(dmg.bus.read_byte(0xFF0F), 0xE1);
+
+        let mgb = GameBoy::new_with_model(Model::Mgb);
+        assert_eq!((mgb.model(), mgb.cpu.regs.a), (Model::Mgb, 0xFF));
+
+        let cgb = GameBoy::new_with_model(Model::Cgb);
+        assert_eq!((cgb.model(), cgb.cpu.regs.a), (Model::Cgb, 0x11));
+        assert_eq!(cgb.cpu.regs.de(), 0xFF56);
+        assert_eq!(cgb.bus.read_byte(WAVE_RAM_START + 1), 0xFF);
+        assert_eq!(cgb.bus.read_byte(0xFF04), 0x00);
+
+        let blank = GameBoy::new();
+        assert_eq!(
+            (blank.cpu.regs.a, blank.bus.read_byte(0xFF40)),
+            (0x00, 0x00)
+        );
+    }
+
+    #[test]
+    fn applies_dmg_compat_side_effects() {
+        let rom = rom_with(0x00, b"HOMEBREW");
+        let mut gb = GameBoy::new_with_model(Model::Cgb);
+        gb.load_rom(&rom.data);
+        gb.apply_cgb_boot_hle(&rom, &[Button::Down]);
+
+        assert_eq!(gb.cpu.regs.a, 0x11);
+        assert_eq!(gb.cpu.regs.de(), 0x0008);
+        assert_eq!(gb.bus.backing(KEY0_ADDR), Some(&KEY0_DMG_COMPAT));
+        assert_eq!(gb.bus.read_byte(KEY0_ADDR), 0xFF, "locked after boot");
+        assert_eq!(gb.bus.read_byte(0xFF40), 0x91);
+        assert_eq!(gb.bus.read_byte(WAVE_RAM_START + 1), 0xFF);
+        assert_eq!(gb.bus.compat_palettes().map(|p| p.bg[1]), Some(0xFF9494));
+    }
+
+    #[test]
+    fn cgb_titles_keep_their_own_palettes() {
+        let rom = rom_with(0x80, b"COLOR GAME");
+        let mut gb = GameBoy::new();
+        gb.apply_cgb_boot_hle(&rom, &[Button::Up]);
+        assert_eq!(gb.bus.compat_palettes(), None);
+        assert_eq!(gb.cpu.regs.de(), 0xFF56);
+    }
+}
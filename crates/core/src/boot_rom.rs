@@ -0,0 +1,138 @@
+use crate::{GameBoy, Registers};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// The DMG boot ROM, mapped over 0x0000-0x00FF.
+pub const DMG_BOOT_ROM_SIZE: usize = 0x100;
+/// The CGB boot ROM, mapped over 0x0000-0x00FF and 0x0200-0x08FF around the cartridge
+/// header.
+pub const CGB_BOOT_ROM_SIZE: usize = 0x900;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BootRomError {
+    pub len: usize,
+}
+
+impl Display for BootRomError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "boot ROM is {} bytes; expected {DMG_BOOT_ROM_SIZE} (DMG) or {CGB_BOOT_ROM_SIZE} (CGB)",
+            self.len
+        )
+    }
+}
+
+impl Error for BootRomError {}
+
+/// Whether `image` is the size of a DMG or CGB boot ROM.
+pub fn check_boot_rom(image: &[u8]) -> Result<(), BootRomError> {
+    match image.len() {
+        DMG_BOOT_ROM_SIZE | CGB_BOOT_ROM_SIZE => Ok(()),
+        len => Err(BootRomError { len }),
+    }
+}
+
+impl GameBoy {
+    /// Maps a DMG or CGB boot ROM over the cartridge and restarts the CPU at 0x0000 with
+    /// cleared registers, as at power-on. It stays mapped until the program writes to
+    /// 0xFF50, and loading a ROM meanwhile leaves the CPU to the boot ROM.
+    pub fn load_boot_rom(&mut self, image: &[u8]) -> Result<(), BootRomError> {
+        check_boot_rom(image)?;
+        self.bus.map_boot_rom(image.into());
+        self.cpu.regs = Registers::default();
+        self.cpu.pc = 0x0000;
+        self.cpu.sp = 0x0000;
+        Ok(())
+    }
+
+    pub fn boot_rom_mapped(&self) -> bool {
+        self.bus.boot_rom_mapped()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{StateError, BOOT_ADDR};
+
+    // Ends the way the real ones do: the unmap write in the last bytes before 0x0100.
+    fn boot_rom(len: usize) -> Vec<u8> {
+        let mut image = vec![0x00; len];
+        image[0xFC..0x100].copy_from_slice(&[0x3E, 0x01, 0xE0, (BOOT_ADDR & 0xFF) as u8]);
+        if let Some(byte) = image.get_mut(0x200) {
+            *byte = 0xB2;
+        }
+        image
+    }
+
+    fn cartridge() -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0000] = 0xCA;
+        rom[0x0150] = 0xC5;
+        rom[0x0200] = 0xC2;
+        rom[0x0900] = 0xC9;
+        rom
+    }
+
+    #[test]
+    fn runs_from_0x0000_until_the_unmap_write() {
+        let mut gb = GameBoy::new();
+        gb.load_rom(&cartridge());
+        gb.cpu.regs.a = 0x01;
+        assert_eq!(gb.load_boot_rom(&boot_rom(DMG_BOOT_ROM_SIZE)), Ok(()));
+        assert_eq!(
+            (gb.cpu.pc, gb.cpu.sp, gb.cpu.regs.a),
+            (0x0000, 0x0000, 0x00)
+        );
+        assert_eq!(gb.bus.read_byte(0x0000), 0x00);
+        assert_eq!(gb.bus.read_byte(0x0200), 0xC2, "a DMG image ends at 0x00FF");
+
+        gb.load_rom(&cartridge());
+        assert_eq!(gb.cpu.pc, 0x0000, "the boot ROM still runs first");
+        while gb.cpu.pc != 0x0100 {
+            gb.step().expect("boot ROM should run");
+        }
+        assert!(!gb.boot_rom_mapped());
+        assert_eq!(gb.bus.read_byte(0x0000), 0xCA);
+        gb.bus.write_byte(BOOT_ADDR, 0x00);
+        assert_eq!(gb.bus.read_byte(0x0000), 0xCA, "it cannot be mapped back");
+    }
+
+    #[test]
+    fn cgb_images_leave_the_header_to_the_cartridge() {
+        let mut gb = GameBoy::new();
+        gb.load_rom(&cartridge());
+        assert_eq!(gb.load_boot_rom(&boot_rom(CGB_BOOT_ROM_SIZE)), Ok(()));
+        assert_eq!(gb.bus.read_byte(0x00FC), 0x3E);
+        assert_eq!(gb.bus.read_byte(0x0150), 0xC5);
+        assert_eq!(gb.bus.read_byte(0x0200), 0xB2);
+        assert_eq!(
+            gb.bus.read_byte(0x0900),
+            0xC9,
+            "past the image is the cartridge"
+        );
+    }
+
+    #[test]
+    fn rejects_other_sizes_and_states_without_the_image() {
+        let mut gb = GameBoy::new();
+        assert_eq!(
+            gb.load_boot_rom(&[0; 0x200]).map_err(|err| err.to_string()),
+            Err("boot ROM is 512 bytes; expected 256 (DMG) or 2304 (CGB)".to_string())
+        );
+        assert!(!gb.boot_rom_mapped());
+
+        let mut booting = GameBoy::new();
+        booting
+            .load_boot_rom(&boot_rom(DMG_BOOT_ROM_SIZE))
+            .expect("DMG boot ROM size");
+        let state = booting.save_state();
+        assert_eq!(gb.load_state(&state), Err(StateError::Invalid("boot ROM")));
+        let mut restored = booting.clone();
+        restored.bus.write_byte(BOOT_ADDR, 0x01);
+        assert!(!restored.boot_rom_mapped());
+        assert!(restored.load_state(&state).is_ok());
+        assert!(restored.boot_rom_mapped());
+    }
+}
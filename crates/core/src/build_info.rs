@@ -0,0 +1,114 @@
+use std::fmt::{Display, Formatter};
+
+const CAPABILITIES: &[&str] = &[
+    "cpu-sm83",
+    "interrupts",
+    "timer",
+    "joypad",
+    "rtc",
+    "link-cable",
+    "cgb-boot-hle",
+    "ppu-background",
+    "ppu-window",
+    "ppu-sprites",
+    "ppu-tall-sprites",
+    "ppu-sprite-priority",
+    "ppu-lcdc-per-line",
+    "ppu-pixel-fifo",
+    "ppu-sprite-fetch-timing",
+    "apu-channels",
+    "apu-frame-sequencer",
+    "apu-wave-ram-access",
+    "ppu-mid-scanline-writes",
+    "oam-dma",
+    "oam-corruption-bug",
+    "stat-write-bug",
+    "mbc1",
+    "mbc2",
+    "mbc3-rtc",
+    "mbc5",
+    "model-lcdc-bit0",
+    "boot-rom",
+    "post-boot-presets",
+    "io-read-masks",
+];
+
+/// Identifies the exact core build that produced a report or state file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_hash: &'static str,
+    features: &'static str,
+    pub capabilities: &'static [&'static str],
+}
+
+impl BuildInfo {
+    pub fn features(&self) -> impl Iterator<Item = &'static str> {
+        self.features
+            .split(',')
+            .filter(|feature| !feature.is_empty())
+    }
+}
+
+impl Display for BuildInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let features = self.features().collect::<Vec<_>>();
+        write!(
+            f,
+            "vibegb-core {} ({}) features=[{}] capabilities=[{}]",
+            self.version,
+            self.git_hash,
+            features.join(","),
+            self.capabilities.join(",")
+        )
+    }
+}
+
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_hash: env!("VIBEGB_GIT_HASH"),
+        features: env!("VIBEGB_FEATURES"),
+        capabilities: capabilities(),
+    }
+}
+
+/// Emulation accuracy capabilities implemented by this build of the core.
+pub fn capabilities() -> &'static [&'static str] {
+    CAPABILITIES
+}
+
+/// Entries of `required` this build does not implement yet.
+pub fn missing_capabilities(required: &[&'static str]) -> Vec<&'static str> {
+    required
+        .iter()
+        .copied()
+        .filter(|capability| !CAPABILITIES.contains(capability))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_info_reports_crate_version_and_capabilities() {
+        let info = build_info();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.git_hash.is_empty());
+        assert!(info.capabilities.contains(&"cpu-sm83"));
+
+        let rendered = info.to_string();
+        assert!(rendered.starts_with(&format!("vibegb-core {}", info.version)));
+        assert!(rendered.contains("capabilities=[cpu-sm83,"));
+    }
+
+    #[test]
+    fn reports_missing_capabilities() {
+        assert!(missing_capabilities(&["cpu-sm83", "ppu-sprites"]).is_empty());
+        assert_eq!(
+            missing_capabilities(&["timer", "not-a-feature"]),
+            vec!["not-a-feature"]
+        );
+    }
+}
@@ -0,0 +1,471 @@
+use crate::bank_stats::BankRecorder;
+use crate::cartridge::{rom_bank_count, ROM_BANK_SIZE};
+use crate::state::{StateReader, StateWriter};
+use crate::{BankStats, Cartridge, GameBoy, MapperRegisters, StateError};
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+/// The sensor image the camera develops: 128x112 pixels, 16x14 tiles.
+pub const CAMERA_WIDTH: usize = 128;
+pub const CAMERA_HEIGHT: usize = 112;
+pub const CAMERA_PIXELS: usize = CAMERA_WIDTH * CAMERA_HEIGHT;
+
+const RAM_BANK_SIZE: usize = 0x2000;
+const RAM_START: u16 = 0xA000;
+const OPEN_BUS: u8 = 0xFF;
+// RAM bank values with this bit set map the camera registers instead of RAM.
+const REGISTER_SELECT: u8 = 0x10;
+// 0xA000-0xA035, mirrored every 0x80 bytes.
+const REGISTER_COUNT: usize = 0x36;
+const REGISTER_MIRROR: u16 = 0x7F;
+const CAPTURE_BUSY: u8 = 0x01;
+const DITHER_MATRIX: usize = 0x06;
+// Developed tiles land in RAM bank 0 after a 0x100-byte header.
+const IMAGE_OFFSET: usize = 0x100;
+const TILE_COLUMNS: usize = CAMERA_WIDTH / 8;
+const EDGE_RATIOS: [f32; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+// Exposure is relative to this value: at 0x1000 the sensor output is the source image.
+const UNIT_EXPOSURE: u32 = 0x1000;
+const NEUTRAL_GRAY: u8 = 0x80;
+
+/// What the Pocket Camera's sensor sees, asked for whenever a capture completes. Tests can
+/// feed synthetic images and frontends a webcam. Closures taking the image buffer work as
+/// sources too.
+pub trait CameraImageSource: Send {
+    /// Fills `image`, row-major, with brightness from 0 (black) to 255 (white).
+    fn capture(&mut self, image: &mut [u8; CAMERA_PIXELS]);
+}
+
+impl<F: FnMut(&mut [u8; CAMERA_PIXELS]) + Send> CameraImageSource for F {
+    fn capture(&mut self, image: &mut [u8; CAMERA_PIXELS]) {
+        self(image)
+    }
+}
+
+/// Holder for the source installed with `GameBoy::set_camera_source`; like the rumble
+/// callback, clones start without one. Without a source the sensor sees flat mid-gray.
+#[derive(Default)]
+pub(crate) struct CameraSource {
+    source: Option<Box<dyn CameraImageSource>>,
+}
+
+impl CameraSource {
+    pub(crate) fn capture(&mut self) -> Box<[u8; CAMERA_PIXELS]> {
+        let mut image = Box::new([NEUTRAL_GRAY; CAMERA_PIXELS]);
+        if let Some(source) = self.source.as_mut() {
+            source.capture(&mut image);
+        }
+        image
+    }
+}
+
+impl Clone for CameraSource {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl Debug for CameraSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(if self.source.is_some() {
+            "CameraSource(set)"
+        } else {
+            "CameraSource(none)"
+        })
+    }
+}
+
+impl GameBoy {
+    /// Feeds the Pocket Camera's sensor from `source`. Replaces any previous source.
+    pub fn set_camera_source(&mut self, source: impl CameraImageSource + 'static) {
+        self.bus.camera_source_mut().source = Some(Box::new(source));
+    }
+
+    pub fn clear_camera_source(&mut self) {
+        self.bus.camera_source_mut().source = None;
+    }
+}
+
+/// The Pocket Camera's MAC-GBD mapper: a 6-bit ROM bank, sixteen 8 KiB RAM banks that read
+/// even while disabled, and the sensor's registers, mapped over 0xA000-0xBFFF by selecting
+/// RAM bank 0x10. Writing 1 to bit 0 of 0xA000 starts a capture, which stays busy for the
+/// exposure time and then develops the sensor image into RAM bank 0 as 2bpp tiles.
+#[derive(Debug, Clone)]
+pub struct PocketCamera {
+    rom: Arc<[u8]>,
+    ram: Vec<u8>,
+    rom_banks: usize,
+    stats: BankRecorder,
+    ram_enabled: bool,
+    rom_bank: u8,
+    select: u8,
+    registers: [u8; REGISTER_COUNT],
+    // T-cycles until the capture in progress develops.
+    capture_cycles: u32,
+}
+
+impl PocketCamera {
+    pub fn new(rom: impl Into<Arc<[u8]>>, ram_size: usize) -> Self {
+        let rom = rom.into();
+        Self {
+            rom_banks: rom_bank_count(rom.len()),
+            stats: BankRecorder::new(rom_bank_count(rom.len()), ram_size),
+            rom,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            rom_bank: 1,
+            select: 0,
+            registers: [0; REGISTER_COUNT],
+            capture_cycles: 0,
+        }
+    }
+
+    fn rom_offset(&self, address: u16) -> usize {
+        let bank = if address < 0x4000 {
+            0
+        } else {
+            usize::from(self.rom_bank) & (self.rom_banks - 1)
+        };
+        bank * ROM_BANK_SIZE + usize::from(address & 0x3FFF)
+    }
+
+    fn ram_index(&self, address: u16) -> Option<usize> {
+        if self.ram.is_empty() {
+            return None;
+        }
+        let offset =
+            usize::from(self.select & 0x0F) * RAM_BANK_SIZE + usize::from(address - RAM_START);
+        Some(offset % self.ram.len())
+    }
+
+    fn registers_mapped(&self) -> bool {
+        self.select & REGISTER_SELECT != 0
+    }
+
+    // Pan Docs' capture time: 32446 M-cycles, 512 more unless N is set, plus 16 per unit of
+    // exposure.
+    fn capture_time(&self) -> u32 {
+        let n_bonus = if self.registers[1] & 0x80 != 0 {
+            0
+        } else {
+            512
+        };
+        (32_446 + n_bonus + 16 * u32::from(self.exposure())) * 4
+    }
+
+    fn exposure(&self) -> u16 {
+        u16::from_be_bytes([self.registers[2], self.registers[3]])
+    }
+
+    /// Turns a sensor image into the tiles a game reads: exposure scales the image, 2D edge
+    /// enhancement (VH=3) sharpens it by the E ratio, I inverts it, and each pixel becomes a
+    /// color through its cell of the 4x4 dither matrix of three thresholds.
+    fn develop(&mut self, image: &[u8; CAMERA_PIXELS]) {
+        let exposure = u32::from(self.exposure());
+        let exposed: Vec<f32> = image
+            .iter()
+            .map(|&pixel| (u32::from(pixel) * exposure / UNIT_EXPOSURE).min(255) as f32)
+            .collect();
+        let at = |x: usize, y: usize| exposed[y * CAMERA_WIDTH + x];
+        let edge_mode = (self.registers[1] >> 5) & 0x03;
+        let ratio = EDGE_RATIOS[usize::from((self.registers[4] >> 4) & 0x07)];
+        let invert = self.registers[4] & 0x08 != 0;
+
+        if self.ram.len() < IMAGE_OFFSET + CAMERA_PIXELS / 4 {
+            return;
+        }
+        for y in 0..CAMERA_HEIGHT {
+            for x in 0..CAMERA_WIDTH {
+                let mut value = at(x, y);
+                if edge_mode == 0x03 {
+                    let neighbors = at(x.saturating_sub(1), y)
+                        + at((x + 1).min(CAMERA_WIDTH - 1), y)
+                        + at(x, y.saturating_sub(1))
+                        + at(x, (y + 1).min(CAMERA_HEIGHT - 1));
+                    value += ratio * (4.0 * value - neighbors);
+                }
+                let mut value = value.clamp(0.0, 255.0) as u8;
+                if invert {
+                    value = !value;
+                }
+                let cell = DITHER_MATRIX + ((y % 4) * 4 + x % 4) * 3;
+                let thresholds = &self.registers[cell..cell + 3];
+                let color = 3 - thresholds
+                    .iter()
+                    .take_while(|&&threshold| value >= threshold)
+                    .count() as u8;
+
+                let tile = (y / 8) * TILE_COLUMNS + x / 8;
+                let index = IMAGE_OFFSET + tile * 16 + (y % 8) * 2;
+                let bit = 0x80 >> (x % 8);
+                for (plane, byte) in self.ram[index..index + 2].iter_mut().enumerate() {
+                    if color >> plane & 1 != 0 {
+                        *byte |= bit;
+                    } else {
+                        *byte &= !bit;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Cartridge for PocketCamera {
+    fn read_rom(&self, address: u16) -> u8 {
+        self.rom
+            .get(self.rom_offset(address))
+            .copied()
+            .unwrap_or(OPEN_BUS)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                let enabled = value & 0x0F == 0x0A;
+                self.stats.record_ram_gate(self.ram_enabled, enabled);
+                self.ram_enabled = enabled;
+            }
+            // Unlike most mappers, bank 0 can be mapped at 0x4000 too.
+            0x2000..=0x3FFF => self.rom_bank = value & 0x3F,
+            0x4000..=0x5FFF => self.select = value & 0x1F,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if self.registers_mapped() {
+            // Only the control register reads back; the rest are write-only.
+            return match address & REGISTER_MIRROR {
+                0 => self.registers[0] & 0x07,
+                _ => 0x00,
+            };
+        }
+        self.ram_index(address)
+            .map_or(OPEN_BUS, |index| self.ram[index])
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if self.registers_mapped() {
+            let register = usize::from(address & REGISTER_MIRROR);
+            if register == 0 {
+                let start = value & CAPTURE_BUSY != 0 && self.capture_cycles == 0;
+                self.registers[0] = value & 0x07;
+                if start {
+                    self.capture_cycles = self.capture_time();
+                } else if value & CAPTURE_BUSY == 0 {
+                    // Clearing the bit aborts the capture.
+                    self.capture_cycles = 0;
+                }
+            } else if register < REGISTER_COUNT {
+                self.registers[register] = value;
+            }
+            return;
+        }
+        if !self.ram_enabled {
+            return;
+        }
+        if let Some(index) = self.ram_index(address) {
+            self.ram[index] = value;
+        }
+    }
+
+    fn rom_bank(&self) -> u16 {
+        (usize::from(self.rom_bank) & (self.rom_banks - 1)) as u16
+    }
+
+    fn decodes_rom_write(&self, address: u16) -> bool {
+        address < 0x6000
+    }
+
+    fn registers(&self) -> MapperRegisters {
+        MapperRegisters {
+            mapper: "CAMERA",
+            rom_bank: self.rom_bank(),
+            ram_bank: self.select,
+            ram_enabled: self.ram_enabled,
+            mode: None,
+        }
+    }
+
+    fn record_read(&self, address: u16) {
+        if address < 0x8000 {
+            self.stats.record_rom(self.rom_offset(address));
+        } else if let Some(index) = self.ram_index(address).filter(|_| !self.registers_mapped()) {
+            self.stats.record_ram(index);
+        }
+    }
+
+    fn stats(&self) -> BankStats {
+        self.stats.stats()
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn tick(&mut self, cycles: u32) -> bool {
+        if self.capture_cycles == 0 {
+            return false;
+        }
+        self.capture_cycles = self.capture_cycles.saturating_sub(cycles);
+        self.capture_cycles == 0
+    }
+
+    fn develop_capture(&mut self, image: &[u8; CAMERA_PIXELS]) {
+        self.develop(image);
+        self.registers[0] &= !CAPTURE_BUSY;
+    }
+
+    fn rom(&self) -> &Arc<[u8]> {
+        &self.rom
+    }
+
+    fn patch_rom(&mut self, address: u16, value: u8) {
+        let offset = self.rom_offset(address);
+        if let Some(byte) = Arc::make_mut(&mut self.rom).get_mut(offset) {
+            *byte = value;
+        }
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        let mut out = StateWriter::default();
+        out.bool(self.ram_enabled);
+        out.u8(self.rom_bank);
+        out.u8(self.select);
+        out.bytes(&self.registers);
+        out.u32(self.capture_cycles);
+        out.byte_vec(&self.ram);
+        out.into_bytes()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut input = StateReader::new(state);
+        let ram_enabled = input.bool()?;
+        let rom_bank = input.u8()?;
+        let select = input.u8()?;
+        let mut registers = [0; REGISTER_COUNT];
+        input.bytes_into(&mut registers)?;
+        let capture_cycles = input.u32()?;
+        let ram = input.byte_vec()?;
+        input.finish()?;
+        if rom_bank > 0x3F || select > 0x1F {
+            return Err(StateError::Invalid("camera bank register"));
+        }
+        if ram.len() != self.ram.len() {
+            return Err(StateError::Invalid("cartridge RAM size"));
+        }
+        self.ram_enabled = ram_enabled;
+        self.rom_bank = rom_bank;
+        self.select = select;
+        self.registers = registers;
+        self.capture_cycles = capture_cycles;
+        self.ram = ram;
+        Ok(())
+    }
+
+    fn clone_box(&self) -> Box<dyn Cartridge> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::new_cartridge;
+
+    const CAMERA_RAM: usize = 0x20000;
+
+    fn camera_gb() -> GameBoy {
+        let mut rom = vec![0; 0x10000];
+        rom[0x147] = 0xFC;
+        rom[0x149] = 0x04;
+        let mut gb = GameBoy::new();
+        gb.load_rom(&rom);
+        gb
+    }
+
+    #[test]
+    fn maps_banks_and_write_only_registers() {
+        let mut camera = new_cartridge(0xFC, CAMERA_RAM, vec![0; 0x10000]);
+        camera.write_rom(0x2000, 0x00);
+        assert_eq!(camera.rom_bank(), 0, "bank 0 is allowed");
+
+        camera.write_rom(0x4000, 0x03);
+        camera.write_ram(0xA010, 0x55);
+        assert_eq!(camera.read_ram(0xA010), 0x00, "writes need RAM enabled");
+        camera.write_rom(0x0000, 0x0A);
+        camera.write_ram(0xA010, 0x55);
+        camera.write_rom(0x0000, 0x00);
+        assert_eq!(camera.read_ram(0xA010), 0x55, "reads do not");
+
+        camera.write_rom(0x4000, 0x10);
+        camera.write_ram(0xA001, 0x80);
+        camera.write_ram(0xA086, 0x40);
+        assert_eq!(camera.read_ram(0xA001), 0x00);
+        assert_eq!(camera.read_ram(0xA080), 0x00);
+        camera.write_ram(0xA000, 0x01);
+        assert_eq!(camera.read_ram(0xA000), 0x01, "a capture is running");
+        assert_eq!(
+            camera.registers().to_string(),
+            "CAMERA rom_bank=0x000 ram_bank=0x10 ram_enabled=no"
+        );
+
+        let state = camera.save_state();
+        let mut restored = PocketCamera::new(vec![0; 0x10000], CAMERA_RAM);
+        assert_eq!(restored.load_state(&state), Ok(()));
+        assert_eq!(restored.read_ram(0xA000), 0x01);
+        restored.write_rom(0x4000, 0x03);
+        assert_eq!(restored.read_ram(0xA010), 0x55);
+    }
+
+    // The two bitplanes of a tile's first row.
+    fn tile_row(gb: &GameBoy, tile: u16) -> [u8; 2] {
+        [0, 1].map(|plane| gb.bus.read_byte(0xA100 + tile * 16 + plane))
+    }
+
+    #[test]
+    fn develops_the_source_image_into_dithered_tiles() {
+        let mut gb = camera_gb();
+        // Left half black, right half white.
+        gb.set_camera_source(|image: &mut [u8; CAMERA_PIXELS]| {
+            for (index, pixel) in image.iter_mut().enumerate() {
+                *pixel = if index % CAMERA_WIDTH < CAMERA_WIDTH / 2 {
+                    0
+                } else {
+                    255
+                };
+            }
+        });
+        gb.bus.write_byte(0x4000, 0x10);
+        gb.bus.write_byte(0xA002, 0x10); // unit exposure
+        for cell in 0..16 {
+            gb.bus.load_bytes(0xA006 + cell * 3, &[0x40, 0x80, 0xC0]);
+        }
+        gb.bus.write_byte(0xA000, 0x01);
+
+        let mut cycles = 0;
+        while gb.bus.read_byte(0xA000) & 0x01 != 0 {
+            gb.bus.tick(4);
+            cycles += 4;
+        }
+        assert_eq!(cycles, (32_446 + 512 + 16 * 0x1000) * 4);
+
+        gb.bus.write_byte(0x4000, 0x00);
+        assert_eq!(tile_row(&gb, 0), [0xFF, 0xFF], "black is color 3");
+        assert_eq!(tile_row(&gb, 15), [0x00, 0x00], "white is color 0");
+
+        // The default source is flat gray, which reaches the middle threshold.
+        gb.clear_camera_source();
+        gb.bus.write_byte(0x4000, 0x10);
+        gb.bus.write_byte(0xA000, 0x01);
+        while gb.bus.read_byte(0xA000) & 0x01 != 0 {
+            gb.bus.tick(1024);
+        }
+        gb.bus.write_byte(0x4000, 0x00);
+        assert_eq!(tile_row(&gb, 0), [0xFF, 0x00], "0x80 is color 1");
+    }
+}
@@ -0,0 +1,568 @@
+use crate::bank_stats::BankRecorder;
+use crate::state::{StateReader, StateWriter};
+use crate::{
+    BankStats, HeaderValidation, Mbc1, Mbc2, Mbc3, Mbc5, PocketCamera, Rom, RomHeader, StateError,
+    CAMERA_PIXELS,
+};
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+
+pub(crate) const ROM_BANK_SIZE: usize = 0x4000;
+const RAM_START: u16 = 0xA000;
+const OPEN_BUS: u8 = 0xFF;
+
+/// The cartridge side of the bus: everything at 0x0000-0x7FFF and 0xA000-0xBFFF goes
+/// through here, so a mapper decides which ROM and RAM banks the CPU sees.
+pub trait Cartridge: Debug + Send {
+    fn read_rom(&self, address: u16) -> u8;
+
+    /// A CPU store into 0x0000-0x7FFF. The ROM itself is read-only; mappers decode these
+    /// as register writes.
+    fn write_rom(&mut self, address: u16, value: u8);
+
+    /// Whether a `write_rom` to `address` lands in a mapper register. Stores anywhere else
+    /// change nothing and usually mean a stray pointer.
+    fn decodes_rom_write(&self, _address: u16) -> bool {
+        true
+    }
+
+    fn read_ram(&self, address: u16) -> u8;
+
+    fn write_ram(&mut self, address: u16, value: u8);
+
+    /// The ROM bank mapped at 0x4000-0x7FFF.
+    fn rom_bank(&self) -> u16;
+
+    fn registers(&self) -> MapperRegisters;
+
+    /// External RAM, as stored in a `.sav` file.
+    fn ram(&self) -> &[u8];
+
+    fn ram_mut(&mut self) -> &mut [u8];
+
+    /// Counts a CPU read of `address` in `stats`. The bus calls this for CPU reads only, so
+    /// debugger peeks through `read_rom` and `read_ram` leave the counts alone.
+    fn record_read(&self, _address: u16) {}
+
+    /// Per-bank read counts and RAM gate switches since the cartridge was built. Counts are
+    /// not saved in states.
+    fn stats(&self) -> BankStats {
+        BankStats::default()
+    }
+
+    /// Whether the cartridge has a real-time clock, which the bus keeps.
+    fn has_rtc(&self) -> bool {
+        false
+    }
+
+    /// The RTC register mapped over 0xA000-0xBFFF in place of RAM, if any.
+    fn rtc_register(&self) -> Option<u8> {
+        None
+    }
+
+    /// Whether a ROM write since the last call asked to latch the clock.
+    fn take_rtc_latch(&mut self) -> bool {
+        false
+    }
+
+    /// Advances the mapper's own timers by `cycles`. Returns true when a camera capture has
+    /// finished exposing and wants the sensor image passed to `develop_capture`.
+    fn tick(&mut self, _cycles: u32) -> bool {
+        false
+    }
+
+    fn develop_capture(&mut self, _image: &[u8; CAMERA_PIXELS]) {}
+
+    /// Whether the cartridge's rumble motor is running; only rumble carts have one.
+    fn rumble(&self) -> bool {
+        false
+    }
+
+    /// The whole ROM image, shared with every clone of the cartridge until one patches it.
+    fn rom(&self) -> &Arc<[u8]>;
+
+    /// Overwrites the ROM byte currently mapped at `address`, for loaders and debugger
+    /// patches rather than the CPU. A shared image is copied first.
+    fn patch_rom(&mut self, address: u16, value: u8);
+
+    /// Mapper registers and external RAM; the ROM is not included, so a state only loads
+    /// back into the same kind of cartridge.
+    fn save_state(&self) -> Vec<u8>;
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError>;
+
+    fn clone_box(&self) -> Box<dyn Cartridge>;
+}
+
+impl Clone for Box<dyn Cartridge> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// A mapper's register state, for reports and debuggers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapperRegisters {
+    pub mapper: &'static str,
+    /// The ROM bank mapped at 0x4000-0x7FFF.
+    pub rom_bank: u16,
+    /// The RAM bank mapped at 0xA000-0xBFFF.
+    pub ram_bank: u8,
+    pub ram_enabled: bool,
+    /// MBC1's banking mode; `None` for mappers without one.
+    pub mode: Option<u8>,
+}
+
+impl Display for MapperRegisters {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} rom_bank=0x{:03X} ram_bank=0x{:02X} ram_enabled={}",
+            self.mapper,
+            self.rom_bank,
+            self.ram_bank,
+            if self.ram_enabled { "yes" } else { "no" }
+        )?;
+        if let Some(mode) = self.mode {
+            write!(f, " mode={mode}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds the cartridge for a header's type code (0x147) with `ram_size` bytes of external
+/// RAM. Passing an `Arc<[u8]>` shares the image instead of copying it.
+pub fn new_cartridge(
+    cartridge_type: u8,
+    ram_size: usize,
+    rom: impl Into<Arc<[u8]>>,
+) -> Box<dyn Cartridge> {
+    let rom = rom.into();
+    match cartridge_type {
+        0x00 | 0x08 | 0x09 => Box::new(RomOnly::new(rom, ram_size)),
+        0x01..=0x03 => Box::new(Mbc1::new(rom, ram_size)),
+        // The RAM is inside the mapper, so headers declare none.
+        0x05 | 0x06 => Box::new(Mbc2::new(rom)),
+        0x0F..=0x13 => Box::new(Mbc3::new(rom, ram_size, cartridge_type <= 0x10)),
+        0x19..=0x1E => Box::new(Mbc5::new(rom, ram_size, cartridge_type >= 0x1C)),
+        0xFC => Box::new(PocketCamera::new(rom, ram_size)),
+        // Mappers without an implementation still get their first two banks fixed in
+        // place, which is enough for anything that never switches banks.
+        _ => Box::new(RomOnly::new(rom, ram_size)),
+    }
+}
+
+/// The cartridge for a raw ROM image. Images too short to carry a header are ROM only.
+pub fn cartridge_for_image(data: impl Into<Arc<[u8]>>) -> Box<dyn Cartridge> {
+    let data = data.into();
+    match RomHeader::parse_with(&data, HeaderValidation::Lenient) {
+        Ok(header) => new_cartridge(
+            header.cartridge_type,
+            header.ram_size_bytes.unwrap_or(0),
+            data,
+        ),
+        Err(_) => Box::new(RomOnly::new(data, 0)),
+    }
+}
+
+/// A header's cartridge type that the image or the declared RAM size contradicts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CartridgeError {
+    /// A ROM-only type (0x00, 0x08, 0x09) on an image bigger than the 32 KiB it can map.
+    RomOnlyTooLarge { rom_len: usize },
+    /// RAM declared on a type without any, none declared on a type with RAM, or more than
+    /// the mapper can bank. MBC2's RAM is built in, so its headers declare none.
+    RamSizeMismatch { cartridge_type: u8, ram_size: usize },
+    /// An image too short to hold a header, so there is no cartridge type to check.
+    NoHeader { rom_len: usize },
+}
+
+impl Display for CartridgeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RomOnlyTooLarge { rom_len } => write!(
+                f,
+                "cartridge type is ROM only but the image is {} KiB",
+                rom_len / 1024
+            ),
+            Self::RamSizeMismatch {
+                cartridge_type,
+                ram_size,
+            } => write!(
+                f,
+                "cartridge type 0x{cartridge_type:02X} cannot have {} KiB of RAM",
+                ram_size / 1024
+            ),
+            Self::NoHeader { rom_len } => {
+                write!(f, "the image is {rom_len} bytes, too short for a header")
+            }
+        }
+    }
+}
+
+impl Error for CartridgeError {}
+
+/// What to do when `check_cartridge` finds the header at odds with the image.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CartridgeValidation {
+    /// Boot anyway with `fit_cartridge`'s guess, as most emulators do for homebrew.
+    #[default]
+    Permissive,
+    /// Refuse to load the ROM.
+    Strict,
+}
+
+/// Options for `GameBoy::load_rom_with_config`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadConfig {
+    pub cartridge: CartridgeValidation,
+}
+
+// Whether the type has external RAM, and the most its mapper can bank.
+fn ram_limits(cartridge_type: u8) -> Option<(bool, usize)> {
+    match cartridge_type {
+        0x00 | 0x01 | 0x05 | 0x06 | 0x0F | 0x11 | 0x19 | 0x1C => Some((false, 0)),
+        0x08 | 0x09 => Some((true, 0x2000)),
+        0x02 | 0x03 | 0x10 | 0x12 | 0x13 => Some((true, 0x8000)),
+        0x1A | 0x1B | 0x1D | 0x1E | 0xFC => Some((true, 0x20000)),
+        // Mappers this crate does not emulate are not second-guessed.
+        _ => None,
+    }
+}
+
+/// Every way the header's cartridge type contradicts an image of `rom_len` bytes.
+pub fn check_cartridge(header: &RomHeader, rom_len: usize) -> Vec<CartridgeError> {
+    let mut errors = Vec::new();
+    let cartridge_type = header.cartridge_type;
+    if matches!(cartridge_type, 0x00 | 0x08 | 0x09) && rom_len > 2 * ROM_BANK_SIZE {
+        errors.push(CartridgeError::RomOnlyTooLarge { rom_len });
+    }
+    let ram_size = header.ram_size_bytes.unwrap_or(0);
+    if let Some((has_ram, max)) = ram_limits(cartridge_type) {
+        if has_ram != (ram_size > 0) || ram_size > max {
+            errors.push(CartridgeError::RamSizeMismatch {
+                cartridge_type,
+                ram_size,
+            });
+        }
+    }
+    errors
+}
+
+/// The cartridge type and RAM size to build when `check_cartridge` objects: oversized
+/// ROM-only images get MBC1 (MBC5 past its 2 MiB), a RAM type declaring none gets one 8 KiB
+/// bank, and RAM beyond the mapper's reach is cut to what it can bank. RAM declared on a
+/// type without any is kept, since homebrew that declares it usually uses it.
+pub fn fit_cartridge(header: &RomHeader, rom_len: usize) -> (u8, usize) {
+    let mut cartridge_type = header.cartridge_type;
+    let mut ram_size = header.ram_size_bytes.unwrap_or(0);
+    for error in check_cartridge(header, rom_len) {
+        match error {
+            CartridgeError::RomOnlyTooLarge { rom_len } if rom_len > 128 * ROM_BANK_SIZE => {
+                cartridge_type = if ram_size > 0 { 0x1B } else { 0x19 };
+            }
+            CartridgeError::RomOnlyTooLarge { .. } => {
+                cartridge_type = if ram_size > 0 { 0x03 } else { 0x01 };
+            }
+            CartridgeError::RamSizeMismatch { .. } => {
+                if let Some((true, max)) = ram_limits(cartridge_type) {
+                    ram_size = if ram_size == 0 {
+                        0x2000
+                    } else {
+                        ram_size.min(max)
+                    };
+                }
+            }
+            CartridgeError::NoHeader { .. } => {}
+        }
+    }
+    (cartridge_type, ram_size)
+}
+
+/// Banks a mapper can address in an image of `len` bytes: a power of two, at least two.
+pub(crate) fn rom_bank_count(len: usize) -> usize {
+    len.div_ceil(ROM_BANK_SIZE).next_power_of_two().max(2)
+}
+
+impl Rom {
+    pub fn cartridge(&self) -> Box<dyn Cartridge> {
+        new_cartridge(
+            self.header.cartridge_type,
+            self.header.ram_size_bytes.unwrap_or(0),
+            self.data.as_slice(),
+        )
+    }
+}
+
+/// 32 KiB of ROM wired straight to the bus, with up to 8 KiB of optional RAM. ROM beyond
+/// the image reads as 0xFF.
+#[derive(Debug, Clone)]
+pub struct RomOnly {
+    rom: Arc<[u8]>,
+    ram: Vec<u8>,
+    stats: BankRecorder,
+}
+
+impl RomOnly {
+    pub fn new(rom: impl Into<Arc<[u8]>>, ram_size: usize) -> Self {
+        let ram_size = ram_size.min(0x2000);
+        Self {
+            rom: rom.into(),
+            ram: vec![0; ram_size],
+            stats: BankRecorder::new(2, ram_size),
+        }
+    }
+
+    fn ram_index(&self, address: u16) -> Option<usize> {
+        (!self.ram.is_empty()).then(|| usize::from(address - RAM_START) % self.ram.len())
+    }
+}
+
+impl Cartridge for RomOnly {
+    fn read_rom(&self, address: u16) -> u8 {
+        self.rom
+            .get(usize::from(address & 0x7FFF))
+            .copied()
+            .unwrap_or(OPEN_BUS)
+    }
+
+    fn write_rom(&mut self, _address: u16, _value: u8) {}
+
+    fn decodes_rom_write(&self, _address: u16) -> bool {
+        false
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        self.ram_index(address)
+            .map_or(OPEN_BUS, |index| self.ram[index])
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if let Some(index) = self.ram_index(address) {
+            self.ram[index] = value;
+        }
+    }
+
+    fn rom_bank(&self) -> u16 {
+        1
+    }
+
+    fn record_read(&self, address: u16) {
+        if address < 0x8000 {
+            self.stats.record_rom(usize::from(address));
+        } else if let Some(index) = self.ram_index(address) {
+            self.stats.record_ram(index);
+        }
+    }
+
+    fn stats(&self) -> BankStats {
+        self.stats.stats()
+    }
+
+    fn registers(&self) -> MapperRegisters {
+        MapperRegisters {
+            mapper: "ROM",
+            rom_bank: 1,
+            ram_bank: 0,
+            // Any RAM is wired straight to the bus.
+            ram_enabled: !self.ram.is_empty(),
+            mode: None,
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn rom(&self) -> &Arc<[u8]> {
+        &self.rom
+    }
+
+    fn patch_rom(&mut self, address: u16, value: u8) {
+        let index = usize::from(address & 0x7FFF);
+        if index >= self.rom.len() {
+            let mut padded = self.rom.to_vec();
+            padded.resize(2 * ROM_BANK_SIZE, OPEN_BUS);
+            self.rom = padded.into();
+        }
+        Arc::make_mut(&mut self.rom)[index] = value;
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        let mut out = StateWriter::default();
+        out.byte_vec(&self.ram);
+        out.into_bytes()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut input = StateReader::new(state);
+        let ram = input.byte_vec()?;
+        input.finish()?;
+        if ram.len() != self.ram.len() {
+            return Err(StateError::Invalid("cartridge RAM size"));
+        }
+        self.ram = ram;
+        Ok(())
+    }
+
+    fn clone_box(&self) -> Box<dyn Cartridge> {
+        Box::new(self.clone())
+    }
+}
+
+/// A ROM whose banks each start with their own bank number, low byte then high byte.
+#[cfg(test)]
+pub(crate) fn numbered_rom(banks: usize) -> Vec<u8> {
+    let mut rom = vec![0; banks * ROM_BANK_SIZE];
+    for bank in 0..banks {
+        rom[bank * ROM_BANK_SIZE] = bank as u8;
+        rom[bank * ROM_BANK_SIZE + 1] = (bank >> 8) as u8;
+    }
+    rom
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rom_only_maps_two_banks_and_ignores_rom_writes() {
+        let mut rom = vec![0x11; 0x10000];
+        rom[0x4000..0x8000].fill(0x22);
+        rom[0x147] = 0x00;
+        let mut cartridge = cartridge_for_image(rom);
+        assert_eq!(cartridge.read_rom(0x0000), 0x11);
+        assert_eq!(cartridge.read_rom(0x7FFF), 0x22);
+        assert_eq!(cartridge.rom_bank(), 1);
+
+        cartridge.write_rom(0x2000, 0x02);
+        assert_eq!(cartridge.read_rom(0x4000), 0x22, "no bank switching");
+        cartridge.write_rom(0x0100, 0x99);
+        assert_eq!(cartridge.read_rom(0x0100), 0x11, "ROM is read-only");
+        cartridge.patch_rom(0x0100, 0x99);
+        assert_eq!(cartridge.read_rom(0x0100), 0x99);
+
+        cartridge.write_ram(0xA000, 0x12);
+        assert_eq!(cartridge.read_ram(0xA000), 0xFF, "no RAM in the header");
+        assert_eq!(
+            cartridge.registers().to_string(),
+            "ROM rom_bank=0x001 ram_bank=0x00 ram_enabled=no"
+        );
+    }
+
+    #[test]
+    fn clones_share_the_rom_until_patched() {
+        let image: Arc<[u8]> = vec![0x00; 0x8000].into();
+        let first = cartridge_for_image(Arc::clone(&image));
+        let mut second = first.clone();
+        assert!(Arc::ptr_eq(first.rom(), &image));
+        assert!(Arc::ptr_eq(second.rom(), &image));
+        assert_eq!(Arc::strong_count(&image), 3);
+
+        second.patch_rom(0x0100, 0x18);
+        assert!(!Arc::ptr_eq(second.rom(), &image));
+        assert_eq!(first.read_rom(0x0100), 0x00);
+        assert_eq!(second.read_rom(0x0100), 0x18);
+    }
+
+    #[test]
+    fn short_images_are_padded_and_ram_comes_from_the_header() {
+        let mut cartridge = cartridge_for_image([0x3C].as_slice());
+        assert_eq!(cartridge.read_rom(0x0000), 0x3C);
+        assert_eq!(cartridge.read_rom(0x0001), 0xFF);
+        cartridge.patch_rom(0x7FFF, 0x76);
+        assert_eq!(cartridge.rom().len(), 0x8000);
+        assert_eq!(cartridge.read_rom(0x7FFF), 0x76);
+
+        cartridge = new_cartridge(0x08, 0x800, vec![0; 0x8000]);
+        cartridge.write_ram(0xA001, 0x12);
+        assert_eq!(cartridge.read_ram(0xA001), 0x12);
+        assert_eq!(cartridge.read_ram(0xA801), 0x12, "2 KiB RAM mirrors");
+
+        let state = cartridge.save_state();
+        let mut copy = cartridge.clone();
+        copy.write_ram(0xA001, 0x34);
+        assert_eq!(copy.load_state(&state), Ok(()));
+        assert_eq!(copy.read_ram(0xA001), 0x12);
+
+        let mut other = new_cartridge(0x08, 0x2000, vec![0; 0x8000]);
+        assert_eq!(
+            other.load_state(&state),
+            Err(StateError::Invalid("cartridge RAM size"))
+        );
+    }
+
+    #[test]
+    fn mismatched_headers_are_refused_or_fitted() {
+        let mut rom = numbered_rom(8);
+        let strict = LoadConfig {
+            cartridge: CartridgeValidation::Strict,
+        };
+        let mut gb = crate::GameBoy::new();
+        assert_eq!(
+            gb.load_rom_with_config(&rom, strict),
+            Err(CartridgeError::RomOnlyTooLarge { rom_len: 0x20000 })
+        );
+        assert_eq!(gb.cpu.pc, 0x0000, "a refused ROM is not loaded");
+
+        let fitted = gb
+            .load_rom_with_config(&rom, LoadConfig::default())
+            .expect("permissive loads boot");
+        assert_eq!(
+            fitted,
+            [CartridgeError::RomOnlyTooLarge { rom_len: 0x20000 }]
+        );
+        assert_eq!(
+            gb.bus.cartridge().expect("cartridge").registers().mapper,
+            "MBC1"
+        );
+        gb.bus.write_byte(0x2000, 0x05);
+        assert_eq!(gb.bus.read_byte(0x4000), 5);
+        assert_eq!(
+            fitted[0].to_string(),
+            "cartridge type is ROM only but the image is 128 KiB"
+        );
+
+        let mut gb = crate::GameBoy::new();
+        let headerless = [0x00; 0x100];
+        assert_eq!(
+            gb.load_rom_with_config(&headerless, strict),
+            Err(CartridgeError::NoHeader { rom_len: 0x100 })
+        );
+        assert_eq!(gb.cpu.pc, 0x0000, "a headerless ROM is refused");
+        assert_eq!(
+            gb.load_rom_with_config(&headerless, LoadConfig::default()),
+            Ok(vec![CartridgeError::NoHeader { rom_len: 0x100 }])
+        );
+        assert_eq!(gb.cpu.pc, 0x0100);
+
+        // MBC1+RAM declaring no RAM gets a bank; MBC2 declaring RAM is only reported.
+        rom[0x147] = 0x03;
+        let fitted = gb
+            .load_rom_with_config(&rom, LoadConfig::default())
+            .expect("permissive loads boot");
+        assert_eq!(gb.bus.cartridge().expect("cartridge").ram().len(), 0x2000);
+        assert_eq!(
+            fitted[0].to_string(),
+            "cartridge type 0x03 cannot have 0 KiB of RAM"
+        );
+        rom[0x147] = 0x06;
+        rom[0x149] = 0x03;
+        let header = RomHeader::parse_with(&rom, HeaderValidation::Lenient).expect("header");
+        assert_eq!(
+            check_cartridge(&header, rom.len()),
+            [CartridgeError::RamSizeMismatch {
+                cartridge_type: 0x06,
+                ram_size: 0x8000
+            }]
+        );
+        assert_eq!(fit_cartridge(&header, rom.len()), (0x06, 0x8000));
+        rom[0x149] = 0x00;
+        assert_eq!(gb.load_rom_with_config(&rom, strict), Ok(Vec::new()));
+    }
+}
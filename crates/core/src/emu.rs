@@ -1,7 +1,36 @@
+use crate::access_stats::AccessRecorder;
+use crate::camera::CameraSource;
+use crate::cartridge::{cartridge_for_image, check_cartridge, fit_cartridge, new_cartridge};
+use crate::events::FrameCallback;
+use crate::irq_latency::LatencyInjector;
+use crate::joypad::Joypad;
+use crate::mbc5::RumbleCallback;
+use crate::movie::MoviePlayback;
+use crate::quirks::{corrupt_oam, OamCorruption};
+use crate::rom_writes::RomWriteLog;
+use crate::state::{
+    read_header, write_header, StateBuild, StateError, StateReader, StateWriter, Stateful,
+};
+use crate::symbols::Breakpoint;
+use crate::trace::TraceRing;
+use crate::{
+    Apu, AudioConfig, BgMapEntry, BusAccessStats, Button, Cartridge, CartridgeError,
+    CartridgeValidation, Channel, CompatPalettes, DebugAnnotation, EventMask, FrameDeltaStats,
+    FramebufferSizeError, HardwareQuirks, HeaderValidation, JoypadEvent, LcdOffOutput, LoadConfig,
+    Model, Movie, OamEntry, Palette, Ppu, PpuMode, PpuRenderer, Recording, Rom, RomHeader, Rtc,
+    RtcMode, RunCounters, SerialCapture, SerialDevice, Tile, TileMap, VideoMemory, BGP_ADDR,
+    LCDC_ADDR, LYC_ADDR, NR10_ADDR, NR52_ADDR, SERIAL_TRANSFER_CYCLES, WAVE_RAM_ADDR, WAVE_RAM_END,
+    WX_ADDR,
+};
+use std::any::Any;
+use std::cell::Cell;
 use std::cmp::min;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
+use std::ops::ControlFlow;
+use std::sync::Arc;
 
+pub const P1_ADDR: u16 = 0xFF00;
 pub const DIV_ADDR: u16 = 0xFF04;
 pub const TIMA_ADDR: u16 = 0xFF05;
 pub const TMA_ADDR: u16 = 0xFF06;
@@ -10,6 +39,14 @@ pub const IF_ADDR: u16 = 0xFF0F;
 pub const IE_ADDR: u16 = 0xFFFF;
 pub const SB_ADDR: u16 = 0xFF01;
 pub const SC_ADDR: u16 = 0xFF02;
+pub const DMA_ADDR: u16 = 0xFF46;
+/// Writing a nonzero value unmaps the boot ROM for good.
+pub const BOOT_ADDR: u16 = 0xFF50;
+
+pub const CPU_CLOCK_HZ: u32 = 4_194_304;
+pub const CYCLES_PER_SCANLINE: u32 = 456;
+pub const SCANLINES_PER_FRAME: u32 = 154;
+pub const CYCLES_PER_FRAME: u32 = CYCLES_PER_SCANLINE * SCANLINES_PER_FRAME;
 
 pub const INTERRUPT_VBLANK: u8 = 0x01;
 pub const INTERRUPT_LCD: u8 = 0x02;
@@ -139,6 +176,7 @@ struct Timer {
     tma: u8,
     tac: u8,
     overflow_reload_delay: Option<u8>,
+    interrupts: u64,
 }
 
 impl Timer {
@@ -198,6 +236,7 @@ impl Timer {
             if delay == 0 {
                 if self.tima == 0 {
                     self.tima = self.tma;
+                    self.interrupts += 1;
                     *interrupt_flags |= INTERRUPT_TIMER;
                 }
                 self.overflow_reload_delay = None;
@@ -231,62 +270,563 @@ impl Timer {
     }
 }
 
+const CARTRIDGE_ROM_END: u16 = 0x8000;
+const IO_START: u16 = 0xFF00;
+const HRAM_START: u16 = 0xFF80;
+const OPEN_BUS: u8 = 0xFF;
+// 0xFEA0-0xFEFF decodes to nothing; the DMG reads it as 0x00 outside the PPU's OAM modes.
+const PROHIBITED_READ: u8 = 0x00;
+const OAM_START: u16 = 0xFE00;
+const OAM_DMA_BYTES: u32 = 160;
+const OAM_DMA_STARTUP_CYCLES: u32 = 4;
+const OAM_DMA_CYCLES_PER_BYTE: u32 = 4;
+
+/// In-flight OAM DMA: one byte per M-cycle after a one M-cycle startup delay.
+#[derive(Debug, Clone, Copy)]
+struct OamDma {
+    source: u16,
+    cycles: u32,
+}
+
+impl OamDma {
+    fn new(value: u8) -> Self {
+        let source = u16::from(value) << 8;
+        // Sources past WRAM read the echo of it, as the DMA unit only decodes 13 address bits
+        // for that half of the map.
+        let source = if source >= 0xE000 {
+            source - 0x2000
+        } else {
+            source
+        };
+        Self { source, cycles: 0 }
+    }
+
+    fn bytes_copied(&self) -> u32 {
+        (self.cycles.saturating_sub(OAM_DMA_STARTUP_CYCLES) / OAM_DMA_CYCLES_PER_BYTE)
+            .min(OAM_DMA_BYTES)
+    }
+
+    fn transferring(&self) -> bool {
+        self.cycles >= OAM_DMA_STARTUP_CYCLES
+    }
+}
+
+const PAGE_COUNT: usize = 0x100;
+
+/// Where a 256-byte page of the address space is served from. ROM and external RAM belong
+/// to the cartridge and its mapper; 0xE000-0xFDFF echoes WRAM, and the OAM page ends in the
+/// prohibited area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Page {
+    Cartridge,
+    Vram,
+    Wram,
+    Oam,
+    Io,
+}
+
+/// Who answers a read of each register in 0xFF00-0xFF7F. Registers kept in the IO array
+/// read back through a mask of their unused bits, which read 1; unmapped ones read 0xFF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IoPort {
+    Joypad,
+    Timer,
+    InterruptFlags,
+    Ppu,
+    Apu,
+    WaveRam,
+    Stored(u8),
+    SerialControl,
+    /// A CGB-only register: stored and masked like `Stored` on a CGB, unmapped on the others.
+    Cgb(u8),
+    Unmapped,
+}
+
+const IO_PORT_COUNT: usize = 0x80;
+const KEY1_ADDR: u16 = 0xFF4D;
+const VBK_ADDR: u16 = 0xFF4F;
+const RP_ADDR: u16 = 0xFF56;
+const BCPS_ADDR: u16 = 0xFF68;
+const BCPD_ADDR: u16 = 0xFF69;
+const OCPS_ADDR: u16 = 0xFF6A;
+const OCPD_ADDR: u16 = 0xFF6B;
+const OPRI_ADDR: u16 = 0xFF6C;
+const SVBK_ADDR: u16 = 0xFF70;
+const PCM12_ADDR: u16 = 0xFF76;
+const PCM34_ADDR: u16 = 0xFF77;
+// SC bit 1 picks the CGB's fast serial clock; the DMG does not have it.
+const SC_DMG_READ_MASK: u8 = 0x7E;
+const SC_CGB_READ_MASK: u8 = 0x7C;
+
+pub(crate) fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+fn io_index(address: u16) -> usize {
+    usize::from(address - IO_START)
+}
+
+fn io_table() -> [IoPort; IO_PORT_COUNT] {
+    std::array::from_fn(|index| match IO_START + index as u16 {
+        P1_ADDR => IoPort::Joypad,
+        SB_ADDR => IoPort::Stored(0x00),
+        SC_ADDR => IoPort::SerialControl,
+        DIV_ADDR..=TAC_ADDR => IoPort::Timer,
+        IF_ADDR => IoPort::InterruptFlags,
+        NR10_ADDR..=NR52_ADDR => IoPort::Apu,
+        WAVE_RAM_ADDR..=WAVE_RAM_END => IoPort::WaveRam,
+        LCDC_ADDR..=LYC_ADDR | BGP_ADDR..=WX_ADDR => IoPort::Ppu,
+        DMA_ADDR => IoPort::Stored(0x00),
+        // The boot ROM bank register reads 0xFF whether or not it is mapped.
+        BOOT_ADDR => IoPort::Unmapped,
+        KEY1_ADDR => IoPort::Cgb(0x7E),
+        VBK_ADDR => IoPort::Cgb(0xFE),
+        // RP bit 1 reads 1 while no infrared light is received.
+        RP_ADDR => IoPort::Cgb(0x3E),
+        BCPS_ADDR | OCPS_ADDR => IoPort::Cgb(0x40),
+        BCPD_ADDR | OCPD_ADDR => IoPort::Cgb(0x00),
+        OPRI_ADDR => IoPort::Cgb(0xFE),
+        SVBK_ADDR => IoPort::Cgb(0xF8),
+        0xFF72..=0xFF74 => IoPort::Cgb(0x00),
+        0xFF75 => IoPort::Cgb(0x8F),
+        // The channels' digital outputs, read-only; silent until the APU exposes them.
+        PCM12_ADDR | PCM34_ADDR => IoPort::Cgb(0x00),
+        // KEY0 locks after boot, and HDMA1-4 are write-only with HDMA5 idle.
+        _ => IoPort::Unmapped,
+    })
+}
+
+fn page_table() -> [Page; PAGE_COUNT] {
+    std::array::from_fn(|page| match page {
+        0x00..=0x7F | 0xA0..=0xBF => Page::Cartridge,
+        0x80..=0x9F => Page::Vram,
+        0xC0..=0xFD => Page::Wram,
+        0xFE => Page::Oam,
+        _ => Page::Io,
+    })
+}
+
 #[derive(Debug, Clone)]
 pub struct Bus {
-    memory: [u8; 0x10000],
+    vram: [u8; 0x2000],
+    wram: [u8; 0x2000],
+    oam: [u8; 0xA0],
+    // 0xFF00-0xFF7F, for the registers without their own state and wave RAM.
+    io: [u8; 0x80],
+    hram: [u8; 0x7F],
+    pages: [Page; PAGE_COUNT],
+    io_ports: [IoPort; IO_PORT_COUNT],
     timer: Timer,
+    joypad: Joypad,
     interrupt_enable: u8,
     interrupt_flags: u8,
-    serial_output: Vec<u8>,
+    serial_device: Box<dyn SerialDevice>,
+    serial_bytes_sent: u64,
+    // SB at the most recent transfer start; only read straight after the step that started it.
+    serial_last_sent: u8,
+    link_connected: bool,
+    serial_request_cycle: Option<u64>,
+    cycle_count: u64,
+    rtc: Rtc,
+    ppu: Ppu,
+    apu: Apu,
+    compat_palettes: Option<CompatPalettes>,
+    access_stats: AccessRecorder,
+    oam_dma: Option<OamDma>,
+    cartridge: Option<Box<dyn Cartridge>>,
+    camera_source: CameraSource,
+    quirks: HardwareQuirks,
+    pub(crate) rom_writes: Option<RomWriteLog>,
+    // Overlays the cartridge from power-on until a write to BOOT_ADDR.
+    boot_rom: Option<Arc<[u8]>>,
+    boot_rom_mapped: bool,
+    // Bus accesses (M-cycles) so far in the current instruction; reset by `tick`.
+    instruction_accesses: Cell<u32>,
+    // While `Some`, `tick` only adds up cycles, for `GameBoy::tick` to run one at a time.
+    #[cfg(feature = "cycle-accurate")]
+    pub(crate) deferred_cycles: Option<u32>,
 }
 
 impl Default for Bus {
     fn default() -> Self {
         Self {
-            memory: [0; 0x10000],
+            vram: [0; 0x2000],
+            wram: [0; 0x2000],
+            oam: [0; 0xA0],
+            io: [0; 0x80],
+            hram: [0; 0x7F],
+            pages: page_table(),
+            io_ports: io_table(),
             timer: Timer::default(),
+            joypad: Joypad::default(),
             interrupt_enable: 0,
             interrupt_flags: 0,
-            serial_output: Vec::new(),
+            serial_device: Box::new(SerialCapture::default()),
+            serial_bytes_sent: 0,
+            serial_last_sent: 0,
+            link_connected: false,
+            serial_request_cycle: None,
+            cycle_count: 0,
+            rtc: Rtc::default(),
+            ppu: Ppu::default(),
+            apu: Apu::default(),
+            compat_palettes: None,
+            access_stats: AccessRecorder::default(),
+            oam_dma: None,
+            cartridge: Some(new_cartridge(0x08, 0x2000, vec![0; 0x8000])),
+            camera_source: CameraSource::default(),
+            quirks: HardwareQuirks::default(),
+            rom_writes: None,
+            boot_rom: None,
+            boot_rom_mapped: false,
+            instruction_accesses: Cell::new(0),
+            #[cfg(feature = "cycle-accurate")]
+            deferred_cycles: None,
         }
     }
 }
 
 impl Bus {
     pub fn read_byte(&self, address: u16) -> u8 {
-        match address {
-            DIV_ADDR => self.timer.div(),
-            TIMA_ADDR => self.timer.tima,
-            TMA_ADDR => self.timer.tma,
-            TAC_ADDR => self.timer.tac_read(),
-            IF_ADDR => 0xE0 | (self.interrupt_flags & 0x1F),
-            IE_ADDR => self.interrupt_enable & 0x1F,
-            _ => self.memory[address as usize],
+        self.access_stats.record_read(address);
+        self.instruction_accesses
+            .set(self.instruction_accesses.get() + 1);
+        if let (Some(cartridge), Page::Cartridge) =
+            (&self.cartridge, self.pages[usize::from(address >> 8)])
+        {
+            if !self.cpu_blocked(address) && self.boot_rom_byte(address).is_none() {
+                cartridge.record_read(address);
+            }
+        }
+        self.peek(address)
+    }
+
+    /// What a CPU read would see, without counting as an access.
+    pub(crate) fn peek(&self, address: u16) -> u8 {
+        if self.cpu_blocked(address) {
+            return OPEN_BUS;
+        }
+        match self.pages[usize::from(address >> 8)] {
+            Page::Cartridge => self.read_cartridge(address),
+            Page::Io => self.read_io(address),
+            _ => self.backing(address).copied().unwrap_or(PROHIBITED_READ),
+        }
+    }
+
+    /// The console RAM byte behind `address`: none for the cartridge, the prohibited area
+    /// and IE.
+    pub(crate) fn backing(&self, address: u16) -> Option<&u8> {
+        let offset = usize::from(address & 0x1FFF);
+        match self.pages[usize::from(address >> 8)] {
+            Page::Cartridge => None,
+            Page::Vram => Some(&self.vram[offset]),
+            Page::Wram => Some(&self.wram[offset]),
+            Page::Oam => self.oam.get(usize::from(address & 0xFF)),
+            Page::Io => match address {
+                IE_ADDR => None,
+                HRAM_START.. => Some(&self.hram[usize::from(address - HRAM_START)]),
+                _ => Some(&self.io[io_index(address)]),
+            },
+        }
+    }
+
+    fn backing_mut(&mut self, address: u16) -> Option<&mut u8> {
+        let offset = usize::from(address & 0x1FFF);
+        match self.pages[usize::from(address >> 8)] {
+            Page::Cartridge => None,
+            Page::Vram => Some(&mut self.vram[offset]),
+            Page::Wram => Some(&mut self.wram[offset]),
+            Page::Oam => self.oam.get_mut(usize::from(address & 0xFF)),
+            Page::Io => match address {
+                IE_ADDR => None,
+                HRAM_START.. => Some(&mut self.hram[usize::from(address - HRAM_START)]),
+                _ => Some(&mut self.io[io_index(address)]),
+            },
+        }
+    }
+
+    pub(crate) fn video(&self) -> VideoMemory<'_> {
+        VideoMemory {
+            vram: &self.vram,
+            oam: &self.oam,
         }
     }
 
     pub fn write_byte(&mut self, address: u16, value: u8) {
+        self.access_stats.record_write(address);
+        self.instruction_accesses
+            .set(self.instruction_accesses.get() + 1);
+        if self.cpu_blocked(address) {
+            return;
+        }
+        match self.pages[usize::from(address >> 8)] {
+            Page::Cartridge => {
+                if let Some(cartridge) = self.cartridge.as_mut() {
+                    if address < CARTRIDGE_ROM_END {
+                        if let Some(log) = self.rom_writes.as_mut() {
+                            if !cartridge.decodes_rom_write(address) {
+                                log.record(address, value);
+                            }
+                        }
+                        cartridge.write_rom(address, value);
+                        if cartridge.take_rtc_latch() {
+                            self.rtc.latch();
+                        }
+                    } else if let Some(register) = cartridge.rtc_register() {
+                        self.rtc.write_register(register, value);
+                    } else {
+                        cartridge.write_ram(address, value);
+                    }
+                }
+            }
+            Page::Io => self.write_io(address, value),
+            _ => {
+                if let Some(byte) = self.backing_mut(address) {
+                    *byte = value;
+                }
+            }
+        }
+    }
+
+    /// While OAM DMA owns the external and video buses the CPU only reaches HRAM and the IO
+    /// registers, which sit on its internal bus. Otherwise the PPU locks VRAM and OAM in
+    /// the modes where it reads them, along with the prohibited area after OAM; blocked
+    /// reads see 0xFF and writes are dropped.
+    fn cpu_blocked(&self, address: u16) -> bool {
+        if address < 0xFF00 && self.oam_dma.is_some_and(|dma| dma.transferring()) {
+            return true;
+        }
+        match address {
+            0x0000..=0x7FFF | 0xA000..=0xBFFF => {
+                self.cartridge.is_none() && self.boot_rom_byte(address).is_none()
+            }
+            0x8000..=0x9FFF => !self.ppu.vram_accessible(),
+            0xFE00..=0xFEFF => !self.ppu.oam_accessible(),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn camera_source_mut(&mut self) -> &mut CameraSource {
+        &mut self.camera_source
+    }
+
+    pub fn cartridge_inserted(&self) -> bool {
+        self.cartridge.is_some()
+    }
+
+    pub fn cartridge(&self) -> Option<&dyn Cartridge> {
+        self.cartridge.as_deref()
+    }
+
+    pub(crate) fn cartridge_mut(&mut self) -> Option<&mut (dyn Cartridge + 'static)> {
+        self.cartridge.as_deref_mut()
+    }
+
+    /// The ROM bank mapped at 0x4000-0x7FFF; bank 1 without a mapper or cartridge.
+    pub fn rom_bank(&self) -> u16 {
+        self.cartridge
+            .as_ref()
+            .map_or(1, |cartridge| cartridge.rom_bank())
+    }
+
+    /// Swaps the cartridge in the slot, or empties it.
+    pub(crate) fn set_cartridge(&mut self, cartridge: Option<Box<dyn Cartridge>>) {
+        self.cartridge = cartridge;
+    }
+
+    fn read_cartridge(&self, address: u16) -> u8 {
+        if let Some(byte) = self.boot_rom_byte(address) {
+            return byte;
+        }
+        match &self.cartridge {
+            Some(cartridge) if address < CARTRIDGE_ROM_END => cartridge.read_rom(address),
+            Some(cartridge) => match cartridge.rtc_register() {
+                Some(register) => self.rtc.read_latched(register),
+                None => cartridge.read_ram(address),
+            },
+            None => OPEN_BUS,
+        }
+    }
+
+    /// Maps `image` over the cartridge until the program writes to `BOOT_ADDR`.
+    pub(crate) fn map_boot_rom(&mut self, image: Arc<[u8]>) {
+        self.boot_rom = Some(image);
+        self.boot_rom_mapped = true;
+    }
+
+    /// Sets the 16-bit counter DIV is the top half of, as a boot ROM leaves it.
+    pub(crate) fn set_divider(&mut self, divider: u16) {
+        self.timer.divider = divider;
+    }
+
+    pub(crate) fn boot_rom_mapped(&self) -> bool {
+        self.boot_rom_mapped
+    }
+
+    /// The boot ROM byte at `address` while it is mapped. A CGB image leaves
+    /// 0x0100-0x01FF to the cartridge so the header can be checked.
+    fn boot_rom_byte(&self, address: u16) -> Option<u8> {
+        let image = self.boot_rom.as_ref().filter(|_| self.boot_rom_mapped)?;
         match address {
-            DIV_ADDR => self.timer.write_div(),
+            0x0100..=0x01FF => None,
+            _ => image.get(usize::from(address)).copied(),
+        }
+    }
+
+    /// Fills WRAM and HRAM with the pseudo-random garbage they hold at power-on. Seed 0
+    /// leaves them cleared.
+    pub(crate) fn fill_power_on_ram(&mut self, seed: u64) {
+        if seed == 0 {
+            return;
+        }
+        let mut state = seed;
+        for ram in [&mut self.wram[..], &mut self.hram[..]] {
+            for chunk in ram.chunks_mut(8) {
+                let bytes = splitmix64(&mut state).to_le_bytes();
+                chunk.copy_from_slice(&bytes[..chunk.len()]);
+            }
+        }
+    }
+
+    pub fn quirks(&self) -> HardwareQuirks {
+        self.quirks
+    }
+
+    /// Fires the DMG OAM corruption bug for an access pattern on `address`, when enabled and
+    /// the PPU is scanning OAM.
+    pub(crate) fn trigger_oam_bug(&mut self, address: u16, kind: OamCorruption) {
+        if !self.quirks.oam_corruption || !(OAM_START..=0xFEFF).contains(&address) {
+            return;
+        }
+        if let Some(row) = self.ppu.oam_scan_row() {
+            corrupt_oam(&mut self.oam, row, kind);
+        }
+    }
+
+    pub fn oam_dma_active(&self) -> bool {
+        self.oam_dma.is_some()
+    }
+
+    fn tick_oam_dma(&mut self, cycles: u32) {
+        let Some(dma) = self.oam_dma.as_mut() else {
+            return;
+        };
+        let copied = dma.bytes_copied();
+        dma.cycles += cycles;
+        let (source, target) = (dma.source, dma.bytes_copied());
+        for offset in copied..target {
+            let offset = offset as u16;
+            let address = source + offset;
+            let value = match self.pages[usize::from(address >> 8)] {
+                Page::Cartridge => self.read_cartridge(address),
+                _ => self.backing(address).copied().unwrap_or(OPEN_BUS),
+            };
+            self.oam[usize::from(offset)] = value;
+        }
+        if target == OAM_DMA_BYTES {
+            self.oam_dma = None;
+        }
+    }
+
+    fn read_io(&self, address: u16) -> u8 {
+        match address {
+            IE_ADDR => return self.interrupt_enable & 0x1F,
+            HRAM_START.. => return self.hram[usize::from(address - HRAM_START)],
+            _ => {}
+        }
+        let stored = self.io[io_index(address)];
+        match self.io_ports[io_index(address)] {
+            IoPort::Joypad => self.joypad.read(self.cycle_count),
+            IoPort::Timer => match address {
+                DIV_ADDR => self.timer.div(),
+                TIMA_ADDR => self.timer.tima,
+                TMA_ADDR => self.timer.tma,
+                _ => self.timer.tac_read(),
+            },
+            IoPort::InterruptFlags => 0xE0 | (self.interrupt_flags & 0x1F),
+            IoPort::Ppu => self.ppu.read_register(address),
+            IoPort::Apu => self.apu.read_register(address),
+            IoPort::WaveRam => self.apu.read_wave_ram(address, self.wave_ram()),
+            IoPort::Stored(mask) => stored | mask,
+            IoPort::SerialControl if self.model() == Model::Cgb => stored | SC_CGB_READ_MASK,
+            IoPort::SerialControl => stored | SC_DMG_READ_MASK,
+            IoPort::Cgb(mask) if self.model() == Model::Cgb => stored | mask,
+            IoPort::Cgb(_) | IoPort::Unmapped => OPEN_BUS,
+        }
+    }
+
+    fn model(&self) -> Model {
+        self.ppu.model()
+    }
+
+    fn write_io(&mut self, address: u16, value: u8) {
+        match address {
+            P1_ADDR => self.joypad.write(value, &mut self.interrupt_flags),
+            DIV_ADDR => {
+                self.timer.write_div();
+                self.apu.reset_divider();
+            }
             TIMA_ADDR => self.timer.write_tima(value),
             TMA_ADDR => self.timer.write_tma(value),
             TAC_ADDR => self.timer.write_tac(value),
             IF_ADDR => self.interrupt_flags = value & 0x1F,
             IE_ADDR => self.interrupt_enable = value & 0x1F,
-            SB_ADDR => self.memory[SB_ADDR as usize] = value,
+            LCDC_ADDR..=LYC_ADDR | BGP_ADDR..=WX_ADDR => {
+                // The CPU runs a whole instruction before the PPU catches up, so tell it
+                // how far into the instruction this write's M-cycle falls.
+                let write_dot = self.instruction_accesses.get().saturating_sub(1) * 4;
+                self.ppu
+                    .write_register_at(address, value, write_dot, &mut self.interrupt_flags)
+            }
+            NR10_ADDR..=NR52_ADDR => self.apu.write_register(address, value),
+            WAVE_RAM_ADDR..=WAVE_RAM_END => {
+                let wave_ram = &mut self.io[io_index(WAVE_RAM_ADDR)..=io_index(WAVE_RAM_END)];
+                self.apu.write_wave_ram(address, value, wave_ram)
+            }
+            SB_ADDR => self.io[io_index(SB_ADDR)] = value,
+            PCM12_ADDR | PCM34_ADDR => {}
+            BOOT_ADDR => {
+                self.io[io_index(BOOT_ADDR)] = value;
+                if value != 0 {
+                    self.boot_rom_mapped = false;
+                }
+            }
+            DMA_ADDR => {
+                self.io[io_index(DMA_ADDR)] = value;
+                self.oam_dma = Some(OamDma::new(value));
+            }
             SC_ADDR => {
-                self.memory[SC_ADDR as usize] = value;
+                self.io[io_index(SC_ADDR)] = value;
                 if value & 0x81 == 0x81 {
-                    self.serial_output.push(self.memory[SB_ADDR as usize]);
-                    self.memory[SC_ADDR as usize] = value & !0x80;
+                    self.serial_bytes_sent += 1;
+                    self.serial_last_sent = self.io[io_index(SB_ADDR)];
+                    if self.link_connected {
+                        self.serial_request_cycle = Some(self.cycle_count);
+                    } else {
+                        let sent = self.io[io_index(SB_ADDR)];
+                        let received = self.serial_device.exchange(sent);
+                        self.complete_serial_transfer(received);
+                    }
+                } else {
+                    self.serial_request_cycle = None;
                 }
             }
             _ => {
-                self.memory[address as usize] = value;
+                if let Some(byte) = self.backing_mut(address) {
+                    *byte = value;
+                }
             }
         }
     }
 
+    fn wave_ram(&self) -> &[u8] {
+        &self.io[io_index(WAVE_RAM_ADDR)..=io_index(WAVE_RAM_END)]
+    }
+
     pub fn read_word(&self, address: u16) -> u16 {
         let lo = self.read_byte(address);
         let hi = self.read_byte(address.wrapping_add(1));
@@ -299,23 +839,64 @@ impl Bus {
         self.write_byte(address.wrapping_add(1), hi);
     }
 
+    /// Copies `data` into the address space from `start`, bypassing the CPU: ROM addresses
+    /// patch the mapped ROM and external RAM is written directly.
     pub fn load_bytes(&mut self, start: u16, data: &[u8]) {
         let start = start as usize;
-        if start >= self.memory.len() {
-            return;
+        let max = min(data.len(), 0x10000 - start);
+        for (offset, &value) in data[..max].iter().enumerate() {
+            let address = (start + offset) as u16;
+            match (&mut self.cartridge, self.pages[usize::from(address >> 8)]) {
+                (Some(cartridge), Page::Cartridge) if address < CARTRIDGE_ROM_END => {
+                    cartridge.patch_rom(address, value)
+                }
+                (Some(cartridge), Page::Cartridge) => cartridge.write_ram(address, value),
+                _ => {
+                    if let Some(byte) = self.backing_mut(address) {
+                        *byte = value;
+                    }
+                }
+            }
         }
-        let max = min(data.len(), self.memory.len() - start);
-        self.memory[start..start + max].copy_from_slice(&data[..max]);
     }
 
     pub fn tick(&mut self, cycles: u32) {
+        #[cfg(feature = "cycle-accurate")]
+        if let Some(deferred) = self.deferred_cycles.as_mut() {
+            *deferred += cycles;
+            self.instruction_accesses.set(0);
+            return;
+        }
+        self.tick_oam_dma(cycles);
         self.timer.tick(cycles, &mut self.interrupt_flags);
+        self.rtc.tick(cycles);
+        if let Some(cartridge) = self.cartridge.as_mut() {
+            if cartridge.tick(cycles) {
+                cartridge.develop_capture(&self.camera_source.capture());
+            }
+        }
+        let video = VideoMemory {
+            vram: &self.vram,
+            oam: &self.oam,
+        };
+        let vblanks = self.ppu.vblank_count();
+        self.ppu.tick(cycles, video, &mut self.interrupt_flags);
+        for _ in vblanks..self.ppu.vblank_count() {
+            self.access_stats.finish_frame();
+        }
+        let wave_ram = &self.io[io_index(WAVE_RAM_ADDR)..=io_index(WAVE_RAM_END)];
+        self.apu.tick(cycles, wave_ram);
+        self.cycle_count += u64::from(cycles);
+        self.instruction_accesses.set(0);
     }
 
     pub fn pending_interrupts(&self) -> u8 {
         self.interrupt_enable & self.interrupt_flags & 0x1F
     }
 
+    /// Sets bits in IF as if the devices in `mask` (`INTERRUPT_*` bits) had signalled. The
+    /// CPU dispatches them at its next step boundary once IME and IE allow, highest priority
+    /// (lowest bit) first.
     pub fn request_interrupt(&mut self, mask: u8) {
         self.interrupt_flags |= mask & 0x1F;
     }
@@ -324,19 +905,163 @@ impl Bus {
         self.interrupt_flags &= !(mask & 0x1F);
     }
 
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        self.joypad.set_button(
+            button,
+            pressed,
+            (self.cycle_count, self.ppu.vblank_count()),
+            &mut self.interrupt_flags,
+        );
+    }
+
+    pub fn is_button_pressed(&self, button: Button) -> bool {
+        self.joypad.is_pressed(button)
+    }
+
+    pub fn compat_palettes(&self) -> Option<CompatPalettes> {
+        self.compat_palettes
+    }
+
+    pub(crate) fn set_compat_palettes(&mut self, palettes: Option<CompatPalettes>) {
+        self.compat_palettes = palettes;
+    }
+
+    pub fn set_access_stats(&mut self, enabled: bool) {
+        self.access_stats.set_enabled(enabled);
+    }
+
+    /// Per-device access counts over completed frames since counting was enabled.
+    pub fn access_stats(&self) -> BusAccessStats {
+        self.access_stats.stats()
+    }
+
+    pub fn ppu(&self) -> &Ppu {
+        &self.ppu
+    }
+
+    pub fn apu(&self) -> &Apu {
+        &self.apu
+    }
+
+    pub fn rtc(&self) -> &Rtc {
+        &self.rtc
+    }
+
+    pub fn rtc_mut(&mut self) -> &mut Rtc {
+        &mut self.rtc
+    }
+
+    /// With a link attached, internally clocked transfers stay pending until the link
+    /// driver completes them instead of finishing instantly. Detaching finishes a pending
+    /// transfer with 0xFF, as nothing is left to answer it.
+    pub fn connect_link(&mut self, connected: bool) {
+        self.link_connected = connected;
+        if !connected && self.serial_request_cycle.is_some() {
+            self.complete_serial_transfer(0xFF);
+        }
+    }
+
+    pub fn serial_transfer_request(&self) -> Option<(u64, u8)> {
+        self.serial_request_cycle
+            .map(|cycle| (cycle, self.io[io_index(SB_ADDR)]))
+    }
+
+    pub fn serial_waiting_for_clock(&self) -> bool {
+        self.link_connected && self.io[io_index(SC_ADDR)] & 0x81 == 0x80
+    }
+
+    pub fn complete_serial_transfer(&mut self, received: u8) -> u8 {
+        let sent = self.io[io_index(SB_ADDR)];
+        self.io[io_index(SB_ADDR)] = received;
+        self.io[io_index(SC_ADDR)] &= !0x80;
+        self.serial_request_cycle = None;
+        self.request_interrupt(INTERRUPT_SERIAL);
+        sent
+    }
+
+    /// Completes a due internally clocked transfer against a device that answers each byte.
+    pub fn poll_serial_transfer(&mut self, exchange: impl FnOnce(u8) -> u8) -> bool {
+        match self.serial_request_cycle {
+            Some(start) if self.cycle_count >= start + SERIAL_TRANSFER_CYCLES => {
+                let sent = self.io[io_index(SB_ADDR)];
+                self.complete_serial_transfer(exchange(sent));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn timer_interrupts(&self) -> u64 {
+        self.timer.interrupts
+    }
+
+    /// Plugs `device` into the link port and returns the one it replaces. Transfers go
+    /// to it only while no link cable is connected.
+    pub fn set_serial_device(&mut self, device: Box<dyn SerialDevice>) -> Box<dyn SerialDevice> {
+        std::mem::replace(&mut self.serial_device, device)
+    }
+
+    /// The installed serial device, if it is a `T`.
+    pub fn serial_device<T: SerialDevice>(&self) -> Option<&T> {
+        (self.serial_device.as_ref() as &dyn Any).downcast_ref()
+    }
+
+    pub fn serial_device_mut<T: SerialDevice>(&mut self) -> Option<&mut T> {
+        (self.serial_device.as_mut() as &mut dyn Any).downcast_mut()
+    }
+
+    /// Bytes kept by the serial device; the default capture keeps every byte sent.
     pub fn serial_output(&self) -> &[u8] {
-        &self.serial_output
+        self.serial_device.output()
+    }
+
+    /// Serial bytes started since power-on; unlike `serial_output`, not reset by
+    /// `take_serial_output`.
+    pub fn serial_bytes_sent(&self) -> u64 {
+        self.serial_bytes_sent
+    }
+
+    pub(crate) fn last_serial_byte(&self) -> u8 {
+        self.serial_last_sent
     }
 
     pub fn take_serial_output(&mut self) -> Vec<u8> {
-        std::mem::take(&mut self.serial_output)
+        self.serial_device.take_output()
     }
 }
 
+/// Passed to `GameBoy::run_steps_with` after every completed step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepInfo {
+    /// Steps completed so far, including this one.
+    pub steps: usize,
+    pub cycles: u32,
+    pub total_cycles: u64,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct GameBoy {
     pub cpu: Cpu,
     pub bus: Bus,
+    pub(crate) seed: u64,
+    pub(crate) breakpoints: Vec<Breakpoint>,
+    pub(crate) frame_callback: FrameCallback,
+    pub(crate) rumble_callback: RumbleCallback,
+    pub(crate) counters: RunCounters,
+    pub(crate) recording: Option<Recording>,
+    pub(crate) movie_recording: Option<Movie>,
+    pub(crate) movie_playback: Option<MoviePlayback>,
+    pub(crate) trace: TraceRing,
+    pub(crate) debug_annotations: Option<Vec<DebugAnnotation>>,
+    pub(crate) interrupt_latency: Option<LatencyInjector>,
+    // T-cycles of the instruction `tick` is part-way through: left to run, its length, and
+    // its PC.
+    #[cfg(feature = "cycle-accurate")]
+    pub(crate) tick_debt: u32,
+    #[cfg(feature = "cycle-accurate")]
+    pub(crate) tick_cycles: u32,
+    #[cfg(feature = "cycle-accurate")]
+    pub(crate) tick_pc: u16,
 }
 
 impl GameBoy {
@@ -344,6 +1069,21 @@ impl GameBoy {
         Self::default()
     }
 
+    /// A console whose power-on state is derived from `seed` alone, so a run can be
+    /// reproduced from it. `new` is seed 0.
+    pub fn with_seed(seed: u64) -> Self {
+        let mut gb = Self {
+            seed,
+            ..Self::default()
+        };
+        gb.bus.fill_power_on_ram(seed);
+        gb
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
     pub fn with_program(start: u16, program: &[u8]) -> Self {
         let mut gb = Self::default();
         gb.cpu.pc = start;
@@ -351,22 +1091,346 @@ impl GameBoy {
         gb
     }
 
+    /// Inserts a cartridge built from the ROM image's header and jumps to its entry point.
     pub fn load_rom(&mut self, rom_data: &[u8]) {
-        self.bus.load_bytes(0x0000, rom_data);
-        self.cpu.pc = 0x0100;
-        self.cpu.sp = 0xFFFE;
+        self.load_shared_rom(rom_data.into());
+    }
+
+    /// `load_rom`, choosing what happens when the header's cartridge type does not fit the
+    /// image, where `load_rom` builds whatever the header says. Returns the mismatches a
+    /// permissive load worked around; a strict load refuses the first one and leaves the
+    /// console as it was.
+    pub fn load_rom_with_config(
+        &mut self,
+        rom_data: &[u8],
+        config: LoadConfig,
+    ) -> Result<Vec<CartridgeError>, CartridgeError> {
+        let Ok(header) = RomHeader::parse_with(rom_data, HeaderValidation::Lenient) else {
+            let error = CartridgeError::NoHeader {
+                rom_len: rom_data.len(),
+            };
+            if config.cartridge == CartridgeValidation::Strict {
+                return Err(error);
+            }
+            self.load_rom(rom_data);
+            return Ok(vec![error]);
+        };
+        let errors = check_cartridge(&header, rom_data.len());
+        if let (CartridgeValidation::Strict, Some(&error)) = (config.cartridge, errors.first()) {
+            return Err(error);
+        }
+        let (cartridge_type, ram_size) = fit_cartridge(&header, rom_data.len());
+        self.bus
+            .set_cartridge(Some(new_cartridge(cartridge_type, ram_size, rom_data)));
+        self.enter_cartridge();
+        Ok(errors)
+    }
+
+    /// `load_rom` without copying the image, so lockstep comparisons, link cable pairs and
+    /// runahead clones can all run from one copy of the ROM.
+    pub fn load_shared_rom(&mut self, rom: Arc<[u8]>) {
+        self.bus.set_cartridge(Some(cartridge_for_image(rom)));
+        self.enter_cartridge();
+    }
+
+    /// Jumps to the cartridge entry point, unless a boot ROM is mapped to run first.
+    fn enter_cartridge(&mut self) {
+        if !self.bus.boot_rom_mapped() {
+            self.cpu.pc = 0x0100;
+            self.cpu.sp = 0xFFFE;
+        }
+    }
+
+    /// Pulls the cartridge while running. ROM and external RAM reads float to 0xFF (so code
+    /// executing from ROM sees `RST 38`) and writes go nowhere until one is inserted.
+    pub fn eject_cartridge(&mut self) {
+        self.bus.set_cartridge(None);
+    }
+
+    /// Inserts `rom` into the running console without resetting the CPU, as a hot swap on
+    /// hardware would.
+    pub fn insert_cartridge(&mut self, rom: Rom) {
+        self.bus.set_cartridge(Some(rom.cartridge()));
     }
 
     pub fn step(&mut self) -> Result<u32, EmuError> {
-        self.cpu.step(&mut self.bus)
+        #[cfg(feature = "cycle-accurate")]
+        self.finish_instruction();
+        self.play_movie_inputs();
+        let vblanks = self.bus.ppu.vblank_count();
+        let was_idle = self.cpu_idle();
+        if !was_idle {
+            self.trace_instruction();
+            self.annotate_instruction();
+            self.stamp_rom_write_pc();
+        }
+        let dispatch = self.interrupt_dispatch_allowed();
+        let cycles = self.cpu.step_dispatching(&mut self.bus, dispatch)?;
+        if !dispatch {
+            self.interrupt_dispatch_held(cycles);
+        }
+        self.count_step(was_idle);
+        self.emit_rumble();
+        if self.bus.ppu.vblank_count() != vblanks {
+            self.record_frame();
+            self.emit_frame();
+        }
+        Ok(cycles)
+    }
+
+    /// Presses or releases `button`, unless a movie being played still has inputs to give.
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        if self.movie_owns_input() {
+            return;
+        }
+        self.record_movie_input(button, pressed);
+        self.bus.set_button(button, pressed);
+    }
+
+    /// Injects an interrupt request; see `Bus::request_interrupt`. Useful for reproducing a
+    /// game's IRQ ordering without emulating the device that raises it.
+    pub fn raise_interrupt(&mut self, mask: u8) {
+        self.bus.request_interrupt(mask);
+    }
+
+    pub fn set_input_latency_tracking(&mut self, enabled: bool) {
+        self.bus.joypad.set_tracking(enabled);
+    }
+
+    pub fn input_events(&self) -> Vec<JoypadEvent> {
+        self.bus.joypad.events()
+    }
+
+    pub fn take_input_events(&mut self) -> Vec<JoypadEvent> {
+        self.bus.joypad.take_events()
+    }
+
+    pub fn set_rtc_mode(&mut self, mode: RtcMode) {
+        self.bus.rtc.set_mode(mode);
+    }
+
+    pub fn set_ppu_renderer(&mut self, renderer: PpuRenderer) {
+        self.bus.ppu.set_renderer(renderer);
+    }
+
+    pub fn model(&self) -> Model {
+        self.bus.ppu.model()
+    }
+
+    /// Switches the model-specific behavior; see `Model`.
+    pub fn set_model(&mut self, model: Model) {
+        self.bus.ppu.set_model(model);
+        self.bus.apu.set_model(model);
+    }
+
+    pub fn set_lcd_off_output(&mut self, output: LcdOffOutput) {
+        self.bus.ppu.set_lcd_off_output(output);
+    }
+
+    /// LCDC bit 7. While clear the PPU is stopped with LY held at 0.
+    pub fn lcd_enabled(&self) -> bool {
+        self.bus.ppu.lcd_enabled()
+    }
+
+    pub fn cycle_count(&self) -> u64 {
+        self.bus.cycle_count
+    }
+
+    /// Frames the PPU has finished, counted at VBlank like `run_frame`. It stands still
+    /// while the LCD is off.
+    pub fn frame_count(&self) -> u64 {
+        self.bus.ppu.vblank_count()
+    }
+
+    /// LY: line 0 while the LCD is off.
+    pub fn current_scanline(&self) -> u8 {
+        self.bus.ppu.ly()
+    }
+
+    pub fn cycles_into_frame(&self) -> u32 {
+        self.bus.ppu.frame_dots()
+    }
+
+    /// Last rendered LCD image, `SCREEN_WIDTH * SCREEN_HEIGHT` shades from 0 (lightest) to 3.
+    pub fn framebuffer(&self) -> &[u8] {
+        self.bus.ppu.framebuffer()
+    }
+
+    /// Same as `framebuffer`: one 2-bit shade index per pixel, before any palette.
+    pub fn framebuffer_indexed(&self) -> &[u8] {
+        self.bus.ppu.framebuffer()
+    }
+
+    /// A whole frame in `framebuffer` shades, whenever it is called. Mid-frame the
+    /// framebuffer holds the top of this frame over the bottom of the last, so a clone runs
+    /// on to VBlank and its frame is returned; this machine does not move. In VBlank or with
+    /// the LCD off the framebuffer is already whole.
+    pub fn screenshot(&self) -> Result<Vec<u8>, EmuError> {
+        if !self.bus.ppu.lcd_enabled() || self.bus.ppu.mode() == PpuMode::VBlank {
+            return Ok(self.bus.ppu.framebuffer().to_vec());
+        }
+        let mut finisher = self.clone();
+        finisher.set_audio_output_enabled(false);
+        finisher.run_until_event(EventMask::VBLANK)?;
+        Ok(finisher.bus.ppu.framebuffer().to_vec())
+    }
+
+    /// All 384 VRAM tiles; see `Ppu::decode_tiles`.
+    pub fn decode_tiles(&self) -> Vec<Tile> {
+        self.bus.ppu.decode_tiles(self.bus.video())
+    }
+
+    pub fn background_map(&self, index: usize) -> Option<TileMap> {
+        self.bus.ppu.background_map(self.bus.video(), index)
+    }
+
+    pub fn oam_entries(&self) -> Vec<OamEntry> {
+        self.bus.ppu.oam_entries(self.bus.video())
+    }
+
+    /// `oam_entries` without the allocation; see `Ppu::oam_iter`.
+    pub fn oam_iter(&self) -> impl Iterator<Item = OamEntry> + '_ {
+        self.bus.ppu.oam_iter(self.bus.video())
+    }
+
+    /// See `Ppu::bg_map_iter`.
+    pub fn bg_map_iter(&self, index: usize) -> Option<impl Iterator<Item = BgMapEntry> + '_> {
+        self.bus.ppu.bg_map_iter(self.bus.video(), index)
+    }
+
+    pub fn frame_delta_stats(&self) -> FrameDeltaStats {
+        self.bus.ppu.frame_delta_stats()
+    }
+
+    /// Last rendered LCD image mapped through the palette, RGBA8888 row-major.
+    pub fn framebuffer_rgba(&self) -> Vec<u8> {
+        self.bus.ppu.framebuffer_rgba()
+    }
+
+    /// `framebuffer_rgba` into a caller-owned buffer, for per-frame use without allocating.
+    pub fn write_framebuffer_rgba(&self, out: &mut [u8]) -> Result<(), FramebufferSizeError> {
+        self.bus.ppu.write_framebuffer_rgba(out)
+    }
+
+    /// The palette-mapped frame as RGB565, e.g. for SPI LCD panels.
+    pub fn write_framebuffer_rgb565(&self, out: &mut [u16]) -> Result<(), FramebufferSizeError> {
+        self.bus.ppu.write_framebuffer_rgb565(out)
+    }
+
+    /// Stereo output rate for `take_audio_samples`; see `Apu::set_sample_rate`.
+    pub fn set_audio_sample_rate(&mut self, rate: u32) {
+        self.bus.apu.set_sample_rate(rate);
+    }
+
+    pub fn set_audio_config(&mut self, config: AudioConfig) {
+        self.bus.apu.set_config(config);
+    }
+
+    pub fn audio_config(&self) -> AudioConfig {
+        self.bus.apu.config()
+    }
+
+    /// See `Apu::set_channel_enabled`.
+    pub fn set_audio_channel_enabled(&mut self, channel: Channel, enabled: bool) {
+        self.bus.apu.set_channel_enabled(channel, enabled);
+    }
+
+    /// See `Apu::set_output_enabled`.
+    pub fn set_audio_output_enabled(&mut self, enabled: bool) {
+        self.bus.apu.set_output_enabled(enabled);
+    }
+
+    pub fn audio_buffered_frames(&self) -> usize {
+        self.bus.apu.buffered_frames()
+    }
+
+    /// Drains the sound output produced so far as interleaved left/right `f32` samples in
+    /// -1.0..=1.0, at the rate set with `set_audio_sample_rate` (48 kHz by default).
+    pub fn take_audio_samples(&mut self) -> Vec<f32> {
+        self.bus.apu.take_samples()
+    }
+
+    /// `take_audio_samples` into a caller-owned buffer; returns the samples written.
+    pub fn read_audio_samples(&mut self, out: &mut [f32]) -> usize {
+        self.bus.apu.read_samples(out)
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.bus.ppu.set_palette(palette);
+    }
+
+    pub fn set_hardware_quirks(&mut self, quirks: HardwareQuirks) {
+        self.bus.quirks = quirks;
+        self.bus.ppu.set_window_glitches(quirks.window_glitches);
+        self.bus.ppu.set_stat_write_bug(quirks.stat_write_bug);
+    }
+
+    /// Steps until the PPU enters VBlank and returns the cycles run. With the LCD off there
+    /// is no VBlank, so it stops after one frame's worth of cycles instead.
+    pub fn run_frame(&mut self) -> Result<u64, EmuError> {
+        self.run_until_event(EventMask::VBLANK)
+            .map(|stop| stop.cycles)
     }
 
     pub fn run_steps(&mut self, steps: usize) -> Result<u64, EmuError> {
-        let mut cycles = 0u64;
-        for _ in 0..steps {
-            cycles += u64::from(self.step()?);
+        self.run_steps_with(steps, |_, _| ControlFlow::Continue(()))
+    }
+
+    /// Like `run_steps`, calling `on_step` after each step; returning `Break` stops the run
+    /// early. The callback gets the machine mutably so it can feed inputs or serial between
+    /// steps.
+    pub fn run_steps_with(
+        &mut self,
+        steps: usize,
+        mut on_step: impl FnMut(&mut GameBoy, StepInfo) -> ControlFlow<()>,
+    ) -> Result<u64, EmuError> {
+        let mut total_cycles = 0u64;
+        for completed in 1..=steps {
+            let cycles = self.step()?;
+            total_cycles += u64::from(cycles);
+            let info = StepInfo {
+                steps: completed,
+                cycles,
+                total_cycles,
+            };
+            if on_step(self, info).is_break() {
+                break;
+            }
         }
-        Ok(cycles)
+        Ok(total_cycles)
+    }
+
+    /// Snapshot of the emulated machine, including mid-instruction-sequence state such as
+    /// a pending EI, the HALT bug, a delayed TIMA reload and an in-flight OAM DMA. Host
+    /// settings, breakpoints, callbacks, counters and recordings are not included. An
+    /// instruction `tick` left part-way is saved as finished.
+    pub fn save_state(&self) -> Vec<u8> {
+        #[cfg(feature = "cycle-accurate")]
+        if self.tick_debt > 0 {
+            let mut finished = self.clone();
+            finished.finish_instruction();
+            return finished.save_state();
+        }
+        let mut out = StateWriter::default();
+        write_header(&mut out);
+        out.u64(self.seed);
+        self.cpu.save_state(&mut out);
+        self.bus.save_state(&mut out);
+        out.into_bytes()
+    }
+
+    /// Restores a `save_state` snapshot and returns the build that wrote it. On error the
+    /// machine is left untouched.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<StateBuild, StateError> {
+        let mut input = StateReader::new(state);
+        let build = read_header(&mut input)?;
+        let mut loaded = self.clone();
+        loaded.seed = input.u64()?;
+        loaded.cpu.load_state(&mut input)?;
+        loaded.bus.load_state(&mut input)?;
+        input.finish()?;
+        *self = loaded;
+        Ok(build)
     }
 }
 
@@ -380,6 +1444,7 @@ pub struct Cpu {
     pub stopped: bool,
     ime_delay: u8,
     halt_bug: bool,
+    dispatched: Option<u16>,
 }
 
 impl Default for Cpu {
@@ -393,12 +1458,26 @@ impl Default for Cpu {
             stopped: false,
             ime_delay: 0,
             halt_bug: false,
+            dispatched: None,
         }
     }
 }
 
 impl Cpu {
     pub fn step(&mut self, bus: &mut Bus) -> Result<u32, EmuError> {
+        self.step_dispatching(bus, true)
+    }
+
+    // With `dispatch` false a due interrupt is held back: the CPU runs the next instruction,
+    // or stays halted, instead of jumping to the vector.
+    pub(crate) fn step_dispatching(
+        &mut self,
+        bus: &mut Bus,
+        dispatch: bool,
+    ) -> Result<u32, EmuError> {
+        self.dispatched = None;
+        // Debugger and frontend reads between steps do not belong to this instruction.
+        bus.instruction_accesses.set(0);
         if self.stopped {
             if bus.pending_interrupts() != 0 {
                 self.stopped = false;
@@ -409,14 +1488,14 @@ impl Cpu {
         }
 
         let pending = bus.pending_interrupts();
-        if self.ime && pending != 0 {
+        if self.ime && pending != 0 && dispatch {
             let cycles = self.service_interrupt(bus);
             bus.tick(cycles);
             return Ok(cycles);
         }
 
         if self.halted {
-            if pending != 0 {
+            if pending != 0 && (dispatch || !self.ime) {
                 self.halted = false;
             } else {
                 bus.tick(4);
@@ -455,17 +1534,20 @@ impl Cpu {
             }
             0x22 => {
                 let hl = self.regs.hl();
+                bus.trigger_oam_bug(hl, OamCorruption::Write);
                 bus.write_byte(hl, self.regs.a);
                 self.regs.set_hl(hl.wrapping_add(1));
                 Ok(8)
             }
             0x32 => {
                 let hl = self.regs.hl();
+                bus.trigger_oam_bug(hl, OamCorruption::Write);
                 bus.write_byte(hl, self.regs.a);
                 self.regs.set_hl(hl.wrapping_sub(1));
                 Ok(8)
             }
             0x03 | 0x13 | 0x23 | 0x33 => {
+                bus.trigger_oam_bug(self.r16(opcode), OamCorruption::Write);
                 match opcode {
                     0x03 => self.regs.set_bc(self.regs.bc().wrapping_add(1)),
                     0x13 => self.regs.set_de(self.regs.de().wrapping_add(1)),
@@ -525,17 +1607,20 @@ impl Cpu {
             }
             0x2A => {
                 let hl = self.regs.hl();
+                bus.trigger_oam_bug(hl, OamCorruption::ReadIncDec);
                 self.regs.a = bus.read_byte(hl);
                 self.regs.set_hl(hl.wrapping_add(1));
                 Ok(8)
             }
             0x3A => {
                 let hl = self.regs.hl();
+                bus.trigger_oam_bug(hl, OamCorruption::ReadIncDec);
                 self.regs.a = bus.read_byte(hl);
                 self.regs.set_hl(hl.wrapping_sub(1));
                 Ok(8)
             }
             0x0B | 0x1B | 0x2B | 0x3B => {
+                bus.trigger_oam_bug(self.r16(opcode), OamCorruption::Write);
                 match opcode {
                     0x0B => self.regs.set_bc(self.regs.bc().wrapping_sub(1)),
                     0x1B => self.regs.set_de(self.regs.de().wrapping_sub(1)),
@@ -903,6 +1988,12 @@ impl Cpu {
         }
     }
 
+    /// Vector the last step jumped to for an interrupt; 0x0000 when the dispatch was
+    /// cancelled by the IE write quirk.
+    pub fn dispatched_interrupt(&self) -> Option<u16> {
+        self.dispatched
+    }
+
     fn service_interrupt(&mut self, bus: &mut Bus) -> u32 {
         self.ime = false;
         self.ime_delay = 0;
@@ -919,6 +2010,7 @@ impl Cpu {
             self.sp = self.sp.wrapping_sub(1);
             bus.write_byte(self.sp, pc_lo);
             self.pc = 0x0000;
+            self.dispatched = Some(self.pc);
             return 20;
         }
 
@@ -929,6 +2021,7 @@ impl Cpu {
 
         bus.clear_interrupt(mask);
         self.pc = vector;
+        self.dispatched = Some(vector);
         20
     }
 
@@ -955,6 +2048,17 @@ impl Cpu {
         }
     }
 
+    /// BC, DE, HL or SP, selected by bits 4-5 of an `INC rr`/`DEC rr` style opcode.
+    fn r16(&self, opcode: u8) -> u16 {
+        match (opcode >> 4) & 0x03 {
+            0 => self.regs.bc(),
+            1 => self.regs.de(),
+            2 => self.regs.hl(),
+            3 => self.sp,
+            _ => unreachable!(),
+        }
+    }
+
     fn condition(&self, code: u8) -> bool {
         match code & 0x03 {
             0 => !self.regs.flag_z(),
@@ -1223,9 +2327,146 @@ impl Cpu {
     }
 }
 
+impl Stateful for Timer {
+    fn save_state(&self, out: &mut StateWriter) {
+        out.u16(self.divider);
+        out.u8(self.tima);
+        out.u8(self.tma);
+        out.u8(self.tac);
+        out.option(self.overflow_reload_delay, StateWriter::u8);
+        out.u64(self.interrupts);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader<'_>) -> Result<(), StateError> {
+        self.divider = input.u16()?;
+        self.tima = input.u8()?;
+        self.tma = input.u8()?;
+        self.tac = input.u8()? & 0x07;
+        self.overflow_reload_delay = input.option(StateReader::u8)?;
+        self.interrupts = input.u64()?;
+        Ok(())
+    }
+}
+
+/// The page table, boot ROM image, access statistics, ROM write log, link connection, compat
+/// palettes and hardware quirk settings are host configuration and stay as the loading
+/// instance has them.
+impl Stateful for Bus {
+    fn save_state(&self, out: &mut StateWriter) {
+        for region in [&self.vram[..], &self.wram, &self.oam, &self.io, &self.hram] {
+            out.bytes(region);
+        }
+        self.timer.save_state(out);
+        self.joypad.save_state(out);
+        out.u8(self.interrupt_enable);
+        out.u8(self.interrupt_flags);
+        out.byte_vec(&self.serial_device.save_state());
+        out.u64(self.serial_bytes_sent);
+        out.option(self.serial_request_cycle, StateWriter::u64);
+        out.u64(self.cycle_count);
+        self.rtc.save_state(out);
+        self.ppu.save_state(out);
+        self.apu.save_state(out);
+        out.option(self.oam_dma, |out, dma| {
+            out.u16(dma.source);
+            out.u32(dma.cycles);
+        });
+        out.bool(self.boot_rom_mapped);
+        out.option(self.cartridge.as_ref(), |out, cartridge| {
+            out.byte_vec(&cartridge.save_state())
+        });
+    }
+
+    fn load_state(&mut self, input: &mut StateReader<'_>) -> Result<(), StateError> {
+        for region in [
+            &mut self.vram[..],
+            &mut self.wram,
+            &mut self.oam,
+            &mut self.io,
+            &mut self.hram,
+        ] {
+            input.bytes_into(region)?;
+        }
+        self.timer.load_state(input)?;
+        self.joypad.load_state(input)?;
+        self.interrupt_enable = input.u8()?;
+        self.interrupt_flags = input.u8()?;
+        self.serial_device.load_state(&input.byte_vec()?)?;
+        self.serial_bytes_sent = input.u64()?;
+        self.serial_request_cycle = input.option(StateReader::u64)?;
+        self.cycle_count = input.u64()?;
+        self.rtc.load_state(input)?;
+        self.ppu.load_state(input)?;
+        self.apu.load_state(input)?;
+        self.oam_dma = input.option(|input| {
+            Ok(OamDma {
+                source: input.u16()?,
+                cycles: input.u32()?,
+            })
+        })?;
+        self.boot_rom_mapped = input.bool()?;
+        if self.boot_rom_mapped && self.boot_rom.is_none() {
+            return Err(StateError::Invalid("boot ROM"));
+        }
+        match (
+            input.option(StateReader::byte_vec)?,
+            self.cartridge.as_mut(),
+        ) {
+            (Some(state), Some(cartridge)) => cartridge.load_state(&state)?,
+            (Some(_), None) => return Err(StateError::Invalid("cartridge")),
+            (None, _) => self.cartridge = None,
+        }
+        self.instruction_accesses.set(0);
+        Ok(())
+    }
+}
+
+impl Stateful for Cpu {
+    fn save_state(&self, out: &mut StateWriter) {
+        let regs = self.regs;
+        out.bytes(&[
+            regs.a, regs.b, regs.c, regs.d, regs.e, regs.h, regs.l, regs.f,
+        ]);
+        out.u16(self.pc);
+        out.u16(self.sp);
+        out.bool(self.ime);
+        out.bool(self.halted);
+        out.bool(self.stopped);
+        out.u8(self.ime_delay);
+        out.bool(self.halt_bug);
+        out.option(self.dispatched, StateWriter::u16);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader<'_>) -> Result<(), StateError> {
+        let mut regs = [0; 8];
+        input.bytes_into(&mut regs)?;
+        let [a, b, c, d, e, h, l, f] = regs;
+        self.regs = Registers {
+            a,
+            b,
+            c,
+            d,
+            e,
+            h,
+            l,
+            f,
+        };
+        self.pc = input.u16()?;
+        self.sp = input.u16()?;
+        self.ime = input.bool()?;
+        self.halted = input.bool()?;
+        self.stopped = input.bool()?;
+        self.ime_delay = input.u8()?;
+        self.halt_bug = input.bool()?;
+        self.dispatched = input.option(StateReader::u16)?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{build_info, state_build, PpuMode, SCREEN_WIDTH, STATE_VERSION};
 
     fn run_steps(gb: &mut GameBoy, steps: usize) {
         for _ in 0..steps {
@@ -1564,4 +2805,748 @@ mod tests {
         assert!(!gb.cpu.stopped);
         assert_eq!(gb.cpu.pc, 0x0003);
     }
+
+    #[test]
+    fn frame_counter_tracks_scanline_and_frame_position() {
+        let mut gb = GameBoy::new(); // zeroed memory executes NOPs (4 cycles each)
+        gb.bus.write_byte(LCDC_ADDR, 0x91);
+        assert_eq!(gb.frame_count(), 0);
+        assert_eq!(gb.current_scanline(), 0);
+
+        let nops = (CYCLES_PER_FRAME + 3 * CYCLES_PER_SCANLINE + 8) / 4;
+        gb.run_steps(nops as usize).expect("NOPs should run");
+        assert_eq!(gb.frame_count(), 1);
+        assert_eq!(gb.current_scanline(), 3);
+        assert_eq!(gb.cycles_into_frame(), 3 * CYCLES_PER_SCANLINE + 8);
+        assert_eq!(gb.frame_count(), gb.bus.ppu().vblank_count());
+    }
+
+    #[test]
+    fn frame_position_follows_the_ppu_across_an_lcd_toggle() {
+        let mut gb = GameBoy::new();
+        gb.bus.write_byte(LCDC_ADDR, 0x91);
+        gb.run_steps((50 * CYCLES_PER_SCANLINE / 4) as usize)
+            .expect("NOPs should run");
+        assert_eq!(gb.current_scanline(), 50);
+
+        gb.bus.write_byte(LCDC_ADDR, 0x11);
+        gb.run_steps((CYCLES_PER_FRAME / 4) as usize)
+            .expect("NOPs should run");
+        assert_eq!(gb.frame_count(), 0, "no frames while the LCD is off");
+        assert_eq!((gb.current_scanline(), gb.cycles_into_frame()), (0, 0));
+
+        gb.bus.write_byte(LCDC_ADDR, 0x91);
+        gb.run_steps((2 * CYCLES_PER_SCANLINE / 4) as usize)
+            .expect("NOPs should run");
+        assert_eq!(gb.current_scanline(), 2);
+        assert!(
+            (2 * CYCLES_PER_SCANLINE..3 * CYCLES_PER_SCANLINE).contains(&gb.cycles_into_frame())
+        );
+        gb.run_frame().expect("NOPs should run");
+        assert_eq!(gb.frame_count(), gb.bus.ppu().vblank_count());
+        assert_eq!(gb.frame_count(), 1);
+    }
+
+    #[test]
+    fn screenshot_finishes_the_frame_on_a_clone() {
+        let mut gb = GameBoy::new(); // zeroed VRAM: every pixel is BGP color 0
+        gb.bus.write_byte(LCDC_ADDR, 0x91);
+        // The first frame after the LCD turns on is blank.
+        gb.run_frame().expect("NOPs should run");
+        gb.run_frame().expect("NOPs should run");
+        assert_eq!(gb.screenshot(), Ok(gb.framebuffer().to_vec()), "in VBlank");
+
+        gb.bus.write_byte(BGP_ADDR, 0x03);
+        while gb.bus.ppu().ly() != 72 {
+            gb.step().expect("NOPs should run");
+        }
+        let torn = gb.framebuffer().to_vec();
+        assert_eq!((torn[0], torn[torn.len() - 1]), (3, 0));
+
+        let cycles = gb.cycle_count();
+        let screenshot = gb.screenshot().expect("NOPs should run");
+        assert!(screenshot.iter().all(|&shade| shade == 3));
+        assert_eq!(gb.cycle_count(), cycles);
+        assert_eq!(gb.framebuffer(), torn);
+    }
+
+    #[test]
+    fn ppu_registers_are_mapped_and_ly_follows_the_bus_clock() {
+        let mut gb = GameBoy::new();
+        gb.bus.write_byte(LCDC_ADDR, 0x91);
+        gb.bus.write_byte(crate::LY_ADDR, 0x55);
+        assert_eq!(gb.bus.read_byte(crate::LY_ADDR), 0);
+
+        let nops = (2 * CYCLES_PER_SCANLINE) / 4;
+        gb.run_steps(nops as usize).expect("NOPs should run");
+        assert_eq!(gb.bus.read_byte(crate::LY_ADDR), 2);
+        assert_eq!(
+            gb.framebuffer().len(),
+            crate::SCREEN_WIDTH * crate::SCREEN_HEIGHT
+        );
+    }
+
+    #[test]
+    fn page_table_routes_only_the_io_page_through_io_dispatch() {
+        let pages = page_table();
+        assert_eq!(pages[0x00], Page::Cartridge);
+        assert_eq!(pages[0xBF], Page::Cartridge);
+        assert_eq!(pages[0x80], Page::Vram);
+        assert_eq!(pages[0xC0], Page::Wram);
+        assert_eq!(pages[0xFD], Page::Wram);
+        assert_eq!(pages[0xFE], Page::Oam);
+        assert_eq!(pages[0xFF], Page::Io);
+
+        let mut bus = Bus::default();
+        bus.write_byte(0xC0FF, 0x12);
+        bus.write_byte(0xFF80, 0x34);
+        bus.write_byte(IF_ADDR, 0xFF);
+        assert_eq!(bus.read_byte(0xC0FF), 0x12);
+        assert_eq!(bus.read_byte(0xFF80), 0x34);
+        assert_eq!(bus.read_byte(IF_ADDR), 0xFF);
+    }
+
+    #[test]
+    fn echo_ram_mirrors_wram_and_the_prohibited_area_reads_zero() {
+        let mut bus = Bus::default();
+        bus.write_byte(0xC123, 0x12);
+        assert_eq!(bus.read_byte(0xE123), 0x12);
+        bus.write_byte(0xFDFF, 0x34);
+        assert_eq!(bus.read_byte(0xDDFF), 0x34);
+
+        bus.write_byte(0xFE9F, 0x56);
+        bus.write_byte(0xFEA0, 0x78);
+        assert_eq!(bus.read_byte(0xFE9F), 0x56);
+        assert_eq!(bus.read_byte(0xFEA0), 0x00);
+        bus.load_bytes(0xFEFF, &[0x9A, 0xBC]);
+        assert_eq!(bus.read_byte(0xFEFF), 0x00);
+        assert_eq!(bus.read_byte(0xFF00) & 0x0F, 0x0F, "the next page is IO");
+
+        bus.write_byte(LCDC_ADDR, 0x91);
+        while bus.ppu.oam_accessible() {
+            bus.tick(4);
+        }
+        assert_eq!(bus.read_byte(0xFEA0), 0xFF, "blocked with OAM");
+    }
+
+    #[test]
+    fn unmapped_io_reads_open_bus_and_stored_registers_are_masked() {
+        let mut bus = Bus::default();
+        for address in [0xFF03, 0xFF08, 0xFF27, BOOT_ADDR, 0xFF4D, 0xFF7F] {
+            bus.write_byte(address, 0x01);
+            assert_eq!(bus.read_byte(address), 0xFF, "{address:04X}");
+        }
+        bus.write_byte(SB_ADDR, 0x5A);
+        assert_eq!(bus.read_byte(SB_ADDR), 0x5A);
+        bus.write_byte(SC_ADDR, 0x01);
+        assert_eq!(bus.read_byte(SC_ADDR), 0x7F);
+
+        bus.ppu.set_model(Model::Cgb);
+        assert_eq!(bus.read_byte(SC_ADDR), 0x7D, "bit 1 is the CGB clock speed");
+        assert_eq!(bus.read_byte(0xFF4D), 0x7F);
+        assert_eq!(bus.read_byte(0xFF03), 0xFF);
+    }
+
+    #[test]
+    fn cgb_registers_read_back_through_their_masks() {
+        let mut bus = Bus::default();
+        bus.ppu.set_model(Model::Cgb);
+        for (address, written, read) in [
+            (KEY1_ADDR, 0x00, 0x7E),
+            (VBK_ADDR, 0x00, 0xFE),
+            (VBK_ADDR, 0x01, 0xFF),
+            (RP_ADDR, 0xC1, 0xFF),
+            (RP_ADDR, 0x00, 0x3E),
+            (BCPS_ADDR, 0x85, 0xC5),
+            (BCPD_ADDR, 0x12, 0x12),
+            (OCPS_ADDR, 0x3F, 0x7F),
+            (OPRI_ADDR, 0x00, 0xFE),
+            (SVBK_ADDR, 0x02, 0xFA),
+            (0xFF72, 0x5A, 0x5A),
+            (0xFF75, 0x00, 0x8F),
+            (PCM12_ADDR, 0x5A, 0x00),
+        ] {
+            bus.write_byte(address, written);
+            assert_eq!(bus.read_byte(address), read, "{address:04X}");
+        }
+        for address in [
+            0xFF4C, 0xFF4E, 0xFF51, 0xFF55, 0xFF57, 0xFF67, 0xFF6D, 0xFF6F, 0xFF71,
+        ]
+        .into_iter()
+        .chain(0xFF78..=0xFF7F)
+        {
+            bus.write_byte(address, 0x00);
+            assert_eq!(bus.read_byte(address), 0xFF, "{address:04X}");
+        }
+
+        bus.ppu.set_model(Model::Dmg);
+        assert_eq!(bus.read_byte(VBK_ADDR), 0xFF);
+        assert_eq!(bus.read_byte(BCPD_ADDR), 0xFF);
+    }
+
+    #[test]
+    fn lyc_stat_interrupt_wakes_halt_and_dispatches_to_0048() {
+        // LYC=5 ; STAT=LYC source ; IE=LCD ; LCDC=$91 ; EI ; HALT ; JR halt
+        let program = [
+            0x3E, 0x05, 0xE0, 0x45, 0x3E, 0x40, 0xE0, 0x41, 0x3E, 0x02, 0xE0, 0xFF, 0x3E, 0x91,
+            0xE0, 0x40, 0xFB, 0x76, 0x18, 0xFD,
+        ];
+        let mut gb = GameBoy::with_program(0x0000, &program);
+        // LDH A,(LY) ; LD B,A ; JR -2
+        gb.bus.load_bytes(0x0048, &[0xF0, 0x44, 0x47, 0x18, 0xFE]);
+
+        gb.run_steps(1_000).expect("program should run");
+        assert_eq!(gb.cpu.regs.b, 5);
+        assert_eq!(gb.cpu.pc, 0x004B);
+    }
+
+    #[test]
+    fn run_frame_stops_at_each_vblank() {
+        // LD A,$91 ; LDH ($40),A ; JR -2
+        let mut gb = GameBoy::with_program(0x0000, &[0x3E, 0x91, 0xE0, 0x40, 0x18, 0xFE]);
+        let first = gb.run_frame().expect("program should run");
+        assert!(first < u64::from(CYCLES_PER_FRAME));
+        assert_eq!(gb.bus.ppu().ly(), 144);
+        assert_ne!(gb.bus.read_byte(IF_ADDR) & INTERRUPT_VBLANK, 0);
+
+        let second = gb.run_frame().expect("program should run");
+        assert!(second.abs_diff(u64::from(CYCLES_PER_FRAME)) < 12);
+        assert_eq!(gb.bus.ppu().vblank_count(), 2);
+    }
+
+    #[test]
+    fn run_frame_with_lcd_off_runs_one_frame_of_cycles() {
+        let mut gb = GameBoy::new();
+        let cycles = gb.run_frame().expect("NOPs should run");
+        assert_eq!(cycles, u64::from(CYCLES_PER_FRAME));
+    }
+
+    #[test]
+    fn run_steps_with_reports_each_step_and_stops_on_break() {
+        let mut gb = GameBoy::new();
+        let mut seen = Vec::new();
+        let cycles = gb
+            .run_steps_with(10, |gb, info| {
+                seen.push(info);
+                if gb.cpu.pc == 3 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+            .expect("NOPs should run");
+
+        assert_eq!(cycles, 12);
+        assert_eq!(seen.len(), 3);
+        assert_eq!(
+            seen[2],
+            StepInfo {
+                steps: 3,
+                cycles: 4,
+                total_cycles: 12,
+            }
+        );
+    }
+
+    fn test_cartridge(program: &[u8]) -> Rom {
+        let mut data = vec![0u8; 0x8000];
+        data[0x104..0x134].copy_from_slice(&crate::TEST_LOGO);
+        data[0x150..0x150 + program.len()].copy_from_slice(program);
+        data[0x147] = 0x08; // ROM+RAM
+        data[0x149] = 0x02; // 8 KiB
+        data[0x14D] = crate::calculate_header_checksum(&data);
+        Rom::from_bytes(data).expect("test ROM header should parse")
+    }
+
+    #[test]
+    fn seed_determines_power_on_ram() {
+        let cleared = GameBoy::new();
+        assert_eq!(cleared.seed(), 0);
+        assert_eq!(GameBoy::with_seed(0).bus.wram, cleared.bus.wram);
+
+        let seeded = GameBoy::with_seed(0x5EED);
+        assert_eq!(seeded.seed(), 0x5EED);
+        assert_eq!(GameBoy::with_seed(0x5EED).bus.wram, seeded.bus.wram);
+        assert_ne!(GameBoy::with_seed(0x5EEE).bus.wram, seeded.bus.wram);
+        assert_ne!(seeded.bus.wram, cleared.bus.wram);
+        assert_ne!(seeded.bus.hram, cleared.bus.hram);
+        assert_eq!(seeded.bus.vram, cleared.bus.vram);
+        assert_eq!(seeded.bus.oam, cleared.bus.oam);
+        assert_eq!(seeded.bus.io, cleared.bus.io);
+    }
+
+    #[test]
+    fn instances_share_one_rom_image() {
+        let mut image = vec![0x00; 0x8000];
+        image[0x100..0x104].copy_from_slice(&[0x3C, 0x3C, 0x18, 0xFC]); // INC A x2 ; JR -4
+        let image: Arc<[u8]> = image.into();
+        let mut first = GameBoy::new();
+        let mut second = GameBoy::with_seed(7);
+        first.load_shared_rom(Arc::clone(&image));
+        second.load_shared_rom(Arc::clone(&image));
+        let runahead = first.clone();
+        assert_eq!(Arc::strong_count(&image), 4);
+        for gb in [&first, &second, &runahead] {
+            let cartridge = gb.bus.cartridge().expect("cartridge inserted");
+            assert!(Arc::ptr_eq(cartridge.rom(), &image));
+        }
+
+        first.run_steps(10).expect("loop should run");
+        second.run_steps(10).expect("loop should run");
+        assert_eq!(first.cpu.regs.a, second.cpu.regs.a);
+        drop(runahead);
+        assert_eq!(Arc::strong_count(&image), 3);
+    }
+
+    #[test]
+    fn ejected_cartridge_floats_the_bus_until_reinserted() {
+        let mut gb = GameBoy::new();
+        gb.insert_cartridge(test_cartridge(&[0x3C])); // INC A
+        gb.bus.write_byte(0xA000, 0x12);
+        gb.bus.write_byte(0xC000, 0x34);
+        assert!(gb.bus.cartridge_inserted());
+        assert_eq!(gb.bus.read_byte(0xA000), 0x12);
+
+        gb.eject_cartridge();
+        assert!(!gb.bus.cartridge_inserted());
+        assert_eq!(gb.bus.read_byte(0x0150), 0xFF);
+        assert_eq!(gb.bus.read_byte(0xA000), 0xFF);
+        gb.bus.write_byte(0xA000, 0x56);
+        assert_eq!(gb.bus.read_byte(0xC000), 0x34, "WRAM lives in the console");
+
+        // Code running from ROM fetches 0xFF, i.e. RST 38.
+        gb.cpu.pc = 0x0150;
+        gb.step().expect("RST 38 should execute");
+        assert_eq!(gb.cpu.pc, 0x0038);
+
+        gb.insert_cartridge(test_cartridge(&[0x3C]));
+        assert_eq!(gb.bus.read_byte(0x0150), 0x3C);
+        assert_eq!(
+            gb.bus.read_byte(0xA000),
+            0x00,
+            "new cartridge RAM starts cleared"
+        );
+        assert_eq!(gb.cpu.pc, 0x0038, "hot swap does not reset the CPU");
+    }
+
+    #[test]
+    fn ppu_modes_lock_vram_and_oam_from_the_cpu() {
+        let mut bus = Bus::default();
+        bus.write_byte(0x8000, 0x11);
+        bus.write_byte(0xFE00, 0x22);
+        bus.write_byte(LCDC_ADDR, 0x91);
+
+        assert_eq!(bus.ppu().mode(), PpuMode::OamScan);
+        assert_eq!(bus.read_byte(0x8000), 0x11);
+        assert_eq!(bus.read_byte(0xFE00), 0xFF);
+        bus.write_byte(0xFE00, 0x33);
+
+        bus.tick(80);
+        assert_eq!(bus.ppu().mode(), PpuMode::Drawing);
+        assert_eq!(bus.read_byte(0x8000), 0xFF);
+        assert_eq!(bus.read_byte(0xFE00), 0xFF);
+        bus.write_byte(0x8000, 0x44);
+        assert_eq!(bus.read_byte(0xC000), 0x00, "WRAM stays accessible");
+
+        bus.tick(172);
+        assert_eq!(bus.ppu().mode(), PpuMode::HBlank);
+        assert_eq!(bus.read_byte(0x8000), 0x11);
+        assert_eq!(bus.read_byte(0xFE00), 0x22);
+    }
+
+    #[test]
+    fn oam_dma_copies_160_bytes_and_blocks_non_hram_access() {
+        let mut bus = Bus::default();
+        let source: Vec<u8> = (0..160).collect();
+        bus.load_bytes(0xC000, &source);
+        bus.write_byte(0xFF80, 0x5A);
+        bus.write_byte(DMA_ADDR, 0xC0);
+        assert_eq!(bus.read_byte(DMA_ADDR), 0xC0);
+        assert_eq!(
+            bus.read_byte(0xC000),
+            0x00,
+            "startup M-cycle still allows access"
+        );
+
+        bus.tick(8);
+        assert!(bus.oam_dma_active());
+        assert_eq!(bus.read_byte(0xC000), 0xFF);
+        assert_eq!(bus.read_byte(0x0000), 0xFF);
+        assert_eq!(bus.read_byte(0xFF80), 0x5A);
+        bus.write_byte(0xC000, 0x99);
+
+        bus.tick(OAM_DMA_BYTES * OAM_DMA_CYCLES_PER_BYTE);
+        assert!(!bus.oam_dma_active());
+        assert_eq!(bus.read_byte(0xC000), 0x00, "blocked write was dropped");
+        for (offset, value) in source.iter().enumerate() {
+            assert_eq!(bus.read_byte(OAM_START + offset as u16), *value);
+        }
+    }
+
+    #[test]
+    fn oam_dma_runs_from_an_hram_wait_loop() {
+        // LD A,$C1 ; LDH ($46),A ; LD A,$29 ; DEC A ; JR NZ,-3 ; HALT
+        let mut gb = GameBoy::with_program(
+            0xFF80,
+            &[0x3E, 0xC1, 0xE0, 0x46, 0x3E, 0x29, 0x3D, 0x20, 0xFD, 0x76],
+        );
+        gb.bus.load_bytes(0xC100, &[0x10, 0x20, 0x30]);
+        gb.bus.load_bytes(0xC19F, &[0xEE]);
+        while !gb.cpu.halted {
+            gb.step().expect("HRAM routine should run");
+        }
+        assert!(!gb.bus.oam_dma_active());
+        assert_eq!(gb.bus.read_byte(0xFE00), 0x10);
+        assert_eq!(gb.bus.read_byte(0xFE02), 0x30);
+        assert_eq!(gb.bus.read_byte(0xFE9F), 0xEE);
+    }
+
+    /// Saves before every step of `gb`, restores into a fresh console and checks both copies
+    /// keep executing identically; `in_flight` must hold at one of the save points.
+    fn assert_resumes_identically(
+        mut gb: GameBoy,
+        steps: usize,
+        in_flight: impl Fn(&GameBoy) -> bool,
+    ) {
+        // States carry no ROM, so they restore into a machine with the same cartridge.
+        let power_on = gb.clone();
+        let mut saved_in_flight = false;
+        for step in 0..steps {
+            saved_in_flight |= in_flight(&gb);
+            let state = gb.save_state();
+            let mut restored = power_on.clone();
+            restored.load_state(&state).expect("state should load");
+            assert_eq!(restored.save_state(), state, "step {step}");
+
+            let mut original = gb.clone();
+            for _ in 0..12 {
+                let expected = original.step().expect("original should run");
+                let actual = restored.step().expect("restored should run");
+                assert_eq!(actual, expected, "cycles after step {step}");
+            }
+            assert_eq!(restored.cpu, original.cpu, "cpu after step {step}");
+            assert_eq!(restored.framebuffer(), original.framebuffer());
+            assert_eq!(restored.cycle_count(), original.cycle_count());
+            assert_eq!(restored.save_state(), original.save_state());
+
+            gb.step().expect("program should run");
+        }
+        assert!(saved_in_flight, "no save point caught the micro-state");
+    }
+
+    #[test]
+    fn save_state_resumes_during_ei_delay() {
+        let mut gb = GameBoy::with_program(0x0000, &[0xFB, 0x04, 0x04, 0x04]); // EI, INC B x3
+        gb.bus.load_bytes(0x0050, &[0xD9]); // RETI
+        gb.bus.write_byte(IE_ADDR, INTERRUPT_TIMER);
+        gb.bus.write_byte(IF_ADDR, INTERRUPT_TIMER);
+        assert_resumes_identically(gb, 4, |gb| gb.cpu.ime_delay > 0);
+    }
+
+    #[test]
+    fn save_state_resumes_during_halt_bug() {
+        let mut gb = GameBoy::with_program(0x0000, &[0x76, 0x04, 0x00, 0x00]); // HALT, INC B
+        gb.bus.write_byte(IE_ADDR, INTERRUPT_VBLANK);
+        gb.bus.write_byte(IF_ADDR, INTERRUPT_VBLANK);
+        assert_resumes_identically(gb, 3, |gb| gb.cpu.halt_bug);
+    }
+
+    #[test]
+    fn save_state_resumes_during_timer_reload_delay() {
+        let mut gb = GameBoy::with_program(0x0000, &[0x00; 32]);
+        gb.bus.write_byte(TAC_ADDR, 0b101);
+        gb.bus.write_byte(TMA_ADDR, 0xAC);
+        gb.bus.write_byte(TIMA_ADDR, 0xFF);
+        assert_resumes_identically(gb, 16, |gb| gb.bus.timer.overflow_reload_delay.is_some());
+    }
+
+    #[test]
+    fn save_state_resumes_during_oam_dma() {
+        // LD A,$C1 ; LDH ($46),A ; LD A,$29 ; DEC A ; JR NZ,-3 ; HALT
+        let mut gb = GameBoy::with_program(
+            0xFF80,
+            &[0x3E, 0xC1, 0xE0, 0x46, 0x3E, 0x29, 0x3D, 0x20, 0xFD, 0x76],
+        );
+        let source: Vec<u8> = (0..160).collect();
+        gb.bus.load_bytes(0xC100, &source);
+        assert_resumes_identically(gb, 24, |gb| gb.bus.oam_dma_active());
+    }
+
+    #[test]
+    fn load_state_rejects_foreign_and_damaged_states() {
+        let mut gb = GameBoy::with_seed(7);
+        gb.step().expect("NOP");
+        let state = gb.save_state();
+        let untouched = gb.save_state();
+
+        let mut other_magic = state.clone();
+        other_magic[0] = b'X';
+        assert_eq!(gb.load_state(&other_magic), Err(StateError::BadMagic));
+
+        let mut newer = state.clone();
+        newer[4..6].copy_from_slice(&(STATE_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            gb.load_state(&newer),
+            Err(StateError::UnsupportedVersion(STATE_VERSION + 1))
+        );
+
+        assert_eq!(
+            gb.load_state(&state[..state.len() - 1]),
+            Err(StateError::Truncated)
+        );
+        let mut padded = state.clone();
+        padded.push(0);
+        assert_eq!(gb.load_state(&padded), Err(StateError::TrailingBytes(1)));
+        assert_eq!(gb.save_state(), untouched, "failed loads change nothing");
+
+        let mut fresh = GameBoy::new();
+        let build = fresh.load_state(&state).expect("state should load");
+        assert_eq!(fresh.seed(), 7);
+        assert_eq!(build.version, build_info().version);
+        assert_eq!(build.git_hash, build_info().git_hash);
+        assert_eq!(state_build(&state), Ok(build));
+    }
+
+    #[test]
+    fn debug_viewers_read_live_vram_and_oam() {
+        let mut gb = GameBoy::new();
+        gb.bus.write_byte(0x8000, 0xFF);
+        gb.bus.write_byte(0x9C05, 0x07);
+        gb.bus.write_byte(0xFE04, 0x20);
+        assert_eq!(gb.decode_tiles()[0].pixels[0], [1; 8]);
+        let map = gb.background_map(1).expect("map 1 exists");
+        assert_eq!(map.entry(5, 0).tile_number, 0x07);
+        assert_eq!(gb.oam_entries()[1].y, 0x10);
+        assert_eq!(gb.oam_iter().filter(|entry| entry.y > -16).count(), 1);
+        let cell = gb
+            .bg_map_iter(1)
+            .expect("map 1 exists")
+            .find(|cell| cell.tile_number != 0)
+            .expect("one cell is set");
+        assert_eq!((cell.column, cell.row, cell.address), (5, 0, 0x9C05));
+    }
+
+    #[test]
+    fn ppu_register_writes_land_on_the_instructions_write_cycle() {
+        // Runs BGP <- 0x08 during mode 3 over a line of color-1 pixels and returns the
+        // pixel showing the DMG old|new blend.
+        let blend_pixel = |program: &[u8]| {
+            let mut gb = GameBoy::with_program(0xC000, program);
+            gb.set_ppu_renderer(PpuRenderer::PixelFifo);
+            gb.bus.load_bytes(0x8000, &[0xFF, 0x00].repeat(8));
+            gb.bus.write_byte(BGP_ADDR, 0xE4);
+            gb.bus.write_byte(LCDC_ADDR, 0x91);
+            gb.cpu.regs.a = 0x08;
+            gb.cpu.regs.set_hl(BGP_ADDR);
+            gb.bus.tick(80 + 40);
+            gb.step().expect("BGP write should run");
+            gb.bus.tick(CYCLES_PER_SCANLINE);
+            gb.framebuffer()[..SCREEN_WIDTH]
+                .iter()
+                .position(|&shade| shade == 3)
+                .expect("one pixel shows the blend")
+        };
+        // LD (HL),A writes on its second M-cycle, LDH (n),A on its third.
+        assert_eq!(blend_pixel(&[0xE0, 0x47]), blend_pixel(&[0x77]) + 4);
+    }
+
+    const OAM_SCAN_AND_DRAW: u32 = 80 + 172;
+
+    #[test]
+    fn inc_hl_in_oam_range_corrupts_scanned_row_only_with_quirk_enabled() {
+        let oam: Vec<u8> = (0..0xA0).collect();
+        let run = |quirks: HardwareQuirks| {
+            // INC HL with HL = FE10 while the PPU scans row 2
+            let mut gb = GameBoy::with_program(0xC000, &[0x23]);
+            gb.set_hardware_quirks(quirks);
+            gb.bus.load_bytes(OAM_START, &oam);
+            gb.cpu.regs.set_hl(0xFE10);
+            gb.bus.write_byte(LCDC_ADDR, 0x91);
+            gb.bus.tick(8);
+            gb.step().expect("INC HL should run");
+            gb.bus.tick(OAM_SCAN_AND_DRAW);
+            (0..0xA0)
+                .map(|offset| gb.bus.read_byte(OAM_START + offset))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(run(HardwareQuirks::default()), oam);
+        let mut expected = oam.clone();
+        corrupt_oam(&mut expected, 2, OamCorruption::Write);
+        assert_ne!(expected, oam);
+        assert_eq!(
+            run(HardwareQuirks {
+                oam_corruption: true,
+                ..HardwareQuirks::default()
+            }),
+            expected
+        );
+    }
+}
+
+/// Checks the ALU helpers against a bit-at-a-time reference model over random inputs.
+#[cfg(test)]
+mod alu_properties {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn cpu_with(a: u8, flags: u8) -> Cpu {
+        let mut cpu = Cpu::default();
+        cpu.regs.a = a;
+        cpu.regs.f = flags & 0xF0;
+        cpu
+    }
+
+    fn flags(z: bool, n: bool, h: bool, c: bool) -> u8 {
+        (u8::from(z) << 7) | (u8::from(n) << 6) | (u8::from(h) << 5) | (u8::from(c) << 4)
+    }
+
+    fn bit(value: u8, index: u32) -> bool {
+        (value >> index) & 1 != 0
+    }
+
+    /// Ripple-carry adder; `subtract` feeds the inverted operand and inverted carry in, and
+    /// the returned carries are borrows in that case.
+    fn ripple(a: u8, b: u8, carry_in: bool, subtract: bool) -> (u8, bool, bool) {
+        let b = if subtract { !b } else { b };
+        let mut carry = carry_in != subtract;
+        let mut result = 0u8;
+        let mut half = false;
+        for index in 0..8 {
+            let (x, y) = (bit(a, index), bit(b, index));
+            result |= u8::from(x ^ y ^ carry) << index;
+            carry = (x && y) || (carry && (x ^ y));
+            if index == 3 {
+                half = carry != subtract;
+            }
+        }
+        (result, half, carry != subtract)
+    }
+
+    fn reference_daa(a: u8, f: u8) -> (u8, u8) {
+        let (n, h, c) = (f & 0x40 != 0, f & 0x20 != 0, f & 0x10 != 0);
+        let (hi, lo) = (a >> 4, a & 0x0F);
+        let (diff, carry) = if n {
+            (u8::from(h) * 0x06 + u8::from(c) * 0x60, c)
+        } else {
+            let low_fix = h || lo > 9;
+            let high_fix = c || hi > 9 || (hi == 9 && lo > 9);
+            (
+                u8::from(low_fix) * 0x06 + u8::from(high_fix) * 0x60,
+                high_fix,
+            )
+        };
+        let result = if n {
+            a.wrapping_sub(diff)
+        } else {
+            a.wrapping_add(diff)
+        };
+        (result, flags(result == 0, n, false, carry))
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    enum Rotate {
+        Rlc,
+        Rrc,
+        Rl,
+        Rr,
+        Sla,
+        Sra,
+        Srl,
+    }
+
+    fn reference_rotate(op: Rotate, value: u8, carry_in: bool) -> (u8, bool) {
+        let mut bits: Vec<bool> = (0..8).map(|index| bit(value, index)).collect();
+        let carry = match op {
+            Rotate::Rlc | Rotate::Rl | Rotate::Sla => bits[7],
+            Rotate::Rrc | Rotate::Rr | Rotate::Sra | Rotate::Srl => bits[0],
+        };
+        match op {
+            Rotate::Rlc => bits.rotate_right(1),
+            Rotate::Rrc => bits.rotate_left(1),
+            Rotate::Rl | Rotate::Sla => {
+                bits.rotate_right(1);
+                bits[0] = matches!(op, Rotate::Rl) && carry_in;
+            }
+            Rotate::Rr | Rotate::Sra | Rotate::Srl => {
+                let top = match op {
+                    Rotate::Rr => carry_in,
+                    Rotate::Sra => bits[7],
+                    _ => false,
+                };
+                bits.rotate_left(1);
+                bits[7] = top;
+            }
+        }
+        let result = bits
+            .iter()
+            .enumerate()
+            .fold(0u8, |acc, (index, set)| acc | (u8::from(*set) << index));
+        (result, carry)
+    }
+
+    fn rotate_op() -> impl Strategy<Value = Rotate> {
+        prop_oneof![
+            Just(Rotate::Rlc),
+            Just(Rotate::Rrc),
+            Just(Rotate::Rl),
+            Just(Rotate::Rr),
+            Just(Rotate::Sla),
+            Just(Rotate::Sra),
+            Just(Rotate::Srl),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn add_a_matches_reference(a: u8, value: u8, f: u8, with_carry: bool) {
+            let mut cpu = cpu_with(a, f);
+            cpu.add_a(value, with_carry);
+            let (result, half, carry) = ripple(a, value, with_carry && f & 0x10 != 0, false);
+            prop_assert_eq!(cpu.regs.a, result);
+            prop_assert_eq!(cpu.regs.f, flags(result == 0, false, half, carry));
+        }
+
+        #[test]
+        fn sub_a_matches_reference(a: u8, value: u8, f: u8, with_carry: bool) {
+            let mut cpu = cpu_with(a, f);
+            cpu.sub_a(value, with_carry);
+            let (result, half, borrow) = ripple(a, value, with_carry && f & 0x10 != 0, true);
+            prop_assert_eq!(cpu.regs.a, result);
+            prop_assert_eq!(cpu.regs.f, flags(result == 0, true, half, borrow));
+        }
+
+        #[test]
+        fn daa_matches_reference(a: u8, f: u8) {
+            let mut cpu = cpu_with(a, f);
+            cpu.daa();
+            prop_assert_eq!((cpu.regs.a, cpu.regs.f), reference_daa(a, f & 0xF0));
+        }
+
+        #[test]
+        fn rotates_match_reference(op in rotate_op(), value: u8, f: u8, set_zero: bool) {
+            let mut cpu = cpu_with(0, f);
+            let result = match op {
+                Rotate::Rlc => cpu.rlc(value, set_zero),
+                Rotate::Rrc => cpu.rrc(value, set_zero),
+                Rotate::Rl => cpu.rl(value, set_zero),
+                Rotate::Rr => cpu.rr(value, set_zero),
+                Rotate::Sla => cpu.sla(value),
+                Rotate::Sra => cpu.sra(value),
+                Rotate::Srl => cpu.srl(value),
+            };
+            let zero_flag = set_zero || matches!(op, Rotate::Sla | Rotate::Sra | Rotate::Srl);
+            let (expected, carry) = reference_rotate(op, value, f & 0x10 != 0);
+            prop_assert_eq!(result, expected);
+            prop_assert_eq!(cpu.regs.f, flags(zero_flag && expected == 0, false, false, carry));
+        }
+
+        #[test]
+        fn add_sp_offset_matches_reference(sp: u16, offset: u8) {
+            let [low, _] = sp.to_le_bytes();
+            let (_, half, carry) = ripple(low, offset, false, false);
+            let expected = (i32::from(sp) + i32::from(offset as i8)).rem_euclid(0x10000) as u16;
+            prop_assert_eq!(Cpu::add_sp_offset(sp, offset), (expected, half, carry));
+        }
+    }
 }
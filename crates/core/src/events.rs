@@ -0,0 +1,252 @@
+use crate::{EmuError, GameBoy, CYCLES_PER_FRAME, SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::fmt::{Debug, Formatter};
+use std::ops::BitOr;
+
+/// Set of events `GameBoy::run_until_event` stops on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventMask(u8);
+
+impl EventMask {
+    pub const NONE: Self = Self(0);
+    pub const VBLANK: Self = Self(0x01);
+    pub const SERIAL_BYTE: Self = Self(0x02);
+    pub const BREAKPOINT: Self = Self(0x04);
+    pub const TIMER_INTERRUPT: Self = Self(0x08);
+    pub const ALL: Self = Self(0x0F);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for EventMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    VBlank,
+    /// A byte the program started shifting out over serial.
+    SerialByte(u8),
+    /// The CPU is about to execute the instruction at this address.
+    Breakpoint(u16),
+    TimerInterrupt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventStop {
+    pub event: Event,
+    pub cycles: u64,
+}
+
+/// A completed frame, lent to the frame callback at VBlank.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame<'a> {
+    /// VBlank count so far, so the first frame is 1.
+    pub number: u64,
+    pub width: usize,
+    pub height: usize,
+    /// One 2-bit shade per pixel, as `GameBoy::framebuffer`.
+    pub pixels: &'a [u8],
+}
+
+/// Holder for the callback installed with `GameBoy::set_frame_callback`. Closures cannot
+/// be cloned, so a cloned `GameBoy` starts without one.
+#[derive(Default)]
+pub(crate) struct FrameCallback(Option<BoxedFrameCallback>);
+
+type BoxedFrameCallback = Box<dyn FnMut(&Frame<'_>) + Send>;
+
+impl Clone for FrameCallback {
+    fn clone(&self) -> Self {
+        Self(None)
+    }
+}
+
+impl Debug for FrameCallback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(if self.0.is_some() {
+            "FrameCallback(set)"
+        } else {
+            "FrameCallback(none)"
+        })
+    }
+}
+
+impl GameBoy {
+    /// Calls `callback` with the finished framebuffer each time the PPU enters VBlank, so
+    /// frontends can present frames without polling. Replaces any previous callback.
+    pub fn set_frame_callback(&mut self, callback: impl FnMut(&Frame<'_>) + Send + 'static) {
+        self.frame_callback.0 = Some(Box::new(callback));
+    }
+
+    pub fn clear_frame_callback(&mut self) {
+        self.frame_callback.0 = None;
+    }
+
+    pub(crate) fn emit_frame(&mut self) {
+        if let Some(callback) = self.frame_callback.0.as_mut() {
+            callback(&Frame {
+                number: self.bus.ppu().vblank_count(),
+                width: SCREEN_WIDTH,
+                height: SCREEN_HEIGHT,
+                pixels: self.bus.ppu().framebuffer(),
+            });
+        }
+    }
+
+    /// Steps until one of the events in `mask` happens and reports which one. At least one
+    /// step always runs, so calling it again from a breakpoint moves past it. With the LCD
+    /// off, one frame's worth of cycles counts as a VBlank. An empty mask never stops.
+    ///
+    /// When several events land in the same step, the first in `Event` order wins.
+    pub fn run_until_event(&mut self, mask: EventMask) -> Result<EventStop, EmuError> {
+        let vblanks = self.bus.ppu().vblank_count();
+        let serial_bytes = self.bus.serial_bytes_sent();
+        let timer_interrupts = self.bus.timer_interrupts();
+        let mut cycles = 0u64;
+        loop {
+            cycles += u64::from(self.step()?);
+            let event = if mask.contains(EventMask::VBLANK)
+                && (self.bus.ppu().vblank_count() != vblanks
+                    || (!self.bus.ppu().lcd_enabled() && cycles >= u64::from(CYCLES_PER_FRAME)))
+            {
+                Some(Event::VBlank)
+            } else if mask.contains(EventMask::SERIAL_BYTE)
+                && self.bus.serial_bytes_sent() != serial_bytes
+            {
+                Some(Event::SerialByte(self.bus.last_serial_byte()))
+            } else if mask.contains(EventMask::BREAKPOINT) && self.at_breakpoint() {
+                Some(Event::Breakpoint(self.cpu.pc))
+            } else if mask.contains(EventMask::TIMER_INTERRUPT)
+                && self.bus.timer_interrupts() != timer_interrupts
+            {
+                Some(Event::TimerInterrupt)
+            } else {
+                None
+            };
+            if let Some(event) = event {
+                return Ok(EventStop { event, cycles });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Breakpoint, GameBoyPrinter, LCDC_ADDR};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn stops_at_breakpoints_and_moves_past_them() {
+        let mut gb = GameBoy::new();
+        gb.add_breakpoint(0x0004);
+        gb.add_breakpoint(0x0004);
+        assert_eq!(gb.breakpoints(), &[Breakpoint::from(0x0004)]);
+
+        let stop = gb.run_until_event(EventMask::ALL).expect("NOPs should run");
+        assert_eq!(
+            stop,
+            EventStop {
+                event: Event::Breakpoint(0x0004),
+                cycles: 16,
+            }
+        );
+        gb.remove_breakpoint(0x0004);
+        let stop = gb.run_until_event(EventMask::ALL).expect("NOPs should run");
+        assert_eq!(stop.event, Event::VBlank);
+    }
+
+    #[test]
+    fn stops_on_serial_byte() {
+        // LD A,$42 ; LDH ($01),A ; LD A,$81 ; LDH ($02),A ; JR -2
+        let mut gb = GameBoy::with_program(
+            0x0000,
+            &[0x3E, 0x42, 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0x18, 0xFE],
+        );
+        let stop = gb
+            .run_until_event(EventMask::SERIAL_BYTE | EventMask::VBLANK)
+            .expect("program should run");
+        assert_eq!(stop.event, Event::SerialByte(0x42));
+        assert_eq!(gb.cpu.pc, 0x0008);
+    }
+
+    #[test]
+    fn serial_byte_fires_for_any_device_and_after_draining_output() {
+        // loop: LD A,$88 ; LDH ($01),A ; LD A,$81 ; LDH ($02),A ; JR loop
+        let program = [0x3E, 0x88, 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0x18, 0xF6];
+        let mut gb = GameBoy::with_program(0x0000, &program);
+        gb.bus.set_serial_device(Box::new(GameBoyPrinter::new()));
+        let stop = gb
+            .run_until_event(EventMask::SERIAL_BYTE)
+            .expect("program should run");
+        assert_eq!(stop.event, Event::SerialByte(0x88));
+        assert!(
+            gb.bus.serial_output().is_empty(),
+            "the printer keeps no output"
+        );
+
+        let mut gb = GameBoy::with_program(0x0000, &program);
+        for _ in 0..2 {
+            let stop = gb
+                .run_until_event(EventMask::SERIAL_BYTE)
+                .expect("program should run");
+            assert_eq!(stop.event, Event::SerialByte(0x88));
+            assert_eq!(gb.bus.take_serial_output(), [0x88]);
+        }
+    }
+
+    #[test]
+    fn stops_on_timer_interrupt_only_when_masked() {
+        // LD A,$05 ; LDH ($07),A ; JR -2  (timer on at 16 cycles per tick)
+        let program = [0x3E, 0x05, 0xE0, 0x07, 0x18, 0xFE];
+        let mut gb = GameBoy::with_program(0x0000, &program);
+        let stop = gb
+            .run_until_event(EventMask::TIMER_INTERRUPT)
+            .expect("program should run");
+        assert_eq!(stop.event, Event::TimerInterrupt);
+        assert!(stop.cycles < 16 * 256 + 32);
+
+        let mut gb = GameBoy::with_program(0x0000, &program);
+        let stop = gb
+            .run_until_event(EventMask::VBLANK)
+            .expect("program should run");
+        assert_eq!(stop.event, Event::VBlank);
+    }
+
+    #[test]
+    fn frame_callback_sees_each_vblank() {
+        // JR -2 with the LCD on
+        let mut gb = GameBoy::with_program(0x0000, &[0x18, 0xFE]);
+        gb.bus.write_byte(LCDC_ADDR, 0x91);
+        let frames = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&frames);
+        gb.set_frame_callback(move |frame| {
+            assert_eq!(frame.pixels.len(), frame.width * frame.height);
+            seen.lock().expect("frame log").push(frame.number);
+        });
+
+        gb.run_frame().expect("loop should run");
+        gb.run_frame().expect("loop should run");
+        assert_eq!(*frames.lock().expect("frame log"), [1, 2]);
+
+        let mut copy = gb.clone();
+        copy.run_frame().expect("loop should run");
+        gb.clear_frame_callback();
+        gb.run_frame().expect("loop should run");
+        assert_eq!(
+            *frames.lock().expect("frame log"),
+            [1, 2],
+            "clones and cleared machines do not call back"
+        );
+    }
+}
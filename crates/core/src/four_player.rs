@@ -0,0 +1,305 @@
+use crate::{EmuError, GameBoy, SERIAL_TRANSFER_CYCLES};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+pub const MAX_PLAYERS: usize = 4;
+
+const PACKET_LEN: usize = 4;
+const PING_HEADER: u8 = 0xFE;
+const PING_ACK: u8 = 0x88;
+const START_REQUEST: u8 = 0xAA;
+const START_ACK: u8 = 0xCC;
+const NOT_READY: u8 = 0xFF;
+const MISSING_PLAYER_BYTE: u8 = 0x00;
+const DEFAULT_PACKET_SIZE: u8 = 1;
+// The adapter clocks every port itself; this spacing approximates its byte rate and the
+// low nibble of RATE stretches it the way games use it to slow the link down.
+const BASE_BYTE_INTERVAL_CYCLES: u64 = SERIAL_TRANSFER_CYCLES + 2048;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerCountError(pub usize);
+
+impl Display for PlayerCountError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "four-player adapter needs 1 to {MAX_PLAYERS} players, got {}",
+            self.0
+        )
+    }
+}
+
+impl Error for PlayerCountError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdapterPhase {
+    Ping,
+    Starting,
+    Transmission,
+}
+
+/// DMG-07 four-player adapter driving up to four in-process Game Boys.
+///
+/// In the ping phase the adapter broadcasts `FE` followed by three status bytes (connected
+/// players in the high nibble, the port's player number in the low bits); player 1 picks
+/// RATE and SIZE in its replies and sends `AA AA AA AA` to start. After four `CC` bytes the
+/// adapter enters the transmission phase, where each player contributes SIZE bytes per round
+/// and receives every player's bytes from the previous round.
+#[derive(Debug, Clone)]
+pub struct FourPlayerAdapter {
+    players: Vec<GameBoy>,
+    phase: AdapterPhase,
+    index: usize,
+    connected: u8,
+    rate: u8,
+    size: u8,
+    player1_packet: [u8; PACKET_LEN],
+    round_in: [Vec<u8>; MAX_PLAYERS],
+    round_out: Vec<u8>,
+    next_transfer: u64,
+}
+
+impl FourPlayerAdapter {
+    pub fn new(players: Vec<GameBoy>) -> Result<Self, PlayerCountError> {
+        if players.is_empty() || players.len() > MAX_PLAYERS {
+            return Err(PlayerCountError(players.len()));
+        }
+        let mut players = players;
+        for player in &mut players {
+            player.bus.connect_link(true);
+        }
+        Ok(Self {
+            players,
+            phase: AdapterPhase::Ping,
+            index: 0,
+            connected: 0,
+            rate: 0,
+            size: DEFAULT_PACKET_SIZE,
+            player1_packet: [0; PACKET_LEN],
+            round_in: Default::default(),
+            round_out: Vec::new(),
+            next_transfer: byte_interval_cycles(0),
+        })
+    }
+
+    pub fn players(&self) -> &[GameBoy] {
+        &self.players
+    }
+
+    pub fn player_mut(&mut self, index: usize) -> Option<&mut GameBoy> {
+        self.players.get_mut(index)
+    }
+
+    pub fn phase(&self) -> AdapterPhase {
+        self.phase
+    }
+
+    pub fn packet_size(&self) -> u8 {
+        self.size
+    }
+
+    /// Steps the player furthest behind (lowest index on ties) and clocks the adapter once
+    /// every player has reached the next byte boundary.
+    pub fn step(&mut self) -> Result<u32, EmuError> {
+        let lagging = self
+            .players
+            .iter_mut()
+            .min_by_key(|player| player.cycle_count())
+            .expect("adapter always has at least one player");
+        let cycles = lagging.step()?;
+
+        if self.slowest_cycle() >= self.next_transfer {
+            self.transfer();
+            self.next_transfer += byte_interval_cycles(self.rate);
+        }
+        Ok(cycles)
+    }
+
+    pub fn run_cycles(&mut self, cycles: u64) -> Result<(), EmuError> {
+        let target = self.slowest_cycle() + cycles;
+        while self.slowest_cycle() < target {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    fn slowest_cycle(&self) -> u64 {
+        self.players
+            .iter()
+            .map(GameBoy::cycle_count)
+            .min()
+            .unwrap_or_default()
+    }
+
+    fn transfer(&mut self) {
+        let mut responses = [NOT_READY; MAX_PLAYERS];
+        let outgoing: Vec<u8> = (0..self.players.len())
+            .map(|port| self.adapter_byte(port))
+            .collect();
+        for ((player, byte), response) in self.players.iter_mut().zip(outgoing).zip(&mut responses)
+        {
+            if player.bus.serial_waiting_for_clock() {
+                *response = player.bus.complete_serial_transfer(byte);
+            }
+        }
+        self.consume(responses);
+    }
+
+    fn adapter_byte(&self, port: usize) -> u8 {
+        match self.phase {
+            AdapterPhase::Ping if self.index == 0 => PING_HEADER,
+            AdapterPhase::Ping => (self.connected << 4) | (port as u8 + 1),
+            AdapterPhase::Starting => START_ACK,
+            AdapterPhase::Transmission => self
+                .round_out
+                .get(self.index)
+                .copied()
+                .unwrap_or(MISSING_PLAYER_BYTE),
+        }
+    }
+
+    fn consume(&mut self, responses: [u8; MAX_PLAYERS]) {
+        match self.phase {
+            AdapterPhase::Ping => {
+                if self.index == 0 {
+                    self.connected = responses
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, byte)| matches!(**byte, PING_ACK | START_REQUEST))
+                        .fold(0, |mask, (port, _)| mask | (1 << port));
+                }
+                self.player1_packet[self.index] = responses[0];
+                self.index += 1;
+                if self.index == PACKET_LEN {
+                    self.index = 0;
+                    self.finish_ping_packet();
+                }
+            }
+            AdapterPhase::Starting => {
+                self.index += 1;
+                if self.index == PACKET_LEN {
+                    self.index = 0;
+                    self.phase = AdapterPhase::Transmission;
+                    self.round_out = vec![MISSING_PLAYER_BYTE; self.round_len()];
+                    self.round_in = Default::default();
+                }
+            }
+            AdapterPhase::Transmission => {
+                if self.index < usize::from(self.size) {
+                    let ports = self.players.len();
+                    for (data, response) in self.round_in.iter_mut().zip(responses).take(ports) {
+                        data.push(response);
+                    }
+                }
+                self.index += 1;
+                if self.index == self.round_len() {
+                    self.index = 0;
+                    self.finish_round();
+                }
+            }
+        }
+    }
+
+    fn finish_ping_packet(&mut self) {
+        if self.player1_packet == [START_REQUEST; PACKET_LEN] {
+            self.phase = AdapterPhase::Starting;
+        } else if self.player1_packet[0] == PING_ACK {
+            self.rate = self.player1_packet[2];
+            self.size = self.player1_packet[3].max(1);
+        }
+    }
+
+    fn finish_round(&mut self) {
+        let size = usize::from(self.size);
+        self.round_out.clear();
+        for data in &mut self.round_in {
+            data.resize(size, MISSING_PLAYER_BYTE);
+            self.round_out.append(data);
+        }
+    }
+
+    fn round_len(&self) -> usize {
+        usize::from(self.size) * MAX_PLAYERS
+    }
+}
+
+fn byte_interval_cycles(rate: u8) -> u64 {
+    BASE_BYTE_INTERVAL_CYCLES + u64::from(rate & 0x0F) * SERIAL_TRANSFER_CYCLES
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // LD HL,$C000
+    // loop: LD A,reply ; LDH ($01),A ; LD A,$80 ; LDH ($02),A
+    // wait: LDH A,($02) ; BIT 7,A ; JR NZ,wait
+    // LDH A,($01) ; LD (HL+),A ; JR loop
+    fn slave_program(reply: u8) -> Vec<u8> {
+        vec![
+            0x21, 0x00, 0xC0, 0x3E, reply, 0xE0, 0x01, 0x3E, 0x80, 0xE0, 0x02, 0xF0, 0x02, 0xCB,
+            0x7F, 0x20, 0xFA, 0xF0, 0x01, 0x22, 0x18, 0xED,
+        ]
+    }
+
+    fn received(player: &GameBoy, count: u16) -> Vec<u8> {
+        (0..count)
+            .map(|offset| player.bus.read_byte(0xC000 + offset))
+            .collect()
+    }
+
+    fn adapter(replies: &[u8]) -> FourPlayerAdapter {
+        let players = replies
+            .iter()
+            .map(|reply| GameBoy::with_program(0x0000, &slave_program(*reply)))
+            .collect();
+        FourPlayerAdapter::new(players).expect("player count should be valid")
+    }
+
+    fn run_transfers(adapter: &mut FourPlayerAdapter, transfers: u64) {
+        adapter
+            .run_cycles(transfers * BASE_BYTE_INTERVAL_CYCLES + 500)
+            .expect("players should run");
+    }
+
+    #[test]
+    fn ping_phase_reports_connected_players_and_numbers() {
+        let mut adapter = adapter(&[PING_ACK, PING_ACK, PING_ACK]);
+        run_transfers(&mut adapter, 4);
+
+        assert_eq!(adapter.phase(), AdapterPhase::Ping);
+        assert_eq!(received(&adapter.players()[0], 4), [0xFE, 0x71, 0x71, 0x71]);
+        assert_eq!(received(&adapter.players()[1], 4), [0xFE, 0x72, 0x72, 0x72]);
+        assert_eq!(received(&adapter.players()[2], 4), [0xFE, 0x73, 0x73, 0x73]);
+    }
+
+    #[test]
+    fn player_one_starts_transmission_and_rounds_broadcast_all_data() {
+        let mut adapter = adapter(&[START_REQUEST, 0x11, PING_ACK]);
+        run_transfers(&mut adapter, 16);
+
+        assert_eq!(adapter.phase(), AdapterPhase::Transmission);
+        assert_eq!(
+            received(&adapter.players()[1], 16),
+            [
+                0xFE, 0x52, 0x52, 0x52, // ping: players 1 and 3 acked
+                0xCC, 0xCC, 0xCC, 0xCC, // start acknowledgement
+                0x00, 0x00, 0x00, 0x00, // first round has no data yet
+                0xAA, 0x11, 0x88, 0x00, // previous round from players 1-3
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_player_counts() {
+        assert_eq!(
+            FourPlayerAdapter::new(Vec::new()).err(),
+            Some(PlayerCountError(0))
+        );
+        let five = (0..5).map(|_| GameBoy::new()).collect();
+        assert_eq!(
+            FourPlayerAdapter::new(five).err(),
+            Some(PlayerCountError(5))
+        );
+    }
+}
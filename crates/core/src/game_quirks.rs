@@ -0,0 +1,109 @@
+use crate::{GameBoy, PpuRenderer, RomHeader};
+use std::fmt::{Display, Formatter};
+
+/// A per-game setting turned on by the known-games table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameQuirk {
+    /// Needs `PpuRenderer::PixelFifo`, e.g. for palette or scroll writes during mode 3.
+    PixelFifoPpu,
+    /// Relies on `HardwareQuirks::oam_corruption` to draw or behave as on hardware.
+    OamCorruption,
+}
+
+impl GameQuirk {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PixelFifoPpu => "pixel-fifo-ppu",
+            Self::OamCorruption => "oam-corruption",
+        }
+    }
+}
+
+impl Display for GameQuirk {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+struct KnownGame {
+    title: &'static str,
+    /// Narrows the match to one release when a title is shared; `None` matches any.
+    global_checksum: Option<u16>,
+    quirks: &'static [GameQuirk],
+}
+
+const KNOWN_GAMES: &[KnownGame] = &[
+    // Rewrites BGP mid-scanline for its title and sky gradients.
+    KnownGame {
+        title: "PREHISTORIK MAN",
+        global_checksum: None,
+        quirks: &[GameQuirk::PixelFifoPpu],
+    },
+];
+
+/// Quirks the known-games table lists for `header`, matched on title and global checksum.
+pub fn game_quirks(header: &RomHeader) -> &'static [GameQuirk] {
+    KNOWN_GAMES
+        .iter()
+        .find(|game| {
+            game.title == header.title
+                && game
+                    .global_checksum
+                    .is_none_or(|checksum| checksum == header.global_checksum)
+        })
+        .map_or(&[], |game| game.quirks)
+}
+
+impl GameBoy {
+    /// Turns on whatever `game_quirks` lists for `header` and returns it for reporting.
+    /// Settings the table does not mention are left as they are.
+    pub fn apply_game_quirks(&mut self, header: &RomHeader) -> &'static [GameQuirk] {
+        let quirks = game_quirks(header);
+        for quirk in quirks {
+            match quirk {
+                GameQuirk::PixelFifoPpu => self.set_ppu_renderer(PpuRenderer::PixelFifo),
+                GameQuirk::OamCorruption => {
+                    let mut hardware = self.bus.quirks();
+                    hardware.oam_corruption = true;
+                    self.set_hardware_quirks(hardware);
+                }
+            }
+        }
+        quirks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HeaderValidation;
+
+    fn header(title: &[u8]) -> RomHeader {
+        let mut rom = vec![0; 0x150];
+        rom[0x134..0x134 + title.len()].copy_from_slice(title);
+        RomHeader::parse_with(&rom, HeaderValidation::Lenient).expect("header should parse")
+    }
+
+    #[test]
+    fn matches_known_titles_only() {
+        assert_eq!(
+            game_quirks(&header(b"PREHISTORIK MAN")),
+            &[GameQuirk::PixelFifoPpu]
+        );
+        assert!(game_quirks(&header(b"PREHISTORIK")).is_empty());
+        assert!(game_quirks(&header(b"TETRIS")).is_empty());
+    }
+
+    #[test]
+    fn applies_listed_quirks_to_the_machine() {
+        let mut gb = GameBoy::new();
+        assert!(gb.apply_game_quirks(&header(b"TETRIS")).is_empty());
+        assert_eq!(gb.bus.ppu().renderer(), PpuRenderer::Scanline);
+
+        let applied = gb.apply_game_quirks(&header(b"PREHISTORIK MAN"));
+        assert_eq!(applied, &[GameQuirk::PixelFifoPpu]);
+        assert_eq!(gb.bus.ppu().renderer(), PpuRenderer::PixelFifo);
+        assert!(!gb.bus.quirks().oam_corruption);
+        assert_eq!(applied[0].to_string(), "pixel-fifo-ppu");
+    }
+}
@@ -0,0 +1,204 @@
+use crate::GameBoy;
+use std::fmt::{Display, Formatter};
+
+/// `ld b,b`, which BGB and most homebrew debuggers treat as a source-code breakpoint.
+pub const SOURCE_BREAKPOINT_OPCODE: u8 = 0x40;
+/// `ld d,d`, which starts a debug message in the no$gmb/BGB format:
+/// `ld d,d ; jr .end ; dw $6464 ; dw $0000 ; db "text" ; .end:`.
+pub const DEBUG_MESSAGE_OPCODE: u8 = 0x52;
+/// Annotations kept before further ones are dropped, so a breakpoint in a hot loop cannot
+/// grow the log without bound.
+pub const MAX_DEBUG_ANNOTATIONS: usize = 1024;
+
+const JR_OPCODE: u8 = 0x18;
+const MESSAGE_MAGIC: [u8; 4] = [0x64, 0x64, 0x00, 0x00];
+// Test ROMs that report through cartridge RAM (blargg's convention, also used by many
+// homebrew test harnesses) write a status byte and this signature at 0xA000, then a
+// zero-terminated result text at 0xA004.
+const MEMORY_RESULT_ADDRESS: u16 = 0xA000;
+const MEMORY_SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const MEMORY_TEXT_ADDRESS: u16 = 0xA004;
+const MEMORY_RUNNING: u8 = 0x80;
+
+/// Something a ROM asked a debugger to notice, recorded as the instruction executes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugAnnotation {
+    /// An `ld b,b` at this address.
+    Breakpoint { pc: u16 },
+    /// An `ld d,d` debug message and its text.
+    Message { pc: u16, text: String },
+}
+
+impl Display for DebugAnnotation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Breakpoint { pc } => write!(f, "PC={pc:04X} breakpoint (ld b,b)"),
+            Self::Message { pc, text } => write!(f, "PC={pc:04X} message: {text}"),
+        }
+    }
+}
+
+/// The result a test ROM left in cartridge RAM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryTestResult {
+    /// 0x80 while the test runs, then 0 for a pass or a failure code.
+    pub status: u8,
+    pub text: String,
+}
+
+impl MemoryTestResult {
+    pub fn running(&self) -> bool {
+        self.status == MEMORY_RUNNING
+    }
+
+    pub fn passed(&self) -> bool {
+        self.status == 0
+    }
+}
+
+impl GameBoy {
+    /// Records `ld b,b` breakpoints and `ld d,d` debug messages as they execute. Off by
+    /// default; turning it off drops the log.
+    pub fn set_debug_annotations(&mut self, enabled: bool) {
+        self.debug_annotations = enabled.then(Vec::new);
+    }
+
+    /// Recorded annotations, oldest first, up to `MAX_DEBUG_ANNOTATIONS`.
+    pub fn debug_annotations(&self) -> &[DebugAnnotation] {
+        self.debug_annotations.as_deref().unwrap_or_default()
+    }
+
+    pub fn take_debug_annotations(&mut self) -> Vec<DebugAnnotation> {
+        self.debug_annotations
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// The debug message at `pc`, if an `ld d,d` there is followed by a well-formed
+    /// message block.
+    pub fn debug_message_at(&self, pc: u16) -> Option<String> {
+        let byte = |offset: u16| self.bus.peek(pc.wrapping_add(offset));
+        if byte(0) != DEBUG_MESSAGE_OPCODE || byte(1) != JR_OPCODE {
+            return None;
+        }
+        // The jump skips the magic words and the text.
+        let text_len = usize::from(byte(2)).checked_sub(MESSAGE_MAGIC.len())?;
+        if (0..4).any(|index| byte(3 + index) != MESSAGE_MAGIC[usize::from(index)]) {
+            return None;
+        }
+        let text = (0..text_len as u16)
+            .map(|index| char::from(byte(7 + index)))
+            .collect();
+        Some(text)
+    }
+
+    /// The status byte of the cartridge RAM test result, once a ROM has written the
+    /// signature. Cartridge RAM must be enabled, as the test ROMs leave it.
+    pub fn memory_test_status(&self) -> Option<u8> {
+        let signature = [1, 2, 3].map(|offset| self.bus.peek(MEMORY_RESULT_ADDRESS + offset));
+        (signature == MEMORY_SIGNATURE).then(|| self.bus.peek(MEMORY_RESULT_ADDRESS))
+    }
+
+    /// `memory_test_status` with the result text.
+    pub fn memory_test_result(&self) -> Option<MemoryTestResult> {
+        let status = self.memory_test_status()?;
+        let text = (MEMORY_TEXT_ADDRESS..=0xBFFF)
+            .map(|address| self.bus.peek(address))
+            .take_while(|&byte| byte != 0)
+            .map(char::from)
+            .collect();
+        Some(MemoryTestResult { status, text })
+    }
+
+    pub(crate) fn annotate_instruction(&mut self) {
+        let Some(log) = self.debug_annotations.as_ref() else {
+            return;
+        };
+        if log.len() >= MAX_DEBUG_ANNOTATIONS {
+            return;
+        }
+        let pc = self.cpu.pc;
+        let annotation = match self.bus.peek(pc) {
+            SOURCE_BREAKPOINT_OPCODE => DebugAnnotation::Breakpoint { pc },
+            DEBUG_MESSAGE_OPCODE => match self.debug_message_at(pc) {
+                Some(text) => DebugAnnotation::Message { pc, text },
+                None => return,
+            },
+            _ => return,
+        };
+        if let Some(log) = self.debug_annotations.as_mut() {
+            log.push(annotation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(text: &str) -> Vec<u8> {
+        let mut bytes = vec![DEBUG_MESSAGE_OPCODE, JR_OPCODE, text.len() as u8 + 4];
+        bytes.extend_from_slice(&MESSAGE_MAGIC);
+        bytes.extend_from_slice(text.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn records_breakpoints_and_messages_only_when_enabled() {
+        let mut program = vec![SOURCE_BREAKPOINT_OPCODE];
+        program.extend(message("hello"));
+        program.extend([0x00, DEBUG_MESSAGE_OPCODE, 0x00]);
+        let mut gb = GameBoy::with_program(0x0000, &program);
+        let mut quiet = gb.clone();
+
+        gb.set_debug_annotations(true);
+        gb.run_steps(5).expect("program should run");
+        assert_eq!(
+            gb.debug_annotations(),
+            [
+                DebugAnnotation::Breakpoint { pc: 0x0000 },
+                DebugAnnotation::Message {
+                    pc: 0x0001,
+                    text: "hello".to_string()
+                },
+            ],
+            "the jump skips the text and a bare ld d,d is not a message"
+        );
+        assert_eq!(gb.cpu.pc, 0x000F);
+        assert_eq!(
+            gb.take_debug_annotations()[1].to_string(),
+            "PC=0001 message: hello"
+        );
+        assert!(gb.debug_annotations().is_empty());
+
+        quiet.run_steps(5).expect("program should run");
+        assert!(quiet.debug_annotations().is_empty());
+    }
+
+    #[test]
+    fn stops_recording_at_the_cap() {
+        let mut gb = GameBoy::with_program(0x0000, &[SOURCE_BREAKPOINT_OPCODE, 0x18, 0xFD]);
+        gb.set_debug_annotations(true);
+        gb.run_steps(4 * MAX_DEBUG_ANNOTATIONS)
+            .expect("loop should run");
+        assert_eq!(gb.debug_annotations().len(), MAX_DEBUG_ANNOTATIONS);
+    }
+
+    #[test]
+    fn reads_the_result_from_cartridge_ram() {
+        let mut gb = GameBoy::new();
+        assert_eq!(gb.memory_test_result(), None);
+
+        gb.bus
+            .load_bytes(0xA000, &[0x80, 0xDE, 0xB0, 0x61, b'o', b'k', 0x00]);
+        let result = gb.memory_test_result().expect("signature is present");
+        assert!(result.running());
+        assert_eq!(result.text, "ok");
+
+        gb.bus.load_bytes(0xA000, &[0x00]);
+        assert!(gb
+            .memory_test_result()
+            .is_some_and(|result| result.passed()));
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{EmuError, GameBoy};
+use std::ops::ControlFlow;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduledInterrupt {
+    /// Cycles after the scenario starts; the request lands at the first step boundary at or
+    /// past this point.
+    pub cycle: u64,
+    pub mask: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterruptDispatch {
+    pub vector: u16,
+    /// Cycles after the scenario start at the end of the dispatch.
+    pub cycle: u64,
+    pub return_address: u16,
+    /// Handlers still running when this one was entered; nonzero means nested via EI.
+    pub depth: usize,
+}
+
+/// Scripted interrupt requests replayed against a `GameBoy`, recording every dispatch so
+/// ordering and nesting bugs reproduce deterministically.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InterruptScenario {
+    requests: Vec<ScheduledInterrupt>,
+}
+
+impl InterruptScenario {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn raise_at(mut self, cycle: u64, mask: u8) -> Self {
+        self.requests.push(ScheduledInterrupt { cycle, mask });
+        self.requests.sort_by_key(|request| request.cycle);
+        self
+    }
+
+    pub fn requests(&self) -> &[ScheduledInterrupt] {
+        &self.requests
+    }
+
+    /// Runs `steps` instructions, raising each request once its cycle is reached.
+    pub fn run(&self, gb: &mut GameBoy, steps: usize) -> Result<Vec<InterruptDispatch>, EmuError> {
+        let mut pending = self.requests.iter().peekable();
+        let mut dispatches = Vec::new();
+        // SP just below each active handler's return address; popped once SP climbs past it.
+        let mut handler_stack: Vec<u16> = Vec::new();
+
+        while let Some(request) = pending.next_if(|request| request.cycle == 0) {
+            gb.raise_interrupt(request.mask);
+        }
+        gb.run_steps_with(steps, |gb, info| {
+            while handler_stack.last().is_some_and(|&sp| gb.cpu.sp > sp) {
+                handler_stack.pop();
+            }
+            if let Some(vector) = gb.cpu.dispatched_interrupt() {
+                dispatches.push(InterruptDispatch {
+                    vector,
+                    cycle: info.total_cycles,
+                    return_address: gb.bus.read_word(gb.cpu.sp),
+                    depth: handler_stack.len(),
+                });
+                handler_stack.push(gb.cpu.sp);
+            }
+            while let Some(request) = pending.next_if(|request| request.cycle <= info.total_cycles)
+            {
+                gb.raise_interrupt(request.mask);
+            }
+            ControlFlow::Continue(())
+        })?;
+        Ok(dispatches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IE_ADDR, INTERRUPT_TIMER, INTERRUPT_VBLANK};
+
+    const MAIN: u16 = 0x0100;
+
+    // VBlank handler returns at once; the timer handler re-enables interrupts and idles
+    // before returning, so a VBlank raised meanwhile nests inside it.
+    fn nesting_machine() -> GameBoy {
+        let mut gb = GameBoy::new();
+        gb.bus.load_bytes(0x0040, &[0xD9]); // RETI
+        let mut timer_handler = vec![0xFB]; // EI
+        timer_handler.extend_from_slice(&[0x00; 16]); // NOP x16
+        timer_handler.push(0xD9); // RETI
+        gb.bus.load_bytes(0x0050, &timer_handler);
+        gb.bus.load_bytes(MAIN, &[0xFB, 0x18, 0xFE]); // EI ; JR -2
+        gb.bus
+            .write_byte(IE_ADDR, INTERRUPT_VBLANK | INTERRUPT_TIMER);
+        gb.cpu.pc = MAIN;
+        gb
+    }
+
+    #[test]
+    fn records_nested_dispatch_after_ei_in_handler() {
+        let mut gb = nesting_machine();
+        let dispatches = InterruptScenario::new()
+            .raise_at(40, INTERRUPT_VBLANK)
+            .raise_at(8, INTERRUPT_TIMER)
+            .run(&mut gb, 40)
+            .expect("scenario should run");
+
+        assert_eq!(dispatches.len(), 2);
+        assert_eq!(dispatches[0].vector, 0x0050);
+        assert_eq!(dispatches[0].return_address, MAIN + 1);
+        assert_eq!(dispatches[0].depth, 0);
+        assert_eq!(dispatches[1].vector, 0x0040);
+        assert_eq!(dispatches[1].depth, 1);
+        assert!((0x0051..0x0062).contains(&dispatches[1].return_address));
+    }
+
+    #[test]
+    fn simultaneous_requests_dispatch_by_priority_without_nesting() {
+        let mut gb = nesting_machine();
+        let dispatches = InterruptScenario::new()
+            .raise_at(0, INTERRUPT_TIMER | INTERRUPT_VBLANK)
+            .run(&mut gb, 10)
+            .expect("scenario should run");
+
+        let order: Vec<(u16, usize)> = dispatches
+            .iter()
+            .map(|dispatch| (dispatch.vector, dispatch.depth))
+            .collect();
+        assert_eq!(order, vec![(0x0040, 0), (0x0050, 0)]);
+    }
+}
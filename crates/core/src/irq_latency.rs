@@ -0,0 +1,177 @@
+use crate::emu::splitmix64;
+use crate::GameBoy;
+
+/// The longest delay injection adds to one dispatch: a scanline's worth of cycles.
+pub const MAX_INTERRUPT_LATENCY: u32 = 456;
+
+/// Holds back each interrupt dispatch by a pseudo-random number of cycles, drawn from
+/// `0..=max_cycles` by a generator seeded with `seed`, so a game's IRQ timing assumptions
+/// can be fuzzed reproducibly. The CPU keeps executing (or stays halted) while a dispatch is
+/// held; a `DI` or an acknowledged request in that window drops it, as a late dispatch
+/// would on hardware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterruptLatency {
+    /// Clamped to `MAX_INTERRUPT_LATENCY`.
+    pub max_cycles: u32,
+    pub seed: u64,
+}
+
+/// What injection has done so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InjectedLatency {
+    /// Dispatches that were held back at least one step.
+    pub delayed: u64,
+    /// Cycles run while a dispatch was held back.
+    pub cycles: u64,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct LatencyInjector {
+    max_cycles: u32,
+    rng: u64,
+    // Cycles still to hold the current dispatch; `None` until one is due.
+    hold: Option<u32>,
+    injected: InjectedLatency,
+}
+
+impl LatencyInjector {
+    fn new(config: InterruptLatency) -> Self {
+        Self {
+            max_cycles: config.max_cycles.min(MAX_INTERRUPT_LATENCY),
+            rng: config.seed,
+            hold: None,
+            injected: InjectedLatency::default(),
+        }
+    }
+
+    // Whether the CPU may dispatch this step, given whether it would.
+    fn allow(&mut self, due: bool) -> bool {
+        if !due {
+            self.hold = None;
+            return true;
+        }
+        let hold = match self.hold {
+            Some(hold) => hold,
+            None => {
+                let hold = (splitmix64(&mut self.rng) % (u64::from(self.max_cycles) + 1)) as u32;
+                if hold > 0 {
+                    self.injected.delayed += 1;
+                }
+                hold
+            }
+        };
+        if hold == 0 {
+            self.hold = None;
+            return true;
+        }
+        self.hold = Some(hold);
+        false
+    }
+
+    fn held(&mut self, cycles: u32) {
+        if let Some(hold) = self.hold.as_mut() {
+            *hold = hold.saturating_sub(cycles);
+            self.injected.cycles += u64::from(cycles);
+        }
+    }
+}
+
+impl GameBoy {
+    /// Starts delaying interrupt dispatches as `InterruptLatency` describes, or stops with
+    /// `None`. Off by default; a test mode, not hardware behavior.
+    pub fn set_interrupt_latency(&mut self, latency: Option<InterruptLatency>) {
+        self.interrupt_latency = latency.map(LatencyInjector::new);
+    }
+
+    pub fn injected_interrupt_latency(&self) -> InjectedLatency {
+        self.interrupt_latency
+            .as_ref()
+            .map_or_else(InjectedLatency::default, |injector| injector.injected)
+    }
+
+    pub(crate) fn interrupt_dispatch_allowed(&mut self) -> bool {
+        let due = self.cpu.ime && self.bus.pending_interrupts() != 0;
+        self.interrupt_latency
+            .as_mut()
+            .is_none_or(|injector| injector.allow(due))
+    }
+
+    pub(crate) fn interrupt_dispatch_held(&mut self, cycles: u32) {
+        if let Some(injector) = self.interrupt_latency.as_mut() {
+            injector.held(cycles);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::INTERRUPT_TIMER;
+
+    // EI, then count up in B until the timer handler at 0x50 stops the count with HALT.
+    fn counting_program() -> GameBoy {
+        let mut gb = GameBoy::with_program(0x0000, &[0xFB, 0x04, 0x18, 0xFD]);
+        gb.bus.load_bytes(0x0050, &[0x76]);
+        gb.bus.write_byte(0xFFFF, INTERRUPT_TIMER);
+        gb
+    }
+
+    fn count_before_dispatch(latency: Option<InterruptLatency>) -> (u8, InjectedLatency) {
+        let mut gb = counting_program();
+        gb.set_interrupt_latency(latency);
+        gb.run_steps(3).expect("program should run");
+        gb.raise_interrupt(INTERRUPT_TIMER);
+        gb.run_steps_with(200, |gb, _| {
+            if gb.cpu.dispatched_interrupt().is_some() {
+                std::ops::ControlFlow::Break(())
+            } else {
+                std::ops::ControlFlow::Continue(())
+            }
+        })
+        .expect("program should run");
+        assert_eq!(gb.cpu.pc, 0x0050);
+        (gb.cpu.regs.b, gb.injected_interrupt_latency())
+    }
+
+    #[test]
+    fn delays_dispatch_within_the_bound_and_reproducibly() {
+        let (prompt, none) = count_before_dispatch(None);
+        assert_eq!(none, InjectedLatency::default());
+
+        let latency = InterruptLatency {
+            max_cycles: 10_000,
+            seed: 7,
+        };
+        let (late, injected) = count_before_dispatch(Some(latency));
+        assert!(
+            late > prompt,
+            "the loop kept running while the dispatch was held"
+        );
+        assert_eq!(injected.delayed, 1);
+        assert!((1..=u64::from(MAX_INTERRUPT_LATENCY) + 12).contains(&injected.cycles));
+        assert_eq!(count_before_dispatch(Some(latency)), (late, injected));
+
+        let off = InterruptLatency {
+            max_cycles: 0,
+            seed: 7,
+        };
+        assert_eq!(count_before_dispatch(Some(off)), (prompt, none));
+    }
+
+    #[test]
+    fn a_held_dispatch_is_dropped_when_the_request_is_acknowledged() {
+        let mut gb = counting_program();
+        gb.set_interrupt_latency(Some(InterruptLatency {
+            max_cycles: MAX_INTERRUPT_LATENCY,
+            seed: 7,
+        }));
+        gb.run_steps(3).expect("program should run");
+        gb.raise_interrupt(INTERRUPT_TIMER);
+        gb.step().expect("program should run");
+        assert_ne!(gb.cpu.pc, 0x0050, "dispatch is held");
+        gb.bus.clear_interrupt(INTERRUPT_TIMER);
+        gb.run_steps(200).expect("program should run");
+        assert_ne!(gb.cpu.pc, 0x0050);
+        assert_eq!(gb.bus.read_byte(0xFF0F) & INTERRUPT_TIMER, 0);
+    }
+}
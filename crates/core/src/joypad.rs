@@ -0,0 +1,294 @@
+use crate::state::{StateError, StateReader, StateWriter, Stateful};
+use crate::INTERRUPT_JOYPAD;
+use std::cell::{Cell, RefCell};
+
+const SELECT_DIRECTIONS: u8 = 0x10;
+const SELECT_ACTIONS: u8 = 0x20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl Button {
+    pub const ALL: [Button; 8] = [
+        Button::Right,
+        Button::Left,
+        Button::Up,
+        Button::Down,
+        Button::A,
+        Button::B,
+        Button::Select,
+        Button::Start,
+    ];
+
+    fn mask(self) -> u8 {
+        match self {
+            Self::Right => 0x01,
+            Self::Left => 0x02,
+            Self::Up => 0x04,
+            Self::Down => 0x08,
+            Self::A => 0x10,
+            Self::B => 0x20,
+            Self::Select => 0x40,
+            Self::Start => 0x80,
+        }
+    }
+}
+
+/// A joypad state change stamped with the emulated time it was applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JoypadEvent {
+    pub cycle: u64,
+    pub frame: u64,
+    pub button: Button,
+    pub pressed: bool,
+    /// Cycle of the first P1 read with this button's group selected.
+    pub observed_cycle: Option<u64>,
+}
+
+impl JoypadEvent {
+    pub fn latency_cycles(&self) -> Option<u64> {
+        self.observed_cycle.map(|observed| observed - self.cycle)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameInputLatency {
+    pub frame: u64,
+    pub events: usize,
+    pub observed: usize,
+    pub max_latency_cycles: Option<u64>,
+}
+
+pub fn input_latency_by_frame(events: &[JoypadEvent]) -> Vec<FrameInputLatency> {
+    let mut frames: Vec<FrameInputLatency> = Vec::new();
+    for event in events {
+        let entry = match frames.last_mut() {
+            Some(entry) if entry.frame == event.frame => entry,
+            _ => {
+                frames.push(FrameInputLatency {
+                    frame: event.frame,
+                    events: 0,
+                    observed: 0,
+                    max_latency_cycles: None,
+                });
+                frames.last_mut().expect("entry was just pushed")
+            }
+        };
+        entry.events += 1;
+        if let Some(latency) = event.latency_cycles() {
+            entry.observed += 1;
+            entry.max_latency_cycles = entry.max_latency_cycles.max(Some(latency));
+        }
+    }
+    frames
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Joypad {
+    select: u8,
+    pressed: u8,
+    tracking: bool,
+    // P1 reads go through `&self`, so the log uses interior mutability to stamp observations.
+    events: RefCell<Vec<JoypadEvent>>,
+    unobserved: Cell<usize>,
+}
+
+impl Default for Joypad {
+    fn default() -> Self {
+        Self {
+            select: SELECT_DIRECTIONS | SELECT_ACTIONS,
+            pressed: 0,
+            tracking: false,
+            events: RefCell::new(Vec::new()),
+            unobserved: Cell::new(0),
+        }
+    }
+}
+
+impl Joypad {
+    pub(crate) fn read(&self, cycle: u64) -> u8 {
+        if self.unobserved.get() > 0 {
+            self.observe(cycle);
+        }
+        0xC0 | self.select | self.lines()
+    }
+
+    pub(crate) fn set_tracking(&mut self, enabled: bool) {
+        self.tracking = enabled;
+    }
+
+    pub(crate) fn events(&self) -> Vec<JoypadEvent> {
+        self.events.borrow().clone()
+    }
+
+    pub(crate) fn take_events(&mut self) -> Vec<JoypadEvent> {
+        self.unobserved.set(0);
+        std::mem::take(self.events.get_mut())
+    }
+
+    pub(crate) fn write(&mut self, value: u8, interrupt_flags: &mut u8) {
+        let previous = self.lines();
+        self.select = value & (SELECT_DIRECTIONS | SELECT_ACTIONS);
+        self.request_on_falling_edge(previous, interrupt_flags);
+    }
+
+    pub(crate) fn set_button(
+        &mut self,
+        button: Button,
+        pressed: bool,
+        (cycle, frame): (u64, u64),
+        interrupt_flags: &mut u8,
+    ) {
+        if self.tracking && self.is_pressed(button) != pressed {
+            self.events.get_mut().push(JoypadEvent {
+                cycle,
+                frame,
+                button,
+                pressed,
+                observed_cycle: None,
+            });
+            self.unobserved.set(self.unobserved.get() + 1);
+        }
+        let previous = self.lines();
+        if pressed {
+            self.pressed |= button.mask();
+        } else {
+            self.pressed &= !button.mask();
+        }
+        self.request_on_falling_edge(previous, interrupt_flags);
+    }
+
+    pub(crate) fn is_pressed(&self, button: Button) -> bool {
+        self.pressed & button.mask() != 0
+    }
+
+    fn observe(&self, cycle: u64) {
+        let mut events = self.events.borrow_mut();
+        for event in events
+            .iter_mut()
+            .filter(|event| event.observed_cycle.is_none())
+        {
+            if self.group_selected(event.button) {
+                event.observed_cycle = Some(cycle);
+                self.unobserved.set(self.unobserved.get() - 1);
+            }
+        }
+    }
+
+    fn group_selected(&self, button: Button) -> bool {
+        let group = if button.mask() & 0x0F != 0 {
+            SELECT_DIRECTIONS
+        } else {
+            SELECT_ACTIONS
+        };
+        self.select & group == 0
+    }
+
+    fn lines(&self) -> u8 {
+        let mut lines = 0x0F;
+        if self.select & SELECT_DIRECTIONS == 0 {
+            lines &= !(self.pressed & 0x0F);
+        }
+        if self.select & SELECT_ACTIONS == 0 {
+            lines &= !(self.pressed >> 4);
+        }
+        lines
+    }
+
+    fn request_on_falling_edge(&self, previous: u8, interrupt_flags: &mut u8) {
+        if previous & !self.lines() != 0 {
+            *interrupt_flags |= INTERRUPT_JOYPAD;
+        }
+    }
+}
+
+// The latency log belongs to the session that recorded it and is not saved.
+impl Stateful for Joypad {
+    fn save_state(&self, out: &mut StateWriter) {
+        out.u8(self.select);
+        out.u8(self.pressed);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader<'_>) -> Result<(), StateError> {
+        self.select = input.u8()?;
+        self.pressed = input.u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::input_latency_by_frame;
+    use crate::{Bus, Button, GameBoy, IF_ADDR, INTERRUPT_JOYPAD, P1_ADDR};
+
+    #[test]
+    fn reads_selected_button_group() {
+        let mut bus = Bus::default();
+        bus.set_button(Button::Start, true);
+        bus.set_button(Button::Left, true);
+
+        assert_eq!(bus.read_byte(P1_ADDR), 0xFF);
+
+        bus.write_byte(P1_ADDR, 0x20); // select directions
+        assert_eq!(bus.read_byte(P1_ADDR), 0xE0 | 0x0D);
+
+        bus.write_byte(P1_ADDR, 0x10); // select actions
+        assert_eq!(bus.read_byte(P1_ADDR), 0xD0 | 0x07);
+
+        bus.write_byte(P1_ADDR, 0x00); // both groups
+        assert_eq!(bus.read_byte(P1_ADDR), 0xC0 | 0x05);
+    }
+
+    #[test]
+    fn press_on_selected_line_requests_interrupt() {
+        let mut bus = Bus::default();
+        bus.write_byte(P1_ADDR, 0x10);
+        bus.write_byte(IF_ADDR, 0x00);
+
+        bus.set_button(Button::Up, true);
+        assert_eq!(bus.read_byte(IF_ADDR) & INTERRUPT_JOYPAD, 0);
+
+        bus.set_button(Button::A, true);
+        assert_ne!(bus.read_byte(IF_ADDR) & INTERRUPT_JOYPAD, 0);
+
+        bus.write_byte(IF_ADDR, 0x00);
+        bus.set_button(Button::A, false);
+        assert_eq!(bus.read_byte(IF_ADDR) & INTERRUPT_JOYPAD, 0);
+    }
+
+    #[test]
+    fn tracks_event_timestamps_and_poll_latency() {
+        // LD A,$10 ; LDH ($00),A ; NOP x4 ; LDH A,($00)
+        let program = [0x3E, 0x10, 0xE0, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF0, 0x00];
+        let mut gb = GameBoy::with_program(0x0100, &program);
+        gb.set_input_latency_tracking(true);
+
+        gb.run_steps(2).expect("select actions");
+        gb.set_button(Button::Start, true);
+        gb.set_button(Button::Up, true);
+        gb.set_button(Button::Up, true); // no state change, not logged
+        gb.run_steps(5).expect("poll P1");
+
+        let events = gb.input_events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].cycle, 20);
+        assert_eq!(events[0].observed_cycle, Some(36));
+        assert_eq!(events[0].latency_cycles(), Some(16));
+        assert_eq!(events[1].observed_cycle, None);
+
+        let frames = input_latency_by_frame(&events);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].events, 2);
+        assert_eq!(frames[0].observed, 1);
+        assert_eq!(frames[0].max_latency_cycles, Some(16));
+    }
+}
@@ -1,6 +1,84 @@
+mod access_stats;
+mod apu;
+mod bank_stats;
+mod battery;
+mod boot;
+mod boot_rom;
+mod build_info;
+mod camera;
+mod cartridge;
 mod emu;
-
+mod events;
+mod four_player;
+mod game_quirks;
+mod homebrew;
+mod interrupt_scenario;
+mod irq_latency;
+mod joypad;
+mod link;
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod metrics;
+mod mobile_adapter;
+mod model;
+mod movie;
+mod orientation;
+mod ppu;
+mod printer;
+mod quirks;
+mod recording;
+mod rom_writes;
+mod rtc;
+mod serial_clock;
+mod serial_device;
+mod state;
+mod symbols;
+#[cfg(feature = "cycle-accurate")]
+mod tick;
+mod trace;
+
+pub use access_stats::{AccessCounts, BusAccessStats, MemoryRegion};
+pub use apu::*;
+pub use bank_stats::BankStats;
+pub use battery::{SaveFileError, RTC_FOOTER_LEN};
+pub use boot::*;
+pub use boot_rom::{check_boot_rom, BootRomError, CGB_BOOT_ROM_SIZE, DMG_BOOT_ROM_SIZE};
+pub use build_info::*;
+pub use camera::{CameraImageSource, PocketCamera, CAMERA_HEIGHT, CAMERA_PIXELS, CAMERA_WIDTH};
+pub use cartridge::*;
 pub use emu::*;
+pub use events::*;
+pub use four_player::*;
+pub use game_quirks::{game_quirks, GameQuirk};
+pub use homebrew::*;
+pub use interrupt_scenario::*;
+pub use irq_latency::{InjectedLatency, InterruptLatency, MAX_INTERRUPT_LATENCY};
+pub use joypad::{input_latency_by_frame, Button, FrameInputLatency, JoypadEvent};
+pub use link::*;
+pub use mbc1::Mbc1;
+pub use mbc2::{Mbc2, MBC2_RAM_SIZE};
+pub use mbc3::Mbc3;
+pub use mbc5::Mbc5;
+pub use metrics::*;
+pub use mobile_adapter::*;
+pub use model::Model;
+pub use movie::{Movie, MovieError, MovieInput, MOVIE_MAGIC, MOVIE_VERSION};
+pub use orientation::*;
+pub use ppu::*;
+pub use printer::*;
+pub use quirks::HardwareQuirks;
+pub use recording::Recording;
+pub use rom_writes::{RomWrite, MAX_ROM_WRITES};
+pub use rtc::*;
+pub use serial_clock::SerialClockProvider;
+pub use serial_device::*;
+pub use state::{state_build, StateBuild, StateError, STATE_MAGIC, STATE_VERSION};
+pub use symbols::*;
+#[cfg(feature = "cycle-accurate")]
+pub use tick::TickInfo;
+pub use trace::{TraceEntry, TRACE_RING_LEN};
 
 use std::error::Error;
 use std::fmt::{Display, Formatter};
@@ -23,11 +101,8 @@ const GLOBAL_CHECKSUM_START: usize = 0x14E;
 const LOGO_START: usize = 0x104;
 const LOGO_END_EXCLUSIVE: usize = 0x134;
 
-const NINTENDO_LOGO: [u8; 48] = [
-    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
-    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
-    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
-];
+// FNV-1a of the 48-byte logo at 0x104, so the bitmap itself never ships in the crate.
+const NINTENDO_LOGO_FNV1A: u64 = 0x0E13_F858_5A99_F41F;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Rom {
@@ -38,7 +113,11 @@ pub struct Rom {
 
 impl Rom {
     pub fn from_bytes(data: Vec<u8>) -> Result<Self, RomError> {
-        let header = RomHeader::parse(&data).map_err(RomError::Header)?;
+        Self::from_bytes_with(data, HeaderValidation::Strict)
+    }
+
+    pub fn from_bytes_with(data: Vec<u8>, validation: HeaderValidation) -> Result<Self, RomError> {
+        let header = RomHeader::parse_with(&data, validation).map_err(RomError::Header)?;
         Ok(Self {
             data,
             header,
@@ -47,12 +126,19 @@ impl Rom {
     }
 
     pub fn from_file(path: impl AsRef<Path>) -> Result<Self, RomError> {
+        Self::from_file_with(path, HeaderValidation::Strict)
+    }
+
+    pub fn from_file_with(
+        path: impl AsRef<Path>,
+        validation: HeaderValidation,
+    ) -> Result<Self, RomError> {
         let path = path.as_ref();
         let data = fs::read(path).map_err(|source| RomError::Io {
             path: path.to_path_buf(),
             source,
         })?;
-        let mut rom = Self::from_bytes(data)?;
+        let mut rom = Self::from_bytes_with(data, validation)?;
         rom.path = Some(path.to_path_buf());
         Ok(rom)
     }
@@ -104,10 +190,44 @@ pub struct RomHeader {
     pub header_checksum: u8,
     pub calculated_header_checksum: u8,
     pub global_checksum: u16,
+    /// Problems tolerated by `HeaderValidation::Lenient`; always empty for strict parses.
+    pub warnings: Vec<HeaderWarning>,
+}
+
+/// How `RomHeader::parse_with` treats a logo or header checksum that does not match.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HeaderValidation {
+    /// Reject the ROM, as the boot ROM would refuse to start it.
+    #[default]
+    Strict,
+    /// Record a `HeaderWarning` and keep going, for homebrew with a custom logo region.
+    Lenient,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderWarning {
+    NintendoLogoMismatch,
+    HeaderChecksumMismatch { expected: u8, actual: u8 },
+}
+
+impl Display for HeaderWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NintendoLogoMismatch => write!(f, "Nintendo logo does not match"),
+            Self::HeaderChecksumMismatch { expected, actual } => write!(
+                f,
+                "header checksum mismatch: expected 0x{expected:02X}, got 0x{actual:02X}"
+            ),
+        }
+    }
 }
 
 impl RomHeader {
     pub fn parse(data: &[u8]) -> Result<Self, HeaderError> {
+        Self::parse_with(data, HeaderValidation::Strict)
+    }
+
+    pub fn parse_with(data: &[u8], validation: HeaderValidation) -> Result<Self, HeaderError> {
         if data.len() < MIN_ROM_SIZE {
             return Err(HeaderError::RomTooSmall {
                 actual: data.len(),
@@ -115,14 +235,25 @@ impl RomHeader {
             });
         }
 
-        if data[LOGO_START..LOGO_END_EXCLUSIVE] != NINTENDO_LOGO {
-            return Err(HeaderError::InvalidNintendoLogo);
+        let lenient = validation == HeaderValidation::Lenient;
+        let mut warnings = Vec::new();
+        if !logo_matches(data) {
+            if !lenient {
+                return Err(HeaderError::InvalidNintendoLogo);
+            }
+            warnings.push(HeaderWarning::NintendoLogoMismatch);
         }
 
         let calculated_header_checksum = calculate_header_checksum(data);
         let header_checksum = data[HEADER_CHECKSUM_ADDR];
         if calculated_header_checksum != header_checksum {
-            return Err(HeaderError::InvalidHeaderChecksum {
+            if !lenient {
+                return Err(HeaderError::InvalidHeaderChecksum {
+                    expected: calculated_header_checksum,
+                    actual: header_checksum,
+                });
+            }
+            warnings.push(HeaderWarning::HeaderChecksumMismatch {
                 expected: calculated_header_checksum,
                 actual: header_checksum,
             });
@@ -161,6 +292,7 @@ impl RomHeader {
                 data[GLOBAL_CHECKSUM_START],
                 data[GLOBAL_CHECKSUM_START + 1],
             ]),
+            warnings,
         })
     }
 
@@ -254,6 +386,33 @@ fn sanitize_printable(byte: u8) -> char {
     }
 }
 
+/// Whether the logo region hashes to the Nintendo logo the boot ROM checks for (or holds
+/// `TEST_LOGO`, with the `test-logo` feature).
+pub fn logo_matches(data: &[u8]) -> bool {
+    data.get(LOGO_START..LOGO_END_EXCLUSIVE)
+        .is_some_and(|logo| fnv1a(logo) == NINTENDO_LOGO_FNV1A || is_test_logo(logo))
+}
+
+/// A stand-in for the Nintendo logo that test ROMs carry at 0x104.
+#[cfg(any(test, feature = "test-logo"))]
+pub const TEST_LOGO: [u8; 48] = *b"VibeGB test ROM logo, in place of the boot logo.";
+
+#[cfg(any(test, feature = "test-logo"))]
+fn is_test_logo(logo: &[u8]) -> bool {
+    logo == TEST_LOGO
+}
+
+#[cfg(not(any(test, feature = "test-logo")))]
+fn is_test_logo(_logo: &[u8]) -> bool {
+    false
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
 fn calculate_header_checksum(data: &[u8]) -> u8 {
     let mut checksum = 0u8;
     for byte in &data[TITLE_START..=MASK_ROM_VERSION_ADDR] {
@@ -346,6 +505,7 @@ mod tests {
         assert_eq!(header.new_licensee_code.as_deref(), Some("01"));
         assert_eq!(header.global_checksum, 0x1234);
         assert_eq!(header.header_checksum, header.calculated_header_checksum);
+        assert!(header.warnings.is_empty());
     }
 
     #[test]
@@ -368,6 +528,30 @@ mod tests {
         assert!(matches!(err, HeaderError::InvalidNintendoLogo));
     }
 
+    #[test]
+    fn lenient_parse_accepts_custom_logo_and_checksum_with_warnings() {
+        let mut rom = make_test_rom();
+        rom[LOGO_START..LOGO_END_EXCLUSIVE].fill(0x00);
+        rom[HEADER_CHECKSUM_ADDR] ^= 0x01;
+        assert!(!logo_matches(&rom));
+
+        let header = RomHeader::parse_with(&rom, HeaderValidation::Lenient)
+            .expect("lenient parse should tolerate the logo");
+        assert_eq!(header.title, "VIBEGB TEST");
+        assert_eq!(header.warnings.len(), 2);
+        assert_eq!(header.warnings[0], HeaderWarning::NintendoLogoMismatch);
+        assert!(matches!(
+            header.warnings[1],
+            HeaderWarning::HeaderChecksumMismatch { .. }
+        ));
+    }
+
+    #[test]
+    fn test_logo_stands_in_for_the_nintendo_logo() {
+        assert!(logo_matches(&make_test_rom()));
+        assert_ne!(fnv1a(&TEST_LOGO), NINTENDO_LOGO_FNV1A);
+    }
+
     #[test]
     fn rejects_invalid_checksum() {
         let mut rom = make_test_rom();
@@ -378,7 +562,7 @@ mod tests {
 
     fn make_test_rom() -> Vec<u8> {
         let mut rom = vec![0; 0x8000];
-        rom[LOGO_START..LOGO_END_EXCLUSIVE].copy_from_slice(&NINTENDO_LOGO);
+        rom[LOGO_START..LOGO_END_EXCLUSIVE].copy_from_slice(&TEST_LOGO);
 
         let title = b"VIBEGB TEST";
         rom[TITLE_START..TITLE_START + title.len()].copy_from_slice(title);
@@ -0,0 +1,248 @@
+use crate::{EmuError, GameBoy, CYCLES_PER_FRAME};
+
+/// Cycles for an 8-bit transfer on the internal 8192 Hz serial clock.
+pub const SERIAL_TRANSFER_CYCLES: u64 = 4096;
+
+const DISCONNECTED_BYTE: u8 = 0xFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkSide {
+    A,
+    B,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkExchange {
+    /// Master clock cycle at which the byte finished shifting.
+    pub cycle: u64,
+    pub master: LinkSide,
+    pub sent: u8,
+    pub received: u8,
+}
+
+/// Two in-process Game Boys joined by a link cable; this is the serial link
+/// `GameBoy::connect_serial` returns. It keeps its `LinkCable` name rather than `SerialLink`
+/// because it predates that API.
+///
+/// The lagging instance always runs next (ties go to `A`), so the clocks never drift
+/// apart by more than one instruction and byte exchanges happen in the same order on
+/// every run.
+#[derive(Debug, Clone)]
+pub struct LinkCable {
+    pub a: GameBoy,
+    pub b: GameBoy,
+    exchanges: Vec<LinkExchange>,
+}
+
+impl LinkCable {
+    pub fn new(mut a: GameBoy, mut b: GameBoy) -> Self {
+        a.bus.connect_link(true);
+        b.bus.connect_link(true);
+        Self {
+            a,
+            b,
+            exchanges: Vec::new(),
+        }
+    }
+
+    pub fn exchanges(&self) -> &[LinkExchange] {
+        &self.exchanges
+    }
+
+    /// Signed cycle difference `a - b`.
+    pub fn skew(&self) -> i64 {
+        self.a.cycle_count() as i64 - self.b.cycle_count() as i64
+    }
+
+    pub fn step(&mut self) -> Result<u32, EmuError> {
+        let side = if self.a.cycle_count() <= self.b.cycle_count() {
+            LinkSide::A
+        } else {
+            LinkSide::B
+        };
+        let cycles = self.side_mut(side).step()?;
+        self.resolve_transfers();
+        Ok(cycles)
+    }
+
+    /// Runs in lockstep until both instances have completed `frames` more frames. As in
+    /// `run_frame`, a frame's worth of cycles counts as one while the LCD is off.
+    pub fn run_frames(&mut self, frames: u64) -> Result<(), EmuError> {
+        let start = [self.a.frame_count(), self.b.frame_count()];
+        let start_cycles = [self.a.cycle_count(), self.b.cycle_count()];
+        let done = |gb: &GameBoy, side: usize| {
+            gb.frame_count() >= start[side] + frames
+                || (!gb.lcd_enabled()
+                    && gb.cycle_count() - start_cycles[side]
+                        >= frames * u64::from(CYCLES_PER_FRAME))
+        };
+        while !done(&self.a, 0) || !done(&self.b, 1) {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Unplugs the cable. Transfers still pending on an internal clock finish at once,
+    /// reading 0xFF.
+    pub fn disconnect(mut self) -> (GameBoy, GameBoy) {
+        self.a.bus.connect_link(false);
+        self.b.bus.connect_link(false);
+        (self.a, self.b)
+    }
+
+    /// Runs until both instances have advanced at least `cycles` past the slower one.
+    pub fn run_cycles(&mut self, cycles: u64) -> Result<(), EmuError> {
+        let target = self.a.cycle_count().min(self.b.cycle_count()) + cycles;
+        while self.a.cycle_count().min(self.b.cycle_count()) < target {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    fn resolve_transfers(&mut self) {
+        for master in [LinkSide::A, LinkSide::B] {
+            let Some((start, sent)) = self.side(master).bus.serial_transfer_request() else {
+                continue;
+            };
+            let due = start + SERIAL_TRANSFER_CYCLES;
+            if self.a.cycle_count() < due || self.b.cycle_count() < due {
+                continue;
+            }
+
+            let (master_gb, slave_gb) = self.pair_mut(master);
+            let received = if slave_gb.bus.serial_waiting_for_clock() {
+                slave_gb.bus.complete_serial_transfer(sent)
+            } else {
+                DISCONNECTED_BYTE
+            };
+            master_gb.bus.complete_serial_transfer(received);
+            self.exchanges.push(LinkExchange {
+                cycle: due,
+                master,
+                sent,
+                received,
+            });
+        }
+    }
+
+    fn side(&self, side: LinkSide) -> &GameBoy {
+        match side {
+            LinkSide::A => &self.a,
+            LinkSide::B => &self.b,
+        }
+    }
+
+    fn side_mut(&mut self, side: LinkSide) -> &mut GameBoy {
+        match side {
+            LinkSide::A => &mut self.a,
+            LinkSide::B => &mut self.b,
+        }
+    }
+
+    fn pair_mut(&mut self, master: LinkSide) -> (&mut GameBoy, &mut GameBoy) {
+        match master {
+            LinkSide::A => (&mut self.a, &mut self.b),
+            LinkSide::B => (&mut self.b, &mut self.a),
+        }
+    }
+}
+
+impl GameBoy {
+    /// Plugs a link cable between this console (side `A`) and `peer` (side `B`). Whichever
+    /// side starts a transfer on its internal clock (SC = 0x81) is the master; the other
+    /// exchanges its SB only if it is waiting on the external clock (SC = 0x80).
+    pub fn connect_serial(self, peer: GameBoy) -> LinkCable {
+        LinkCable::new(self, peer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IF_ADDR, INTERRUPT_SERIAL, SB_ADDR, SC_ADDR};
+
+    // LD A,sb ; LDH ($01),A ; LD A,sc ; LDH ($02),A
+    // wait: LDH A,($02) ; BIT 7,A ; JR NZ,wait
+    // LDH A,($01) ; LD B,A ; JR -2
+    fn transfer_program(sb: u8, sc: u8) -> Vec<u8> {
+        vec![
+            0x3E, sb, 0xE0, 0x01, 0x3E, sc, 0xE0, 0x02, 0xF0, 0x02, 0xCB, 0x7F, 0x20, 0xFA, 0xF0,
+            0x01, 0x47, 0x18, 0xFE,
+        ]
+    }
+
+    fn linked_pair() -> LinkCable {
+        let master = GameBoy::with_program(0x0000, &transfer_program(0x42, 0x81));
+        let slave = GameBoy::with_program(0x0000, &transfer_program(0x99, 0x80));
+        LinkCable::new(master, slave)
+    }
+
+    #[test]
+    fn exchanges_bytes_between_master_and_slave() {
+        let mut link = linked_pair();
+        link.run_cycles(SERIAL_TRANSFER_CYCLES + 200)
+            .expect("linked programs should run");
+
+        assert_eq!(link.a.cpu.regs.b, 0x99);
+        assert_eq!(link.b.cpu.regs.b, 0x42);
+        assert_eq!(link.exchanges().len(), 1);
+        assert_eq!(link.exchanges()[0].master, LinkSide::A);
+        assert_eq!(link.exchanges()[0].sent, 0x42);
+        assert_eq!(link.exchanges()[0].received, 0x99);
+    }
+
+    #[test]
+    fn lockstep_bounds_skew_and_is_reproducible() {
+        let run = || {
+            let mut link = linked_pair();
+            for _ in 0..2_000 {
+                link.step().expect("linked programs should run");
+                assert!(link.skew().abs() <= 24);
+            }
+            (
+                link.exchanges().to_vec(),
+                link.a.cycle_count(),
+                link.b.cycle_count(),
+            )
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn either_side_can_clock_and_frames_run_in_lockstep() {
+        let slave = GameBoy::with_program(0x0000, &transfer_program(0x24, 0x80));
+        let master = GameBoy::with_program(0x0000, &transfer_program(0x7E, 0x81));
+        let mut link = slave.connect_serial(master);
+        link.run_frames(2).expect("linked programs should run");
+
+        let frames = 2 * u64::from(CYCLES_PER_FRAME);
+        assert!(link.a.cycle_count() >= frames && link.b.cycle_count() >= frames);
+        assert!(link.skew().abs() <= 24);
+        assert_eq!(link.exchanges().len(), 1);
+        assert_eq!(link.exchanges()[0].master, LinkSide::B);
+        let (slave, master) = link.disconnect();
+        assert_eq!((slave.cpu.regs.b, master.cpu.regs.b), (0x7E, 0x24));
+    }
+
+    #[test]
+    fn master_reads_ff_without_a_listening_slave() {
+        let master = GameBoy::with_program(0x0000, &transfer_program(0x42, 0x81));
+        let mut link = LinkCable::new(master, GameBoy::new());
+        link.run_cycles(SERIAL_TRANSFER_CYCLES + 200)
+            .expect("linked programs should run");
+        assert_eq!(link.a.cpu.regs.b, DISCONNECTED_BYTE);
+    }
+
+    #[test]
+    fn disconnecting_finishes_a_pending_transfer() {
+        let master = GameBoy::with_program(0x0000, &transfer_program(0x42, 0x81));
+        let mut link = LinkCable::new(master, GameBoy::new());
+        link.run_cycles(200).expect("linked programs should run");
+        assert!(link.a.bus.serial_transfer_request().is_some());
+
+        let (master, _) = link.disconnect();
+        assert_eq!(master.bus.read_byte(SB_ADDR), DISCONNECTED_BYTE);
+        assert_eq!(master.bus.read_byte(SC_ADDR) & 0x80, 0);
+        assert_ne!(master.bus.read_byte(IF_ADDR) & INTERRUPT_SERIAL, 0);
+    }
+}
@@ -0,0 +1,307 @@
+use crate::bank_stats::BankRecorder;
+use crate::cartridge::{rom_bank_count, ROM_BANK_SIZE};
+use crate::state::{StateReader, StateWriter};
+use crate::{logo_matches, BankStats, Cartridge, MapperRegisters, StateError};
+use std::sync::Arc;
+
+const RAM_BANK_SIZE: usize = 0x2000;
+const RAM_START: u16 = 0xA000;
+const OPEN_BUS: u8 = 0xFF;
+// MBC1M carts are 8 Mbit with a game, each starting with its own header, every 16 banks.
+const MULTICART_ROM_SIZE: usize = 0x10_0000;
+const MULTICART_GAME_BANKS: usize = 0x10;
+
+/// MBC1: a 5-bit ROM bank register, a 2-bit register that supplies ROM bank bits 5-6 or
+/// the RAM bank, and a mode bit choosing whether that second register also applies to
+/// 0x0000-0x3FFF and RAM.
+#[derive(Debug, Clone)]
+pub struct Mbc1 {
+    rom: Arc<[u8]>,
+    ram: Vec<u8>,
+    rom_banks: usize,
+    stats: BankRecorder,
+    multicart: bool,
+    ram_enabled: bool,
+    bank1: u8,
+    bank2: u8,
+    mode: bool,
+}
+
+impl Mbc1 {
+    pub fn new(rom: impl Into<Arc<[u8]>>, ram_size: usize) -> Self {
+        let rom = rom.into();
+        Self {
+            rom_banks: rom_bank_count(rom.len()),
+            stats: BankRecorder::new(rom_bank_count(rom.len()), ram_size),
+            multicart: is_multicart(&rom),
+            rom,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            bank1: 1,
+            bank2: 0,
+            mode: false,
+        }
+    }
+
+    /// MBC1M wiring, where bank bit 4 is not connected and the upper register starts at
+    /// bit 4 instead of 5.
+    pub fn is_multicart(&self) -> bool {
+        self.multicart
+    }
+
+    fn upper_bits(&self) -> usize {
+        usize::from(self.bank2) << if self.multicart { 4 } else { 5 }
+    }
+
+    fn lower_bits(&self) -> usize {
+        usize::from(if self.multicart {
+            self.bank1 & 0x0F
+        } else {
+            self.bank1
+        })
+    }
+
+    fn bank_at(&self, address: u16) -> usize {
+        let bank = if address < 0x4000 {
+            if self.mode {
+                self.upper_bits()
+            } else {
+                0
+            }
+        } else {
+            self.upper_bits() | self.lower_bits()
+        };
+        bank & (self.rom_banks - 1)
+    }
+
+    fn rom_offset(&self, address: u16) -> usize {
+        self.bank_at(address) * ROM_BANK_SIZE + usize::from(address & 0x3FFF)
+    }
+
+    fn ram_index(&self, address: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return None;
+        }
+        let bank = if self.mode {
+            usize::from(self.bank2)
+        } else {
+            0
+        };
+        Some((bank * RAM_BANK_SIZE + usize::from(address - RAM_START)) % self.ram.len())
+    }
+}
+
+/// A multicart repeats the Nintendo logo at the start of the second game, bank 0x10.
+fn is_multicart(rom: &[u8]) -> bool {
+    rom.len() == MULTICART_ROM_SIZE && logo_matches(&rom[MULTICART_GAME_BANKS * ROM_BANK_SIZE..])
+}
+
+impl Cartridge for Mbc1 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let offset = self.rom_offset(address);
+        self.rom.get(offset).copied().unwrap_or(OPEN_BUS)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                let enabled = value & 0x0F == 0x0A;
+                self.stats.record_ram_gate(self.ram_enabled, enabled);
+                self.ram_enabled = enabled;
+            }
+            // Bank 0 cannot be selected here, so 0x00/0x20/0x40/0x60 map 0x01/0x21/...
+            0x2000..=0x3FFF => self.bank1 = (value & 0x1F).max(1),
+            0x4000..=0x5FFF => self.bank2 = value & 0x03,
+            _ => self.mode = value & 0x01 != 0,
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        self.ram_index(address)
+            .map_or(OPEN_BUS, |index| self.ram[index])
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if let Some(index) = self.ram_index(address) {
+            self.ram[index] = value;
+        }
+    }
+
+    fn rom_bank(&self) -> u16 {
+        self.bank_at(0x4000) as u16
+    }
+
+    fn registers(&self) -> MapperRegisters {
+        MapperRegisters {
+            mapper: "MBC1",
+            rom_bank: self.rom_bank(),
+            ram_bank: if self.mode { self.bank2 } else { 0 },
+            ram_enabled: self.ram_enabled,
+            mode: Some(u8::from(self.mode)),
+        }
+    }
+
+    fn record_read(&self, address: u16) {
+        if address < 0x8000 {
+            self.stats.record_rom(self.rom_offset(address));
+        } else if let Some(index) = self.ram_index(address) {
+            self.stats.record_ram(index);
+        }
+    }
+
+    fn stats(&self) -> BankStats {
+        self.stats.stats()
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn rom(&self) -> &Arc<[u8]> {
+        &self.rom
+    }
+
+    fn patch_rom(&mut self, address: u16, value: u8) {
+        let offset = self.rom_offset(address);
+        if let Some(byte) = Arc::make_mut(&mut self.rom).get_mut(offset) {
+            *byte = value;
+        }
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        let mut out = StateWriter::default();
+        out.bool(self.ram_enabled);
+        out.u8(self.bank1);
+        out.u8(self.bank2);
+        out.bool(self.mode);
+        out.byte_vec(&self.ram);
+        out.into_bytes()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut input = StateReader::new(state);
+        let ram_enabled = input.bool()?;
+        let bank1 = input.u8()?;
+        let bank2 = input.u8()?;
+        let mode = input.bool()?;
+        let ram = input.byte_vec()?;
+        input.finish()?;
+        if !(1..=0x1F).contains(&bank1) || bank2 > 0x03 {
+            return Err(StateError::Invalid("MBC1 bank register"));
+        }
+        if ram.len() != self.ram.len() {
+            return Err(StateError::Invalid("cartridge RAM size"));
+        }
+        self.ram_enabled = ram_enabled;
+        self.bank1 = bank1;
+        self.bank2 = bank2;
+        self.mode = mode;
+        self.ram = ram;
+        Ok(())
+    }
+
+    fn clone_box(&self) -> Box<dyn Cartridge> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::numbered_rom;
+
+    #[test]
+    fn bank_register_zero_aliases_to_the_next_bank() {
+        let mut mbc = Mbc1::new(numbered_rom(128), 0);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+        mbc.write_rom(0x2000, 0xE5);
+        assert_eq!(mbc.read_rom(0x4000), 5, "only five bits are wired");
+
+        mbc.write_rom(0x2000, 0x00);
+        mbc.write_rom(0x4000, 0x01);
+        assert_eq!(mbc.read_rom(0x4000), 0x21, "bank 0x20 is unreachable");
+        assert_eq!(mbc.rom_bank(), 0x21);
+        assert_eq!(mbc.read_rom(0x0000), 0x00, "mode 0 keeps bank 0 low");
+
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_rom(0x0000), 0x20, "mode 1 applies bank2 to 0x0000");
+        mbc.write_rom(0x4000, 0x03);
+        assert_eq!(mbc.read_rom(0x0000), 0x60);
+        assert_eq!(mbc.read_rom(0x4000), 0x61);
+    }
+
+    #[test]
+    fn banks_wrap_to_the_rom_size() {
+        let mut mbc = Mbc1::new(numbered_rom(32), 0);
+        mbc.write_rom(0x2000, 0x12);
+        mbc.write_rom(0x4000, 0x01);
+        assert_eq!(mbc.read_rom(0x4000), 0x12);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_rom(0x0000), 0x00);
+
+        let mut small = Mbc1::new(numbered_rom(4), 0);
+        small.write_rom(0x2000, 0x07);
+        assert_eq!(small.read_rom(0x4000), 0x03);
+    }
+
+    #[test]
+    fn ram_needs_enabling_and_banks_only_in_mode_1() {
+        let mut mbc = Mbc1::new(numbered_rom(8), 0x8000);
+        mbc.write_ram(0xA000, 0x12);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF, "disabled RAM floats");
+
+        mbc.write_rom(0x0000, 0x1A);
+        mbc.write_ram(0xA000, 0x12);
+        assert_eq!(mbc.read_ram(0xA000), 0x12);
+        mbc.write_rom(0x4000, 0x02);
+        assert_eq!(mbc.read_ram(0xA000), 0x12, "mode 0 pins RAM bank 0");
+
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_ram(0xA000), 0x00);
+        assert_eq!(
+            mbc.registers().to_string(),
+            "MBC1 rom_bank=0x001 ram_bank=0x02 ram_enabled=yes mode=1"
+        );
+        mbc.write_ram(0xA000, 0x34);
+        mbc.write_rom(0x6000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0x12);
+
+        let state = mbc.save_state();
+        let mut restored = Mbc1::new(numbered_rom(8), 0x8000);
+        assert_eq!(restored.load_state(&state), Ok(()));
+        restored.write_rom(0x6000, 0x01);
+        assert_eq!(restored.read_ram(0xA000), 0x34);
+
+        mbc.write_rom(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn multicarts_use_four_bit_banks() {
+        let mut rom = numbered_rom(64);
+        assert!(!Mbc1::new(rom.clone(), 0).is_multicart());
+        for game in [0x00, 0x10, 0x20, 0x30] {
+            let start = game * ROM_BANK_SIZE;
+            rom[start + 0x104..start + 0x134].copy_from_slice(&crate::TEST_LOGO);
+        }
+        let mut mbc = Mbc1::new(rom, 0);
+        assert!(mbc.is_multicart());
+
+        mbc.write_rom(0x2000, 0x12);
+        assert_eq!(mbc.read_rom(0x4000), 0x02, "bit 4 is not connected");
+        mbc.write_rom(0x4000, 0x01);
+        assert_eq!(mbc.read_rom(0x4000), 0x12);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(
+            mbc.read_rom(0x0000),
+            0x10,
+            "selects the second game's header"
+        );
+    }
+}
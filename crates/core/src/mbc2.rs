@@ -0,0 +1,216 @@
+use crate::bank_stats::BankRecorder;
+use crate::cartridge::{rom_bank_count, ROM_BANK_SIZE};
+use crate::state::{StateReader, StateWriter};
+use crate::{BankStats, Cartridge, MapperRegisters, StateError};
+use std::sync::Arc;
+
+/// 512 four-bit cells, built into the mapper chip.
+pub const MBC2_RAM_SIZE: usize = 0x200;
+const OPEN_BUS: u8 = 0xFF;
+// Address bit 8 picks the register a 0x0000-0x3FFF write lands in.
+const ROM_BANK_SELECT: u16 = 0x0100;
+
+/// MBC2: a 4-bit ROM bank register and 512x4 bits of RAM inside the mapper, both written
+/// through 0x0000-0x3FFF with address bit 8 choosing between them.
+#[derive(Debug, Clone)]
+pub struct Mbc2 {
+    rom: Arc<[u8]>,
+    ram: Vec<u8>,
+    rom_banks: usize,
+    stats: BankRecorder,
+    ram_enabled: bool,
+    rom_bank: u8,
+}
+
+impl Mbc2 {
+    pub fn new(rom: impl Into<Arc<[u8]>>) -> Self {
+        let rom = rom.into();
+        Self {
+            rom_banks: rom_bank_count(rom.len()),
+            stats: BankRecorder::new(rom_bank_count(rom.len()), MBC2_RAM_SIZE),
+            rom,
+            ram: vec![0; MBC2_RAM_SIZE],
+            ram_enabled: false,
+            rom_bank: 1,
+        }
+    }
+
+    fn rom_offset(&self, address: u16) -> usize {
+        let bank = if address < 0x4000 {
+            0
+        } else {
+            usize::from(self.rom_bank) & (self.rom_banks - 1)
+        };
+        bank * ROM_BANK_SIZE + usize::from(address & 0x3FFF)
+    }
+
+    fn ram_index(&self, address: u16) -> Option<usize> {
+        // Only nine address lines reach the RAM, so it mirrors across 0xA000-0xBFFF.
+        self.ram_enabled
+            .then_some(usize::from(address) & (MBC2_RAM_SIZE - 1))
+    }
+}
+
+impl Cartridge for Mbc2 {
+    fn read_rom(&self, address: u16) -> u8 {
+        self.rom
+            .get(self.rom_offset(address))
+            .copied()
+            .unwrap_or(OPEN_BUS)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x3FFF if address & ROM_BANK_SELECT != 0 => {
+                self.rom_bank = (value & 0x0F).max(1)
+            }
+            0x0000..=0x3FFF => {
+                let enabled = value & 0x0F == 0x0A;
+                self.stats.record_ram_gate(self.ram_enabled, enabled);
+                self.ram_enabled = enabled;
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        // The upper nibble is not driven.
+        self.ram_index(address)
+            .map_or(OPEN_BUS, |index| self.ram[index] | 0xF0)
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if let Some(index) = self.ram_index(address) {
+            self.ram[index] = value & 0x0F;
+        }
+    }
+
+    fn rom_bank(&self) -> u16 {
+        (usize::from(self.rom_bank) & (self.rom_banks - 1)) as u16
+    }
+
+    fn decodes_rom_write(&self, address: u16) -> bool {
+        address < 0x4000
+    }
+
+    fn registers(&self) -> MapperRegisters {
+        MapperRegisters {
+            mapper: "MBC2",
+            rom_bank: self.rom_bank(),
+            ram_bank: 0,
+            ram_enabled: self.ram_enabled,
+            mode: None,
+        }
+    }
+
+    fn record_read(&self, address: u16) {
+        if address < 0x8000 {
+            self.stats.record_rom(self.rom_offset(address));
+        } else if let Some(index) = self.ram_index(address) {
+            self.stats.record_ram(index);
+        }
+    }
+
+    fn stats(&self) -> BankStats {
+        self.stats.stats()
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn rom(&self) -> &Arc<[u8]> {
+        &self.rom
+    }
+
+    fn patch_rom(&mut self, address: u16, value: u8) {
+        let offset = self.rom_offset(address);
+        if let Some(byte) = Arc::make_mut(&mut self.rom).get_mut(offset) {
+            *byte = value;
+        }
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        let mut out = StateWriter::default();
+        out.bool(self.ram_enabled);
+        out.u8(self.rom_bank);
+        out.byte_vec(&self.ram);
+        out.into_bytes()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut input = StateReader::new(state);
+        let ram_enabled = input.bool()?;
+        let rom_bank = input.u8()?;
+        let ram = input.byte_vec()?;
+        input.finish()?;
+        if !(1..=0x0F).contains(&rom_bank) {
+            return Err(StateError::Invalid("MBC2 bank register"));
+        }
+        if ram.len() != MBC2_RAM_SIZE {
+            return Err(StateError::Invalid("cartridge RAM size"));
+        }
+        self.ram_enabled = ram_enabled;
+        self.rom_bank = rom_bank;
+        self.ram = ram;
+        Ok(())
+    }
+
+    fn clone_box(&self) -> Box<dyn Cartridge> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::numbered_rom;
+    use crate::new_cartridge;
+
+    #[test]
+    fn address_bit_8_selects_the_register() {
+        let mut mbc = Mbc2::new(numbered_rom(16));
+        assert_eq!(mbc.read_rom(0x4000), 1);
+        mbc.write_rom(0x2000, 0x05);
+        assert_eq!(mbc.read_rom(0x4000), 1, "bit 8 clear is the RAM enable");
+        mbc.write_rom(0x0100, 0xF5);
+        assert_eq!(mbc.read_rom(0x4000), 5, "only four bits are wired");
+        mbc.write_rom(0x3FFF, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 1, "bank 0 maps bank 1");
+        mbc.write_rom(0x4100, 0x03);
+        assert_eq!(mbc.rom_bank(), 1, "0x4000-0x7FFF has no registers");
+
+        let mut small = Mbc2::new(numbered_rom(4));
+        small.write_rom(0x2100, 0x0E);
+        assert_eq!(small.read_rom(0x4000), 2, "banks wrap to the ROM size");
+    }
+
+    #[test]
+    fn ram_is_512_nibbles_mirrored_over_the_ram_area() {
+        let mut mbc = new_cartridge(0x06, 0, numbered_rom(4));
+        mbc.write_ram(0xA000, 0x12);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF, "disabled RAM floats");
+
+        mbc.write_rom(0x0100, 0x0A);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF, "bit 8 set is the ROM bank");
+        mbc.write_rom(0x0000, 0x1A);
+        mbc.write_ram(0xA000, 0x12);
+        assert_eq!(mbc.read_ram(0xA000), 0xF2, "only the low nibble is stored");
+        assert_eq!(mbc.read_ram(0xA200), 0xF2);
+        assert_eq!(mbc.read_ram(0xBE00), 0xF2);
+        mbc.write_ram(0xBFFF, 0x0C);
+        assert_eq!(mbc.read_ram(0xA1FF), 0xFC);
+
+        let state = mbc.save_state();
+        let mut restored = Mbc2::new(numbered_rom(4));
+        assert_eq!(restored.load_state(&state), Ok(()));
+        assert_eq!(restored.read_ram(0xA000), 0xF2);
+
+        mbc.write_rom(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+    }
+}
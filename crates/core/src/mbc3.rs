@@ -0,0 +1,253 @@
+use crate::bank_stats::BankRecorder;
+use crate::cartridge::{rom_bank_count, ROM_BANK_SIZE};
+use crate::state::{StateReader, StateWriter};
+use crate::{BankStats, Cartridge, MapperRegisters, StateError, RTC_DAY_HIGH, RTC_SECONDS};
+use std::sync::Arc;
+
+const RAM_BANK_SIZE: usize = 0x2000;
+const RAM_START: u16 = 0xA000;
+const OPEN_BUS: u8 = 0xFF;
+
+/// MBC3: a 7-bit ROM bank, four RAM banks and, on timer carts, the real-time clock
+/// registers, which are mapped over 0xA000-0xBFFF by selecting 0x08-0x0C as the RAM bank.
+/// The clock itself lives on the bus; the mapper only decodes access to it.
+#[derive(Debug, Clone)]
+pub struct Mbc3 {
+    rom: Arc<[u8]>,
+    ram: Vec<u8>,
+    rom_banks: usize,
+    stats: BankRecorder,
+    has_rtc: bool,
+    ram_enabled: bool,
+    rom_bank: u8,
+    select: u8,
+    // The latch fires on a 0x00 then 0x01 write to 0x6000-0x7FFF.
+    latch_armed: bool,
+    latch_pending: bool,
+}
+
+impl Mbc3 {
+    pub fn new(rom: impl Into<Arc<[u8]>>, ram_size: usize, has_rtc: bool) -> Self {
+        let rom = rom.into();
+        Self {
+            rom_banks: rom_bank_count(rom.len()),
+            stats: BankRecorder::new(rom_bank_count(rom.len()), ram_size),
+            rom,
+            ram: vec![0; ram_size],
+            has_rtc,
+            ram_enabled: false,
+            rom_bank: 1,
+            select: 0,
+            latch_armed: false,
+            latch_pending: false,
+        }
+    }
+
+    fn rom_offset(&self, address: u16) -> usize {
+        let bank = if address < 0x4000 {
+            0
+        } else {
+            usize::from(self.rom_bank) & (self.rom_banks - 1)
+        };
+        bank * ROM_BANK_SIZE + usize::from(address & 0x3FFF)
+    }
+
+    fn ram_index(&self, address: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram.is_empty() || self.select > 0x03 {
+            return None;
+        }
+        let offset = usize::from(self.select) * RAM_BANK_SIZE + usize::from(address - RAM_START);
+        Some(offset % self.ram.len())
+    }
+}
+
+impl Cartridge for Mbc3 {
+    fn read_rom(&self, address: u16) -> u8 {
+        self.rom
+            .get(self.rom_offset(address))
+            .copied()
+            .unwrap_or(OPEN_BUS)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                let enabled = value & 0x0F == 0x0A;
+                self.stats.record_ram_gate(self.ram_enabled, enabled);
+                self.ram_enabled = enabled;
+            }
+            0x2000..=0x3FFF => self.rom_bank = (value & 0x7F).max(1),
+            0x4000..=0x5FFF => self.select = value & 0x0F,
+            _ => {
+                if self.latch_armed && value == 0x01 {
+                    self.latch_pending = self.has_rtc;
+                }
+                self.latch_armed = value == 0x00;
+            }
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        self.ram_index(address)
+            .map_or(OPEN_BUS, |index| self.ram[index])
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if let Some(index) = self.ram_index(address) {
+            self.ram[index] = value;
+        }
+    }
+
+    fn rom_bank(&self) -> u16 {
+        (usize::from(self.rom_bank) & (self.rom_banks - 1)) as u16
+    }
+
+    fn registers(&self) -> MapperRegisters {
+        MapperRegisters {
+            mapper: "MBC3",
+            rom_bank: self.rom_bank(),
+            ram_bank: self.select,
+            ram_enabled: self.ram_enabled,
+            mode: None,
+        }
+    }
+
+    fn record_read(&self, address: u16) {
+        if address < 0x8000 {
+            self.stats.record_rom(self.rom_offset(address));
+        } else if let Some(index) = self.ram_index(address) {
+            self.stats.record_ram(index);
+        }
+    }
+
+    fn stats(&self) -> BankStats {
+        self.stats.stats()
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn has_rtc(&self) -> bool {
+        self.has_rtc
+    }
+
+    fn rtc_register(&self) -> Option<u8> {
+        (self.has_rtc && self.ram_enabled && (RTC_SECONDS..=RTC_DAY_HIGH).contains(&self.select))
+            .then_some(self.select)
+    }
+
+    fn take_rtc_latch(&mut self) -> bool {
+        std::mem::take(&mut self.latch_pending)
+    }
+
+    fn rom(&self) -> &Arc<[u8]> {
+        &self.rom
+    }
+
+    fn patch_rom(&mut self, address: u16, value: u8) {
+        let offset = self.rom_offset(address);
+        if let Some(byte) = Arc::make_mut(&mut self.rom).get_mut(offset) {
+            *byte = value;
+        }
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        let mut out = StateWriter::default();
+        out.bool(self.ram_enabled);
+        out.u8(self.rom_bank);
+        out.u8(self.select);
+        out.bool(self.latch_armed);
+        out.byte_vec(&self.ram);
+        out.into_bytes()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut input = StateReader::new(state);
+        let ram_enabled = input.bool()?;
+        let rom_bank = input.u8()?;
+        let select = input.u8()?;
+        let latch_armed = input.bool()?;
+        let ram = input.byte_vec()?;
+        input.finish()?;
+        if !(1..=0x7F).contains(&rom_bank) || select > 0x0F {
+            return Err(StateError::Invalid("MBC3 bank register"));
+        }
+        if ram.len() != self.ram.len() {
+            return Err(StateError::Invalid("cartridge RAM size"));
+        }
+        self.ram_enabled = ram_enabled;
+        self.rom_bank = rom_bank;
+        self.select = select;
+        self.latch_armed = latch_armed;
+        self.latch_pending = false;
+        self.ram = ram;
+        Ok(())
+    }
+
+    fn clone_box(&self) -> Box<dyn Cartridge> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::numbered_rom;
+    use crate::{GameBoy, RTC_MINUTES};
+
+    #[test]
+    fn selects_seven_bit_rom_banks_and_four_ram_banks() {
+        let mut mbc = Mbc3::new(numbered_rom(128), 0x8000, false);
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 1, "bank 0 maps bank 1");
+        mbc.write_rom(0x2000, 0xFF);
+        assert_eq!(mbc.read_rom(0x4000), 0x7F);
+
+        mbc.write_rom(0x0000, 0x0A);
+        for bank in 0..4 {
+            mbc.write_rom(0x4000, bank);
+            mbc.write_ram(0xA123, bank + 0x10);
+        }
+        mbc.write_rom(0x4000, 0x02);
+        assert_eq!(mbc.read_ram(0xA123), 0x12);
+        mbc.write_rom(0x4000, RTC_SECONDS);
+        assert_eq!(mbc.read_ram(0xA123), 0xFF, "no clock on this cart");
+        assert_eq!(mbc.rtc_register(), None);
+
+        let state = mbc.save_state();
+        let mut restored = Mbc3::new(numbered_rom(128), 0x8000, false);
+        assert_eq!(restored.load_state(&state), Ok(()));
+        restored.write_rom(0x4000, 0x03);
+        assert_eq!(restored.read_ram(0xA123), 0x13);
+    }
+
+    #[test]
+    fn maps_the_bus_clock_and_latches_on_zero_then_one() {
+        let mut rom = numbered_rom(4);
+        rom[0x147] = 0x10; // MBC3+TIMER+RAM+BATTERY
+        rom[0x149] = 0x02;
+        let mut gb = GameBoy::new();
+        gb.load_rom(&rom);
+        gb.bus.write_byte(0x0000, 0x0A);
+        gb.bus.write_byte(0x4000, RTC_MINUTES);
+        gb.bus.write_byte(0xA000, 42);
+        assert_eq!(gb.bus.rtc().registers()[1], 42);
+        assert_eq!(gb.bus.read_byte(0xA000), 0, "reads see the latched copy");
+
+        gb.bus.write_byte(0x6000, 0x01);
+        assert_eq!(gb.bus.read_byte(0xA000), 0, "a latch needs 0x00 first");
+        gb.bus.write_byte(0x6000, 0x00);
+        gb.bus.write_byte(0x6000, 0x01);
+        assert_eq!(gb.bus.read_byte(0xA000), 42);
+
+        gb.bus.write_byte(0x4000, 0x00);
+        gb.bus.write_byte(0xA000, 0x99);
+        assert_eq!(gb.bus.read_byte(0xA000), 0x99, "bank 0 is RAM again");
+        assert_eq!(gb.bus.rtc().registers()[1], 42);
+    }
+}
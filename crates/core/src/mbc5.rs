@@ -0,0 +1,329 @@
+use crate::bank_stats::BankRecorder;
+use crate::cartridge::{rom_bank_count, ROM_BANK_SIZE};
+use crate::state::{StateReader, StateWriter};
+use crate::{BankStats, Cartridge, GameBoy, MapperRegisters, StateError};
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+const RAM_BANK_SIZE: usize = 0x2000;
+const RAM_START: u16 = 0xA000;
+const OPEN_BUS: u8 = 0xFF;
+// On rumble carts bit 3 of the RAM bank register drives the motor instead.
+const RUMBLE_MOTOR: u8 = 0x08;
+
+/// MBC5: a 9-bit ROM bank split over two registers (bank 0 included), up to 16 RAM banks
+/// and, on rumble carts, a motor wired to bit 3 of the RAM bank register.
+#[derive(Debug, Clone)]
+pub struct Mbc5 {
+    rom: Arc<[u8]>,
+    ram: Vec<u8>,
+    rom_banks: usize,
+    stats: BankRecorder,
+    has_rumble: bool,
+    ram_enabled: bool,
+    rom_bank: u16,
+    ram_bank: u8,
+    rumble: bool,
+}
+
+impl Mbc5 {
+    pub fn new(rom: impl Into<Arc<[u8]>>, ram_size: usize, has_rumble: bool) -> Self {
+        let rom = rom.into();
+        Self {
+            rom_banks: rom_bank_count(rom.len()),
+            stats: BankRecorder::new(rom_bank_count(rom.len()), ram_size),
+            rom,
+            ram: vec![0; ram_size],
+            has_rumble,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            rumble: false,
+        }
+    }
+
+    fn rom_offset(&self, address: u16) -> usize {
+        let bank = if address < 0x4000 {
+            0
+        } else {
+            usize::from(self.rom_bank) & (self.rom_banks - 1)
+        };
+        bank * ROM_BANK_SIZE + usize::from(address & 0x3FFF)
+    }
+
+    fn ram_index(&self, address: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return None;
+        }
+        let offset = usize::from(self.ram_bank) * RAM_BANK_SIZE + usize::from(address - RAM_START);
+        Some(offset % self.ram.len())
+    }
+}
+
+impl Cartridge for Mbc5 {
+    fn read_rom(&self, address: u16) -> u8 {
+        self.rom
+            .get(self.rom_offset(address))
+            .copied()
+            .unwrap_or(OPEN_BUS)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            // Unlike MBC1, all eight bits are decoded.
+            0x0000..=0x1FFF => {
+                let enabled = value == 0x0A;
+                self.stats.record_ram_gate(self.ram_enabled, enabled);
+                self.ram_enabled = enabled;
+            }
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | u16::from(value),
+            0x3000..=0x3FFF => {
+                self.rom_bank = (self.rom_bank & 0xFF) | (u16::from(value & 0x01) << 8)
+            }
+            0x4000..=0x5FFF if self.has_rumble => {
+                self.rumble = value & RUMBLE_MOTOR != 0;
+                self.ram_bank = value & 0x07;
+            }
+            0x4000..=0x5FFF => self.ram_bank = value & 0x0F,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        self.ram_index(address)
+            .map_or(OPEN_BUS, |index| self.ram[index])
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if let Some(index) = self.ram_index(address) {
+            self.ram[index] = value;
+        }
+    }
+
+    fn rom_bank(&self) -> u16 {
+        (usize::from(self.rom_bank) & (self.rom_banks - 1)) as u16
+    }
+
+    fn decodes_rom_write(&self, address: u16) -> bool {
+        address < 0x6000
+    }
+
+    fn registers(&self) -> MapperRegisters {
+        MapperRegisters {
+            mapper: "MBC5",
+            rom_bank: self.rom_bank(),
+            ram_bank: self.ram_bank,
+            ram_enabled: self.ram_enabled,
+            mode: None,
+        }
+    }
+
+    fn record_read(&self, address: u16) {
+        if address < 0x8000 {
+            self.stats.record_rom(self.rom_offset(address));
+        } else if let Some(index) = self.ram_index(address) {
+            self.stats.record_ram(index);
+        }
+    }
+
+    fn stats(&self) -> BankStats {
+        self.stats.stats()
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn rumble(&self) -> bool {
+        self.rumble
+    }
+
+    fn rom(&self) -> &Arc<[u8]> {
+        &self.rom
+    }
+
+    fn patch_rom(&mut self, address: u16, value: u8) {
+        let offset = self.rom_offset(address);
+        if let Some(byte) = Arc::make_mut(&mut self.rom).get_mut(offset) {
+            *byte = value;
+        }
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        let mut out = StateWriter::default();
+        out.bool(self.ram_enabled);
+        out.u16(self.rom_bank);
+        out.u8(self.ram_bank);
+        out.bool(self.rumble);
+        out.byte_vec(&self.ram);
+        out.into_bytes()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut input = StateReader::new(state);
+        let ram_enabled = input.bool()?;
+        let rom_bank = input.u16()?;
+        let ram_bank = input.u8()?;
+        let rumble = input.bool()?;
+        let ram = input.byte_vec()?;
+        input.finish()?;
+        if rom_bank > 0x1FF || ram_bank > 0x0F {
+            return Err(StateError::Invalid("MBC5 bank register"));
+        }
+        if ram.len() != self.ram.len() {
+            return Err(StateError::Invalid("cartridge RAM size"));
+        }
+        self.ram_enabled = ram_enabled;
+        self.rom_bank = rom_bank;
+        self.ram_bank = ram_bank;
+        self.rumble = rumble && self.has_rumble;
+        self.ram = ram;
+        Ok(())
+    }
+
+    fn clone_box(&self) -> Box<dyn Cartridge> {
+        Box::new(self.clone())
+    }
+}
+
+/// Holder for the callback installed with `GameBoy::set_rumble_callback`; like the frame
+/// callback, clones start without one.
+#[derive(Default)]
+pub(crate) struct RumbleCallback {
+    callback: Option<Box<dyn FnMut(bool) + Send>>,
+    motor_on: bool,
+}
+
+impl Clone for RumbleCallback {
+    fn clone(&self) -> Self {
+        Self {
+            callback: None,
+            motor_on: self.motor_on,
+        }
+    }
+}
+
+impl Debug for RumbleCallback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(if self.callback.is_some() {
+            "RumbleCallback(set)"
+        } else {
+            "RumbleCallback(none)"
+        })
+    }
+}
+
+impl GameBoy {
+    /// Calls `callback` with the new motor state whenever a rumble cartridge switches its
+    /// motor on or off, so frontends can drive controller rumble. Replaces any previous
+    /// callback.
+    pub fn set_rumble_callback(&mut self, callback: impl FnMut(bool) + Send + 'static) {
+        self.rumble_callback.callback = Some(Box::new(callback));
+    }
+
+    pub fn clear_rumble_callback(&mut self) {
+        self.rumble_callback.callback = None;
+    }
+
+    pub(crate) fn emit_rumble(&mut self) {
+        let Some(callback) = self.rumble_callback.callback.as_mut() else {
+            return;
+        };
+        let motor_on = self
+            .bus
+            .cartridge()
+            .is_some_and(|cartridge| cartridge.rumble());
+        if motor_on != self.rumble_callback.motor_on {
+            self.rumble_callback.motor_on = motor_on;
+            callback(motor_on);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::numbered_rom;
+    use crate::new_cartridge;
+    use std::sync::Mutex;
+
+    fn bank_at_4000(mbc: &Mbc5) -> usize {
+        usize::from(mbc.read_rom(0x4000)) | usize::from(mbc.read_rom(0x4001)) << 8
+    }
+
+    #[test]
+    fn selects_nine_bit_rom_banks_including_zero() {
+        let mut mbc = Mbc5::new(numbered_rom(512), 0, false);
+        assert_eq!(bank_at_4000(&mbc), 1);
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(bank_at_4000(&mbc), 0, "bank 0 is selectable");
+        mbc.write_rom(0x2000, 0x34);
+        mbc.write_rom(0x3000, 0x01);
+        assert_eq!(bank_at_4000(&mbc), 0x134);
+        assert_eq!(mbc.rom_bank(), 0x134);
+        mbc.write_rom(0x2FFF, 0xFF);
+        assert_eq!(bank_at_4000(&mbc), 0x1FF);
+        assert_eq!(
+            mbc.registers().to_string(),
+            "MBC5 rom_bank=0x1FF ram_bank=0x00 ram_enabled=no"
+        );
+        assert_eq!(mbc.read_rom(0x0000), 0x00);
+
+        let mut small = Mbc5::new(numbered_rom(64), 0, false);
+        small.write_rom(0x3000, 0x01);
+        small.write_rom(0x2000, 0x45);
+        assert_eq!(bank_at_4000(&small), 0x05, "banks wrap to the ROM size");
+    }
+
+    #[test]
+    fn switches_sixteen_ram_banks_after_an_exact_enable() {
+        let mut mbc = Mbc5::new(numbered_rom(4), 0x20000, false);
+        mbc.write_rom(0x0000, 0x1A);
+        mbc.write_ram(0xA000, 0x12);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF, "only 0x0A enables RAM");
+
+        mbc.write_rom(0x0000, 0x0A);
+        for bank in 0..16 {
+            mbc.write_rom(0x4000, bank);
+            mbc.write_ram(0xBFFF, bank + 0x40);
+        }
+        for bank in 0..16 {
+            mbc.write_rom(0x4000, bank);
+            assert_eq!(mbc.read_ram(0xBFFF), bank + 0x40);
+        }
+
+        let state = mbc.save_state();
+        let mut restored = Mbc5::new(numbered_rom(4), 0x20000, false);
+        assert_eq!(restored.load_state(&state), Ok(()));
+        assert_eq!(restored.read_ram(0xBFFF), 0x4F);
+    }
+
+    #[test]
+    fn rumble_carts_report_the_motor_to_the_callback() {
+        let mut rom = numbered_rom(4);
+        rom[0x147] = 0x1C; // MBC5+RUMBLE
+        let mut gb = GameBoy::new();
+        gb.load_rom(&rom);
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&changes);
+        gb.set_rumble_callback(move |on| seen.lock().expect("rumble log").push(on));
+
+        // LD A,$08 ; LD ($4000),A ; LD A,$03 ; LD ($4000),A ; XOR A ; LD ($4000),A
+        gb.bus.load_bytes(
+            0x0100,
+            &[
+                0x3E, 0x08, 0xEA, 0x00, 0x40, 0x3E, 0x03, 0xEA, 0x00, 0x40, 0xAF, 0xEA, 0x00, 0x40,
+            ],
+        );
+        gb.run_steps(6).expect("program should run");
+        assert_eq!(*changes.lock().expect("rumble log"), [true, false]);
+
+        let mut plain = new_cartridge(0x19, 0, numbered_rom(4));
+        plain.write_rom(0x4000, RUMBLE_MOTOR);
+        assert!(!plain.rumble(), "plain MBC5 has no motor");
+    }
+}
@@ -0,0 +1,107 @@
+use crate::GameBoy;
+use std::fmt::Write as _;
+
+/// Monotonic counters since the machine was created, for monitoring long soak runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunCounters {
+    /// Times the PPU entered VBlank.
+    pub frames: u64,
+    /// Opcodes executed; HALT/STOP idle steps and interrupt dispatches are not counted.
+    pub instructions: u64,
+    pub interrupts: u64,
+    /// Bytes the program started shifting out over serial.
+    pub serial_bytes: u64,
+}
+
+impl RunCounters {
+    /// Prometheus text exposition format, one `vibegb_*_total` counter per field.
+    pub fn prometheus_text(&self) -> String {
+        let mut text = String::new();
+        for (name, help, value) in [
+            ("frames", "PPU frames completed.", self.frames),
+            (
+                "instructions",
+                "CPU instructions executed.",
+                self.instructions,
+            ),
+            ("interrupts", "Interrupts dispatched.", self.interrupts),
+            ("serial_bytes", "Bytes sent over serial.", self.serial_bytes),
+        ] {
+            write_prometheus_counter(&mut text, name, help, value);
+        }
+        text
+    }
+}
+
+/// Appends one counter as `vibegb_<name>_total`, so frontends can add their own (e.g.
+/// audio underruns) alongside `RunCounters::prometheus_text`.
+pub fn write_prometheus_counter(text: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(text, "# HELP vibegb_{name}_total {help}");
+    let _ = writeln!(text, "# TYPE vibegb_{name}_total counter");
+    let _ = writeln!(text, "vibegb_{name}_total {value}");
+}
+
+impl GameBoy {
+    pub fn counters(&self) -> RunCounters {
+        RunCounters {
+            frames: self.bus.ppu().vblank_count(),
+            serial_bytes: self.bus.serial_bytes_sent(),
+            ..self.counters
+        }
+    }
+
+    /// Tallies the step `Cpu::step` just ran, given whether the CPU was idle before it.
+    pub(crate) fn count_step(&mut self, was_idle: bool) {
+        if self.cpu.dispatched_interrupt().is_some() {
+            self.counters.interrupts += 1;
+        } else if !(was_idle && self.cpu_idle()) {
+            self.counters.instructions += 1;
+        }
+    }
+
+    pub(crate) fn cpu_idle(&self) -> bool {
+        self.cpu.halted || self.cpu.stopped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_instructions_interrupts_and_serial_bytes() {
+        // LD A,0x41; LD (FF01),A; LD A,0x81; LD (FF02),A; EI; HALT; then NOPs.
+        let program = [
+            0x3E, 0x41, 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0xFB, 0x76, 0x00, 0x00,
+        ];
+        let mut gb = GameBoy::with_program(0xC000, &program);
+        gb.bus.write_byte(0xFFFF, 0x01);
+        gb.run_steps(6).expect("program should run");
+        assert_eq!(gb.counters().instructions, 6);
+        assert_eq!(gb.counters().serial_bytes, 1);
+
+        gb.run_steps(10).expect("halt should idle");
+        assert_eq!(gb.counters().instructions, 6);
+
+        gb.raise_interrupt(0x01);
+        gb.step().expect("interrupt should dispatch");
+        let counters = gb.counters();
+        assert_eq!(counters.interrupts, 1);
+        assert_eq!(counters.instructions, 6);
+    }
+
+    #[test]
+    fn renders_prometheus_counters() {
+        let counters = RunCounters {
+            frames: 3,
+            instructions: 1000,
+            interrupts: 2,
+            serial_bytes: 0,
+        };
+        let text = counters.prometheus_text();
+        assert!(text.contains("# TYPE vibegb_frames_total counter\nvibegb_frames_total 3\n"));
+        assert!(text.contains("vibegb_instructions_total 1000\n"));
+        assert!(text.contains("# HELP vibegb_interrupts_total Interrupts dispatched.\n"));
+        assert!(text.ends_with("vibegb_serial_bytes_total 0\n"));
+    }
+}
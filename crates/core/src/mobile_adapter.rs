@@ -0,0 +1,317 @@
+use crate::state::{StateReader, StateWriter};
+use crate::{SerialDevice, StateError};
+use std::collections::VecDeque;
+
+const MAGIC: [u8; 2] = [0x99, 0x66];
+const IDLE: u8 = 0xD2;
+const ADAPTER_DEVICE_ID: u8 = 0x88;
+const CHECKSUM_ERROR_ACK: u8 = 0xF1;
+const HEADER_LEN: usize = 4;
+const CHECKSUM_LEN: usize = 2;
+
+pub const MOBILE_BEGIN_SESSION: u8 = 0x10;
+pub const MOBILE_END_SESSION: u8 = 0x11;
+pub const MOBILE_ERROR: u8 = 0x6E;
+/// Error code reported for every request that would need the phone network.
+pub const MOBILE_NO_SERVICE: u8 = 0x03;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RxState {
+    Idle,
+    Magic,
+    Body,
+    DeviceId,
+    Ack,
+}
+
+const RX_STATES: [RxState; 5] = [
+    RxState::Idle,
+    RxState::Magic,
+    RxState::Body,
+    RxState::DeviceId,
+    RxState::Ack,
+];
+
+/// Mobile Adapter GB stand-in with no phone line behind it.
+///
+/// Session begin/end are acknowledged so titles detect the adapter, and every other command
+/// gets a well-formed error packet (`0x6E`, echoing the command and `MOBILE_NO_SERVICE`)
+/// instead of silence, which would leave games waiting on serial forever.
+#[derive(Debug, Clone)]
+pub struct MobileAdapter {
+    state: RxState,
+    packet: Vec<u8>,
+    outgoing: VecDeque<u8>,
+    requests: Vec<u8>,
+}
+
+impl Default for MobileAdapter {
+    fn default() -> Self {
+        Self {
+            state: RxState::Idle,
+            packet: Vec::new(),
+            outgoing: VecDeque::new(),
+            requests: Vec::new(),
+        }
+    }
+}
+
+impl MobileAdapter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Commands received so far, in order.
+    pub fn requests(&self) -> &[u8] {
+        &self.requests
+    }
+
+    pub fn exchange(&mut self, out_byte: u8) -> u8 {
+        if let Some(byte) = self.outgoing.pop_front() {
+            return byte;
+        }
+        self.receive(out_byte)
+    }
+
+    fn receive(&mut self, byte: u8) -> u8 {
+        match self.state {
+            RxState::Idle => {
+                if byte == MAGIC[0] {
+                    self.state = RxState::Magic;
+                }
+                IDLE
+            }
+            RxState::Magic => {
+                self.state = if byte == MAGIC[1] {
+                    self.packet.clear();
+                    RxState::Body
+                } else {
+                    RxState::Idle
+                };
+                IDLE
+            }
+            RxState::Body => {
+                self.packet.push(byte);
+                if self.packet.len() >= HEADER_LEN
+                    && self.packet.len() == HEADER_LEN + self.data_len() + CHECKSUM_LEN
+                {
+                    self.state = RxState::DeviceId;
+                }
+                IDLE
+            }
+            RxState::DeviceId => {
+                self.state = RxState::Ack;
+                ADAPTER_DEVICE_ID
+            }
+            RxState::Ack => {
+                self.state = RxState::Idle;
+                let body_len = self.packet.len() - CHECKSUM_LEN;
+                let expected =
+                    u16::from_be_bytes([self.packet[body_len], self.packet[body_len + 1]]);
+                if checksum(&self.packet[..body_len]) != expected {
+                    return CHECKSUM_ERROR_ACK;
+                }
+                let command = self.packet[0] & 0x7F;
+                self.requests.push(command);
+                self.queue_response(command);
+                command ^ 0x80
+            }
+        }
+    }
+
+    fn data_len(&self) -> usize {
+        usize::from(self.packet[3])
+    }
+
+    fn queue_response(&mut self, command: u8) {
+        let (reply_command, data) = match command {
+            MOBILE_BEGIN_SESSION => (
+                command,
+                self.packet[HEADER_LEN..][..self.data_len()].to_vec(),
+            ),
+            MOBILE_END_SESSION => (command, Vec::new()),
+            _ => (MOBILE_ERROR, vec![command, MOBILE_NO_SERVICE]),
+        };
+        let mut body = vec![reply_command | 0x80, 0x00, 0x00, data.len() as u8];
+        body.extend_from_slice(&data);
+        let sum = checksum(&body);
+
+        self.outgoing.extend(MAGIC);
+        self.outgoing.extend(body);
+        self.outgoing.extend(sum.to_be_bytes());
+        self.outgoing.extend([ADAPTER_DEVICE_ID, 0x00]);
+    }
+}
+
+impl SerialDevice for MobileAdapter {
+    fn exchange(&mut self, out_byte: u8) -> u8 {
+        MobileAdapter::exchange(self, out_byte)
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        let mut out = StateWriter::default();
+        out.u8(RX_STATES
+            .iter()
+            .position(|&state| state == self.state)
+            .expect("every state is in RX_STATES") as u8);
+        out.byte_vec(&self.packet);
+        out.byte_vec(&self.outgoing.iter().copied().collect::<Vec<_>>());
+        out.byte_vec(&self.requests);
+        out.into_bytes()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut input = StateReader::new(state);
+        let rx_state = *RX_STATES
+            .get(usize::from(input.u8()?))
+            .ok_or(StateError::Invalid("mobile adapter state"))?;
+        let packet = input.byte_vec()?;
+        let outgoing = input.byte_vec()?;
+        let requests = input.byte_vec()?;
+        input.finish()?;
+        // The reply bytes read the checksum out of a complete packet.
+        let complete = packet.len() >= HEADER_LEN
+            && packet.len() == HEADER_LEN + usize::from(packet[3]) + CHECKSUM_LEN;
+        if matches!(rx_state, RxState::DeviceId | RxState::Ack) && !complete {
+            return Err(StateError::Invalid("mobile adapter packet"));
+        }
+        self.state = rx_state;
+        self.packet = packet;
+        self.outgoing = outgoing.into();
+        self.requests = requests;
+        Ok(())
+    }
+
+    fn clone_box(&self) -> Box<dyn SerialDevice> {
+        Box::new(self.clone())
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u16 {
+    bytes
+        .iter()
+        .fold(0u16, |sum, byte| sum.wrapping_add(u16::from(*byte)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GameBoy, SERIAL_TRANSFER_CYCLES};
+
+    fn request(command: u8, data: &[u8]) -> Vec<u8> {
+        let mut body = vec![command, 0x00, 0x00, data.len() as u8];
+        body.extend_from_slice(data);
+        let sum = checksum(&body);
+        let mut packet = MAGIC.to_vec();
+        packet.extend(body);
+        packet.extend(sum.to_be_bytes());
+        packet.extend([0x81, 0x00]);
+        packet
+    }
+
+    fn send(adapter: &mut MobileAdapter, bytes: &[u8]) -> Vec<u8> {
+        bytes.iter().map(|byte| adapter.exchange(*byte)).collect()
+    }
+
+    fn read_response(adapter: &mut MobileAdapter) -> Vec<u8> {
+        let mut response = send(adapter, &[0x4B; 6]);
+        let len = usize::from(response[5]);
+        response.extend(send(adapter, &vec![0x4B; len + 2]));
+        response.extend(send(adapter, &[0x81, 0x00]));
+        response
+    }
+
+    #[test]
+    fn begin_session_is_acknowledged_and_echoed() {
+        let mut adapter = MobileAdapter::new();
+        let replies = send(&mut adapter, &request(MOBILE_BEGIN_SESSION, b"NINTENDO"));
+        assert!(replies[..replies.len() - 2]
+            .iter()
+            .all(|byte| *byte == IDLE));
+        assert_eq!(replies[replies.len() - 2..], [ADAPTER_DEVICE_ID, 0x90]);
+
+        let response = read_response(&mut adapter);
+        assert_eq!(response[..6], [0x99, 0x66, 0x90, 0x00, 0x00, 0x08]);
+        assert_eq!(&response[6..14], b"NINTENDO");
+        assert_eq!(response[response.len() - 2..], [ADAPTER_DEVICE_ID, 0x00]);
+        assert_eq!(adapter.exchange(0x4B), IDLE);
+    }
+
+    #[test]
+    fn network_commands_get_no_service_errors() {
+        let mut adapter = MobileAdapter::new();
+        send(&mut adapter, &request(0x12, b"0120"));
+        let response = read_response(&mut adapter);
+
+        let body = [
+            MOBILE_ERROR | 0x80,
+            0x00,
+            0x00,
+            0x02,
+            0x12,
+            MOBILE_NO_SERVICE,
+        ];
+        assert_eq!(response[2..8], body);
+        assert_eq!(response[8..10], checksum(&body).to_be_bytes());
+        assert_eq!(adapter.requests(), [0x12]);
+    }
+
+    #[test]
+    fn save_state_resumes_mid_packet() {
+        let mut adapter = MobileAdapter::new();
+        let packet = request(MOBILE_BEGIN_SESSION, b"NINTENDO");
+        let (head, tail) = packet.split_at(10);
+        send(&mut adapter, head);
+
+        let mut restored = MobileAdapter::new();
+        assert_eq!(
+            SerialDevice::load_state(&mut restored, &adapter.save_state()),
+            Ok(())
+        );
+        assert_eq!(send(&mut restored, tail), send(&mut adapter, tail));
+        assert_eq!(read_response(&mut restored), read_response(&mut adapter));
+        assert_eq!(
+            SerialDevice::load_state(&mut restored, &[9, 0, 0, 0]),
+            Err(StateError::Invalid("mobile adapter state"))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_checksum() {
+        let mut adapter = MobileAdapter::new();
+        let mut packet = request(MOBILE_END_SESSION, &[]);
+        packet[7] ^= 0xFF;
+        let replies = send(&mut adapter, &packet);
+        assert_eq!(replies.last(), Some(&CHECKSUM_ERROR_ACK));
+        assert_eq!(adapter.exchange(0x4B), IDLE);
+    }
+
+    #[test]
+    fn answers_internally_clocked_transfers_from_a_game_boy() {
+        // LD A,$99 ; LDH ($01),A ; LD A,$81 ; LDH ($02),A ; JR -2
+        let program = [0x3E, 0x99, 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0x18, 0xFE];
+        let mut gb = GameBoy::with_program(0x0000, &program);
+        gb.bus.set_serial_device(Box::new(MobileAdapter::new()));
+
+        while gb.cycle_count() < SERIAL_TRANSFER_CYCLES + 100 {
+            gb.step().expect("program should run");
+        }
+        assert_eq!(gb.bus.read_byte(crate::SB_ADDR), IDLE);
+        let adapter = gb
+            .bus
+            .serial_device::<MobileAdapter>()
+            .expect("the adapter is installed");
+        assert!(adapter.requests().is_empty(), "0x99 alone is not a packet");
+        let state = gb.save_state();
+        let mut restored = GameBoy::new();
+        restored
+            .bus
+            .set_serial_device(Box::new(MobileAdapter::new()));
+        assert!(restored.load_state(&state).is_ok());
+        assert_eq!(
+            restored.save_state(),
+            state,
+            "the adapter state came back too"
+        );
+    }
+}
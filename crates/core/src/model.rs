@@ -0,0 +1,27 @@
+use std::fmt::{Display, Formatter};
+
+/// Which console is being emulated, for the behavior that differs between them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Model {
+    #[default]
+    Dmg,
+    /// The Game Boy Pocket: DMG hardware behavior with its own boot state.
+    Mgb,
+    Cgb,
+}
+
+impl Model {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Dmg => "dmg",
+            Self::Mgb => "mgb",
+            Self::Cgb => "cgb",
+        }
+    }
+}
+
+impl Display for Model {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
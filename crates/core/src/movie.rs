@@ -0,0 +1,284 @@
+use crate::state::{StateReader, StateWriter};
+use crate::{Button, GameBoy, StateError};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+pub const MOVIE_MAGIC: [u8; 4] = *b"VGBM";
+pub const MOVIE_VERSION: u16 = 1;
+
+/// A button change, stamped with the CPU cycle count it happened at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovieInput {
+    pub cycle: u64,
+    pub button: Button,
+    pub pressed: bool,
+}
+
+/// A save state and every button change after it, enough to replay a run exactly on the
+/// same ROM. Host settings such as the PPU renderer and hardware quirks are not included
+/// and must match the recording.
+///
+/// The file is `MOVIE_MAGIC`, `MOVIE_VERSION` (`u16`), a hash of the ROM (`u64`), the
+/// length-prefixed save state, then the input count and each input as its cycle (`u64`),
+/// its index in `Button::ALL` and a pressed flag, in the save state encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    rom_hash: u64,
+    state: Vec<u8>,
+    inputs: Vec<MovieInput>,
+}
+
+impl Movie {
+    pub fn inputs(&self) -> &[MovieInput] {
+        &self.inputs
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = StateWriter::default();
+        out.bytes(&MOVIE_MAGIC);
+        out.u16(MOVIE_VERSION);
+        out.u64(self.rom_hash);
+        out.byte_vec(&self.state);
+        out.usize(self.inputs.len());
+        for input in &self.inputs {
+            out.u64(input.cycle);
+            out.u8(button_index(input.button));
+            out.bool(input.pressed);
+        }
+        out.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MovieError> {
+        let mut input = StateReader::new(bytes);
+        let mut magic = [0; 4];
+        input.bytes_into(&mut magic)?;
+        if magic != MOVIE_MAGIC {
+            return Err(MovieError::BadMagic);
+        }
+        let version = input.u16()?;
+        if version != MOVIE_VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+        let rom_hash = input.u64()?;
+        let state = input.byte_vec()?;
+        let count = input.usize()?;
+        let mut inputs = Vec::new();
+        for _ in 0..count {
+            let cycle = input.u64()?;
+            let button = *Button::ALL
+                .get(usize::from(input.u8()?))
+                .ok_or(StateError::Invalid("movie button"))?;
+            let pressed = input.bool()?;
+            if inputs
+                .last()
+                .is_some_and(|last: &MovieInput| last.cycle > cycle)
+            {
+                return Err(StateError::Invalid("movie input order").into());
+            }
+            inputs.push(MovieInput {
+                cycle,
+                button,
+                pressed,
+            });
+        }
+        input.finish()?;
+        Ok(Self {
+            rom_hash,
+            state,
+            inputs,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovieError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    /// The movie file or the save state inside it does not load.
+    State(StateError),
+    /// The movie was recorded with a different ROM in the slot.
+    RomMismatch,
+}
+
+impl Display for MovieError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadMagic => f.write_str("not a vibegb movie"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "movie version {version} is not supported (expected {MOVIE_VERSION})"
+            ),
+            Self::State(err) => write!(f, "movie does not load: {err}"),
+            Self::RomMismatch => f.write_str("movie was recorded with a different ROM"),
+        }
+    }
+}
+
+impl Error for MovieError {}
+
+impl From<StateError> for MovieError {
+    fn from(err: StateError) -> Self {
+        Self::State(err)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct MoviePlayback {
+    inputs: Vec<MovieInput>,
+    played: usize,
+}
+
+impl GameBoy {
+    /// Starts a movie from this moment: a save state now, then every `set_button` call.
+    /// Restarting drops the inputs recorded so far.
+    pub fn start_movie_recording(&mut self) {
+        self.movie_recording = Some(Movie {
+            rom_hash: self.rom_hash(),
+            state: self.save_state(),
+            inputs: Vec::new(),
+        });
+    }
+
+    /// Stops recording and returns the movie, or `None` if not recording.
+    pub fn stop_movie_recording(&mut self) -> Option<Movie> {
+        self.movie_recording.take()
+    }
+
+    /// Restores the movie's starting state and replays its inputs as the CPU reaches the
+    /// cycles they were recorded at. Host `set_button` calls are ignored until every input
+    /// has played. The ROM must be the one the movie was recorded with.
+    pub fn play_movie(&mut self, movie: &Movie) -> Result<(), MovieError> {
+        if movie.rom_hash != self.rom_hash() {
+            return Err(MovieError::RomMismatch);
+        }
+        self.load_state(&movie.state)?;
+        self.movie_playback = Some(MoviePlayback {
+            inputs: movie.inputs.clone(),
+            played: 0,
+        });
+        Ok(())
+    }
+
+    /// Inputs played so far and in total, while a movie is loaded.
+    pub fn movie_progress(&self) -> Option<(usize, usize)> {
+        self.movie_playback
+            .as_ref()
+            .map(|playback| (playback.played, playback.inputs.len()))
+    }
+
+    pub(crate) fn movie_owns_input(&self) -> bool {
+        self.movie_playback
+            .as_ref()
+            .is_some_and(|playback| playback.played < playback.inputs.len())
+    }
+
+    pub(crate) fn record_movie_input(&mut self, button: Button, pressed: bool) {
+        let cycle = self.cycle_count();
+        if let Some(movie) = self.movie_recording.as_mut() {
+            movie.inputs.push(MovieInput {
+                cycle,
+                button,
+                pressed,
+            });
+        }
+    }
+
+    pub(crate) fn play_movie_inputs(&mut self) {
+        let cycle = self.cycle_count();
+        let Some(playback) = self.movie_playback.as_mut() else {
+            return;
+        };
+        while let Some(input) = playback.inputs.get(playback.played) {
+            if input.cycle > cycle {
+                break;
+            }
+            self.bus.set_button(input.button, input.pressed);
+            playback.played += 1;
+        }
+    }
+
+    fn rom_hash(&self) -> u64 {
+        let rom = self
+            .bus
+            .cartridge()
+            .map(|cartridge| &cartridge.rom()[..])
+            .unwrap_or_default();
+        rom.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01B3)
+        })
+    }
+}
+
+fn button_index(button: Button) -> u8 {
+    Button::ALL
+        .iter()
+        .position(|&candidate| candidate == button)
+        .expect("every button is in Button::ALL") as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Selects the d-pad, then counts in B while Right is held: LD A,$20 ; LDH ($00),A ;
+    // loop: LDH A,($00) ; BIT 0,A ; JR NZ,loop ; INC B ; JR loop
+    const PROGRAM: [u8; 12] = [
+        0x3E, 0x20, 0xE0, 0x00, 0xF0, 0x00, 0xCB, 0x47, 0x20, 0xFA, 0x04, 0x18,
+    ];
+
+    fn console() -> GameBoy {
+        let mut rom = vec![0; 0x8000];
+        rom[0x100..0x104].copy_from_slice(&[0xC3, 0x50, 0x01, 0x00]);
+        rom[0x150..0x150 + PROGRAM.len()].copy_from_slice(&PROGRAM);
+        rom[0x150 + PROGRAM.len()] = 0xF7; // JR -9
+        let mut gb = GameBoy::with_seed(7);
+        gb.load_rom(&rom);
+        gb
+    }
+
+    #[test]
+    fn replays_recorded_inputs_to_the_same_state() {
+        let mut gb = console();
+        gb.run_steps(50).expect("program should run");
+        gb.start_movie_recording();
+        for (steps, pressed) in [(100, true), (37, false), (211, true), (5, false)] {
+            gb.run_steps(steps).expect("program should run");
+            gb.set_button(Button::Right, pressed);
+        }
+        gb.run_steps(80).expect("program should run");
+        let movie = gb.stop_movie_recording().expect("recording was started");
+        assert_eq!(movie.inputs().len(), 4);
+        assert_ne!(gb.cpu.regs.b, 0, "Right was held for a while");
+
+        let movie = Movie::from_bytes(&movie.to_bytes()).expect("movie should round trip");
+        let mut replay = console();
+        assert_eq!(replay.play_movie(&movie), Ok(()));
+        replay.set_button(Button::Right, true);
+        replay.run_steps(433).expect("program should run");
+        assert_eq!(replay.movie_progress(), Some((4, 4)));
+        assert_eq!(replay.save_state(), gb.save_state());
+    }
+
+    #[test]
+    fn rejects_other_roms_and_damaged_files() {
+        let mut gb = console();
+        gb.start_movie_recording();
+        gb.set_button(Button::A, true);
+        let movie = gb.stop_movie_recording().expect("recording was started");
+
+        let mut other = GameBoy::new();
+        assert_eq!(other.play_movie(&movie), Err(MovieError::RomMismatch));
+        assert_eq!(
+            Movie::from_bytes(b"VGBS"),
+            Err(MovieError::BadMagic),
+            "a save state is not a movie"
+        );
+        let mut bytes = movie.to_bytes();
+        let last = bytes.len() - 2;
+        bytes[last] = 8;
+        assert_eq!(
+            Movie::from_bytes(&bytes).map_err(|err| err.to_string()),
+            Err("movie does not load: save state has an invalid movie button".to_string())
+        );
+    }
+}
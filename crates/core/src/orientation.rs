@@ -0,0 +1,123 @@
+use crate::{GameBoy, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// Clockwise rotation applied to an exported frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+/// Display orientation for frame export. Flips are applied to the LCD image first, then
+/// the result is rotated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Orientation {
+    pub rotation: Rotation,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+impl Orientation {
+    /// Width and height of a `width` x `height` image after this orientation.
+    pub fn output_size(self, width: usize, height: usize) -> (usize, usize) {
+        match self.rotation {
+            Rotation::None | Rotation::Cw180 => (width, height),
+            Rotation::Cw90 | Rotation::Cw270 => (height, width),
+        }
+    }
+
+    /// Reorders row-major `pixels` of `bytes_per_pixel` bytes each; works for both the
+    /// indexed and RGBA framebuffers.
+    pub fn apply(
+        self,
+        pixels: &[u8],
+        width: usize,
+        height: usize,
+        bytes_per_pixel: usize,
+    ) -> Vec<u8> {
+        assert_eq!(pixels.len(), width * height * bytes_per_pixel);
+        let (out_width, out_height) = self.output_size(width, height);
+        let mut output = Vec::with_capacity(pixels.len());
+        for out_y in 0..out_height {
+            for out_x in 0..out_width {
+                let (mut x, mut y) = match self.rotation {
+                    Rotation::None => (out_x, out_y),
+                    Rotation::Cw90 => (out_y, height - 1 - out_x),
+                    Rotation::Cw180 => (width - 1 - out_x, height - 1 - out_y),
+                    Rotation::Cw270 => (width - 1 - out_y, out_x),
+                };
+                if self.flip_horizontal {
+                    x = width - 1 - x;
+                }
+                if self.flip_vertical {
+                    y = height - 1 - y;
+                }
+                let offset = (y * width + x) * bytes_per_pixel;
+                output.extend_from_slice(&pixels[offset..offset + bytes_per_pixel]);
+            }
+        }
+        output
+    }
+}
+
+impl GameBoy {
+    /// `framebuffer_rgba` reoriented for displays mounted rotated or mirrored; returns the
+    /// pixels with the output width and height.
+    pub fn framebuffer_rgba_oriented(&self, orientation: Orientation) -> (Vec<u8>, usize, usize) {
+        let (width, height) = orientation.output_size(SCREEN_WIDTH, SCREEN_HEIGHT);
+        let pixels = orientation.apply(&self.framebuffer_rgba(), SCREEN_WIDTH, SCREEN_HEIGHT, 4);
+        (pixels, width, height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 3x2 image:
+    // 0 1 2
+    // 3 4 5
+    const IMAGE: [u8; 6] = [0, 1, 2, 3, 4, 5];
+
+    fn oriented(rotation: Rotation, flip_horizontal: bool, flip_vertical: bool) -> Vec<u8> {
+        Orientation {
+            rotation,
+            flip_horizontal,
+            flip_vertical,
+        }
+        .apply(&IMAGE, 3, 2, 1)
+    }
+
+    #[test]
+    fn rotates_clockwise() {
+        assert_eq!(oriented(Rotation::None, false, false), IMAGE);
+        assert_eq!(oriented(Rotation::Cw90, false, false), [3, 0, 4, 1, 5, 2]);
+        assert_eq!(oriented(Rotation::Cw180, false, false), [5, 4, 3, 2, 1, 0]);
+        assert_eq!(oriented(Rotation::Cw270, false, false), [2, 5, 1, 4, 0, 3]);
+    }
+
+    #[test]
+    fn flips_before_rotating() {
+        assert_eq!(oriented(Rotation::None, true, false), [2, 1, 0, 5, 4, 3]);
+        assert_eq!(oriented(Rotation::None, false, true), [3, 4, 5, 0, 1, 2]);
+        assert_eq!(
+            oriented(Rotation::None, true, true),
+            oriented(Rotation::Cw180, false, false)
+        );
+        assert_eq!(oriented(Rotation::Cw90, true, false), [5, 2, 4, 1, 3, 0]);
+    }
+
+    #[test]
+    fn rgba_export_swaps_dimensions_for_quarter_turns() {
+        let gb = GameBoy::new();
+        let orientation = Orientation {
+            rotation: Rotation::Cw90,
+            ..Orientation::default()
+        };
+        let (pixels, width, height) = gb.framebuffer_rgba_oriented(orientation);
+        assert_eq!((width, height), (SCREEN_HEIGHT, SCREEN_WIDTH));
+        assert_eq!(pixels.len(), SCREEN_WIDTH * SCREEN_HEIGHT * 4);
+    }
+}
@@ -0,0 +1,2162 @@
+use crate::state::{StateError, StateReader, StateWriter, Stateful};
+use crate::{Model, CYCLES_PER_SCANLINE, INTERRUPT_LCD, INTERRUPT_VBLANK, SCANLINES_PER_FRAME};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+pub const LCDC_ADDR: u16 = 0xFF40;
+pub const STAT_ADDR: u16 = 0xFF41;
+pub const SCY_ADDR: u16 = 0xFF42;
+pub const SCX_ADDR: u16 = 0xFF43;
+pub const LY_ADDR: u16 = 0xFF44;
+pub const LYC_ADDR: u16 = 0xFF45;
+pub const BGP_ADDR: u16 = 0xFF47;
+pub const OBP0_ADDR: u16 = 0xFF48;
+pub const OBP1_ADDR: u16 = 0xFF49;
+pub const WY_ADDR: u16 = 0xFF4A;
+pub const WX_ADDR: u16 = 0xFF4B;
+
+const VRAM_START: u16 = 0x8000;
+const OAM_ENTRIES: usize = 40;
+const MAX_SPRITES_PER_LINE: usize = 10;
+const OAM_SCAN_CYCLES: u32 = 80;
+const SCANLINE_DRAWING_CYCLES: u32 = 172;
+const DRAWING_END_CYCLES: u32 = OAM_SCAN_CYCLES + SCANLINE_DRAWING_CYCLES;
+// Background fetch steps take two dots each: tile number, data low, data high.
+const TILE_NUMBER_DOT: u8 = 2;
+const TILE_DATA_LOW_DOT: u8 = 4;
+const TILE_FETCH_DOTS: u8 = 6;
+// The fetcher's first tile of every line is fetched twice; the first copy is thrown away.
+const FIFO_STARTUP_DOTS: u32 = TILE_FETCH_DOTS as u32;
+const SPRITE_FETCH_DOTS: u8 = 6;
+// Most dots an object fetch waits for the background fetcher to finish its tile.
+const SPRITE_ALIGNMENT_DOTS: u32 = 5;
+// The fetcher restarts on window tiles when the window starts, as it does after the
+// line's first tile.
+const WINDOW_START_DOTS: u32 = TILE_FETCH_DOTS as u32;
+// WX is the window's left edge plus 7; past 166 it never starts.
+const WX_OFFSET: i16 = 7;
+const WX_MAX: u8 = 166;
+// Line 0 after the LCD is switched back on starts this many dots in.
+const LCD_ENABLE_SKIPPED_DOTS: u32 = 4;
+
+const LCDC_ENABLE: u8 = 0x80;
+const LCDC_WINDOW_TILE_MAP: u8 = 0x40;
+const LCDC_WINDOW_ENABLE: u8 = 0x20;
+const LCDC_BG_TILE_MAP: u8 = 0x08;
+const LCDC_OBJ_SIZE: u8 = 0x04;
+const LCDC_OBJ_ENABLE: u8 = 0x02;
+const LCDC_TILE_DATA: u8 = 0x10;
+const LCDC_BG_ENABLE: u8 = 0x01;
+
+const OBJ_BEHIND_BG: u8 = 0x80;
+const OBJ_Y_FLIP: u8 = 0x40;
+const OBJ_X_FLIP: u8 = 0x20;
+const OBJ_PALETTE1: u8 = 0x10;
+
+const STAT_HBLANK_INTERRUPT: u8 = 0x08;
+const STAT_VBLANK_INTERRUPT: u8 = 0x10;
+const STAT_OAM_INTERRUPT: u8 = 0x20;
+const STAT_LYC_INTERRUPT: u8 = 0x40;
+const STAT_WRITABLE: u8 = 0x78;
+const STAT_LYC_EQUAL: u8 = 0x04;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PpuMode {
+    HBlank,
+    VBlank,
+    OamScan,
+    Drawing,
+}
+
+/// How mode 3 is emulated. `Scanline` draws a whole line at once, holding mode 3 for 172
+/// cycles plus each object's fetch penalty; `PixelFifo` runs the background fetcher and
+/// pixel FIFOs dot by dot, so mode 3 stretches with SCX fine scroll and sprite fetches and
+/// mid-line register writes land on the right pixel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PpuRenderer {
+    #[default]
+    Scanline,
+    PixelFifo,
+}
+
+/// What the framebuffer shows while LCDC bit 7 is clear. Test ROMs disagree on what a
+/// disabled screen should hash to, so frame-hash expectations are only comparable when
+/// recorded under the same policy: `White` and `Blank` overwrite the buffer the moment
+/// the LCD turns off, `LastFrame` leaves the final rendered image in place.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LcdOffOutput {
+    /// Shade 0, which is what the DMG panel shows.
+    #[default]
+    White,
+    LastFrame,
+    /// A caller-chosen value, which may lie outside 0-3 so frontends can tell it apart.
+    Blank(u8),
+}
+
+/// How much the picture changes from frame to frame, updated at every VBlank.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameDeltaStats {
+    /// Frames compared so far; the first is compared against an all-zero screen.
+    pub frames: u64,
+    /// Pixels that differ between the last two completed frames.
+    pub changed_pixels: usize,
+    /// Frames that changed at least one pixel.
+    pub active_frames: u64,
+    /// Frames in a row, up to the latest, that changed nothing.
+    pub static_frames: u64,
+}
+
+impl FrameDeltaStats {
+    /// `changed_pixels` as a fraction of the screen, 0.0 to 1.0.
+    pub fn change_ratio(&self) -> f64 {
+        self.changed_pixels as f64 / (SCREEN_WIDTH * SCREEN_HEIGHT) as f64
+    }
+
+    fn record(&mut self, changed_pixels: usize) {
+        self.frames += 1;
+        self.changed_pixels = changed_pixels;
+        if changed_pixels == 0 {
+            self.static_frames += 1;
+        } else {
+            self.active_frames += 1;
+            self.static_frames = 0;
+        }
+    }
+}
+
+/// RGBA colors for the four DMG shades, lightest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [[u8; 4]; 4],
+}
+
+impl Palette {
+    pub const GRAYSCALE: Self = Self::from_rgb([0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000]);
+    /// The pea-green tint of the original DMG screen.
+    pub const DMG_GREEN: Self = Self::from_rgb([0x9BBC0F, 0x8BAC0F, 0x306230, 0x0F380F]);
+
+    /// Builds an opaque palette from 24-bit RGB values, as used by `CompatPalettes`.
+    pub const fn from_rgb(colors: [u32; 4]) -> Self {
+        const fn opaque(rgb: u32) -> [u8; 4] {
+            [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 0xFF]
+        }
+        Self {
+            colors: [
+                opaque(colors[0]),
+                opaque(colors[1]),
+                opaque(colors[2]),
+                opaque(colors[3]),
+            ],
+        }
+    }
+
+    /// Color for a framebuffer value; anything outside 0-3 (see `LcdOffOutput::Blank`)
+    /// becomes fully transparent.
+    pub fn rgba(&self, shade: u8) -> [u8; 4] {
+        self.colors
+            .get(usize::from(shade))
+            .copied()
+            .unwrap_or([0; 4])
+    }
+
+    /// `rgba` packed as RGB565 (red in the top bits); alpha is dropped, so out-of-range
+    /// values come out black.
+    pub fn rgb565(&self, shade: u8) -> u16 {
+        let [r, g, b, _] = self.rgba(shade);
+        (u16::from(r >> 3) << 11) | (u16::from(g >> 2) << 5) | u16::from(b >> 3)
+    }
+}
+
+/// A caller-provided export buffer does not hold exactly one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FramebufferSizeError {
+    pub expected: usize,
+    pub actual: usize,
+}
+
+impl Display for FramebufferSizeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "framebuffer export needs {} elements, buffer has {}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl Error for FramebufferSizeError {}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::GRAYSCALE
+    }
+}
+
+impl PpuMode {
+    fn bits(self) -> u8 {
+        match self {
+            Self::HBlank => 0,
+            Self::VBlank => 1,
+            Self::OamScan => 2,
+            Self::Drawing => 3,
+        }
+    }
+}
+
+/// The memory the PPU reads: 8 KiB of VRAM from 0x8000 and the 160 bytes of OAM.
+#[derive(Debug, Clone, Copy)]
+pub struct VideoMemory<'a> {
+    pub vram: &'a [u8],
+    pub oam: &'a [u8],
+}
+
+impl VideoMemory<'_> {
+    /// The VRAM byte at bus address `address`.
+    pub fn vram(&self, address: u16) -> u8 {
+        self.vram[usize::from(address - VRAM_START)]
+    }
+}
+
+/// DMG LCD controller. Each visible line is rendered in one go when mode 3 ends; the
+/// framebuffer holds BGP-mapped shades, 0 (lightest) to 3 (darkest).
+#[derive(Debug, Clone)]
+pub struct Ppu {
+    lcdc: u8,
+    stat_enable: u8,
+    scy: u8,
+    scx: u8,
+    ly: u8,
+    lyc: u8,
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
+    wy: u8,
+    wx: u8,
+    mode: PpuMode,
+    line_cycles: u32,
+    stat_line: bool,
+    framebuffer: Vec<u8>,
+    previous_frame: Vec<u8>,
+    frame_delta: FrameDeltaStats,
+    renderer: PpuRenderer,
+    model: Model,
+    fifo: LineFifo,
+    mode3_cycles: u32,
+    // Line dot at which the `Scanline` renderer's mode 3 ends, object fetches included.
+    drawing_end: u32,
+    lcd_off_output: LcdOffOutput,
+    vblanks: u64,
+    palette: Palette,
+    pending_write: Option<PendingWrite>,
+    enabled_once: bool,
+    blank_frame: bool,
+    window: WindowState,
+    window_glitches: bool,
+    stat_write_bug: bool,
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Self {
+            lcdc: 0,
+            stat_enable: 0,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            lyc: 0,
+            bgp: 0,
+            obp0: 0,
+            obp1: 0,
+            wy: 0,
+            wx: 0,
+            mode: PpuMode::HBlank,
+            line_cycles: 0,
+            stat_line: false,
+            framebuffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            previous_frame: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frame_delta: FrameDeltaStats::default(),
+            renderer: PpuRenderer::Scanline,
+            model: Model::Dmg,
+            fifo: LineFifo::default(),
+            mode3_cycles: SCANLINE_DRAWING_CYCLES,
+            drawing_end: DRAWING_END_CYCLES,
+            lcd_off_output: LcdOffOutput::White,
+            vblanks: 0,
+            palette: Palette::default(),
+            pending_write: None,
+            enabled_once: false,
+            blank_frame: false,
+            window: WindowState::default(),
+            window_glitches: false,
+            stat_write_bug: false,
+        }
+    }
+}
+
+impl Ppu {
+    pub fn lcd_enabled(&self) -> bool {
+        self.lcdc & LCDC_ENABLE != 0
+    }
+
+    pub fn mode(&self) -> PpuMode {
+        self.mode
+    }
+
+    /// The CPU loses VRAM while the PPU is fetching pixels (mode 3).
+    pub fn vram_accessible(&self) -> bool {
+        self.mode != PpuMode::Drawing
+    }
+
+    /// The CPU loses OAM while the PPU scans or draws sprites (modes 2 and 3).
+    pub fn oam_accessible(&self) -> bool {
+        matches!(self.mode, PpuMode::HBlank | PpuMode::VBlank)
+    }
+
+    /// OAM row (8 bytes, two sprites) the PPU is scanning this M-cycle, if in mode 2.
+    pub(crate) fn oam_scan_row(&self) -> Option<usize> {
+        (self.lcd_enabled() && self.mode == PpuMode::OamScan)
+            .then_some((self.line_cycles / 4) as usize)
+    }
+
+    pub fn ly(&self) -> u8 {
+        self.ly
+    }
+
+    /// Dots since line 0 began: whole lines up to LY plus the dots into the current one. The
+    /// short line 0 after a re-enable starts part-way in, and with the LCD off this is 0.
+    pub fn frame_dots(&self) -> u32 {
+        u32::from(self.ly) * CYCLES_PER_SCANLINE + self.line_cycles
+    }
+
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.framebuffer
+    }
+
+    pub fn palette(&self) -> Palette {
+        self.palette
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// The framebuffer through the current palette, 4 bytes (RGBA) per pixel.
+    pub fn framebuffer_rgba(&self) -> Vec<u8> {
+        let mut rgba = vec![0; self.framebuffer.len() * 4];
+        self.write_framebuffer_rgba(&mut rgba)
+            .expect("buffer is sized from the framebuffer");
+        rgba
+    }
+
+    /// Fills `out` (exactly `SCREEN_WIDTH * SCREEN_HEIGHT * 4` bytes) with RGBA8888 pixels
+    /// without allocating.
+    pub fn write_framebuffer_rgba(&self, out: &mut [u8]) -> Result<(), FramebufferSizeError> {
+        check_export_size(self.framebuffer.len() * 4, out.len())?;
+        for (pixel, &shade) in out.chunks_exact_mut(4).zip(&self.framebuffer) {
+            pixel.copy_from_slice(&self.palette.rgba(shade));
+        }
+        Ok(())
+    }
+
+    /// Fills `out` (exactly `SCREEN_WIDTH * SCREEN_HEIGHT` entries) with RGB565 pixels
+    /// without allocating.
+    pub fn write_framebuffer_rgb565(&self, out: &mut [u16]) -> Result<(), FramebufferSizeError> {
+        check_export_size(self.framebuffer.len(), out.len())?;
+        for (pixel, &shade) in out.iter_mut().zip(&self.framebuffer) {
+            *pixel = self.palette.rgb565(shade);
+        }
+        Ok(())
+    }
+
+    /// Number of times the PPU has entered VBlank (line 144).
+    pub fn vblank_count(&self) -> u64 {
+        self.vblanks
+    }
+
+    pub fn frame_delta_stats(&self) -> FrameDeltaStats {
+        self.frame_delta
+    }
+
+    pub fn renderer(&self) -> PpuRenderer {
+        self.renderer
+    }
+
+    /// Takes effect from the next line's mode 3.
+    pub fn set_renderer(&mut self, renderer: PpuRenderer) {
+        self.renderer = renderer;
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// The model decides what LCDC bit 0 means: on DMG clearing it blanks the background
+    /// and window, on CGB it only takes away their priority over objects.
+    pub fn set_model(&mut self, model: Model) {
+        self.model = model;
+    }
+
+    fn bg_visible(&self) -> bool {
+        self.lcdc & LCDC_BG_ENABLE != 0 || self.model == Model::Cgb
+    }
+
+    fn bg_priority(&self) -> bool {
+        self.lcdc & LCDC_BG_ENABLE != 0
+    }
+
+    pub fn lcd_off_output(&self) -> LcdOffOutput {
+        self.lcd_off_output
+    }
+
+    /// Applies immediately if the LCD is already off.
+    pub fn set_lcd_off_output(&mut self, output: LcdOffOutput) {
+        self.lcd_off_output = output;
+        if !self.lcd_enabled() {
+            self.apply_lcd_off_output();
+        }
+    }
+
+    /// Emulates the DMG's window edge bugs: a window started at the last pixel of a line
+    /// (WX=166) fills the whole of the next one, and one at WX=0 is shifted left by SCX's
+    /// fine scroll. Off by default, like the other `HardwareQuirks`.
+    pub fn set_window_glitches(&mut self, enabled: bool) {
+        self.window_glitches = enabled;
+    }
+
+    /// Emulates the DMG STAT write bug; see `HardwareQuirks::stat_write_bug`.
+    pub fn set_stat_write_bug(&mut self, enabled: bool) {
+        self.stat_write_bug = enabled;
+    }
+
+    /// Length of the most recently completed mode 3, in cycles.
+    pub fn mode3_cycles(&self) -> u32 {
+        self.mode3_cycles
+    }
+
+    pub fn read_register(&self, address: u16) -> u8 {
+        match address {
+            LCDC_ADDR => self.lcdc,
+            STAT_ADDR => {
+                let coincidence = if self.ly == self.lyc {
+                    STAT_LYC_EQUAL
+                } else {
+                    0
+                };
+                let mode = if self.lcd_enabled() {
+                    self.mode.bits()
+                } else {
+                    0
+                };
+                0x80 | self.stat_enable | coincidence | mode
+            }
+            SCY_ADDR => self.scy,
+            SCX_ADDR => self.scx,
+            LY_ADDR => self.ly,
+            LYC_ADDR => self.lyc,
+            BGP_ADDR => self.bgp,
+            OBP0_ADDR => self.obp0,
+            OBP1_ADDR => self.obp1,
+            WY_ADDR => self.wy,
+            WX_ADDR => self.wx,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8, interrupt_flags: &mut u8) {
+        match address {
+            LCDC_ADDR => {
+                let was_enabled = self.lcd_enabled();
+                self.lcdc = value;
+                if was_enabled && !self.lcd_enabled() {
+                    self.ly = 0;
+                    self.line_cycles = 0;
+                    self.mode = PpuMode::HBlank;
+                    self.fifo.active = false;
+                    self.window = WindowState::default();
+                    self.apply_lcd_off_output();
+                } else if !was_enabled && self.lcd_enabled() {
+                    self.mode = PpuMode::OamScan;
+                    // Without a boot ROM the first enable stands in for the one the boot ROM
+                    // would have done. Later ones restart on a short line 0, and the panel
+                    // shows nothing until the following frame.
+                    if self.enabled_once {
+                        self.line_cycles = LCD_ENABLE_SKIPPED_DOTS;
+                        self.blank_frame = true;
+                    }
+                    self.enabled_once = true;
+                }
+            }
+            STAT_ADDR => {
+                if self.stat_write_bug {
+                    // For one cycle the write enables the mode 0/1 and LYC sources, so a
+                    // game writing STAT during blanking or on a matching line gets an
+                    // interrupt.
+                    self.stat_enable =
+                        STAT_HBLANK_INTERRUPT | STAT_VBLANK_INTERRUPT | STAT_LYC_INTERRUPT;
+                    self.update_stat_line(interrupt_flags);
+                }
+                self.stat_enable = value & STAT_WRITABLE;
+            }
+            SCY_ADDR => self.scy = value,
+            SCX_ADDR => self.scx = value,
+            LYC_ADDR => self.lyc = value,
+            BGP_ADDR => {
+                if self.fifo.active {
+                    self.fifo.bgp_blend = Some(self.bgp);
+                }
+                self.bgp = value;
+            }
+            OBP0_ADDR => self.obp0 = value,
+            OBP1_ADDR => self.obp1 = value,
+            WY_ADDR => self.wy = value,
+            WX_ADDR => self.wx = value,
+            _ => {}
+        }
+        self.update_stat_line(interrupt_flags);
+    }
+
+    /// A register write landing `write_dot` dots into the CPU instruction being executed,
+    /// which the PPU has not caught up with yet. Under `PixelFifo` the rendering registers
+    /// are held back until the PPU reaches that dot, so mid-line changes hit the fetcher
+    /// step or pixel they do on hardware.
+    pub fn write_register_at(
+        &mut self,
+        address: u16,
+        value: u8,
+        write_dot: u32,
+        interrupt_flags: &mut u8,
+    ) {
+        let latched = matches!(
+            address,
+            LCDC_ADDR | SCY_ADDR | SCX_ADDR | BGP_ADDR | OBP0_ADDR | OBP1_ADDR
+        );
+        if write_dot == 0
+            || !latched
+            || self.renderer != PpuRenderer::PixelFifo
+            || !self.lcd_enabled()
+        {
+            self.write_register(address, value, interrupt_flags);
+            return;
+        }
+        if let Some(earlier) = self.pending_write.take() {
+            self.write_register(earlier.address, earlier.value, interrupt_flags);
+        }
+        self.pending_write = Some(PendingWrite {
+            dots: write_dot,
+            address,
+            value,
+        });
+    }
+
+    pub fn tick(&mut self, cycles: u32, memory: VideoMemory<'_>, interrupt_flags: &mut u8) {
+        let mut remaining = cycles;
+        if let Some(write) = self.pending_write.take() {
+            let lead = write.dots.min(remaining);
+            self.run_dots(lead, memory, interrupt_flags);
+            remaining -= lead;
+            self.write_register(write.address, write.value, interrupt_flags);
+        }
+        self.run_dots(remaining, memory, interrupt_flags);
+    }
+
+    fn run_dots(&mut self, cycles: u32, memory: VideoMemory<'_>, interrupt_flags: &mut u8) {
+        if !self.lcd_enabled() {
+            return;
+        }
+        let mut remaining = cycles;
+        while remaining > 0 {
+            if self.mode == PpuMode::Drawing && self.fifo.active {
+                remaining -= 1;
+                self.line_cycles += 1;
+                if self.fifo_dot(memory) {
+                    self.fifo.active = false;
+                    self.finish_window_line(self.fifo.in_window);
+                    self.mode3_cycles = self.line_cycles - OAM_SCAN_CYCLES;
+                    self.mode = PpuMode::HBlank;
+                    self.update_stat_line(interrupt_flags);
+                }
+                continue;
+            }
+            let boundary = match self.mode {
+                PpuMode::OamScan => OAM_SCAN_CYCLES,
+                PpuMode::Drawing => self.drawing_end,
+                PpuMode::HBlank | PpuMode::VBlank => CYCLES_PER_SCANLINE,
+            };
+            let step = remaining.min(boundary - self.line_cycles);
+            self.line_cycles += step;
+            remaining -= step;
+            if self.line_cycles == boundary {
+                self.advance_mode(memory, interrupt_flags);
+            }
+        }
+    }
+
+    fn advance_mode(&mut self, memory: VideoMemory<'_>, interrupt_flags: &mut u8) {
+        match self.mode {
+            PpuMode::OamScan => {
+                self.mode = PpuMode::Drawing;
+                self.start_window_line();
+                if self.renderer == PpuRenderer::PixelFifo {
+                    self.start_fifo_line(memory);
+                } else {
+                    let window_dots = if self.window.line_start.is_some() {
+                        WINDOW_START_DOTS
+                    } else {
+                        0
+                    };
+                    self.drawing_end =
+                        DRAWING_END_CYCLES + self.sprite_fetch_penalty(memory) + window_dots;
+                }
+            }
+            PpuMode::Drawing => {
+                self.render_line(memory);
+                self.finish_window_line(self.window.line_start.is_some());
+                self.mode3_cycles = self.drawing_end - OAM_SCAN_CYCLES;
+                self.mode = PpuMode::HBlank;
+            }
+            PpuMode::HBlank => {
+                self.line_cycles = 0;
+                self.ly += 1;
+                if usize::from(self.ly) == SCREEN_HEIGHT {
+                    self.mode = PpuMode::VBlank;
+                    self.blank_frame = false;
+                    self.vblanks += 1;
+                    self.compare_with_previous_frame();
+                    *interrupt_flags |= INTERRUPT_VBLANK;
+                } else {
+                    self.mode = PpuMode::OamScan;
+                }
+            }
+            PpuMode::VBlank => {
+                self.line_cycles = 0;
+                self.ly += 1;
+                if u32::from(self.ly) == SCANLINES_PER_FRAME {
+                    self.ly = 0;
+                    self.mode = PpuMode::OamScan;
+                    self.window = WindowState::default();
+                }
+            }
+        }
+        self.update_stat_line(interrupt_flags);
+    }
+
+    // The WY match latches for the rest of the frame, so the window can be switched off
+    // and back on with LCDC bit 5 and picks up at the window line it reached.
+    fn start_window_line(&mut self) {
+        if self.ly == self.wy {
+            self.window.wy_matched = true;
+        }
+        let wrapped = std::mem::take(&mut self.window.wrap_next_line);
+        self.window.line_start =
+            if self.lcdc & LCDC_WINDOW_ENABLE != 0 && self.window.wy_matched && self.wx <= WX_MAX {
+                Some(if wrapped {
+                    0
+                } else if self.window_glitches && self.wx == 0 {
+                    // At WX=0 the window starts during the fine scroll discard, which
+                    // then eats window pixels too, so it jitters with SCX.
+                    -WX_OFFSET - i16::from(self.scx & 0x07)
+                } else {
+                    i16::from(self.wx) - WX_OFFSET
+                })
+            } else {
+                None
+            };
+        if self.window_glitches && !wrapped && self.window.line_start.is_some() {
+            self.window.wrap_next_line = self.wx == WX_MAX;
+        }
+    }
+
+    // The window line only advances on lines that actually drew the window.
+    fn finish_window_line(&mut self, drawn: bool) {
+        if drawn {
+            self.window.line = self.window.line.wrapping_add(1);
+        }
+    }
+
+    fn compare_with_previous_frame(&mut self) {
+        let changed = self
+            .framebuffer
+            .iter()
+            .zip(&self.previous_frame)
+            .filter(|(current, previous)| current != previous)
+            .count();
+        self.previous_frame.copy_from_slice(&self.framebuffer);
+        self.frame_delta.record(changed);
+    }
+
+    fn apply_lcd_off_output(&mut self) {
+        match self.lcd_off_output {
+            LcdOffOutput::White => self.framebuffer.fill(0),
+            LcdOffOutput::LastFrame => {}
+            LcdOffOutput::Blank(value) => self.framebuffer.fill(value),
+        }
+    }
+
+    // STAT requests an interrupt on the rising edge of the OR of its enabled sources.
+    fn update_stat_line(&mut self, interrupt_flags: &mut u8) {
+        let line = self.lcd_enabled()
+            && ((self.stat_enable & STAT_HBLANK_INTERRUPT != 0 && self.mode == PpuMode::HBlank)
+                || (self.stat_enable & STAT_VBLANK_INTERRUPT != 0 && self.mode == PpuMode::VBlank)
+                || (self.stat_enable & STAT_OAM_INTERRUPT != 0 && self.mode == PpuMode::OamScan)
+                || (self.stat_enable & STAT_LYC_INTERRUPT != 0 && self.ly == self.lyc));
+        if line && !self.stat_line {
+            *interrupt_flags |= INTERRUPT_LCD;
+        }
+        self.stat_line = line;
+    }
+
+    fn render_line(&mut self, memory: VideoMemory<'_>) {
+        if self.blank_frame {
+            return;
+        }
+        let bg_colors = self.background_line(memory);
+        let row_start = usize::from(self.ly) * SCREEN_WIDTH;
+        let row = &mut self.framebuffer[row_start..row_start + SCREEN_WIDTH];
+        for (pixel, color) in row.iter_mut().zip(bg_colors) {
+            *pixel = (self.bgp >> (color * 2)) & 0x03;
+        }
+        if self.lcdc & LCDC_OBJ_ENABLE != 0 {
+            self.render_sprites(memory, &bg_colors);
+        }
+    }
+
+    /// Raw background color numbers (before BGP) for the current line.
+    fn background_line(&self, memory: VideoMemory<'_>) -> [u8; SCREEN_WIDTH] {
+        let mut colors = [0u8; SCREEN_WIDTH];
+        if !self.bg_visible() {
+            return colors;
+        }
+
+        let map_base = self.bg_map_base();
+        let y = self.ly.wrapping_add(self.scy);
+        for (x, color) in colors.iter_mut().enumerate() {
+            let x = (x as u8).wrapping_add(self.scx);
+            let map_address = map_base + u16::from(y / 8) * 32 + u16::from(x / 8);
+            let tile = memory.vram(map_address);
+            let row_address = tile_data_address(self.lcdc, tile) + u16::from(y % 8) * 2;
+            *color = tile_pixel(memory, row_address, x % 8);
+        }
+        if let Some(start) = self.window.line_start {
+            // With WX below 7 the window's first 7 - WX pixels are off the left edge.
+            let y = self.window.line;
+            for (x, color) in colors.iter_mut().enumerate().skip(start.max(0) as usize) {
+                let x = (x as i16 - start) as u8;
+                let map_address = self.window_map_base() + u16::from(y / 8) * 32 + u16::from(x / 8);
+                let tile = memory.vram(map_address);
+                let row_address = tile_data_address(self.lcdc, tile) + u16::from(y % 8) * 2;
+                *color = tile_pixel(memory, row_address, x % 8);
+            }
+        }
+        colors
+    }
+
+    /// Up to ten objects overlapping this line, in OAM order.
+    fn line_sprites(&self, memory: VideoMemory<'_>) -> Vec<Sprite> {
+        let height = self.sprite_height();
+        let line = i16::from(self.ly);
+        memory.oam[..OAM_ENTRIES * 4]
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(index, entry)| Sprite {
+                index,
+                y: i16::from(entry[0]) - 16,
+                x: i16::from(entry[1]) - 8,
+                tile: entry[2],
+                flags: entry[3],
+            })
+            .filter(|sprite| (sprite.y..sprite.y + height).contains(&line))
+            .take(MAX_SPRITES_PER_LINE)
+            .collect()
+    }
+
+    /// Dots the line's object fetches add to mode 3 (Pan Docs "Mode 3 length"): each
+    /// visible object takes 6, and the first one over each background tile also waits up
+    /// to 5 more for the fetcher to finish that tile. An object at OAM X 0 always takes 11.
+    fn sprite_fetch_penalty(&self, memory: VideoMemory<'_>) -> u32 {
+        if self.lcdc & LCDC_OBJ_ENABLE == 0 {
+            return 0;
+        }
+        let mut sprites = self.line_sprites(memory);
+        sprites.sort_by_key(|sprite| (sprite.x, sprite.index));
+
+        let fetch = u32::from(SPRITE_FETCH_DOTS);
+        let mut considered_tile = None;
+        let mut penalty = 0;
+        for sprite in sprites
+            .iter()
+            .filter(|sprite| sprite.x < SCREEN_WIDTH as i16)
+        {
+            let oam_x = sprite.x + 8;
+            if oam_x == 0 {
+                penalty += fetch + SPRITE_ALIGNMENT_DOTS;
+                continue;
+            }
+            penalty += fetch;
+            let position = oam_x + i16::from(self.scx);
+            if considered_tile != Some(position / 8) {
+                considered_tile = Some(position / 8);
+                let offset = (position % 8) as u32;
+                penalty += SPRITE_ALIGNMENT_DOTS.saturating_sub(offset);
+            }
+        }
+        penalty
+    }
+
+    // On DMG the object with the smaller X wins, then the lower OAM index. An object that
+    // wins a pixel still hides lower-priority objects there even when BG covers it.
+    fn render_sprites(&mut self, memory: VideoMemory<'_>, bg_colors: &[u8; SCREEN_WIDTH]) {
+        let mut sprites = self.line_sprites(memory);
+        sprites.sort_by_key(|sprite| (sprite.x, sprite.index));
+
+        let row_start = usize::from(self.ly) * SCREEN_WIDTH;
+        let mut claimed = [false; SCREEN_WIDTH];
+        for sprite in sprites {
+            let row_address = self.sprite_row_address(&sprite);
+            let palette = if sprite.flags & OBJ_PALETTE1 != 0 {
+                self.obp1
+            } else {
+                self.obp0
+            };
+
+            for column in 0..8u8 {
+                let x = sprite.x + i16::from(column);
+                if !(0..SCREEN_WIDTH as i16).contains(&x) || claimed[x as usize] {
+                    continue;
+                }
+                let bit = if sprite.flags & OBJ_X_FLIP != 0 {
+                    7 - column
+                } else {
+                    column
+                };
+                let color = tile_pixel(memory, row_address, bit);
+                if color == 0 {
+                    continue;
+                }
+                let x = x as usize;
+                claimed[x] = true;
+                if sprite.flags & OBJ_BEHIND_BG != 0 && bg_colors[x] != 0 && self.bg_priority() {
+                    continue;
+                }
+                self.framebuffer[row_start + x] = (palette >> (color * 2)) & 0x03;
+            }
+        }
+    }
+
+    fn sprite_row_address(&self, sprite: &Sprite) -> u16 {
+        let height = self.sprite_height();
+        let mut row = i16::from(self.ly) - sprite.y;
+        if sprite.flags & OBJ_Y_FLIP != 0 {
+            row = height - 1 - row;
+        }
+        let tile = if height == 16 {
+            sprite.tile & 0xFE
+        } else {
+            sprite.tile
+        };
+        VRAM_START + u16::from(tile) * 16 + row as u16 * 2
+    }
+
+    fn bg_map_base(&self) -> u16 {
+        if self.lcdc & LCDC_BG_TILE_MAP != 0 {
+            0x9C00
+        } else {
+            0x9800
+        }
+    }
+
+    fn window_map_base(&self) -> u16 {
+        if self.lcdc & LCDC_WINDOW_TILE_MAP != 0 {
+            0x9C00
+        } else {
+            0x9800
+        }
+    }
+
+    fn sprite_height(&self) -> i16 {
+        if self.lcdc & LCDC_OBJ_SIZE != 0 {
+            16
+        } else {
+            8
+        }
+    }
+}
+
+impl Ppu {
+    fn start_fifo_line(&mut self, memory: VideoMemory<'_>) {
+        let mut sprites = if self.lcdc & LCDC_OBJ_ENABLE != 0 {
+            self.line_sprites(memory)
+        } else {
+            Vec::new()
+        };
+        sprites.sort_by_key(|sprite| (sprite.x, sprite.index));
+        self.fifo = LineFifo {
+            active: true,
+            startup: FIFO_STARTUP_DOTS,
+            discard: self.scx % 8,
+            sprites: sprites.into(),
+            ..LineFifo::default()
+        };
+    }
+
+    /// Advances mode 3 by one dot; returns true once the 160th pixel is out.
+    fn fifo_dot(&mut self, memory: VideoMemory<'_>) -> bool {
+        if self.fifo.startup > 0 {
+            self.fifo.startup -= 1;
+            return false;
+        }
+
+        if let Some((sprite, dots)) = self.fifo.sprite_fetch {
+            // The sprite fetch overlaps only the last step of the background tile fetch.
+            if self.fifo.fetch_step < TILE_FETCH_DOTS - 1 {
+                self.bg_fetcher_dot(memory);
+            } else if dots > 1 {
+                self.fifo.sprite_fetch = Some((sprite, dots - 1));
+            } else {
+                self.fifo.sprite_fetch = None;
+                self.merge_sprite(memory, &sprite);
+            }
+            return false;
+        }
+
+        if let Some(start) = self.window.line_start.filter(|_| !self.fifo.in_window) {
+            if self.fifo.discard == 0 && i16::from(self.fifo.x) >= start {
+                // The window's tiles replace whatever background was queued; with WX below
+                // 7 its first pixels are dropped like SCX fine scroll.
+                self.fifo.in_window = true;
+                self.fifo.bg.clear();
+                self.fifo.fetch_step = 0;
+                self.fifo.fetch_column = 0;
+                self.fifo.discard = (-start).max(0) as u8;
+                return false;
+            }
+        }
+
+        self.bg_fetcher_dot(memory);
+        if self.fifo.discard == 0 && !self.fifo.bg.is_empty() {
+            let x = i16::from(self.fifo.x);
+            if let Some(sprite) = self.fifo.sprites.front().filter(|sprite| sprite.x <= x) {
+                // This dot already counts toward the sprite fetch.
+                self.fifo.sprite_fetch = Some((*sprite, SPRITE_FETCH_DOTS - 1));
+                self.fifo.sprites.pop_front();
+                return false;
+            }
+        }
+        let Some(bg_color) = self.fifo.bg.pop_front() else {
+            return false;
+        };
+        if self.fifo.discard > 0 {
+            self.fifo.discard -= 1;
+            return false;
+        }
+        let obj = self.fifo.obj.pop_front();
+        let bg_color = if self.bg_visible() { bg_color } else { 0 };
+        let shade = match obj {
+            Some(obj)
+                if obj.color != 0
+                    && self.lcdc & LCDC_OBJ_ENABLE != 0
+                    && !(obj.behind_bg && bg_color != 0 && self.bg_priority()) =>
+            {
+                (obj.palette >> (obj.color * 2)) & 0x03
+            }
+            _ => {
+                // DMG: a BGP write shows the old and new palettes ORed for one pixel.
+                let bgp = self.bgp | self.fifo.bgp_blend.unwrap_or(0);
+                (bgp >> (bg_color * 2)) & 0x03
+            }
+        };
+        self.fifo.bgp_blend = None;
+        let x = usize::from(self.fifo.x);
+        if !self.blank_frame {
+            self.framebuffer[usize::from(self.ly) * SCREEN_WIDTH + x] = shade;
+        }
+        self.fifo.x += 1;
+        usize::from(self.fifo.x) == SCREEN_WIDTH
+    }
+
+    // Each fetch step samples LCDC, SCX and SCY when it completes, so a mid-line write
+    // changes the map, tile data or scroll from the next step on. The fine scroll was
+    // already latched into `discard` at the start of the line.
+    fn bg_fetcher_dot(&mut self, memory: VideoMemory<'_>) {
+        if self.fifo.fetch_step < TILE_FETCH_DOTS {
+            self.fifo.fetch_step += 1;
+            let (y, map_base, first_column) = if self.fifo.in_window {
+                (self.window.line, self.window_map_base(), 0)
+            } else {
+                (
+                    self.ly.wrapping_add(self.scy),
+                    self.bg_map_base(),
+                    self.scx / 8,
+                )
+            };
+            match self.fifo.fetch_step {
+                TILE_NUMBER_DOT => {
+                    let column = first_column.wrapping_add(self.fifo.fetch_column) & 0x1F;
+                    let map_address = map_base + u16::from(y / 8) * 32 + u16::from(column);
+                    self.fifo.tile = memory.vram(map_address);
+                }
+                TILE_DATA_LOW_DOT | TILE_FETCH_DOTS => {
+                    let row_address =
+                        tile_data_address(self.lcdc, self.fifo.tile) + u16::from(y % 8) * 2;
+                    if self.fifo.fetch_step == TILE_DATA_LOW_DOT {
+                        self.fifo.tile_data[0] = memory.vram(row_address);
+                    } else {
+                        self.fifo.tile_data[1] = memory.vram(row_address + 1);
+                    }
+                }
+                _ => {}
+            }
+        } else if self.fifo.bg.is_empty() {
+            let [lo, hi] = self.fifo.tile_data;
+            self.fifo
+                .bg
+                .extend((0..8).map(|column| pixel_color(lo, hi, column)));
+            self.fifo.fetch_column = self.fifo.fetch_column.wrapping_add(1);
+            self.fifo.fetch_step = 0;
+        }
+    }
+
+    // Only transparent slots are overwritten, so the sprite fetched first (smaller X, then
+    // lower OAM index) keeps priority.
+    fn merge_sprite(&mut self, memory: VideoMemory<'_>, sprite: &Sprite) {
+        let row_address = self.sprite_row_address(sprite);
+        let palette = if sprite.flags & OBJ_PALETTE1 != 0 {
+            self.obp1
+        } else {
+            self.obp0
+        };
+        let clipped = (i16::from(self.fifo.x) - sprite.x).max(0) as u8;
+        while self.fifo.obj.len() < 8 {
+            self.fifo.obj.push_back(ObjPixel::default());
+        }
+        for column in clipped..8 {
+            let bit = if sprite.flags & OBJ_X_FLIP != 0 {
+                7 - column
+            } else {
+                column
+            };
+            let slot = &mut self.fifo.obj[usize::from(column - clipped)];
+            if slot.color == 0 {
+                *slot = ObjPixel {
+                    color: tile_pixel(memory, row_address, bit),
+                    palette,
+                    behind_bg: sprite.flags & OBJ_BEHIND_BG != 0,
+                };
+            }
+        }
+    }
+}
+
+/// Tiles in the 0x8000-0x97FF tile data area, in address order.
+pub const TILE_COUNT: usize = 384;
+/// Tile maps are 32x32 tile numbers.
+pub const TILE_MAP_SIZE: usize = 32;
+
+/// An 8x8 tile decoded to color numbers (0-3, before BGP/OBP), row-major.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub address: u16,
+    pub pixels: [[u8; 8]; 8],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileMapEntry {
+    pub tile_number: u8,
+    /// Index into `decode_tiles` under the current LCDC tile data addressing.
+    pub tile_index: usize,
+}
+
+/// One background tile map (0x9800 or 0x9C00), row-major.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileMap {
+    pub base_address: u16,
+    pub entries: Vec<TileMapEntry>,
+}
+
+impl TileMap {
+    pub fn entry(&self, column: usize, row: usize) -> TileMapEntry {
+        self.entries[row * TILE_MAP_SIZE + column]
+    }
+}
+
+/// One background map cell and where it sits, as yielded by `Ppu::bg_map_iter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BgMapEntry {
+    pub column: u8,
+    pub row: u8,
+    /// The map byte's address.
+    pub address: u16,
+    pub tile_number: u8,
+    /// Index into `decode_tiles` under the current LCDC tile data addressing.
+    pub tile_index: usize,
+}
+
+/// A decoded OAM entry; positions are screen coordinates, so hidden objects go negative.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OamEntry {
+    pub index: usize,
+    pub y: i16,
+    pub x: i16,
+    pub tile: u8,
+    /// The raw attribute byte the flags below are decoded from.
+    pub flags: u8,
+    pub obp1: bool,
+    pub x_flip: bool,
+    pub y_flip: bool,
+    pub behind_background: bool,
+}
+
+// Viewer data for debug frontends.
+impl Ppu {
+    pub fn decode_tiles(&self, memory: VideoMemory<'_>) -> Vec<Tile> {
+        (0..TILE_COUNT)
+            .map(|index| {
+                let address = VRAM_START + index as u16 * 16;
+                let mut pixels = [[0; 8]; 8];
+                for (row, pixel_row) in pixels.iter_mut().enumerate() {
+                    let row_address = address + row as u16 * 2;
+                    for (column, pixel) in pixel_row.iter_mut().enumerate() {
+                        *pixel = tile_pixel(memory, row_address, column as u8);
+                    }
+                }
+                Tile { address, pixels }
+            })
+            .collect()
+    }
+
+    /// Tile map 0 (0x9800) or 1 (0x9C00); `None` for any other index.
+    pub fn background_map(&self, memory: VideoMemory<'_>, index: usize) -> Option<TileMap> {
+        let entries = self
+            .bg_map_iter(memory, index)?
+            .map(|entry| TileMapEntry {
+                tile_number: entry.tile_number,
+                tile_index: entry.tile_index,
+            })
+            .collect();
+        Some(TileMap {
+            base_address: background_map_base(index)?,
+            entries,
+        })
+    }
+
+    /// The cells of tile map 0 or 1, row-major, without collecting them; `None` for any
+    /// other index.
+    pub fn bg_map_iter<'a>(
+        &self,
+        memory: VideoMemory<'a>,
+        index: usize,
+    ) -> Option<impl Iterator<Item = BgMapEntry> + 'a> {
+        let base_address = background_map_base(index)?;
+        let lcdc = self.lcdc;
+        Some((0..TILE_MAP_SIZE * TILE_MAP_SIZE).map(move |offset| {
+            let address = base_address + offset as u16;
+            let tile_number = memory.vram(address);
+            BgMapEntry {
+                column: (offset % TILE_MAP_SIZE) as u8,
+                row: (offset / TILE_MAP_SIZE) as u8,
+                address,
+                tile_number,
+                tile_index: usize::from((tile_data_address(lcdc, tile_number) - VRAM_START) / 16),
+            }
+        }))
+    }
+
+    pub fn oam_entries(&self, memory: VideoMemory<'_>) -> Vec<OamEntry> {
+        self.oam_iter(memory).collect()
+    }
+
+    /// The 40 OAM entries in index order, without collecting them.
+    pub fn oam_iter<'a>(&self, memory: VideoMemory<'a>) -> impl Iterator<Item = OamEntry> + 'a {
+        memory.oam[..OAM_ENTRIES * 4]
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(index, entry)| OamEntry {
+                index,
+                y: i16::from(entry[0]) - 16,
+                x: i16::from(entry[1]) - 8,
+                tile: entry[2],
+                flags: entry[3],
+                obp1: entry[3] & OBJ_PALETTE1 != 0,
+                x_flip: entry[3] & OBJ_X_FLIP != 0,
+                y_flip: entry[3] & OBJ_Y_FLIP != 0,
+                behind_background: entry[3] & OBJ_BEHIND_BG != 0,
+            })
+    }
+}
+
+fn background_map_base(index: usize) -> Option<u16> {
+    match index {
+        0 => Some(0x9800),
+        1 => Some(0x9C00),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ObjPixel {
+    color: u8,
+    palette: u8,
+    behind_bg: bool,
+}
+
+/// Mode 3 state for `PpuRenderer::PixelFifo`.
+#[derive(Debug, Clone, Default)]
+struct LineFifo {
+    active: bool,
+    startup: u32,
+    discard: u8,
+    x: u8,
+    fetch_step: u8,
+    fetch_column: u8,
+    tile: u8,
+    tile_data: [u8; 2],
+    bgp_blend: Option<u8>,
+    in_window: bool,
+    bg: VecDeque<u8>,
+    obj: VecDeque<ObjPixel>,
+    sprites: VecDeque<Sprite>,
+    sprite_fetch: Option<(Sprite, u8)>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct WindowState {
+    /// WY has matched LY at least once this frame.
+    wy_matched: bool,
+    /// Internal window line counter, the row of the window the next window line draws.
+    line: u8,
+    /// Screen X the window starts at on the current line, negative when WX < 7. Pixels
+    /// left of the screen are dropped.
+    line_start: Option<i16>,
+    wrap_next_line: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingWrite {
+    dots: u32,
+    address: u16,
+    value: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sprite {
+    index: usize,
+    y: i16,
+    x: i16,
+    tile: u8,
+    flags: u8,
+}
+
+fn check_export_size(expected: usize, actual: usize) -> Result<(), FramebufferSizeError> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(FramebufferSizeError { expected, actual })
+    }
+}
+
+/// Color number of pixel `column` (0 = leftmost) in the tile row at `row_address`.
+fn tile_pixel(memory: VideoMemory<'_>, row_address: u16, column: u8) -> u8 {
+    pixel_color(
+        memory.vram(row_address),
+        memory.vram(row_address + 1),
+        column,
+    )
+}
+
+fn pixel_color(lo: u8, hi: u8, column: u8) -> u8 {
+    let bit = 7 - column;
+    (((hi >> bit) & 1) << 1) | ((lo >> bit) & 1)
+}
+
+fn tile_data_address(lcdc: u8, tile: u8) -> u16 {
+    if lcdc & LCDC_TILE_DATA != 0 {
+        VRAM_START + u16::from(tile) * 16
+    } else {
+        (0x9000i32 + i32::from(tile as i8) * 16) as u16
+    }
+}
+
+const SAVED_MODES: [PpuMode; 4] = [
+    PpuMode::HBlank,
+    PpuMode::VBlank,
+    PpuMode::OamScan,
+    PpuMode::Drawing,
+];
+const OAM_SPRITES: usize = 40;
+
+fn save_sprite(out: &mut StateWriter, sprite: Sprite) {
+    out.u8(sprite.index as u8);
+    out.i16(sprite.y);
+    out.i16(sprite.x);
+    out.u8(sprite.tile);
+    out.u8(sprite.flags);
+}
+
+fn load_sprite(input: &mut StateReader<'_>) -> Result<Sprite, StateError> {
+    let sprite = Sprite {
+        index: usize::from(input.u8()?),
+        y: input.i16()?,
+        x: input.i16()?,
+        tile: input.u8()?,
+        flags: input.u8()?,
+    };
+    if sprite.index >= OAM_SPRITES {
+        return Err(StateError::Invalid("sprite index"));
+    }
+    Ok(sprite)
+}
+
+impl Stateful for LineFifo {
+    fn save_state(&self, out: &mut StateWriter) {
+        out.bool(self.active);
+        out.u32(self.startup);
+        out.u8(self.discard);
+        out.u8(self.x);
+        out.u8(self.fetch_step);
+        out.u8(self.fetch_column);
+        out.u8(self.tile);
+        out.bytes(&self.tile_data);
+        out.option(self.bgp_blend, StateWriter::u8);
+        out.bool(self.in_window);
+        out.byte_vec(&self.bg.iter().copied().collect::<Vec<_>>());
+        out.u32(self.obj.len() as u32);
+        for pixel in &self.obj {
+            out.u8(pixel.color);
+            out.u8(pixel.palette);
+            out.bool(pixel.behind_bg);
+        }
+        out.u32(self.sprites.len() as u32);
+        for sprite in &self.sprites {
+            save_sprite(out, *sprite);
+        }
+        out.option(self.sprite_fetch, |out, (sprite, step)| {
+            save_sprite(out, sprite);
+            out.u8(step);
+        });
+    }
+
+    fn load_state(&mut self, input: &mut StateReader<'_>) -> Result<(), StateError> {
+        self.active = input.bool()?;
+        self.startup = input.u32()?;
+        self.discard = input.u8()?;
+        self.x = input.u8()?;
+        self.fetch_step = input.u8()?;
+        self.fetch_column = input.u8()?;
+        self.tile = input.u8()?;
+        input.bytes_into(&mut self.tile_data)?;
+        self.bgp_blend = input.option(StateReader::u8)?;
+        self.in_window = input.bool()?;
+        self.bg = input.byte_vec()?.into();
+        self.obj.clear();
+        for _ in 0..input.u32()? {
+            self.obj.push_back(ObjPixel {
+                color: input.u8()?,
+                palette: input.u8()?,
+                behind_bg: input.bool()?,
+            });
+        }
+        self.sprites.clear();
+        for _ in 0..input.u32()? {
+            self.sprites.push_back(load_sprite(input)?);
+        }
+        self.sprite_fetch = input.option(|input| Ok((load_sprite(input)?, input.u8()?)))?;
+        Ok(())
+    }
+}
+
+/// The renderer choice, LCD-off policy, output palette and window glitch setting are host
+/// configuration and stay as the loading instance has them.
+impl Stateful for Ppu {
+    fn save_state(&self, out: &mut StateWriter) {
+        for register in [
+            self.lcdc,
+            self.stat_enable,
+            self.scy,
+            self.scx,
+            self.ly,
+            self.lyc,
+            self.bgp,
+            self.obp0,
+            self.obp1,
+            self.wy,
+            self.wx,
+            self.mode.bits(),
+        ] {
+            out.u8(register);
+        }
+        out.u32(self.line_cycles);
+        out.bool(self.stat_line);
+        out.bytes(&self.framebuffer);
+        out.bytes(&self.previous_frame);
+        out.u64(self.frame_delta.frames);
+        out.usize(self.frame_delta.changed_pixels);
+        out.u64(self.frame_delta.active_frames);
+        out.u64(self.frame_delta.static_frames);
+        self.fifo.save_state(out);
+        out.u32(self.mode3_cycles);
+        out.u32(self.drawing_end);
+        out.u64(self.vblanks);
+        out.option(self.pending_write, |out, write| {
+            out.u32(write.dots);
+            out.u16(write.address);
+            out.u8(write.value);
+        });
+        out.bool(self.enabled_once);
+        out.bool(self.blank_frame);
+        out.bool(self.window.wy_matched);
+        out.u8(self.window.line);
+        out.option(self.window.line_start, StateWriter::i16);
+        out.bool(self.window.wrap_next_line);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader<'_>) -> Result<(), StateError> {
+        for register in [
+            &mut self.lcdc,
+            &mut self.stat_enable,
+            &mut self.scy,
+            &mut self.scx,
+            &mut self.ly,
+            &mut self.lyc,
+            &mut self.bgp,
+            &mut self.obp0,
+            &mut self.obp1,
+            &mut self.wy,
+            &mut self.wx,
+        ] {
+            *register = input.u8()?;
+        }
+        self.mode = *SAVED_MODES
+            .get(usize::from(input.u8()?))
+            .ok_or(StateError::Invalid("PPU mode"))?;
+        self.line_cycles = input.u32()?;
+        self.stat_line = input.bool()?;
+        input.bytes_into(&mut self.framebuffer)?;
+        input.bytes_into(&mut self.previous_frame)?;
+        self.frame_delta = FrameDeltaStats {
+            frames: input.u64()?,
+            changed_pixels: input.usize()?,
+            active_frames: input.u64()?,
+            static_frames: input.u64()?,
+        };
+        self.fifo.load_state(input)?;
+        self.mode3_cycles = input.u32()?;
+        self.drawing_end = input.u32()?;
+        self.vblanks = input.u64()?;
+        self.pending_write = input.option(|input| {
+            Ok(PendingWrite {
+                dots: input.u32()?,
+                address: input.u16()?,
+                value: input.u8()?,
+            })
+        })?;
+        self.enabled_once = input.bool()?;
+        self.blank_frame = input.bool()?;
+        self.window = WindowState {
+            wy_matched: input.bool()?,
+            line: input.u8()?,
+            line_start: input.option(StateReader::i16)?,
+            wrap_next_line: input.bool()?,
+        };
+        if u32::from(self.ly) >= SCANLINES_PER_FRAME {
+            return Err(StateError::Invalid("LY"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OAM_START: usize = 0xFE00;
+
+    fn video(memory: &[u8]) -> VideoMemory<'_> {
+        VideoMemory {
+            vram: &memory[0x8000..0xA000],
+            oam: &memory[OAM_START..OAM_START + 0xA0],
+        }
+    }
+
+    fn memory_with_tile(tile_index: u8, rows: [[u8; 2]; 8]) -> Vec<u8> {
+        let mut memory = vec![0u8; 0x10000];
+        let base = usize::from(VRAM_START) + usize::from(tile_index) * 16;
+        for (row, bytes) in rows.iter().enumerate() {
+            memory[base + row * 2..base + row * 2 + 2].copy_from_slice(bytes);
+        }
+        memory
+    }
+
+    fn enabled_ppu(lcdc: u8) -> (Ppu, u8) {
+        let mut ppu = Ppu::default();
+        let mut flags = 0;
+        ppu.write_register(BGP_ADDR, 0xE4, &mut flags);
+        ppu.write_register(LCDC_ADDR, lcdc, &mut flags);
+        (ppu, flags)
+    }
+
+    #[test]
+    fn ly_walks_the_frame_and_raises_vblank() {
+        let memory = vec![0u8; 0x10000];
+        let (mut ppu, mut flags) = enabled_ppu(0x91);
+        assert_eq!(ppu.mode(), PpuMode::OamScan);
+
+        ppu.tick(CYCLES_PER_SCANLINE * 144 - 1, video(&memory), &mut flags);
+        assert_eq!(ppu.ly(), 143);
+        assert_eq!(flags & INTERRUPT_VBLANK, 0);
+
+        ppu.tick(1, video(&memory), &mut flags);
+        assert_eq!(ppu.ly(), 144);
+        assert_eq!(ppu.mode(), PpuMode::VBlank);
+        assert_ne!(flags & INTERRUPT_VBLANK, 0);
+        assert_eq!(ppu.vblank_count(), 1);
+
+        ppu.tick(CYCLES_PER_SCANLINE * 10, video(&memory), &mut flags);
+        assert_eq!(ppu.ly(), 0);
+        assert_eq!(ppu.read_register(STAT_ADDR) & 0x03, 2);
+    }
+
+    #[test]
+    fn renders_scrolled_background_through_bgp() {
+        // Tile 1: left column is color 3, everything else color 0.
+        let mut memory = memory_with_tile(1, [[0x80, 0x80]; 8]);
+        memory[0x9800 + 1] = 1;
+        let (mut ppu, mut flags) = enabled_ppu(0x91);
+        ppu.write_register(SCX_ADDR, 4, &mut flags);
+        ppu.write_register(BGP_ADDR, 0x1B, &mut flags);
+
+        ppu.tick(CYCLES_PER_SCANLINE, video(&memory), &mut flags);
+        let line = &ppu.framebuffer()[..SCREEN_WIDTH];
+        assert_eq!(line[4], 0x00);
+        assert_eq!(line[3], 0x03);
+        assert_eq!(line[5], 0x03);
+    }
+
+    fn put_sprite(memory: &mut [u8], index: usize, y: u8, x: u8, tile: u8, flags: u8) {
+        memory[OAM_START + index * 4..OAM_START + index * 4 + 4]
+            .copy_from_slice(&[y, x, tile, flags]);
+    }
+
+    fn render_first_line(memory: &[u8], lcdc: u8) -> Vec<u8> {
+        let (mut ppu, mut flags) = enabled_ppu(lcdc);
+        ppu.write_register(OBP0_ADDR, 0xE4, &mut flags);
+        ppu.write_register(OBP1_ADDR, 0x1B, &mut flags);
+        ppu.tick(CYCLES_PER_SCANLINE, video(memory), &mut flags);
+        ppu.framebuffer()[..SCREEN_WIDTH].to_vec()
+    }
+
+    #[test]
+    fn sprites_flip_pick_palettes_and_respect_bg_priority() {
+        // Tile 1 row 0: leftmost pixel color 1; tile 2 is solid color 3.
+        let mut memory = memory_with_tile(1, [[0x80, 0x00]; 8]);
+        memory[0x8020..0x8030].fill(0xFF);
+        put_sprite(&mut memory, 0, 16, 8, 1, 0x00);
+        put_sprite(&mut memory, 1, 16, 16, 1, OBJ_X_FLIP | OBJ_PALETTE1);
+        put_sprite(&mut memory, 2, 16, 32, 2, OBJ_BEHIND_BG);
+
+        let line = render_first_line(&memory, 0x93);
+        assert_eq!(line[0], 1);
+        assert_eq!(line[1], 0);
+        assert_eq!(line[15], 2);
+        assert_eq!(line[24], 3);
+
+        // Behind-BG objects only show through BG color 0.
+        memory[0x9800 + 3] = 1;
+        let line = render_first_line(&memory, 0x93);
+        assert_eq!(line[24], 1);
+        assert_eq!(line[25], 3);
+    }
+
+    #[test]
+    fn lcdc_bit_0_blanks_the_background_on_dmg_and_drops_its_priority_on_cgb() {
+        // Tile 1: solid color 1 across the map; tile 2: solid color 3 as a behind-BG object.
+        let mut memory = memory_with_tile(1, [[0xFF, 0x00]; 8]);
+        memory[0x8020..0x8030].fill(0xFF);
+        memory[0x9800..0x9C00].fill(1);
+        put_sprite(&mut memory, 0, 16, 8, 2, OBJ_BEHIND_BG);
+        for renderer in [PpuRenderer::Scanline, PpuRenderer::PixelFifo] {
+            let line = |model: Model, lcdc: u8| {
+                let (mut ppu, mut flags) = enabled_ppu(lcdc);
+                ppu.set_renderer(renderer);
+                ppu.set_model(model);
+                ppu.write_register(OBP0_ADDR, 0xE4, &mut flags);
+                ppu.tick(CYCLES_PER_SCANLINE, video(&memory), &mut flags);
+                ppu.framebuffer()[..SCREEN_WIDTH].to_vec()
+            };
+            for model in [Model::Dmg, Model::Cgb] {
+                let shown = line(model, 0x93);
+                assert_eq!((shown[0], shown[20]), (1, 1), "{renderer:?} {model}");
+            }
+
+            let dmg = line(Model::Dmg, 0x92);
+            assert_eq!(
+                (dmg[0], dmg[20]),
+                (3, 0),
+                "{renderer:?}: BG blank, object shows"
+            );
+            let cgb = line(Model::Cgb, 0x92);
+            assert_eq!(
+                (cgb[0], cgb[20]),
+                (3, 1),
+                "{renderer:?}: objects always on top"
+            );
+        }
+    }
+
+    #[test]
+    fn smaller_x_wins_and_ten_sprites_per_line() {
+        let mut memory = memory_with_tile(1, [[0xFF, 0x00]; 8]);
+        memory[0x8020..0x8030].copy_from_slice(&[0xFF; 16]);
+        put_sprite(&mut memory, 0, 16, 12, 1, 0);
+        put_sprite(&mut memory, 1, 16, 8, 2, 0);
+        let line = render_first_line(&memory, 0x93);
+        assert_eq!(line[4], 3, "lower X wins even with a higher OAM index");
+
+        let mut memory = memory_with_tile(1, [[0xFF, 0x00]; 8]);
+        for index in 0..11 {
+            put_sprite(&mut memory, index, 16, 8 + index as u8 * 8, 1, 0);
+        }
+        let line = render_first_line(&memory, 0x93);
+        assert_eq!(line[9 * 8], 1);
+        assert_eq!(line[10 * 8], 0, "eleventh sprite is dropped");
+    }
+
+    #[test]
+    fn tall_sprites_use_tile_pairs_and_flip_across_both() {
+        let mut memory = vec![0u8; 0x10000];
+        memory[0x8030..0x8032].copy_from_slice(&[0xFF, 0xFF]); // tile 3, row 0
+        put_sprite(&mut memory, 0, 16, 8, 0x02, OBJ_Y_FLIP);
+        let (mut ppu, mut flags) = enabled_ppu(0x97);
+        ppu.write_register(OBP0_ADDR, 0xE4, &mut flags);
+        ppu.tick(CYCLES_PER_SCANLINE * 16, video(&memory), &mut flags);
+        assert_eq!(ppu.framebuffer()[7 * SCREEN_WIDTH], 3);
+        assert_eq!(ppu.framebuffer()[8 * SCREEN_WIDTH], 0);
+        assert_eq!(ppu.framebuffer()[0], 0);
+    }
+
+    fn run_line_with(renderer: PpuRenderer, memory: &[u8], scx: u8) -> (Vec<u8>, u32) {
+        let (mut ppu, mut flags) = enabled_ppu(0x93);
+        ppu.set_renderer(renderer);
+        ppu.write_register(OBP0_ADDR, 0xE4, &mut flags);
+        ppu.write_register(OBP1_ADDR, 0x1B, &mut flags);
+        ppu.write_register(SCX_ADDR, scx, &mut flags);
+        ppu.tick(CYCLES_PER_SCANLINE, video(memory), &mut flags);
+        (
+            ppu.framebuffer()[..SCREEN_WIDTH].to_vec(),
+            ppu.mode3_cycles(),
+        )
+    }
+
+    fn busy_scene() -> Vec<u8> {
+        let mut memory = memory_with_tile(1, [[0x5A, 0xC3]; 8]);
+        memory[0x8020..0x8030].copy_from_slice(&[0xF0, 0x3C].repeat(8));
+        for (index, tile) in memory[0x9800..0x9820].iter_mut().enumerate() {
+            *tile = (index % 3) as u8;
+        }
+        put_sprite(&mut memory, 0, 16, 4, 1, OBJ_X_FLIP);
+        put_sprite(&mut memory, 1, 16, 30, 2, OBJ_PALETTE1);
+        put_sprite(&mut memory, 2, 16, 34, 1, OBJ_BEHIND_BG);
+        put_sprite(&mut memory, 3, 16, 90, 2, 0);
+        memory
+    }
+
+    #[test]
+    fn pixel_fifo_matches_scanline_output() {
+        let memory = busy_scene();
+        for scx in [0, 3, 7, 13] {
+            let (scanline, _) = run_line_with(PpuRenderer::Scanline, &memory, scx);
+            let (fifo, _) = run_line_with(PpuRenderer::PixelFifo, &memory, scx);
+            assert_eq!(fifo, scanline, "scx={scx}");
+        }
+    }
+
+    #[test]
+    fn scanline_mode3_adds_sprite_fetch_penalties() {
+        let mode3_with = |sprites: &[u8], scx: u8, lcdc: u8| {
+            let mut memory = vec![0u8; 0x10000];
+            for (index, &x) in sprites.iter().enumerate() {
+                put_sprite(&mut memory, index, 16, x, 0, 0);
+            }
+            let (mut ppu, mut flags) = enabled_ppu(lcdc);
+            ppu.write_register(SCX_ADDR, scx, &mut flags);
+            ppu.tick(OAM_SCAN_CYCLES + 1, video(&memory), &mut flags);
+            assert_eq!(ppu.mode(), PpuMode::Drawing);
+            ppu.tick(
+                CYCLES_PER_SCANLINE - OAM_SCAN_CYCLES - 1,
+                video(&memory),
+                &mut flags,
+            );
+            ppu.mode3_cycles()
+        };
+
+        assert_eq!(mode3_with(&[], 0, 0x93), 172);
+        assert_eq!(mode3_with(&[8], 0, 0x93), 172 + 11);
+        assert_eq!(mode3_with(&[10], 0, 0x93), 172 + 9);
+        assert_eq!(mode3_with(&[13], 0, 0x93), 172 + 6);
+        assert_eq!(mode3_with(&[10], 3, 0x93), 172 + 6);
+        assert_eq!(mode3_with(&[0], 3, 0x93), 172 + 11);
+        // Only the first object over a tile waits for the fetcher.
+        assert_eq!(mode3_with(&[8, 9], 0, 0x93), 172 + 11 + 6);
+        assert_eq!(mode3_with(&[8, 16], 0, 0x93), 172 + 11 + 11);
+        // Objects past the right edge and disabled objects are never fetched.
+        assert_eq!(mode3_with(&[168], 0, 0x93), 172);
+        assert_eq!(mode3_with(&[8], 0, 0x91), 172);
+    }
+
+    #[test]
+    fn pixel_fifo_mode3_grows_with_fine_scroll_and_sprites() {
+        let empty = vec![0u8; 0x10000];
+        assert_eq!(run_line_with(PpuRenderer::Scanline, &empty, 5).1, 172);
+        assert_eq!(run_line_with(PpuRenderer::PixelFifo, &empty, 0).1, 172);
+        assert_eq!(run_line_with(PpuRenderer::PixelFifo, &empty, 5).1, 177);
+        assert_eq!(run_line_with(PpuRenderer::PixelFifo, &empty, 8).1, 172);
+
+        let mut one_sprite = vec![0u8; 0x10000];
+        put_sprite(&mut one_sprite, 0, 16, 8 + 8, 0, 0);
+        let (_, cycles) = run_line_with(PpuRenderer::PixelFifo, &one_sprite, 0);
+        assert!(
+            (172 + 6..=172 + 11).contains(&cycles),
+            "mode 3 took {cycles}"
+        );
+    }
+
+    // Window tile rows have color 3 at column `row` and 2 elsewhere; the background is 0.
+    fn window_frame(
+        renderer: PpuRenderer,
+        wx: u8,
+        glitches: bool,
+        lcdc_for_line: impl Fn(u8) -> u8,
+    ) -> Vec<u8> {
+        scrolled_window_frame(renderer, wx, 0, glitches, lcdc_for_line)
+    }
+
+    fn scrolled_window_frame(
+        renderer: PpuRenderer,
+        wx: u8,
+        scx: u8,
+        glitches: bool,
+        lcdc_for_line: impl Fn(u8) -> u8,
+    ) -> Vec<u8> {
+        let rows: [[u8; 2]; 8] = std::array::from_fn(|row| [0x80 >> row, 0xFF]);
+        let mut memory = memory_with_tile(1, rows);
+        memory[0x9C00..0xA000].fill(1);
+        let (mut ppu, mut flags) = enabled_ppu(lcdc_for_line(0));
+        ppu.set_renderer(renderer);
+        ppu.set_window_glitches(glitches);
+        ppu.write_register(WY_ADDR, 2, &mut flags);
+        ppu.write_register(WX_ADDR, wx, &mut flags);
+        ppu.write_register(SCX_ADDR, scx, &mut flags);
+        for ly in 0..SCREEN_HEIGHT as u8 {
+            ppu.write_register(LCDC_ADDR, lcdc_for_line(ly), &mut flags);
+            ppu.tick(CYCLES_PER_SCANLINE, video(&memory), &mut flags);
+        }
+        ppu.framebuffer().to_vec()
+    }
+
+    fn window_shade(column: usize, row: usize) -> u8 {
+        if column % 8 == row % 8 {
+            3
+        } else {
+            2
+        }
+    }
+
+    const WINDOW_ON: u8 = 0xF1;
+
+    #[test]
+    fn window_starts_at_wy_and_wx_minus_seven() {
+        for renderer in [PpuRenderer::Scanline, PpuRenderer::PixelFifo] {
+            let frame = window_frame(renderer, 47, false, |_| WINDOW_ON);
+            let pixel = |x: usize, y: usize| frame[y * SCREEN_WIDTH + x];
+            assert!(frame[..2 * SCREEN_WIDTH].iter().all(|&shade| shade == 0));
+            assert_eq!(pixel(39, 2), 0, "{renderer:?}");
+            for x in 40..SCREEN_WIDTH {
+                assert_eq!(pixel(x, 2), window_shade(x - 40, 0), "{renderer:?} x={x}");
+                assert_eq!(pixel(x, 9), window_shade(x - 40, 7), "{renderer:?} x={x}");
+            }
+        }
+    }
+
+    #[test]
+    fn window_left_of_wx_seven_is_clipped() {
+        for renderer in [PpuRenderer::Scanline, PpuRenderer::PixelFifo] {
+            let frame = window_frame(renderer, 3, false, |_| WINDOW_ON);
+            let line = &frame[3 * SCREEN_WIDTH..4 * SCREEN_WIDTH];
+            for (x, &shade) in line.iter().enumerate() {
+                assert_eq!(shade, window_shade(x + 4, 1), "{renderer:?} x={x}");
+            }
+        }
+    }
+
+    #[test]
+    fn window_resumes_at_its_own_line_after_being_disabled() {
+        for renderer in [PpuRenderer::Scanline, PpuRenderer::PixelFifo] {
+            let frame = window_frame(renderer, 7, false, |ly| {
+                if (4..10).contains(&ly) {
+                    WINDOW_ON & !LCDC_WINDOW_ENABLE
+                } else {
+                    WINDOW_ON
+                }
+            });
+            let line = |y: usize| &frame[y * SCREEN_WIDTH..(y + 1) * SCREEN_WIDTH];
+            assert!(line(4).iter().all(|&shade| shade == 0));
+            // Lines 2 and 3 drew window rows 0 and 1, so line 10 continues with row 2.
+            for (x, &shade) in line(10).iter().enumerate() {
+                assert_eq!(shade, window_shade(x, 2), "{renderer:?} x={x}");
+            }
+        }
+    }
+
+    #[test]
+    fn wx_166_fills_the_next_line_only_with_window_glitches() {
+        for renderer in [PpuRenderer::Scanline, PpuRenderer::PixelFifo] {
+            let frame = window_frame(renderer, 166, false, |_| WINDOW_ON);
+            let pixel = |x: usize, y: usize| frame[y * SCREEN_WIDTH + x];
+            assert_eq!(pixel(159, 2), window_shade(0, 0), "{renderer:?}");
+            assert_eq!(pixel(158, 2), 0);
+            assert_eq!(pixel(0, 3), 0);
+            assert_eq!(pixel(159, 3), window_shade(0, 1), "{renderer:?}");
+
+            let frame = window_frame(renderer, 166, true, |_| WINDOW_ON);
+            let line = &frame[3 * SCREEN_WIDTH..4 * SCREEN_WIDTH];
+            for (x, &shade) in line.iter().enumerate() {
+                assert_eq!(shade, window_shade(x, 1), "{renderer:?} x={x}");
+            }
+            assert_eq!(frame[4 * SCREEN_WIDTH], 0);
+            assert_eq!(frame[5 * SCREEN_WIDTH], window_shade(0, 3));
+        }
+    }
+
+    #[test]
+    fn wx_0_follows_fine_scroll_only_with_window_glitches() {
+        for renderer in [PpuRenderer::Scanline, PpuRenderer::PixelFifo] {
+            for scx in [0, 3, 7] {
+                let frame = scrolled_window_frame(renderer, 0, scx, false, |_| WINDOW_ON);
+                let line = &frame[3 * SCREEN_WIDTH..4 * SCREEN_WIDTH];
+                for (x, &shade) in line.iter().enumerate() {
+                    assert_eq!(shade, window_shade(x + 7, 1), "{renderer:?} x={x}");
+                }
+
+                let shift = usize::from(scx);
+                let frame = scrolled_window_frame(renderer, 0, scx, true, |_| WINDOW_ON);
+                let line = &frame[3 * SCREEN_WIDTH..4 * SCREEN_WIDTH];
+                for (x, &shade) in line.iter().enumerate() {
+                    assert_eq!(
+                        shade,
+                        window_shade(x + 7 + shift, 1),
+                        "{renderer:?} scx={scx} x={x}"
+                    );
+                }
+            }
+        }
+    }
+
+    fn fifo_line_with_write(memory: &[u8], address: u16, value: u8, write_dot: u32) -> Vec<u8> {
+        let (mut ppu, mut flags) = enabled_ppu(0x91);
+        ppu.set_renderer(PpuRenderer::PixelFifo);
+        ppu.tick(OAM_SCAN_CYCLES + 40, video(memory), &mut flags);
+        ppu.write_register_at(address, value, write_dot, &mut flags);
+        ppu.tick(
+            CYCLES_PER_SCANLINE - OAM_SCAN_CYCLES - 40,
+            video(memory),
+            &mut flags,
+        );
+        ppu.framebuffer()[..SCREEN_WIDTH].to_vec()
+    }
+
+    #[test]
+    fn mid_line_bgp_write_lands_on_its_dot_with_dmg_blend() {
+        let memory = memory_with_tile(0, [[0xFF, 0x00]; 8]);
+        let blend_at = |write_dot| {
+            let line = fifo_line_with_write(&memory, BGP_ADDR, 0x08, write_dot);
+            let blend = line
+                .iter()
+                .position(|&shade| shade == 3)
+                .expect("one pixel shows old | new");
+            assert!(line[..blend].iter().all(|&shade| shade == 1));
+            assert!(line[blend + 1..].iter().all(|&shade| shade == 2));
+            blend
+        };
+        assert_eq!(blend_at(8), blend_at(0) + 8);
+    }
+
+    #[test]
+    fn mid_line_tile_map_switch_applies_from_the_next_fetch() {
+        let mut memory = memory_with_tile(1, [[0xFF, 0xFF]; 8]);
+        memory[0x9C00..0x9C20].fill(1);
+        let line = fifo_line_with_write(&memory, LCDC_ADDR, 0x99, 0);
+        let switch = line
+            .iter()
+            .position(|&shade| shade == 3)
+            .expect("later tiles come from the 9C00 map");
+        assert!(switch > 0 && switch % 8 == 0, "switched at pixel {switch}");
+        assert!(line[switch..].iter().all(|&shade| shade == 3));
+    }
+
+    #[test]
+    fn pixel_fifo_keeps_line_timing() {
+        let memory = busy_scene();
+        let (mut ppu, mut flags) = enabled_ppu(0x93);
+        ppu.set_renderer(PpuRenderer::PixelFifo);
+        ppu.tick(CYCLES_PER_SCANLINE * 144, video(&memory), &mut flags);
+        assert_eq!(ppu.ly(), 144);
+        assert_ne!(flags & INTERRUPT_VBLANK, 0);
+    }
+
+    #[test]
+    fn frame_delta_stats_track_changed_pixels_and_static_runs() {
+        let mut memory = memory_with_tile(1, [[0xFF, 0x00]; 8]);
+        let (mut ppu, mut flags) = enabled_ppu(0x91);
+        let frame = CYCLES_PER_SCANLINE * SCANLINES_PER_FRAME;
+        ppu.tick(frame, video(&memory), &mut flags);
+        assert_eq!(
+            ppu.frame_delta_stats(),
+            FrameDeltaStats {
+                frames: 1,
+                changed_pixels: 0,
+                active_frames: 0,
+                static_frames: 1,
+            }
+        );
+
+        // One map entry switches to a tile of color 1: 8x8 pixels change once.
+        memory[0x9800] = 1;
+        ppu.tick(frame, video(&memory), &mut flags);
+        let stats = ppu.frame_delta_stats();
+        assert_eq!(stats.changed_pixels, 64);
+        assert_eq!(stats.active_frames, 1);
+        assert_eq!(stats.static_frames, 0);
+        assert!((stats.change_ratio() - 64.0 / 23040.0).abs() < 1e-12);
+
+        ppu.tick(frame * 2, video(&memory), &mut flags);
+        let stats = ppu.frame_delta_stats();
+        assert_eq!((stats.frames, stats.static_frames), (4, 2));
+        assert_eq!(stats.changed_pixels, 0);
+    }
+
+    #[test]
+    fn lcd_off_output_policy_controls_the_framebuffer() {
+        let memory = vec![0u8; 0x10000];
+        let mut switched_off = None;
+        for output in [
+            LcdOffOutput::White,
+            LcdOffOutput::LastFrame,
+            LcdOffOutput::Blank(0xFF),
+        ] {
+            let (mut ppu, mut flags) = enabled_ppu(0x91);
+            ppu.write_register(BGP_ADDR, 0xFF, &mut flags);
+            ppu.set_lcd_off_output(output);
+            ppu.tick(CYCLES_PER_SCANLINE, video(&memory), &mut flags);
+            assert_eq!(ppu.framebuffer()[0], 3);
+
+            ppu.write_register(LCDC_ADDR, 0x11, &mut flags);
+            switched_off.get_or_insert_with(|| ppu.clone());
+            let expected = match output {
+                LcdOffOutput::White => 0,
+                LcdOffOutput::LastFrame => 3,
+                LcdOffOutput::Blank(value) => value,
+            };
+            assert_eq!(ppu.framebuffer()[0], expected, "{output:?}");
+        }
+
+        let mut ppu = switched_off.expect("first policy ran");
+        ppu.set_lcd_off_output(LcdOffOutput::Blank(7));
+        assert_eq!(ppu.framebuffer()[SCREEN_WIDTH * SCREEN_HEIGHT - 1], 7);
+    }
+
+    #[test]
+    fn palette_maps_shades_to_rgba() {
+        let memory = vec![0u8; 0x10000];
+        let (mut ppu, mut flags) = enabled_ppu(0x91);
+        ppu.write_register(BGP_ADDR, 0xFF, &mut flags);
+        ppu.tick(CYCLES_PER_SCANLINE, video(&memory), &mut flags);
+        assert_eq!(&ppu.framebuffer_rgba()[..4], &[0x00, 0x00, 0x00, 0xFF]);
+
+        ppu.set_palette(Palette::DMG_GREEN);
+        let rgba = ppu.framebuffer_rgba();
+        assert_eq!(rgba.len(), SCREEN_WIDTH * SCREEN_HEIGHT * 4);
+        assert_eq!(&rgba[..4], &[0x0F, 0x38, 0x0F, 0xFF]);
+        assert_eq!(Palette::GRAYSCALE.rgba(1), [0xAA, 0xAA, 0xAA, 0xFF]);
+        assert_eq!(Palette::GRAYSCALE.rgba(7), [0; 4]);
+    }
+
+    #[test]
+    fn exports_into_caller_buffers() {
+        let memory = vec![0u8; 0x10000];
+        let (mut ppu, mut flags) = enabled_ppu(0x91);
+        ppu.write_register(BGP_ADDR, 0xFF, &mut flags);
+        ppu.tick(CYCLES_PER_SCANLINE, video(&memory), &mut flags);
+        ppu.set_palette(Palette::DMG_GREEN);
+
+        let mut rgba = vec![0xAB; SCREEN_WIDTH * SCREEN_HEIGHT * 4];
+        ppu.write_framebuffer_rgba(&mut rgba)
+            .expect("buffer holds one frame");
+        assert_eq!(rgba, ppu.framebuffer_rgba());
+
+        let mut rgb565 = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT];
+        ppu.write_framebuffer_rgb565(&mut rgb565)
+            .expect("buffer holds one frame");
+        // 0x0F380F -> r=1, g=14, b=1
+        assert_eq!(rgb565[0], (1 << 11) | (14 << 5) | 1);
+        assert_eq!(Palette::GRAYSCALE.rgb565(0), 0xFFFF);
+        assert_eq!(Palette::GRAYSCALE.rgb565(3), 0x0000);
+
+        assert_eq!(
+            ppu.write_framebuffer_rgb565(&mut rgb565[1..]),
+            Err(FramebufferSizeError {
+                expected: SCREEN_WIDTH * SCREEN_HEIGHT,
+                actual: SCREEN_WIDTH * SCREEN_HEIGHT - 1,
+            })
+        );
+    }
+
+    #[test]
+    fn debug_viewers_decode_tiles_maps_and_oam() {
+        let mut memory = memory_with_tile(1, [[0b1000_0001, 0b1000_0000]; 8]);
+        memory[0x9800 + TILE_MAP_SIZE + 2] = 1;
+        memory[0x9C00] = 0xFF;
+        put_sprite(&mut memory, 3, 20, 12, 1, OBJ_X_FLIP | OBJ_PALETTE1);
+        let (ppu, _) = enabled_ppu(0x91);
+
+        let tiles = ppu.decode_tiles(video(&memory));
+        assert_eq!(tiles.len(), TILE_COUNT);
+        assert_eq!(tiles[1].address, 0x8010);
+        assert_eq!(tiles[1].pixels[0], [3, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(tiles[0].pixels, [[0; 8]; 8]);
+
+        let map = ppu.background_map(video(&memory), 0).expect("map 0 exists");
+        assert_eq!(map.base_address, 0x9800);
+        assert_eq!(map.entry(2, 1).tile_number, 1);
+        assert_eq!(map.entry(2, 1).tile_index, 1);
+        let map = ppu.background_map(video(&memory), 1).expect("map 1 exists");
+        assert_eq!(map.entry(0, 0).tile_index, 255);
+        assert_eq!(map.entry(1, 0).tile_index, 0);
+        let (signed, _) = enabled_ppu(0x81);
+        let map = signed
+            .background_map(video(&memory), 1)
+            .expect("map 1 exists");
+        assert_eq!(map.entry(0, 0).tile_index, 255);
+        assert_eq!(map.entry(1, 0).tile_index, 256);
+        assert!(ppu.background_map(video(&memory), 2).is_none());
+        let cell = ppu
+            .bg_map_iter(video(&memory), 0)
+            .expect("map 0 exists")
+            .nth(TILE_MAP_SIZE + 2)
+            .expect("the map has 1024 cells");
+        assert_eq!((cell.column, cell.row, cell.address), (2, 1, 0x9822));
+        assert_eq!((cell.tile_number, cell.tile_index), (1, 1));
+        assert!(ppu.bg_map_iter(video(&memory), 2).is_none());
+
+        let oam = ppu.oam_entries(video(&memory));
+        assert_eq!(oam.len(), OAM_ENTRIES);
+        assert_eq!(
+            oam[3],
+            OamEntry {
+                index: 3,
+                y: 4,
+                x: 4,
+                tile: 1,
+                flags: 0x30,
+                obp1: true,
+                x_flip: true,
+                y_flip: false,
+                behind_background: false,
+            }
+        );
+        assert_eq!((oam[0].x, oam[0].y), (-8, -16));
+    }
+
+    #[test]
+    fn signed_tile_data_addressing() {
+        assert_eq!(tile_data_address(0x00, 0x00), 0x9000);
+        assert_eq!(tile_data_address(0x00, 0xFF), 0x8FF0);
+        assert_eq!(tile_data_address(LCDC_TILE_DATA, 0xFF), 0x8FF0);
+        assert_eq!(tile_data_address(LCDC_TILE_DATA, 0x01), 0x8010);
+    }
+
+    #[test]
+    fn lyc_match_requests_stat_interrupt() {
+        let memory = vec![0u8; 0x10000];
+        let (mut ppu, mut flags) = enabled_ppu(0x91);
+        ppu.write_register(LYC_ADDR, 2, &mut flags);
+        ppu.write_register(STAT_ADDR, STAT_LYC_INTERRUPT, &mut flags);
+        assert_eq!(flags & INTERRUPT_LCD, 0);
+
+        ppu.tick(CYCLES_PER_SCANLINE * 2, video(&memory), &mut flags);
+        assert_ne!(flags & INTERRUPT_LCD, 0);
+        assert_ne!(ppu.read_register(STAT_ADDR) & STAT_LYC_EQUAL, 0);
+    }
+
+    #[test]
+    fn stat_write_during_blanking_fires_spurious_interrupt() {
+        let memory = vec![0u8; 0x10000];
+        let (mut ppu, mut flags) = enabled_ppu(0x91);
+        ppu.write_register(LYC_ADDR, 0x90, &mut flags);
+        ppu.tick(DRAWING_END_CYCLES, video(&memory), &mut flags);
+        assert_eq!(ppu.mode(), PpuMode::HBlank);
+
+        ppu.write_register(STAT_ADDR, 0x00, &mut flags);
+        assert_eq!(flags & INTERRUPT_LCD, 0, "the bug is off by default");
+        ppu.set_stat_write_bug(true);
+        ppu.write_register(STAT_ADDR, 0x00, &mut flags);
+        assert_ne!(flags & INTERRUPT_LCD, 0);
+
+        // During mode 2 on a non-matching line the write is harmless.
+        flags = 0;
+        ppu.tick(
+            CYCLES_PER_SCANLINE - DRAWING_END_CYCLES,
+            video(&memory),
+            &mut flags,
+        );
+        assert_eq!(ppu.mode(), PpuMode::OamScan);
+        ppu.write_register(STAT_ADDR, 0x00, &mut flags);
+        assert_eq!(flags & INTERRUPT_LCD, 0);
+    }
+
+    #[test]
+    fn disabling_lcd_resets_ly() {
+        let memory = vec![0u8; 0x10000];
+        let (mut ppu, mut flags) = enabled_ppu(0x91);
+        ppu.tick(CYCLES_PER_SCANLINE * 5, video(&memory), &mut flags);
+        ppu.write_register(LCDC_ADDR, 0x11, &mut flags);
+        assert_eq!(ppu.ly(), 0);
+        assert_eq!(ppu.read_register(STAT_ADDR) & 0x03, 0);
+    }
+
+    #[test]
+    fn reenabling_lcd_runs_a_short_blank_first_frame() {
+        let memory = vec![0u8; 0x10000];
+        let (mut ppu, mut flags) = enabled_ppu(0x91);
+        ppu.write_register(BGP_ADDR, 0xFF, &mut flags);
+        ppu.tick(CYCLES_PER_SCANLINE * 5, video(&memory), &mut flags);
+        ppu.write_register(LCDC_ADDR, 0x11, &mut flags);
+        assert!(!ppu.lcd_enabled());
+        ppu.tick(CYCLES_PER_SCANLINE * 3, video(&memory), &mut flags);
+        assert_eq!(ppu.ly(), 0, "the PPU is stopped while off");
+
+        flags = 0;
+        ppu.write_register(LCDC_ADDR, 0x91, &mut flags);
+        assert_eq!(ppu.mode(), PpuMode::OamScan);
+        let short_frame = CYCLES_PER_SCANLINE * SCREEN_HEIGHT as u32 - LCD_ENABLE_SKIPPED_DOTS;
+        ppu.tick(short_frame - 1, video(&memory), &mut flags);
+        assert_eq!(flags & INTERRUPT_VBLANK, 0);
+        assert!(
+            ppu.framebuffer().iter().all(|&shade| shade == 0),
+            "first frame after re-enabling is not shown"
+        );
+        ppu.tick(1, video(&memory), &mut flags);
+        assert_eq!(ppu.ly(), 144);
+        assert_ne!(flags & INTERRUPT_VBLANK, 0);
+
+        ppu.tick(
+            CYCLES_PER_SCANLINE * (SCANLINES_PER_FRAME - 144 + 1),
+            video(&memory),
+            &mut flags,
+        );
+        assert!(ppu.framebuffer()[..SCREEN_WIDTH]
+            .iter()
+            .all(|&shade| shade == 3));
+    }
+}
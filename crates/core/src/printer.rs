@@ -0,0 +1,525 @@
+use crate::state::{StateReader, StateWriter};
+use crate::{SerialDevice, StateError};
+
+/// Printed strips are always 20 tiles wide.
+pub const PRINTER_WIDTH: usize = 160;
+/// Image data the printer holds between prints: nine full data packets, 18 tile rows.
+pub const PRINTER_BUFFER_SIZE: usize = 9 * MAX_DATA_LEN;
+
+const MAGIC: [u8; 2] = [0x88, 0x33];
+const DEVICE_ID: u8 = 0x81;
+const MAX_DATA_LEN: usize = 0x280;
+const TILE_BYTES: usize = 16;
+const TILES_PER_ROW: usize = PRINTER_WIDTH / 8;
+const PRINT_ARGS_LEN: usize = 4;
+// Status packets that report busy after a print, so games see the head move and then stop.
+const PRINT_BUSY_POLLS: u8 = 3;
+// A print with palette 0x00 uses the default mapping, as on hardware.
+const DEFAULT_PALETTE: u8 = 0xE4;
+
+const CMD_INIT: u8 = 0x01;
+const CMD_PRINT: u8 = 0x02;
+const CMD_DATA: u8 = 0x04;
+const CMD_STATUS: u8 = 0x0F;
+
+pub const PRINTER_CHECKSUM_ERROR: u8 = 0x01;
+pub const PRINTER_BUSY: u8 = 0x02;
+pub const PRINTER_IMAGE_FULL: u8 = 0x04;
+pub const PRINTER_UNPROCESSED: u8 = 0x08;
+pub const PRINTER_PACKET_ERROR: u8 = 0x10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RxState {
+    Magic,
+    Magic2,
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    DeviceId,
+    Status,
+}
+
+const RX_STATES: [RxState; 11] = [
+    RxState::Magic,
+    RxState::Magic2,
+    RxState::Command,
+    RxState::Compression,
+    RxState::LengthLow,
+    RxState::LengthHigh,
+    RxState::Data,
+    RxState::ChecksumLow,
+    RxState::ChecksumHigh,
+    RxState::DeviceId,
+    RxState::Status,
+];
+
+/// One printed sheet: shades 0-3 (white to black) after the print palette, row-major and
+/// `PRINTER_WIDTH` pixels wide.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Printout {
+    pub pixels: Vec<u8>,
+    /// Blank lines fed before (high nibble) and after (low nibble) the image.
+    pub margins: u8,
+    pub exposure: u8,
+}
+
+impl Printout {
+    pub fn height(&self) -> usize {
+        self.pixels.len() / PRINTER_WIDTH
+    }
+}
+
+/// Game Boy Printer on the link port.
+///
+/// Packets are `88 33`, command, compression flag, a little-endian length, the data and a
+/// little-endian sum of the bytes from the command on; the printer answers the two bytes
+/// after that with `0x81` and its status. Data packets fill the image buffer (run-length
+/// decoded when compressed) and a print command turns it into one `Printout` per sheet.
+#[derive(Debug, Clone)]
+pub struct GameBoyPrinter {
+    state: RxState,
+    command: u8,
+    compressed: bool,
+    length: usize,
+    data: Vec<u8>,
+    checksum: u16,
+    image: Vec<u8>,
+    status: u8,
+    busy_polls: u8,
+    printouts: Vec<Printout>,
+}
+
+impl Default for GameBoyPrinter {
+    fn default() -> Self {
+        Self {
+            state: RxState::Magic,
+            command: 0,
+            compressed: false,
+            length: 0,
+            data: Vec::new(),
+            checksum: 0,
+            image: Vec::new(),
+            status: 0,
+            busy_polls: 0,
+            printouts: Vec::new(),
+        }
+    }
+}
+
+impl GameBoyPrinter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sheets printed so far, in order.
+    pub fn printouts(&self) -> &[Printout] {
+        &self.printouts
+    }
+
+    pub fn take_printouts(&mut self) -> Vec<Printout> {
+        std::mem::take(&mut self.printouts)
+    }
+
+    fn status_byte(&self) -> u8 {
+        let busy = if self.busy_polls > 0 { PRINTER_BUSY } else { 0 };
+        let full = if self.image.len() >= PRINTER_BUFFER_SIZE {
+            PRINTER_IMAGE_FULL
+        } else {
+            0
+        };
+        let unprocessed = if self.image.is_empty() {
+            0
+        } else {
+            PRINTER_UNPROCESSED
+        };
+        self.status | busy | full | unprocessed
+    }
+
+    fn add_to_checksum(&mut self, byte: u8) {
+        self.checksum = self.checksum.wrapping_add(u16::from(byte));
+    }
+
+    fn finish_packet(&mut self, checksum: u16) {
+        self.status &= !(PRINTER_CHECKSUM_ERROR | PRINTER_PACKET_ERROR);
+        if checksum != self.checksum {
+            self.status |= PRINTER_CHECKSUM_ERROR;
+            return;
+        }
+        let data = std::mem::take(&mut self.data);
+        let data = if self.compressed {
+            decompress(&data)
+        } else {
+            data
+        };
+        match self.command {
+            CMD_INIT => {
+                self.image.clear();
+                self.status = 0;
+                self.busy_polls = 0;
+            }
+            CMD_PRINT if data.len() == PRINT_ARGS_LEN => self.print(&data),
+            CMD_DATA if self.image.len() + data.len() <= PRINTER_BUFFER_SIZE => {
+                self.image.extend_from_slice(&data)
+            }
+            CMD_STATUS => self.busy_polls = self.busy_polls.saturating_sub(1),
+            _ => self.status |= PRINTER_PACKET_ERROR,
+        }
+    }
+
+    fn print(&mut self, args: &[u8]) {
+        let [sheets, margins, palette, exposure] = [args[0], args[1], args[2], args[3]];
+        let palette = if palette == 0 {
+            DEFAULT_PALETTE
+        } else {
+            palette
+        };
+        let pixels = render_tiles(&self.image, palette);
+        // An empty buffer only feeds paper, so there is no sheet to keep.
+        let sheets = if pixels.is_empty() { 0 } else { sheets };
+        for _ in 0..sheets {
+            self.printouts.push(Printout {
+                pixels: pixels.clone(),
+                margins,
+                exposure,
+            });
+        }
+        self.image.clear();
+        self.busy_polls = PRINT_BUSY_POLLS;
+    }
+}
+
+impl SerialDevice for GameBoyPrinter {
+    fn exchange(&mut self, out_byte: u8) -> u8 {
+        let mut reply = 0x00;
+        self.state = match self.state {
+            RxState::Magic if out_byte == MAGIC[0] => RxState::Magic2,
+            RxState::Magic => RxState::Magic,
+            RxState::Magic2 if out_byte == MAGIC[1] => {
+                self.checksum = 0;
+                RxState::Command
+            }
+            RxState::Magic2 if out_byte == MAGIC[0] => RxState::Magic2,
+            RxState::Magic2 => RxState::Magic,
+            RxState::Command => {
+                self.add_to_checksum(out_byte);
+                self.command = out_byte;
+                RxState::Compression
+            }
+            RxState::Compression => {
+                self.add_to_checksum(out_byte);
+                self.compressed = out_byte & 0x01 != 0;
+                RxState::LengthLow
+            }
+            RxState::LengthLow => {
+                self.add_to_checksum(out_byte);
+                self.length = usize::from(out_byte);
+                RxState::LengthHigh
+            }
+            RxState::LengthHigh => {
+                self.add_to_checksum(out_byte);
+                self.length |= usize::from(out_byte) << 8;
+                self.data.clear();
+                if self.length == 0 {
+                    RxState::ChecksumLow
+                } else {
+                    RxState::Data
+                }
+            }
+            RxState::Data => {
+                self.add_to_checksum(out_byte);
+                // Oversized packets are still read to the end, but only a packet's worth kept.
+                if self.data.len() < MAX_DATA_LEN {
+                    self.data.push(out_byte);
+                }
+                self.length -= 1;
+                if self.length == 0 {
+                    RxState::ChecksumLow
+                } else {
+                    RxState::Data
+                }
+            }
+            RxState::ChecksumLow => {
+                self.length = usize::from(out_byte);
+                RxState::ChecksumHigh
+            }
+            RxState::ChecksumHigh => {
+                let checksum = self.length as u16 | u16::from(out_byte) << 8;
+                self.finish_packet(checksum);
+                RxState::DeviceId
+            }
+            RxState::DeviceId => {
+                reply = DEVICE_ID;
+                RxState::Status
+            }
+            RxState::Status => {
+                reply = self.status_byte();
+                RxState::Magic
+            }
+        };
+        reply
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        let mut out = StateWriter::default();
+        out.u8(RX_STATES
+            .iter()
+            .position(|&state| state == self.state)
+            .expect("every state is in RX_STATES") as u8);
+        out.u8(self.command);
+        out.bool(self.compressed);
+        out.usize(self.length);
+        out.byte_vec(&self.data);
+        out.u16(self.checksum);
+        out.byte_vec(&self.image);
+        out.u8(self.status);
+        out.u8(self.busy_polls);
+        out.usize(self.printouts.len());
+        for printout in &self.printouts {
+            out.byte_vec(&printout.pixels);
+            out.u8(printout.margins);
+            out.u8(printout.exposure);
+        }
+        out.into_bytes()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut input = StateReader::new(state);
+        let rx_state = *RX_STATES
+            .get(usize::from(input.u8()?))
+            .ok_or(StateError::Invalid("printer state"))?;
+        let command = input.u8()?;
+        let compressed = input.bool()?;
+        let length = input.usize()?;
+        let data = input.byte_vec()?;
+        let checksum = input.u16()?;
+        let image = input.byte_vec()?;
+        let status = input.u8()?;
+        let busy_polls = input.u8()?;
+        let mut printouts = Vec::new();
+        for _ in 0..input.usize()? {
+            let pixels = input.byte_vec()?;
+            if pixels.len() % PRINTER_WIDTH != 0 {
+                return Err(StateError::Invalid("printout size"));
+            }
+            printouts.push(Printout {
+                pixels,
+                margins: input.u8()?,
+                exposure: input.u8()?,
+            });
+        }
+        input.finish()?;
+        if data.len() > MAX_DATA_LEN || image.len() > PRINTER_BUFFER_SIZE {
+            return Err(StateError::Invalid("printer buffer"));
+        }
+        *self = Self {
+            state: rx_state,
+            command,
+            compressed,
+            length,
+            data,
+            checksum,
+            image,
+            status,
+            busy_polls,
+            printouts,
+        };
+        Ok(())
+    }
+
+    fn clone_box(&self) -> Box<dyn SerialDevice> {
+        Box::new(self.clone())
+    }
+}
+
+// A control byte with bit 7 set repeats the next byte (control & 0x7F) + 2 times; otherwise
+// the next control + 1 bytes are copied as they are.
+fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut bytes = data.iter().copied();
+    while let Some(control) = bytes.next() {
+        if control & 0x80 != 0 {
+            let Some(byte) = bytes.next() else { break };
+            out.extend(std::iter::repeat_n(byte, usize::from(control & 0x7F) + 2));
+        } else {
+            out.extend(bytes.by_ref().take(usize::from(control) + 1));
+        }
+    }
+    out
+}
+
+// The buffer holds tiles left to right, 20 per tile row; a trailing partial row is dropped.
+fn render_tiles(image: &[u8], palette: u8) -> Vec<u8> {
+    let tile_rows = image.len() / (TILE_BYTES * TILES_PER_ROW);
+    let mut pixels = vec![0; tile_rows * 8 * PRINTER_WIDTH];
+    for (index, tile) in image
+        .chunks_exact(TILE_BYTES)
+        .take(tile_rows * TILES_PER_ROW)
+        .enumerate()
+    {
+        let left = index % TILES_PER_ROW * 8;
+        let top = index / TILES_PER_ROW * 8;
+        for (row, planes) in tile.chunks_exact(2).enumerate() {
+            for column in 0..8 {
+                let bit = 7 - column;
+                let color = (planes[0] >> bit) & 1 | ((planes[1] >> bit) & 1) << 1;
+                pixels[(top + row) * PRINTER_WIDTH + left + column] =
+                    (palette >> (color * 2)) & 0x03;
+            }
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GameBoy, SB_ADDR, SC_ADDR};
+
+    fn packet(command: u8, compressed: bool, data: &[u8]) -> Vec<u8> {
+        let mut body = vec![command, u8::from(compressed)];
+        body.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        body.extend_from_slice(data);
+        let checksum = body
+            .iter()
+            .fold(0u16, |sum, &byte| sum.wrapping_add(u16::from(byte)));
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(body);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes.extend_from_slice(&[0x00, 0x00]);
+        bytes
+    }
+
+    // The printer's answer to the last two bytes: its ID and status.
+    fn send(printer: &mut GameBoyPrinter, bytes: &[u8]) -> [u8; 2] {
+        let replies: Vec<u8> = bytes.iter().map(|&byte| printer.exchange(byte)).collect();
+        [replies[replies.len() - 2], replies[replies.len() - 1]]
+    }
+
+    // One tile row: every tile's pixels are color 1, except the last tile's are color 3.
+    fn tile_row() -> Vec<u8> {
+        let mut data = [0xFF, 0x00].repeat(TILES_PER_ROW * 8);
+        let last = (TILES_PER_ROW - 1) * TILE_BYTES;
+        data[last..].fill(0xFF);
+        data
+    }
+
+    #[test]
+    fn prints_buffered_tiles_through_the_palette() {
+        let mut printer = GameBoyPrinter::new();
+        assert_eq!(send(&mut printer, &packet(CMD_INIT, false, &[])), [0x81, 0]);
+        let row = tile_row();
+        assert_eq!(
+            send(&mut printer, &packet(CMD_DATA, false, &row)),
+            [0x81, PRINTER_UNPROCESSED]
+        );
+        // The same row again, run-length encoded.
+        let mut compressed = Vec::new();
+        for _ in 0..(TILES_PER_ROW - 1) * 8 {
+            compressed.extend_from_slice(&[0x01, 0xFF, 0x00]);
+        }
+        compressed.extend_from_slice(&[0x8E, 0xFF]);
+        assert_eq!(decompress(&compressed), row);
+        send(&mut printer, &packet(CMD_DATA, true, &compressed));
+        send(&mut printer, &packet(CMD_DATA, false, &[]));
+
+        assert_eq!(
+            send(
+                &mut printer,
+                &packet(CMD_PRINT, false, &[2, 0x13, 0x00, 0x40])
+            ),
+            [0x81, PRINTER_BUSY]
+        );
+        let printouts = printer.printouts();
+        assert_eq!(printouts.len(), 2, "two sheets");
+        assert_eq!(printouts[0].height(), 16);
+        assert_eq!((printouts[0].margins, printouts[0].exposure), (0x13, 0x40));
+        let line = &printouts[0].pixels[15 * PRINTER_WIDTH..];
+        assert_eq!(
+            (line[0], line[PRINTER_WIDTH - 1]),
+            (1, 3),
+            "default palette E4"
+        );
+
+        for _ in 0..PRINT_BUSY_POLLS - 1 {
+            assert_eq!(
+                send(&mut printer, &packet(CMD_STATUS, false, &[]))[1],
+                PRINTER_BUSY
+            );
+        }
+        assert_eq!(send(&mut printer, &packet(CMD_STATUS, false, &[]))[1], 0);
+    }
+
+    #[test]
+    fn printing_an_empty_buffer_keeps_no_sheet() {
+        let mut printer = GameBoyPrinter::new();
+        send(&mut printer, &packet(CMD_INIT, false, &[]));
+        send(&mut printer, &packet(CMD_DATA, false, &[]));
+        assert_eq!(
+            send(
+                &mut printer,
+                &packet(CMD_PRINT, false, &[1, 0x13, 0x00, 0x40])
+            ),
+            [0x81, PRINTER_BUSY]
+        );
+        assert!(printer.printouts().is_empty());
+    }
+
+    #[test]
+    fn save_state_resumes_mid_packet() {
+        let mut printer = GameBoyPrinter::new();
+        send(&mut printer, &packet(CMD_DATA, false, &tile_row()));
+        let print = packet(CMD_PRINT, false, &[1, 0x00, 0xE4, 0x40]);
+        let (head, tail) = print.split_at(7);
+        send(&mut printer, head);
+
+        let mut restored = GameBoyPrinter::new();
+        assert_eq!(restored.load_state(&printer.save_state()), Ok(()));
+        assert_eq!(send(&mut restored, tail), send(&mut printer, tail));
+        assert_eq!(restored.printouts(), printer.printouts());
+        assert_eq!(restored.printouts().len(), 1);
+        assert_eq!(
+            restored.load_state(&[0xFF]),
+            Err(StateError::Invalid("printer state"))
+        );
+    }
+
+    #[test]
+    fn flags_bad_checksums_and_unknown_commands() {
+        let mut printer = GameBoyPrinter::new();
+        let mut bad = packet(CMD_DATA, false, &[0x12; 16]);
+        let checksum = bad.len() - 4;
+        bad[checksum] ^= 0x01;
+        assert_eq!(send(&mut printer, &bad), [0x81, PRINTER_CHECKSUM_ERROR]);
+        assert_eq!(
+            send(&mut printer, &packet(0x09, false, &[])),
+            [0x81, PRINTER_PACKET_ERROR]
+        );
+        assert_eq!(
+            send(&mut printer, &packet(CMD_STATUS, false, &[])),
+            [0x81, 0]
+        );
+        assert!(printer.printouts().is_empty());
+    }
+
+    #[test]
+    fn answers_a_game_boy_sending_on_its_internal_clock() {
+        let mut gb = GameBoy::new();
+        gb.bus.set_serial_device(Box::new(GameBoyPrinter::new()));
+        let mut replies = Vec::new();
+        for byte in packet(CMD_STATUS, false, &[]) {
+            gb.bus.write_byte(SB_ADDR, byte);
+            gb.bus.write_byte(SC_ADDR, 0x81);
+            replies.push(gb.bus.read_byte(SB_ADDR));
+        }
+        assert_eq!(replies[replies.len() - 2..], [0x81, 0x00]);
+        assert!(gb.bus.serial_output().is_empty());
+        assert!(gb
+            .bus
+            .serial_device::<GameBoyPrinter>()
+            .is_some_and(|printer| printer.printouts().is_empty()));
+    }
+}
@@ -0,0 +1,140 @@
+const OAM_ROW_BYTES: usize = 8;
+const OAM_ROWS: usize = 20;
+
+/// Hardware bugs that are only emulated on request; all default off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HardwareQuirks {
+    /// DMG OAM corruption: 16-bit `INC`/`DEC` and `LD (HL+/-)` with an address in
+    /// FE00-FEFF while the PPU scans OAM (mode 2) scramble the row it is reading.
+    pub oam_corruption: bool,
+    /// DMG window bugs: at WX=166 a window started at the last pixel fills the whole next
+    /// line, and at WX=0 the window is shifted left by SCX's fine scroll as well.
+    pub window_glitches: bool,
+    /// DMG STAT write bug: any write to STAT briefly enables every mode 0/1 and LYC source,
+    /// so writing it during blanking or on a matching line requests a spurious interrupt.
+    pub stat_write_bug: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OamCorruption {
+    Write,
+    Read,
+    ReadIncDec,
+}
+
+/// Applies one corruption event to the 160-byte OAM while the PPU reads `row` (0-19).
+/// Row 0 is never affected. Patterns follow Pan Docs' "OAM Corruption Bug" section, with
+/// OAM treated as 20 rows of four little-endian words.
+pub(crate) fn corrupt_oam(oam: &mut [u8], row: usize, kind: OamCorruption) {
+    if row == 0 || row >= OAM_ROWS {
+        return;
+    }
+    match kind {
+        OamCorruption::Write => {
+            let a = word(oam, row, 0);
+            let b = word(oam, row - 1, 0);
+            let c = word(oam, row - 1, 2);
+            set_word(oam, row, 0, ((a ^ c) & (b ^ c)) ^ c);
+            copy_tail_from_previous(oam, row);
+        }
+        OamCorruption::Read => {
+            let a = word(oam, row, 0);
+            let b = word(oam, row - 1, 0);
+            let c = word(oam, row - 1, 2);
+            set_word(oam, row, 0, b | (a & c));
+            copy_tail_from_previous(oam, row);
+        }
+        OamCorruption::ReadIncDec => {
+            if (4..OAM_ROWS - 1).contains(&row) {
+                let a = word(oam, row - 2, 0);
+                let b = word(oam, row - 1, 0);
+                let c = word(oam, row, 0);
+                let d = word(oam, row - 1, 2);
+                set_word(oam, row - 1, 0, (b & (a | c | d)) | (a & c & d));
+                let previous = row_range(row - 1);
+                oam.copy_within(previous.clone(), row_range(row).start);
+                oam.copy_within(previous, row_range(row - 2).start);
+            }
+            corrupt_oam(oam, row, OamCorruption::Read);
+        }
+    }
+}
+
+fn row_range(row: usize) -> std::ops::Range<usize> {
+    row * OAM_ROW_BYTES..(row + 1) * OAM_ROW_BYTES
+}
+
+fn word(oam: &[u8], row: usize, index: usize) -> u16 {
+    let offset = row * OAM_ROW_BYTES + index * 2;
+    u16::from_le_bytes([oam[offset], oam[offset + 1]])
+}
+
+fn set_word(oam: &mut [u8], row: usize, index: usize, value: u16) {
+    let offset = row * OAM_ROW_BYTES + index * 2;
+    oam[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn copy_tail_from_previous(oam: &mut [u8], row: usize) {
+    let previous = row_range(row - 1);
+    oam.copy_within(previous.start + 2..previous.end, row_range(row).start + 2);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered_oam() -> Vec<u8> {
+        (0..OAM_ROWS * OAM_ROW_BYTES)
+            .map(|byte| byte as u8)
+            .collect()
+    }
+
+    #[test]
+    fn write_corruption_mixes_first_word_and_copies_previous_row() {
+        let mut oam = numbered_oam();
+        corrupt_oam(&mut oam, 2, OamCorruption::Write);
+        let (a, b, c) = (0x1110u16, 0x0908u16, 0x0D0Cu16);
+        assert_eq!(word(&oam, 2, 0), ((a ^ c) & (b ^ c)) ^ c);
+        assert_eq!(&oam[18..24], &[10, 11, 12, 13, 14, 15]);
+        assert_eq!(&oam[..16], &numbered_oam()[..16]);
+    }
+
+    #[test]
+    fn read_corruption_ors_previous_first_word() {
+        let mut oam = numbered_oam();
+        corrupt_oam(&mut oam, 5, OamCorruption::Read);
+        assert_eq!(word(&oam, 5, 0), 0x2120 | (0x2928 & 0x2524));
+        assert_eq!(&oam[42..48], &oam[34..40]);
+    }
+
+    #[test]
+    fn row_zero_is_never_corrupted() {
+        let mut oam = numbered_oam();
+        for kind in [
+            OamCorruption::Write,
+            OamCorruption::Read,
+            OamCorruption::ReadIncDec,
+        ] {
+            corrupt_oam(&mut oam, 0, kind);
+        }
+        assert_eq!(oam, numbered_oam());
+    }
+
+    #[test]
+    fn read_inc_dec_spreads_previous_row_outside_first_four_rows() {
+        let mut oam = numbered_oam();
+        corrupt_oam(&mut oam, 6, OamCorruption::ReadIncDec);
+        let (a, b, c, d) = (0x2120u16, 0x2928u16, 0x3130u16, 0x2D2Cu16);
+        let blended = (b & (a | c | d)) | (a & c & d);
+        assert_eq!(word(&oam, 5, 0), blended);
+        assert_eq!(oam[row_range(4)], oam[row_range(5)]);
+        assert_eq!(word(&oam, 6, 0), blended | (blended & word(&oam, 5, 2)));
+        assert_eq!(oam[50..56], oam[42..48]);
+
+        let mut early = numbered_oam();
+        let mut plain = numbered_oam();
+        corrupt_oam(&mut early, 3, OamCorruption::ReadIncDec);
+        corrupt_oam(&mut plain, 3, OamCorruption::Read);
+        assert_eq!(early, plain);
+    }
+}
@@ -0,0 +1,272 @@
+use crate::{GameBoy, Palette, SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::collections::HashMap;
+
+// Browsers play delays under 2/100 s as 10/100 s, so recordings run at 50 fps rather
+// than the DMG's 59.7.
+const GIF_FRAME_DELAY_CS: u16 = 2;
+const GIF_MIN_CODE_SIZE: u8 = 2;
+const GIF_MAX_CODES: u16 = 4096;
+const GIF_MAX_SUB_BLOCK: usize = 255;
+
+/// Framebuffers captured at each VBlank between `GameBoy::start_recording` and
+/// `stop_recording`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    frames: Vec<Vec<u8>>,
+}
+
+impl Recording {
+    /// Captured frames, one 2-bit shade per pixel as `GameBoy::framebuffer`.
+    pub fn frames(&self) -> &[Vec<u8>] {
+        &self.frames
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Encodes the frames as a looping animated GIF with a four-color `palette`. Pixels
+    /// outside 0-3 (blanked LCD output) use color 0.
+    pub fn encode_gif(&self, palette: &Palette) -> Vec<u8> {
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend_from_slice(&(SCREEN_WIDTH as u16).to_le_bytes());
+        gif.extend_from_slice(&(SCREEN_HEIGHT as u16).to_le_bytes());
+        // Global color table of 2^(1+1) entries, 8 bits per primary.
+        gif.extend_from_slice(&[0xF1, 0x00, 0x00]);
+        for shade in 0..4 {
+            gif.extend_from_slice(&palette.rgba(shade)[..3]);
+        }
+        gif.extend_from_slice(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00");
+
+        for frame in &self.frames {
+            gif.extend_from_slice(&[0x21, 0xF9, 0x04, 0x04]);
+            gif.extend_from_slice(&GIF_FRAME_DELAY_CS.to_le_bytes());
+            gif.extend_from_slice(&[0x00, 0x00]);
+
+            gif.extend_from_slice(&[0x2C, 0x00, 0x00, 0x00, 0x00]);
+            gif.extend_from_slice(&(SCREEN_WIDTH as u16).to_le_bytes());
+            gif.extend_from_slice(&(SCREEN_HEIGHT as u16).to_le_bytes());
+            gif.push(0x00);
+
+            gif.push(GIF_MIN_CODE_SIZE);
+            let indices: Vec<u8> = frame
+                .iter()
+                .map(|&shade| if shade < 4 { shade } else { 0 })
+                .collect();
+            for block in lzw_encode(&indices).chunks(GIF_MAX_SUB_BLOCK) {
+                gif.push(block.len() as u8);
+                gif.extend_from_slice(block);
+            }
+            gif.push(0x00);
+        }
+        gif.push(0x3B);
+        gif
+    }
+}
+
+impl GameBoy {
+    /// Starts capturing the framebuffer at every VBlank, discarding any recording in
+    /// progress.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Recording::default());
+    }
+
+    /// Stops capturing and returns what was recorded, or `None` if not recording.
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub(crate) fn record_frame(&mut self) {
+        if let Some(recording) = self.recording.as_mut() {
+            recording.frames.push(self.bus.ppu().framebuffer().to_vec());
+        }
+    }
+}
+
+/// Variable-width LZW as GIF uses it, packed LSB-first; a clear code restarts the table
+/// whenever it fills.
+fn lzw_encode(indices: &[u8]) -> Vec<u8> {
+    let clear = 1u16 << GIF_MIN_CODE_SIZE;
+    let end = clear + 1;
+    let mut writer = BitWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = u32::from(GIF_MIN_CODE_SIZE) + 1;
+    let mut next_code = end + 1;
+    writer.write(clear, code_size);
+
+    let mut pixels = indices.iter();
+    let Some(&first) = pixels.next() else {
+        writer.write(end, code_size);
+        return writer.finish();
+    };
+    let mut current = u16::from(first);
+    for &pixel in pixels {
+        if let Some(&code) = table.get(&(current, pixel)) {
+            current = code;
+            continue;
+        }
+        writer.write(current, code_size);
+        table.insert((current, pixel), next_code);
+        next_code += 1;
+        if next_code > 1 << code_size && code_size < 12 {
+            code_size += 1;
+        }
+        if next_code == GIF_MAX_CODES {
+            writer.write(clear, code_size);
+            table.clear();
+            code_size = u32::from(GIF_MIN_CODE_SIZE) + 1;
+            next_code = end + 1;
+        }
+        current = u16::from(pixel);
+    }
+    writer.write(current, code_size);
+    writer.write(end, code_size);
+    writer.finish()
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.buffer |= u32::from(code) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reference GIF LZW decoder, independent of the encoder's table layout.
+    fn lzw_decode(data: &[u8]) -> Vec<u8> {
+        let clear = 1usize << GIF_MIN_CODE_SIZE;
+        let end = clear + 1;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut code_size = 0;
+        let mut previous: Option<usize> = None;
+        let mut output = Vec::new();
+        let (mut buffer, mut bits, mut bytes) = (0u32, 0u32, data.iter());
+        loop {
+            while bits < code_size.max(3) {
+                buffer |= u32::from(*bytes.next().expect("stream ended early")) << bits;
+                bits += 8;
+            }
+            let size = code_size.max(3);
+            let code = (buffer & ((1 << size) - 1)) as usize;
+            buffer >>= size;
+            bits -= size;
+            if code == clear {
+                table = (0..clear).map(|index| vec![index as u8]).collect();
+                table.extend([Vec::new(), Vec::new()]);
+                code_size = u32::from(GIF_MIN_CODE_SIZE) + 1;
+                previous = None;
+                continue;
+            }
+            if code == end {
+                return output;
+            }
+            let entry = match (table.get(code), previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => {
+                    let mut entry = table[previous].clone();
+                    entry.push(table[previous][0]);
+                    entry
+                }
+                (None, None) => panic!("first code {code} not in table"),
+            };
+            if let Some(previous) = previous {
+                if table.len() < usize::from(GIF_MAX_CODES) {
+                    let mut added = table[previous].clone();
+                    added.push(entry[0]);
+                    table.push(added);
+                }
+            }
+            if table.len() == 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+            output.extend_from_slice(&entry);
+            previous = Some(code);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trips_through_table_resets() {
+        let mut state = 0x1234_5678u32;
+        let noisy: Vec<u8> = (0..SCREEN_WIDTH * SCREEN_HEIGHT)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state & 0x03) as u8
+            })
+            .collect();
+        assert_eq!(lzw_decode(&lzw_encode(&noisy)), noisy);
+
+        let flat = vec![2u8; SCREEN_WIDTH * SCREEN_HEIGHT];
+        assert_eq!(lzw_decode(&lzw_encode(&flat)), flat);
+        assert!(lzw_decode(&lzw_encode(&[])).is_empty());
+    }
+
+    #[test]
+    fn records_one_frame_per_vblank_until_stopped() {
+        // JR -2 with the LCD on.
+        let mut gb = GameBoy::with_program(0xC000, &[0x18, 0xFE]);
+        gb.bus.write_byte(0xFF40, 0x91);
+        gb.run_frame().expect("first frame");
+        gb.start_recording();
+        for _ in 0..3 {
+            gb.run_frame().expect("frame should run");
+        }
+        let recording = gb.stop_recording().expect("recording was started");
+        assert_eq!(recording.len(), 3);
+        assert!(!gb.is_recording());
+        assert_eq!(recording.frames()[0].len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+
+        let gif = recording.encode_gif(&Palette::GRAYSCALE);
+        assert!(gif.starts_with(b"GIF89a\xA0\x00\x90\x00\xF1"));
+        assert_eq!(&gif[13..16], &[0xFF, 0xFF, 0xFF]);
+        assert_eq!(gif.last(), Some(&0x3B));
+
+        // Header, color table and loop extension, then the first frame's control and
+        // image descriptor blocks.
+        let mut offset = 13 + 12 + 19;
+        assert_eq!(&gif[offset..offset + 4], &[0x21, 0xF9, 0x04, 0x04]);
+        offset += 8;
+        assert_eq!(gif[offset], 0x2C);
+        offset += 10;
+        assert_eq!(gif[offset], GIF_MIN_CODE_SIZE);
+        offset += 1;
+        let mut data = Vec::new();
+        while gif[offset] != 0 {
+            let len = usize::from(gif[offset]);
+            data.extend_from_slice(&gif[offset + 1..offset + 1 + len]);
+            offset += 1 + len;
+        }
+        assert_eq!(lzw_decode(&data), recording.frames()[0]);
+    }
+}
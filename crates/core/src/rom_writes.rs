@@ -0,0 +1,117 @@
+use crate::GameBoy;
+use std::fmt::{Display, Formatter};
+
+/// Logged writes kept before further ones are dropped, so a runaway store loop cannot grow
+/// the log without bound.
+pub const MAX_ROM_WRITES: usize = 1024;
+
+/// A CPU store into 0x0000-0x7FFF that no mapper register decodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RomWrite {
+    pub pc: u16,
+    pub address: u16,
+    pub value: u8,
+}
+
+impl Display for RomWrite {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "PC={:04X} wrote 0x{:02X} to 0x{:04X}",
+            self.pc, self.value, self.address
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RomWriteLog {
+    // The instruction being executed, stamped by `GameBoy::step`.
+    pub(crate) pc: u16,
+    pub(crate) writes: Vec<RomWrite>,
+}
+
+impl RomWriteLog {
+    pub(crate) fn record(&mut self, address: u16, value: u8) {
+        if self.writes.len() < MAX_ROM_WRITES {
+            self.writes.push(RomWrite {
+                pc: self.pc,
+                address,
+                value,
+            });
+        }
+    }
+}
+
+impl GameBoy {
+    /// Logs stores into the ROM region that miss every mapper register, which on real
+    /// hardware change nothing. Off by default; turning it off drops the log.
+    pub fn set_rom_write_logging(&mut self, enabled: bool) {
+        self.bus.rom_writes = enabled.then(RomWriteLog::default);
+    }
+
+    /// Logged writes, oldest first, up to `MAX_ROM_WRITES`.
+    pub fn rom_writes(&self) -> &[RomWrite] {
+        self.bus
+            .rom_writes
+            .as_ref()
+            .map_or(&[], |log| log.writes.as_slice())
+    }
+
+    pub fn take_rom_writes(&mut self) -> Vec<RomWrite> {
+        self.bus
+            .rom_writes
+            .as_mut()
+            .map(|log| std::mem::take(&mut log.writes))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn stamp_rom_write_pc(&mut self) {
+        if let Some(log) = self.bus.rom_writes.as_mut() {
+            log.pc = self.cpu.pc;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::new_cartridge;
+
+    #[test]
+    fn logs_only_writes_no_mapper_register_decodes() {
+        // LD A,$42 ; LD ($2000),A ; LD ($6000),A ; LD ($0150),A
+        let program = [
+            0x3E, 0x42, 0xEA, 0x00, 0x20, 0xEA, 0x00, 0x60, 0xEA, 0x50, 0x01,
+        ];
+        let mut rom = vec![0; 0x8000];
+        rom[0x100..0x100 + program.len()].copy_from_slice(&program);
+        rom[0x147] = 0x19; // MBC5
+        let mut gb = GameBoy::new();
+        gb.load_rom(&rom);
+        let mut quiet = gb.clone();
+
+        gb.set_rom_write_logging(true);
+        gb.run_steps(4).expect("program should run");
+        assert_eq!(
+            gb.rom_writes(),
+            [RomWrite {
+                pc: 0x0105,
+                address: 0x6000,
+                value: 0x42
+            }],
+            "MBC5 decodes 0x2000 but has nothing at 0x6000"
+        );
+        assert_eq!(gb.bus.peek(0x6000), 0x00, "the image is untouched");
+        assert_eq!(
+            gb.take_rom_writes()[0].to_string(),
+            "PC=0105 wrote 0x42 to 0x6000"
+        );
+        assert!(gb.rom_writes().is_empty());
+
+        quiet.run_steps(4).expect("program should run");
+        assert!(quiet.rom_writes().is_empty());
+
+        let rom_only = new_cartridge(0x00, 0, vec![0; 0x8000]);
+        assert!(!rom_only.decodes_rom_write(0x2000));
+    }
+}
@@ -0,0 +1,318 @@
+use crate::state::{StateError, StateReader, StateWriter, Stateful};
+use crate::{CPU_CLOCK_HZ, CYCLES_PER_FRAME};
+use std::time::Instant;
+
+pub const RTC_SECONDS: u8 = 0x08;
+pub const RTC_MINUTES: u8 = 0x09;
+pub const RTC_HOURS: u8 = 0x0A;
+pub const RTC_DAY_LOW: u8 = 0x0B;
+pub const RTC_DAY_HIGH: u8 = 0x0C;
+
+const DAY_HIGH_BIT8: u8 = 0x01;
+const DAY_HIGH_HALT: u8 = 0x40;
+const DAY_HIGH_CARRY: u8 = 0x80;
+const MAX_DAYS: u16 = 512;
+
+/// How the cartridge clock advances. `Emulated` only counts CPU cycles, so runs are
+/// reproducible; `WallClock` follows the host clock the way a real cartridge battery would.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RtcMode {
+    #[default]
+    Emulated,
+    WallClock,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Rtc {
+    mode: RtcMode,
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    days: u16,
+    halted: bool,
+    day_carry: bool,
+    subsecond_cycles: u32,
+    wall_anchor: Option<Instant>,
+    latched: [u8; 5],
+}
+
+impl Rtc {
+    pub fn new(mode: RtcMode) -> Self {
+        let mut rtc = Self::default();
+        rtc.set_mode(mode);
+        rtc
+    }
+
+    pub fn mode(&self) -> RtcMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: RtcMode) {
+        self.mode = mode;
+        self.subsecond_cycles = 0;
+        self.wall_anchor = match mode {
+            RtcMode::Emulated => None,
+            RtcMode::WallClock => Some(Instant::now()),
+        };
+    }
+
+    pub fn tick(&mut self, cycles: u32) {
+        self.subsecond_cycles += cycles;
+        match self.mode {
+            RtcMode::Emulated => {
+                while self.subsecond_cycles >= CPU_CLOCK_HZ {
+                    self.subsecond_cycles -= CPU_CLOCK_HZ;
+                    self.advance_seconds(1);
+                }
+            }
+            RtcMode::WallClock => {
+                // Polling the host clock every instruction is wasteful; once a frame is plenty.
+                if self.subsecond_cycles >= CYCLES_PER_FRAME {
+                    self.subsecond_cycles = 0;
+                    self.sync_wall_clock();
+                }
+            }
+        }
+    }
+
+    pub fn latch(&mut self) {
+        if self.mode == RtcMode::WallClock {
+            self.sync_wall_clock();
+        }
+        self.latched = self.registers();
+    }
+
+    pub fn read_latched(&self, register: u8) -> u8 {
+        match register {
+            RTC_SECONDS..=RTC_DAY_HIGH => self.latched[usize::from(register - RTC_SECONDS)],
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write_register(&mut self, register: u8, value: u8) {
+        match register {
+            RTC_SECONDS => {
+                self.seconds = value & 0x3F;
+                self.subsecond_cycles = 0;
+            }
+            RTC_MINUTES => self.minutes = value & 0x3F,
+            RTC_HOURS => self.hours = value & 0x1F,
+            RTC_DAY_LOW => self.days = (self.days & 0x100) | u16::from(value),
+            RTC_DAY_HIGH => {
+                self.days = (self.days & 0xFF) | (u16::from(value & DAY_HIGH_BIT8) << 8);
+                self.day_carry = value & DAY_HIGH_CARRY != 0;
+                let halted = value & DAY_HIGH_HALT != 0;
+                if self.halted && !halted && self.mode == RtcMode::WallClock {
+                    self.wall_anchor = Some(Instant::now());
+                }
+                self.halted = halted;
+            }
+            _ => {}
+        }
+    }
+
+    /// The copy the last latch took, which is what the CPU reads.
+    pub fn latched(&self) -> [u8; 5] {
+        self.latched
+    }
+
+    /// Sets the running and latched registers, as when restoring a save file.
+    pub fn set_registers(&mut self, current: [u8; 5], latched: [u8; 5]) {
+        for (register, value) in (RTC_SECONDS..=RTC_DAY_HIGH).zip(current) {
+            self.write_register(register, value);
+        }
+        self.latched = latched;
+        if self.mode == RtcMode::WallClock {
+            self.wall_anchor = Some(Instant::now());
+        }
+    }
+
+    /// Moves the running clock on by `seconds` unless it is halted, e.g. for the time a
+    /// save file spent on disk.
+    pub fn advance(&mut self, seconds: u64) {
+        self.advance_seconds(seconds);
+    }
+
+    pub fn registers(&self) -> [u8; 5] {
+        let mut day_high = ((self.days >> 8) as u8) & DAY_HIGH_BIT8;
+        if self.halted {
+            day_high |= DAY_HIGH_HALT;
+        }
+        if self.day_carry {
+            day_high |= DAY_HIGH_CARRY;
+        }
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.days as u8,
+            day_high,
+        ]
+    }
+
+    fn sync_wall_clock(&mut self) {
+        let Some(anchor) = self.wall_anchor else {
+            return;
+        };
+        if self.halted {
+            return;
+        }
+        let elapsed = anchor.elapsed().as_secs();
+        if elapsed > 0 {
+            self.advance_seconds(elapsed);
+            self.wall_anchor = anchor.checked_add(std::time::Duration::from_secs(elapsed));
+        }
+    }
+
+    fn advance_seconds(&mut self, mut seconds: u64) {
+        if self.halted {
+            return;
+        }
+        // Out-of-range values a game wrote count up to their wrap one second at a time, as
+        // the counters do; once every field is in range the rest is plain arithmetic.
+        while seconds > 0 && (self.seconds >= 60 || self.minutes >= 60 || self.hours >= 24) {
+            self.tick_second();
+            seconds -= 1;
+        }
+        if seconds == 0 {
+            return;
+        }
+        let total = seconds
+            + u64::from(self.seconds)
+            + 60 * u64::from(self.minutes)
+            + 3600 * u64::from(self.hours);
+        self.seconds = (total % 60) as u8;
+        self.minutes = (total / 60 % 60) as u8;
+        self.hours = (total / 3600 % 24) as u8;
+        let days = u64::from(self.days) + total / 86_400;
+        if days >= u64::from(MAX_DAYS) {
+            self.day_carry = true;
+        }
+        self.days = (days % u64::from(MAX_DAYS)) as u16;
+    }
+
+    fn tick_second(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+        self.days += 1;
+        if self.days == MAX_DAYS {
+            self.days = 0;
+            self.day_carry = true;
+        }
+    }
+}
+
+impl Stateful for Rtc {
+    fn save_state(&self, out: &mut StateWriter) {
+        out.u8(self.seconds);
+        out.u8(self.minutes);
+        out.u8(self.hours);
+        out.u16(self.days);
+        out.bool(self.halted);
+        out.bool(self.day_carry);
+        out.u32(self.subsecond_cycles);
+        out.bytes(&self.latched);
+    }
+
+    /// In `WallClock` mode the clock resumes from the saved time, not the time elapsed since.
+    fn load_state(&mut self, input: &mut StateReader<'_>) -> Result<(), StateError> {
+        self.seconds = input.u8()?;
+        self.minutes = input.u8()?;
+        self.hours = input.u8()?;
+        self.days = input.u16()?;
+        self.halted = input.bool()?;
+        self.day_carry = input.bool()?;
+        self.subsecond_cycles = input.u32()?;
+        input.bytes_into(&mut self.latched)?;
+        if self.days >= MAX_DAYS {
+            return Err(StateError::Invalid("RTC day counter"));
+        }
+        if self.mode == RtcMode::WallClock {
+            self.wall_anchor = Some(Instant::now());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GameBoy;
+
+    #[test]
+    fn emulated_mode_advances_only_with_cycles() {
+        let mut rtc = Rtc::new(RtcMode::Emulated);
+        rtc.tick(CPU_CLOCK_HZ - 4);
+        rtc.latch();
+        assert_eq!(rtc.read_latched(RTC_SECONDS), 0);
+
+        rtc.tick(4);
+        rtc.latch();
+        assert_eq!(rtc.read_latched(RTC_SECONDS), 1);
+    }
+
+    #[test]
+    fn rolls_over_into_days_and_sets_carry() {
+        let mut rtc = Rtc::new(RtcMode::Emulated);
+        rtc.write_register(RTC_SECONDS, 59);
+        rtc.write_register(RTC_MINUTES, 59);
+        rtc.write_register(RTC_HOURS, 23);
+        rtc.write_register(RTC_DAY_LOW, 0xFF);
+        rtc.write_register(RTC_DAY_HIGH, DAY_HIGH_BIT8);
+
+        rtc.tick(CPU_CLOCK_HZ);
+        assert_eq!(rtc.registers(), [0, 0, 0, 0, DAY_HIGH_CARRY]);
+    }
+
+    #[test]
+    fn advancing_in_one_go_matches_ticking_each_second() {
+        // Includes out-of-range minutes and hours, which wrap without carrying.
+        for start in [
+            [0, 0, 0, 0, 0],
+            [58, 62, 30, 0xFE, DAY_HIGH_BIT8],
+            [61, 59, 23, 7, 0],
+        ] {
+            let mut stepped = Rtc::new(RtcMode::Emulated);
+            stepped.set_registers(start, [0; 5]);
+            let mut jumped = stepped.clone();
+            for _ in 0..200_000 {
+                stepped.advance(1);
+            }
+            jumped.advance(200_000);
+            assert_eq!(jumped.registers(), stepped.registers(), "from {start:?}");
+        }
+    }
+
+    #[test]
+    fn halt_freezes_the_clock() {
+        let mut rtc = Rtc::new(RtcMode::Emulated);
+        rtc.write_register(RTC_DAY_HIGH, DAY_HIGH_HALT);
+        rtc.tick(CPU_CLOCK_HZ * 3);
+        assert_eq!(rtc.registers()[0], 0);
+    }
+
+    #[test]
+    fn identical_runs_produce_identical_clocks() {
+        let run = || {
+            let mut gb = GameBoy::new();
+            gb.run_steps(2_500_000).expect("NOPs should run");
+            gb.bus.rtc().registers()
+        };
+        let first = run();
+        assert_eq!(first, run());
+        assert_eq!(first[0], 2); // 10M cycles ~= 2.38s
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{GameBoy, CPU_CLOCK_HZ};
+
+const NO_RESPONSE: u8 = 0xFF;
+
+/// Link partner that drives the serial clock itself, at a fixed rate, and answers every
+/// byte from a fixed list (cycled; 0xFF when empty).
+///
+/// The clock free-runs from power-on, so an externally clocked transfer finishes on the
+/// eighth clock edge after it starts rather than a fixed time after the SC write, which is
+/// what `serial/boot_sclk_align` style tests measure. Internally clocked transfers are
+/// answered from the same list.
+#[derive(Debug, Clone)]
+pub struct SerialClockProvider {
+    bit_cycles: u64,
+    responses: Vec<u8>,
+    next_response: usize,
+    transfer_start: Option<u64>,
+    received: Vec<u8>,
+}
+
+impl SerialClockProvider {
+    /// `clock_hz` is clamped to the CPU clock, one bit per cycle.
+    pub fn new(clock_hz: u32, responses: Vec<u8>) -> Self {
+        Self {
+            bit_cycles: u64::from(CPU_CLOCK_HZ / clock_hz.clamp(1, CPU_CLOCK_HZ)),
+            responses,
+            next_response: 0,
+            transfer_start: None,
+            received: Vec::new(),
+        }
+    }
+
+    /// Connects `gb`'s link port so externally clocked transfers wait for this provider.
+    pub fn attach(&self, gb: &mut GameBoy) {
+        gb.bus.connect_link(true);
+    }
+
+    /// Bytes the Game Boy sent, in transfer order.
+    pub fn received(&self) -> &[u8] {
+        &self.received
+    }
+
+    /// Completes any transfer that is due; call after every step.
+    pub fn poll(&mut self, gb: &mut GameBoy) {
+        let now = gb.cycle_count();
+        if gb.bus.serial_waiting_for_clock() {
+            let start = *self.transfer_start.get_or_insert(now);
+            let done = (start / self.bit_cycles + 8) * self.bit_cycles;
+            if now >= done {
+                self.transfer_start = None;
+                let response = self.next_response();
+                let sent = gb.bus.complete_serial_transfer(response);
+                self.received.push(sent);
+            }
+            return;
+        }
+        self.transfer_start = None;
+        let mut sent = None;
+        gb.bus.poll_serial_transfer(|byte| {
+            sent = Some(byte);
+            self.next_response()
+        });
+        self.received.extend(sent);
+    }
+
+    fn next_response(&mut self) -> u8 {
+        let Some(&byte) = self.responses.get(self.next_response) else {
+            return NO_RESPONSE;
+        };
+        self.next_response = (self.next_response + 1) % self.responses.len();
+        byte
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // LD A,sb ; LDH ($01),A ; LD A,sc ; LDH ($02),A
+    // wait: LDH A,($02) ; BIT 7,A ; JR NZ,wait
+    // LDH A,($01) ; LD B,A ; JR -2
+    fn transfer_program(sb: u8, sc: u8) -> Vec<u8> {
+        vec![
+            0x3E, sb, 0xE0, 0x01, 0x3E, sc, 0xE0, 0x02, 0xF0, 0x02, 0xCB, 0x7F, 0x20, 0xFA, 0xF0,
+            0x01, 0x47, 0x18, 0xFE,
+        ]
+    }
+
+    fn run(gb: &mut GameBoy, provider: &mut SerialClockProvider, cycles: u64) {
+        while gb.cycle_count() < cycles {
+            gb.step().expect("transfer program should run");
+            provider.poll(gb);
+        }
+    }
+
+    #[test]
+    fn clocks_external_transfers_on_aligned_edges() {
+        let mut gb = GameBoy::with_program(0x0000, &transfer_program(0x42, 0x80));
+        let mut provider = SerialClockProvider::new(CPU_CLOCK_HZ / 64, vec![0x5A, 0xA5]);
+        provider.attach(&mut gb);
+
+        run(&mut gb, &mut provider, 8 * 64 - 4);
+        assert_eq!(gb.cpu.regs.b, 0x00, "eighth edge not reached yet");
+        assert!(gb.bus.serial_waiting_for_clock());
+
+        run(&mut gb, &mut provider, 8 * 64 + 80);
+        assert_eq!(gb.cpu.regs.b, 0x5A);
+        assert_eq!(provider.received(), &[0x42]);
+    }
+
+    #[test]
+    fn answers_internal_transfers_and_cycles_responses() {
+        let mut provider = SerialClockProvider::new(8192, vec![0x11, 0x22]);
+        for expected in [0x11, 0x22, 0x11] {
+            let mut gb = GameBoy::with_program(0x0000, &transfer_program(0x99, 0x81));
+            provider.attach(&mut gb);
+            run(&mut gb, &mut provider, 5000);
+            assert_eq!(gb.cpu.regs.b, expected);
+        }
+        assert_eq!(provider.received(), &[0x99; 3]);
+
+        let mut silent = SerialClockProvider::new(CPU_CLOCK_HZ, Vec::new());
+        let mut gb = GameBoy::with_program(0x0000, &transfer_program(0x99, 0x80));
+        silent.attach(&mut gb);
+        run(&mut gb, &mut silent, 200);
+        assert_eq!(gb.cpu.regs.b, NO_RESPONSE);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::StateError;
+use std::any::Any;
+use std::fmt::Debug;
+
+/// Whatever is plugged into the link port when no link cable is connected. Each internally
+/// clocked transfer hands the device SB and loads its answer back into SB.
+pub trait SerialDevice: Any + Debug + Send {
+    fn exchange(&mut self, out_byte: u8) -> u8;
+
+    /// Bytes kept for `Bus::serial_output`; devices that keep none return an empty slice.
+    fn output(&self) -> &[u8] {
+        &[]
+    }
+
+    fn take_output(&mut self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Device state stored in the console's save state. The default keeps nothing.
+    fn save_state(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn load_state(&mut self, _state: &[u8]) -> Result<(), StateError> {
+        Ok(())
+    }
+
+    fn clone_box(&self) -> Box<dyn SerialDevice>;
+}
+
+impl Clone for Box<dyn SerialDevice> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// The default device: records every byte sent and answers with the same byte, so SB
+/// keeps its value. Test ROMs print their results this way.
+#[derive(Debug, Clone, Default)]
+pub struct SerialCapture {
+    output: Vec<u8>,
+}
+
+impl SerialDevice for SerialCapture {
+    fn exchange(&mut self, out_byte: u8) -> u8 {
+        self.output.push(out_byte);
+        out_byte
+    }
+
+    fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        self.output.clone()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        self.output = state.to_vec();
+        Ok(())
+    }
+
+    fn clone_box(&self) -> Box<dyn SerialDevice> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bus, IF_ADDR, INTERRUPT_SERIAL, SB_ADDR, SC_ADDR};
+
+    fn send(bus: &mut Bus, byte: u8) -> u8 {
+        bus.write_byte(SB_ADDR, byte);
+        bus.write_byte(SC_ADDR, 0x81);
+        bus.read_byte(SB_ADDR)
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct Inverter {
+        sent: Vec<u8>,
+    }
+
+    impl SerialDevice for Inverter {
+        fn exchange(&mut self, out_byte: u8) -> u8 {
+            self.sent.push(out_byte);
+            !out_byte
+        }
+
+        fn clone_box(&self) -> Box<dyn SerialDevice> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn installed_device_answers_transfers_in_place_of_the_capture() {
+        let mut bus = Bus::default();
+        assert_eq!(send(&mut bus, 0x41), 0x41);
+
+        let previous = bus.set_serial_device(Box::new(Inverter::default()));
+        assert_eq!(previous.output(), b"A");
+        assert_eq!(send(&mut bus, 0x0F), 0xF0);
+        assert_eq!(bus.read_byte(SC_ADDR) & 0x80, 0);
+        assert_ne!(bus.read_byte(IF_ADDR) & INTERRUPT_SERIAL, 0);
+        assert!(
+            bus.serial_output().is_empty(),
+            "the inverter keeps no output"
+        );
+        assert_eq!(bus.serial_bytes_sent(), 2);
+
+        let copy = bus.clone();
+        let inverter = copy
+            .serial_device::<Inverter>()
+            .expect("the inverter is installed");
+        assert_eq!(inverter.sent, [0x0F]);
+        assert!(copy.serial_device::<SerialCapture>().is_none());
+    }
+}
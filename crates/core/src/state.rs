@@ -0,0 +1,444 @@
+use crate::build_info;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// Save states are a byte stream that reads the same on every platform and build of a
+/// given version, so one written by a desktop, wasm or embedded build loads in the others:
+///
+/// - Integers are little-endian and fixed-width; nothing is written as `usize`, so 32- and
+///   64-bit hosts agree. Counts and lengths are `u32`, except where noted as `u64`.
+/// - `bool` is one byte, 0 or 1; any other value is rejected.
+/// - `f64` is its IEEE 754 bit pattern as a `u64`.
+/// - Fixed-size blocks (memory, framebuffers) are raw bytes with no length.
+/// - Variable-size blocks are a `u32` length followed by the bytes.
+/// - `Option<T>` is a `bool` presence flag, then `T` only when present.
+///
+/// The stream is `STATE_MAGIC`, `STATE_VERSION` (`u16`), the writing build's crate version, git
+/// hash and comma-separated cargo features as three variable-size UTF-8 blocks, the power-on seed
+/// (`u64`), then the CPU and the bus. The bus writes VRAM, WRAM, OAM, the IO page below 0xFF80 and
+/// HRAM, then the timer, joypad, IE and IF, the serial log and counters, the cycle counter, the
+/// RTC, the PPU, the APU, any in-flight OAM DMA, whether the boot ROM is still mapped and finally
+/// the cartridge as a length-prefixed block in the mapper's own layout. Each component's
+/// `Stateful::save_state` is the field order. Host settings and cargo features never change the
+/// layout after the build block; anything that would bumps `STATE_VERSION`. The build block is
+/// informational and never checked on load.
+pub const STATE_MAGIC: [u8; 4] = *b"VGBS";
+pub const STATE_VERSION: u16 = 6;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    Truncated,
+    TrailingBytes(usize),
+    Invalid(&'static str),
+}
+
+impl Display for StateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "not a vibegb save state"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "save state version {version} is not supported (expected {STATE_VERSION})"
+            ),
+            Self::Truncated => write!(f, "save state is truncated"),
+            Self::TrailingBytes(count) => {
+                write!(f, "save state has {count} unexpected trailing bytes")
+            }
+            Self::Invalid(field) => write!(f, "save state has an invalid {field}"),
+        }
+    }
+}
+
+impl Error for StateError {}
+
+/// The core build that wrote a save state, read back with `state_build`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateBuild {
+    pub version: String,
+    pub git_hash: String,
+    pub features: Vec<String>,
+}
+
+impl Display for StateBuild {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "vibegb-core {} ({}) features=[{}]",
+            self.version,
+            self.git_hash,
+            self.features.join(",")
+        )
+    }
+}
+
+/// The build recorded in a save state's header, without loading the rest.
+pub fn state_build(state: &[u8]) -> Result<StateBuild, StateError> {
+    read_header(&mut StateReader::new(state))
+}
+
+pub(crate) fn write_header(out: &mut StateWriter) {
+    let build = build_info();
+    out.bytes(&STATE_MAGIC);
+    out.u16(STATE_VERSION);
+    out.byte_vec(build.version.as_bytes());
+    out.byte_vec(build.git_hash.as_bytes());
+    out.byte_vec(build.features().collect::<Vec<_>>().join(",").as_bytes());
+}
+
+pub(crate) fn read_header(input: &mut StateReader<'_>) -> Result<StateBuild, StateError> {
+    let mut magic = [0; 4];
+    input.bytes_into(&mut magic)?;
+    if magic != STATE_MAGIC {
+        return Err(StateError::BadMagic);
+    }
+    let version = input.u16()?;
+    if version != STATE_VERSION {
+        return Err(StateError::UnsupportedVersion(version));
+    }
+    let mut text =
+        || String::from_utf8(input.byte_vec()?).map_err(|_| StateError::Invalid("build info"));
+    let version = text()?;
+    let git_hash = text()?;
+    let features = text()?
+        .split(',')
+        .filter(|feature| !feature.is_empty())
+        .map(str::to_string)
+        .collect();
+    Ok(StateBuild {
+        version,
+        git_hash,
+        features,
+    })
+}
+
+/// A piece of emulated hardware that can be captured in a save state. Only machine state
+/// is saved; host-side settings such as the PPU renderer, sample rate or RTC mode are
+/// left as the loading instance has them.
+pub(crate) trait Stateful {
+    fn save_state(&self, out: &mut StateWriter);
+    fn load_state(&mut self, input: &mut StateReader<'_>) -> Result<(), StateError>;
+}
+
+/// Little-endian, fixed-order field encoder.
+#[derive(Debug, Default)]
+pub(crate) struct StateWriter {
+    bytes: Vec<u8>,
+}
+
+impl StateWriter {
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub(crate) fn bool(&mut self, value: bool) {
+        self.u8(u8::from(value));
+    }
+
+    pub(crate) fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn i16(&mut self, value: i16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn f64(&mut self, value: f64) {
+        self.u64(value.to_bits());
+    }
+
+    /// A host-sized count, widened to `u64`.
+    pub(crate) fn usize(&mut self, value: usize) {
+        self.u64(value as u64);
+    }
+
+    /// A fixed-size block; the reader must know its length.
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// A variable-size block, prefixed with its length.
+    pub(crate) fn byte_vec(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.bytes(bytes);
+    }
+
+    pub(crate) fn option<T>(&mut self, value: Option<T>, write: impl FnOnce(&mut Self, T)) {
+        self.bool(value.is_some());
+        if let Some(value) = value {
+            write(self, value);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct StateReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    pub(crate) fn finish(&self) -> Result<(), StateError> {
+        match self.bytes.len() - self.position {
+            0 => Ok(()),
+            trailing => Err(StateError::TrailingBytes(trailing)),
+        }
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], StateError> {
+        let mut array = [0; N];
+        self.bytes_into(&mut array)?;
+        Ok(array)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    pub(crate) fn bool(&mut self) -> Result<bool, StateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Invalid("flag")),
+        }
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, StateError> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    pub(crate) fn i16(&mut self) -> Result<i16, StateError> {
+        self.take().map(i16::from_le_bytes)
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, StateError> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, StateError> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    pub(crate) fn f64(&mut self) -> Result<f64, StateError> {
+        self.u64().map(f64::from_bits)
+    }
+
+    /// A count written by `StateWriter::usize`; rejected if this host cannot hold it.
+    pub(crate) fn usize(&mut self) -> Result<usize, StateError> {
+        usize::try_from(self.u64()?).map_err(|_| StateError::Invalid("count"))
+    }
+
+    pub(crate) fn bytes_into(&mut self, out: &mut [u8]) -> Result<(), StateError> {
+        let end = self
+            .position
+            .checked_add(out.len())
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(StateError::Truncated)?;
+        out.copy_from_slice(&self.bytes[self.position..end]);
+        self.position = end;
+        Ok(())
+    }
+
+    pub(crate) fn byte_vec(&mut self) -> Result<Vec<u8>, StateError> {
+        let len = self.u32()? as usize;
+        if len > self.bytes.len() - self.position {
+            return Err(StateError::Truncated);
+        }
+        let mut bytes = vec![0; len];
+        self.bytes_into(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub(crate) fn option<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, StateError>,
+    ) -> Result<Option<T>, StateError> {
+        if self.bool()? {
+            read(self).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_round_trip_in_little_endian_order() {
+        let mut out = StateWriter::default();
+        out.u8(0x12);
+        out.bool(true);
+        out.u16(0x3456);
+        out.i16(-2);
+        out.u32(0x789A_BCDE);
+        out.u64(u64::MAX - 1);
+        out.f64(-0.25);
+        out.usize(0x1234);
+        out.byte_vec(b"abc");
+        out.option(Some(7u8), StateWriter::u8);
+        out.option(None::<u8>, StateWriter::u8);
+        let bytes = out.into_bytes();
+        assert_eq!(&bytes[..4], &[0x12, 0x01, 0x56, 0x34]);
+
+        let mut input = StateReader::new(&bytes);
+        assert_eq!(input.u8(), Ok(0x12));
+        assert_eq!(input.bool(), Ok(true));
+        assert_eq!(input.u16(), Ok(0x3456));
+        assert_eq!(input.i16(), Ok(-2));
+        assert_eq!(input.u32(), Ok(0x789A_BCDE));
+        assert_eq!(input.u64(), Ok(u64::MAX - 1));
+        assert_eq!(input.f64(), Ok(-0.25));
+        assert_eq!(input.usize(), Ok(0x1234));
+        assert_eq!(input.byte_vec(), Ok(b"abc".to_vec()));
+        assert_eq!(input.option(StateReader::u8), Ok(Some(7)));
+        assert_eq!(input.option(StateReader::u8), Ok(None));
+        assert_eq!(input.finish(), Ok(()));
+        assert_eq!(input.u8(), Err(StateError::Truncated));
+
+        let mut bad_flag = StateReader::new(&[2]);
+        assert_eq!(bad_flag.bool(), Err(StateError::Invalid("flag")));
+        let mut huge_vec = StateReader::new(&[0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(huge_vec.byte_vec(), Err(StateError::Truncated));
+        assert_eq!(
+            StateReader::new(&[1, 2]).finish(),
+            Err(StateError::TrailingBytes(2))
+        );
+    }
+
+    fn fnv1a(bytes: &[u8]) -> u64 {
+        bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01B3)
+        })
+    }
+
+    // Pins the version 6 layout after the build block: if this changes, so must
+    // STATE_VERSION. CI runs it with every feature combination, so features cannot change
+    // the layout either.
+    #[test]
+    fn layout_is_pinned_for_this_version() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x147] = 0x03; // MBC1+RAM+BATTERY
+        rom[0x149] = 0x02;
+        rom[0x100..0x104].copy_from_slice(&[0xC3, 0x50, 0x01, 0x00]);
+        rom[0x150..0x164].copy_from_slice(&[
+            0x3E, 0x91, 0xE0, 0x40, // LCD on
+            0x3E, 0x80, 0xE0, 0x26, // sound on
+            0x3E, 0x0A, 0xEA, 0x00, 0x00, // RAM on
+            0x21, 0x00, 0xA0, // LD HL,A000
+            0x22, // loop: LD (HL+),A
+            0x3C, // INC A
+            0x18, 0xFC, // JR loop
+        ]);
+        let mut gb = crate::GameBoy::with_seed(0x5EED);
+        gb.load_rom(&rom);
+        gb.set_audio_output_enabled(false);
+        gb.run_steps(20_000).expect("program should run");
+
+        let state = gb.save_state();
+        assert_eq!(&state[..4], b"VGBS");
+        assert_eq!(&state[4..6], &STATE_VERSION.to_le_bytes());
+        let build = state_build(&state).expect("header should parse");
+        assert_eq!(build.version, build_info().version);
+        assert_eq!(
+            build.features,
+            build_info()
+                .features()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        );
+        let body = &state[6
+            + 12
+            + build.version.len()
+            + build.git_hash.len()
+            + build.features.join(",").len()..];
+        assert_eq!(&body[..8], &0x5EEDu64.to_le_bytes());
+        let pc = u16::from_le_bytes([body[16], body[17]]);
+        assert_eq!(
+            pc, gb.cpu.pc,
+            "after the build block, seed and eight registers"
+        );
+        assert_eq!((body.len(), fnv1a(body)), (71_413, 0xAED4_13F1_2E29_4B36));
+    }
+}
+
+/// Runs random programs, saves at a random step and checks that a fresh console restored
+/// from that state never diverges from the original.
+#[cfg(test)]
+mod round_trip_properties {
+    use crate::GameBoy;
+    use proptest::prelude::*;
+
+    const CONTINUED_STEPS: usize = 2000;
+    const ILLEGAL_OPCODES: [u8; 11] = [
+        0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD,
+    ];
+    // Sound on, LCD on, timer at 262 kHz, every interrupt enabled, EI.
+    const PROLOGUE: [u8; 21] = [
+        0x3E, 0x80, 0xE0, 0x26, 0x3E, 0x91, 0xE0, 0x40, 0x3E, 0x05, 0xE0, 0x07, 0x3E, 0x1F, 0xE0,
+        0xFF, 0xFB, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    /// The prologue followed by random bytes, with illegal opcodes turned into NOPs so
+    /// most runs last.
+    fn program() -> impl Strategy<Value = Vec<u8>> {
+        prop::collection::vec(any::<u8>(), 1..0x800).prop_map(|body| {
+            let body = body.into_iter().map(|byte| {
+                if ILLEGAL_OPCODES.contains(&byte) {
+                    0x00
+                } else {
+                    byte
+                }
+            });
+            PROLOGUE.into_iter().chain(body).collect()
+        })
+    }
+
+    fn run(gb: &mut GameBoy, steps: usize) -> Vec<Result<u32, crate::EmuError>> {
+        (0..steps).map(|_| gb.step()).collect()
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn restored_state_continues_identically(
+            seed: u64,
+            program in program(),
+            save_step in 0usize..20_000,
+        ) {
+            let mut gb = GameBoy::with_seed(seed);
+            gb.bus.load_bytes(0x0000, &program);
+            let mut restored = GameBoy::with_program(0x0000, &program);
+            run(&mut gb, save_step);
+            gb.take_audio_samples();
+
+            let state = gb.save_state();
+            prop_assert!(restored.load_state(&state).is_ok());
+            prop_assert_eq!(restored.save_state(), state);
+
+            prop_assert_eq!(run(&mut restored, CONTINUED_STEPS), run(&mut gb, CONTINUED_STEPS));
+            prop_assert_eq!(restored.save_state(), gb.save_state());
+            prop_assert_eq!(restored.framebuffer(), gb.framebuffer());
+            prop_assert_eq!(restored.take_audio_samples(), gb.take_audio_samples());
+        }
+    }
+}
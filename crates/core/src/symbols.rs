@@ -0,0 +1,288 @@
+use crate::GameBoy;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+const ROMX_START: u16 = 0x4000;
+const VRAM_START: u16 = 0x8000;
+const WRAMX_START: u16 = 0xD000;
+const ECHO_START: u16 = 0xE000;
+// The DMG has no WRAM banking, so 0xD000-0xDFFF is always bank 1, as RGBDS numbers it.
+const DMG_WRAMX_BANK: u16 = 1;
+
+/// A CPU address qualified by the bank mapped there, written `bank:address` in hex as in
+/// RGBDS `.sym` files (`3:4F00`, `03:4F00`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BankedAddress {
+    pub bank: u16,
+    pub address: u16,
+}
+
+impl BankedAddress {
+    pub fn new(bank: u16, address: u16) -> Self {
+        Self { bank, address }
+    }
+}
+
+impl Display for BankedAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:02X}:{:04X}", self.bank, self.address)
+    }
+}
+
+impl FromStr for BankedAddress {
+    type Err = SymbolError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || SymbolError::InvalidLocation(text.to_string());
+        let (bank, address) = text.trim().split_once(':').ok_or_else(invalid)?;
+        Ok(Self {
+            bank: u16::from_str_radix(bank, 16).map_err(|_| invalid())?,
+            address: u16::from_str_radix(address, 16).map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// A debugger breakpoint. Without a bank it stops whatever is mapped at the address; with
+/// one it stops only while that bank is mapped there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub bank: Option<u16>,
+    pub address: u16,
+}
+
+impl Breakpoint {
+    fn hits(self, location: BankedAddress) -> bool {
+        self.address == location.address && self.bank.is_none_or(|bank| bank == location.bank)
+    }
+}
+
+impl From<u16> for Breakpoint {
+    fn from(address: u16) -> Self {
+        Self {
+            bank: None,
+            address,
+        }
+    }
+}
+
+impl From<BankedAddress> for Breakpoint {
+    fn from(location: BankedAddress) -> Self {
+        Self {
+            bank: Some(location.bank),
+            address: location.address,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolError {
+    InvalidLocation(String),
+    InvalidLine { line: usize, text: String },
+}
+
+impl Display for SymbolError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidLocation(text) => {
+                write!(f, "invalid location '{text}': expected BANK:ADDRESS in hex")
+            }
+            Self::InvalidLine { line, text } => {
+                write!(f, "invalid symbol on line {line}: '{text}'")
+            }
+        }
+    }
+}
+
+impl Error for SymbolError {}
+
+/// Labels from an RGBDS-style `.sym` file (`BB:AAAA Name` per line, `;` comments).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    // Sorted by location, so lookups can find the nearest preceding label.
+    symbols: Vec<(BankedAddress, String)>,
+}
+
+impl SymbolTable {
+    pub fn parse(text: &str) -> Result<Self, SymbolError> {
+        let mut symbols = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let content = line.split(';').next().unwrap_or_default().trim();
+            if content.is_empty() {
+                continue;
+            }
+            let invalid = || SymbolError::InvalidLine {
+                line: index + 1,
+                text: line.to_string(),
+            };
+            let (location, name) = content
+                .split_once(char::is_whitespace)
+                .ok_or_else(invalid)?;
+            let location = location.parse().map_err(|_| invalid())?;
+            symbols.push((location, name.trim().to_string()));
+        }
+        symbols.sort_by_key(|(location, _)| *location);
+        Ok(Self { symbols })
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Where a label is defined.
+    pub fn location_of(&self, name: &str) -> Option<BankedAddress> {
+        self.symbols
+            .iter()
+            .find(|(_, symbol)| symbol == name)
+            .map(|(location, _)| *location)
+    }
+
+    /// The closest label at or before `location` in the same bank, with the offset from it,
+    /// so `01:4F03` might resolve to `("Mainloop", 3)`.
+    pub fn resolve(&self, location: BankedAddress) -> Option<(&str, u16)> {
+        let index = self
+            .symbols
+            .partition_point(|(symbol, _)| *symbol <= location);
+        let (symbol, name) = self.symbols[..index].last()?;
+        (symbol.bank == location.bank).then(|| (name.as_str(), location.address - symbol.address))
+    }
+}
+
+impl GameBoy {
+    /// The bank currently mapped at `address`: 0 for the fixed regions, the switchable ROM
+    /// bank for 0x4000-0x7FFF and bank 1 for WRAM at 0xD000-0xDFFF.
+    pub fn bank_at(&self, address: u16) -> u16 {
+        match address {
+            ROMX_START..VRAM_START => self.bus.rom_bank(),
+            WRAMX_START..ECHO_START => DMG_WRAMX_BANK,
+            _ => 0,
+        }
+    }
+
+    /// The program counter with its bank.
+    pub fn location(&self) -> BankedAddress {
+        BankedAddress::new(self.bank_at(self.cpu.pc), self.cpu.pc)
+    }
+
+    /// Adds a breakpoint at a flat address (`0x4F00`, any bank) or a `BankedAddress`
+    /// (`3:4F00`, only while bank 3 is mapped).
+    pub fn add_breakpoint(&mut self, breakpoint: impl Into<Breakpoint>) {
+        let breakpoint = breakpoint.into();
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: impl Into<Breakpoint>) {
+        let breakpoint = breakpoint.into();
+        self.breakpoints.retain(|&existing| existing != breakpoint);
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub(crate) fn at_breakpoint(&self) -> bool {
+        let location = self.location();
+        self.breakpoints
+            .iter()
+            .any(|breakpoint| breakpoint.hits(location))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYMBOLS: &str = "; File generated by rgblink\n\
+        00:0150 Start\n\
+        01:4000 Bank1Entry\n\
+        03:4F00 Mainloop\n\
+        03:4F10 Mainloop.wait ; local label\n\
+        \n\
+        01:D000 wScratch\n";
+
+    #[test]
+    fn parses_and_prints_banked_addresses() {
+        let location: BankedAddress = "3:4F00".parse().expect("location should parse");
+        assert_eq!(location, BankedAddress::new(3, 0x4F00));
+        assert_eq!(location.to_string(), "03:4F00");
+        assert!("4F00".parse::<BankedAddress>().is_err());
+        assert_eq!(
+            "3:XYZ".parse::<BankedAddress>(),
+            Err(SymbolError::InvalidLocation("3:XYZ".to_string()))
+        );
+    }
+
+    #[test]
+    fn resolves_labels_within_their_bank() {
+        let symbols = SymbolTable::parse(SYMBOLS).expect("symbols should parse");
+        assert_eq!(symbols.len(), 5);
+        assert_eq!(
+            symbols.location_of("Mainloop.wait"),
+            Some(BankedAddress::new(3, 0x4F10))
+        );
+        assert_eq!(
+            symbols.resolve(BankedAddress::new(3, 0x4F03)),
+            Some(("Mainloop", 3))
+        );
+        assert_eq!(
+            symbols.resolve(BankedAddress::new(1, 0x4F03)),
+            Some(("Bank1Entry", 0x0F03))
+        );
+        assert_eq!(
+            symbols.resolve(BankedAddress::new(2, 0x4F03)),
+            None,
+            "bank 2 has no labels"
+        );
+        assert_eq!(
+            symbols.resolve(BankedAddress::new(0, 0x0100)),
+            None,
+            "before the first label"
+        );
+
+        assert_eq!(
+            SymbolTable::parse("00:0150 Start\nbogus\n"),
+            Err(SymbolError::InvalidLine {
+                line: 2,
+                text: "bogus".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn banked_breakpoints_only_stop_in_their_bank() {
+        let mut gb = GameBoy::new();
+        assert_eq!(gb.bank_at(0x0150), 0);
+        assert_eq!(gb.bank_at(0x4F00), 1);
+        assert_eq!(gb.bank_at(0xD000), 1);
+        gb.cpu.pc = 0x4F00;
+        assert_eq!(gb.location(), BankedAddress::new(1, 0x4F00));
+
+        gb.add_breakpoint(BankedAddress::new(3, 0x4F00));
+        assert!(!gb.at_breakpoint(), "bank 1 is mapped");
+        gb.add_breakpoint(BankedAddress::new(1, 0x4F00));
+        assert!(gb.at_breakpoint());
+
+        gb.remove_breakpoint(BankedAddress::new(1, 0x4F00));
+        gb.add_breakpoint(0x4F00);
+        assert!(gb.at_breakpoint(), "flat breakpoints match any bank");
+        assert_eq!(
+            gb.breakpoints(),
+            &[
+                Breakpoint {
+                    bank: Some(3),
+                    address: 0x4F00
+                },
+                Breakpoint {
+                    bank: None,
+                    address: 0x4F00
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{EmuError, GameBoy};
+
+/// Where a `GameBoy::tick` left the machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TickInfo {
+    /// PC of the instruction (or halted/dispatch step) this cycle belongs to.
+    pub pc: u16,
+    /// This cycle's position in it, from 0.
+    pub cycle: u32,
+    /// Its length in T-cycles.
+    pub instruction_cycles: u32,
+    /// The instruction's last cycle; the next tick starts another.
+    pub instruction_done: bool,
+}
+
+impl GameBoy {
+    /// Advances the whole machine by one T-cycle. The PPU, APU, timer, OAM DMA and RTC move
+    /// a cycle at a time, so mode changes and LY can be watched inside an instruction. The
+    /// CPU is still instruction-stepped: its reads, writes and register changes all land on
+    /// an instruction's first cycle.
+    ///
+    /// `step` and the other run methods finish an instruction left part-way first, and
+    /// `save_state` saves it as finished, since the cycles left are bus cycles only.
+    pub fn tick(&mut self) -> Result<TickInfo, EmuError> {
+        if self.tick_debt == 0 {
+            self.tick_pc = self.cpu.pc;
+            self.bus.deferred_cycles = Some(0);
+            let result = self.step();
+            let deferred = self.bus.deferred_cycles.take().unwrap_or_default();
+            result?;
+            self.tick_cycles = deferred;
+            self.tick_debt = deferred;
+        }
+        self.tick_bus(1);
+        self.tick_debt -= 1;
+        Ok(TickInfo {
+            pc: self.tick_pc,
+            cycle: self.tick_cycles - self.tick_debt - 1,
+            instruction_cycles: self.tick_cycles,
+            instruction_done: self.tick_debt == 0,
+        })
+    }
+
+    /// Runs the cycles left of an instruction `tick` started; returns how many.
+    pub fn finish_instruction(&mut self) -> u32 {
+        let remaining = std::mem::take(&mut self.tick_debt);
+        if remaining > 0 {
+            self.tick_bus(remaining);
+        }
+        remaining
+    }
+
+    fn tick_bus(&mut self, cycles: u32) {
+        let vblanks = self.bus.ppu().vblank_count();
+        self.bus.tick(cycles);
+        if self.bus.ppu().vblank_count() != vblanks {
+            self.record_frame();
+            self.emit_frame();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PpuMode;
+
+    // LCD on, then a tight loop of 16-cycle PUSH BC / POP BC pairs.
+    fn lcd_program() -> GameBoy {
+        GameBoy::with_program(
+            0xC000,
+            &[
+                0x3E, 0x91, // LD A,91
+                0xE0, 0x40, // LDH (LCDC),A
+                0xC5, // PUSH BC
+                0xC1, // POP BC
+                0x18, 0xFC, // JR -4
+            ],
+        )
+    }
+
+    #[test]
+    fn ticking_matches_stepping_cycle_for_cycle() {
+        let mut stepped = lcd_program();
+        let mut ticked = lcd_program();
+        let mut starts = Vec::new();
+        for _ in 0..2_000 {
+            let cycles = stepped.step().expect("program should run");
+            for cycle in 0..cycles {
+                let info = ticked.tick().expect("program should run");
+                assert_eq!(info.cycle, cycle);
+                assert_eq!(info.instruction_cycles, cycles);
+                assert_eq!(info.instruction_done, cycle + 1 == cycles);
+                if cycle == 0 {
+                    starts.push(info.pc);
+                }
+            }
+            assert_eq!(ticked.cycle_count(), stepped.cycle_count());
+            assert_eq!(ticked.bus.ppu().ly(), stepped.bus.ppu().ly());
+        }
+        assert_eq!(ticked.save_state(), stepped.save_state());
+        assert_eq!(&starts[..4], &[0xC000, 0xC002, 0xC004, 0xC005]);
+    }
+
+    #[test]
+    fn ppu_modes_change_inside_an_instruction() {
+        let mut gb = lcd_program();
+        gb.run_steps(2).expect("LCD should turn on");
+        let mut mid_instruction_changes = 0;
+        let mut mode = gb.bus.ppu().mode();
+        for _ in 0..456 * 4 {
+            let info = gb.tick().expect("program should run");
+            if gb.bus.ppu().mode() != mode && !info.instruction_done {
+                mid_instruction_changes += 1;
+            }
+            mode = gb.bus.ppu().mode();
+        }
+        assert!(mid_instruction_changes > 0);
+        assert_ne!(mode, PpuMode::VBlank);
+
+        while !gb.tick().expect("program should run").instruction_done {}
+        gb.tick().expect("program should run");
+        let before = gb.cycle_count();
+        let remaining = gb.finish_instruction();
+        assert_eq!(gb.cycle_count(), before + u64::from(remaining));
+        assert_eq!(gb.finish_instruction(), 0);
+    }
+
+    #[test]
+    fn step_finishes_a_partly_ticked_instruction() {
+        let mut ticked = lcd_program();
+        let mut stepped = lcd_program();
+        ticked.tick().expect("program should run");
+        ticked.step().expect("program should run");
+        stepped.run_steps(2).expect("program should run");
+        assert_eq!(ticked.cycle_count(), stepped.cycle_count());
+        assert_eq!(ticked.cpu.pc, stepped.cpu.pc);
+    }
+
+    #[test]
+    fn save_state_finishes_a_partly_ticked_instruction() {
+        let mut ticked = lcd_program();
+        ticked.run_steps(3).expect("program should run");
+        let info = ticked.tick().expect("PUSH BC should start");
+        assert!(!info.instruction_done);
+        let state = ticked.save_state();
+
+        let mut finished = ticked.clone();
+        finished.finish_instruction();
+        assert_eq!(state, finished.save_state());
+        assert_eq!(ticked.finish_instruction(), info.instruction_cycles - 1);
+
+        let mut restored = GameBoy::new();
+        restored.load_state(&state).expect("state should load");
+        assert_eq!(restored.cpu.pc, finished.cpu.pc);
+        assert_eq!(restored.tick().expect("POP BC should start").cycle, 0);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::GameBoy;
+use std::fmt::{Display, Formatter};
+
+/// Instructions kept by the always-on trace ring.
+pub const TRACE_RING_LEN: usize = 32;
+
+/// An instruction about to execute, with the registers it starts from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub opcode: u8,
+    pub af: u16,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+    pub sp: u16,
+}
+
+impl Display for TraceEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "PC={:04X} OP={:02X} AF={:04X} BC={:04X} DE={:04X} HL={:04X} SP={:04X}",
+            self.pc, self.opcode, self.af, self.bc, self.de, self.hl, self.sp
+        )
+    }
+}
+
+/// Fixed-size ring of the last executed instructions, cheap enough to keep recording on
+/// every step so a one-off failure still has context.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TraceRing {
+    entries: [TraceEntry; TRACE_RING_LEN],
+    next: usize,
+    len: usize,
+}
+
+impl TraceRing {
+    fn push(&mut self, entry: TraceEntry) {
+        self.entries[self.next] = entry;
+        self.next = (self.next + 1) % TRACE_RING_LEN;
+        self.len = (self.len + 1).min(TRACE_RING_LEN);
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &TraceEntry> {
+        let start = (self.next + TRACE_RING_LEN - self.len) % TRACE_RING_LEN;
+        (0..self.len).map(move |offset| &self.entries[(start + offset) % TRACE_RING_LEN])
+    }
+}
+
+impl GameBoy {
+    /// Up to `TRACE_RING_LEN` of the most recent instructions, oldest first. The last one
+    /// is the instruction the latest step started, including one that failed.
+    pub fn recent_instructions(&self) -> Vec<TraceEntry> {
+        self.trace.iter().copied().collect()
+    }
+
+    pub(crate) fn trace_instruction(&mut self) {
+        let regs = self.cpu.regs;
+        self.trace.push(TraceEntry {
+            pc: self.cpu.pc,
+            opcode: self.bus.peek(self.cpu.pc),
+            af: regs.af(),
+            bc: regs.bc(),
+            de: regs.de(),
+            hl: regs.hl(),
+            sp: self.cpu.sp,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EmuError;
+
+    #[test]
+    fn keeps_the_last_instructions_up_to_a_failing_one() {
+        // LD B,$12 ; INC B ; illegal $D3
+        let mut gb = GameBoy::with_program(0x0000, &[0x06, 0x12, 0x04, 0xD3]);
+        gb.step().expect("LD B");
+        gb.step().expect("INC B");
+        assert_eq!(gb.step(), Err(EmuError::IllegalOpcode(0xD3)));
+
+        let trace = gb.recent_instructions();
+        assert_eq!(trace.len(), 3);
+        assert_eq!(trace[0].opcode, 0x06);
+        assert_eq!(trace[2].pc, 0x0003);
+        assert_eq!(trace[2].bc >> 8, 0x13);
+        assert_eq!(
+            trace[2].to_string(),
+            "PC=0003 OP=D3 AF=0000 BC=1300 DE=0000 HL=0000 SP=FFFE"
+        );
+    }
+
+    #[test]
+    fn ring_drops_the_oldest_entries() {
+        let mut gb = GameBoy::new();
+        gb.run_steps(TRACE_RING_LEN + 5).expect("NOPs should run");
+        let trace = gb.recent_instructions();
+        assert_eq!(trace.len(), TRACE_RING_LEN);
+        assert_eq!(trace[0].pc, 5);
+        assert_eq!(trace[TRACE_RING_LEN - 1].pc, TRACE_RING_LEN as u16 + 4);
+    }
+}
@@ -0,0 +1,99 @@
+use vibegb_core::missing_capabilities;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Acid2Variant {
+    Dmg,
+    Cgb,
+}
+
+impl Acid2Variant {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        match raw {
+            "" | "dmg" => Ok(Self::Dmg),
+            "cgb" => Ok(Self::Cgb),
+            _ => Err(format!("acid2 variant must be 'dmg' or 'cgb', got '{raw}'")),
+        }
+    }
+}
+
+struct Acid2Reference {
+    name: &'static str,
+    requirements: &'static [&'static str],
+    /// What `frame_hash` gives for the upstream reference PNG with its four grays mapped to
+    /// shades 0-3, lightest first. `None` until that image has been hashed into this table.
+    frame_hash: Option<&'static str>,
+}
+
+/// Both tests finish drawing within a few frames; this leaves ample margin.
+pub const ACID2_FRAMES: u64 = 60;
+
+const DMG_ACID2: Acid2Reference = Acid2Reference {
+    name: "dmg-acid2",
+    requirements: &[
+        "ppu-background",
+        "ppu-window",
+        "ppu-sprites",
+        "ppu-tall-sprites",
+        "ppu-sprite-priority",
+        "ppu-lcdc-per-line",
+    ],
+    frame_hash: None,
+};
+
+const CGB_ACID2: Acid2Reference = Acid2Reference {
+    name: "cgb-acid2",
+    requirements: &[
+        "ppu-background",
+        "ppu-window",
+        "ppu-sprites",
+        "ppu-tall-sprites",
+        "ppu-sprite-priority",
+        "ppu-lcdc-per-line",
+        "cgb-ppu",
+    ],
+    frame_hash: None,
+};
+
+/// Expected frame hash for the variant, or why the case cannot be checked on this build.
+pub fn acid2_expected_hash(variant: Acid2Variant) -> Result<&'static str, String> {
+    let reference = match variant {
+        Acid2Variant::Dmg => &DMG_ACID2,
+        Acid2Variant::Cgb => &CGB_ACID2,
+    };
+    let missing = missing_capabilities(reference.requirements);
+    if !missing.is_empty() {
+        return Err(format!(
+            "{} needs rendering features this build lacks: {}",
+            reference.name,
+            missing.join(", ")
+        ));
+    }
+    reference
+        .frame_hash
+        .ok_or_else(|| format!("{} has no reference frame hash recorded", reference.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_variants() {
+        assert_eq!(Acid2Variant::parse(""), Ok(Acid2Variant::Dmg));
+        assert_eq!(Acid2Variant::parse("cgb"), Ok(Acid2Variant::Cgb));
+        assert!(Acid2Variant::parse("sgb").is_err());
+    }
+
+    #[test]
+    fn reports_missing_rendering_features() {
+        let err = acid2_expected_hash(Acid2Variant::Cgb).expect_err("no CGB PPU yet");
+        assert!(err.contains("cgb-acid2 needs rendering features"));
+        assert!(err.contains("cgb-ppu"));
+    }
+
+    #[test]
+    fn dmg_acid2_only_lacks_a_recorded_hash() {
+        let err = acid2_expected_hash(Acid2Variant::Dmg).expect_err("no hash recorded yet");
+        assert_eq!(err, "dmg-acid2 has no reference frame hash recorded");
+    }
+}
@@ -0,0 +1,190 @@
+/// Compat runs resample sound at a low rate; the checks below only need its rough shape.
+pub const COMPAT_SAMPLE_RATE: u32 = 16_000;
+
+// Peak below which a frame counts as silent (about -40 dBFS).
+const SILENCE_PEAK: f32 = 0.01;
+const CLIPPING_LEVEL: f32 = 0.99;
+// More than 1 clipped sample in 100 marks the output as clipping.
+const CLIPPING_LIMIT_PER_MILLE: u64 = 10;
+// Frames whose zero crossings and RMS stay this close count as the same tone.
+const TONE_CROSSING_SLACK: u32 = 2;
+const TONE_RMS_SLACK: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioHealth {
+    /// Sound plays and changes over the run.
+    Ok,
+    /// Never rose above the silence threshold.
+    Silent,
+    /// One unchanging tone from the moment sound started, e.g. a stuck channel.
+    ConstantTone,
+    /// Too many samples at full scale.
+    Clipping,
+}
+
+impl AudioHealth {
+    pub const ALL: [Self; 4] = [Self::Ok, Self::Silent, Self::ConstantTone, Self::Clipping];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Silent => "silent",
+            Self::ConstantTone => "constant-tone",
+            Self::Clipping => "clipping",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioStats {
+    pub health: AudioHealth,
+    /// Samples analysed, counting left and right separately.
+    pub samples: u64,
+    pub clipped: u64,
+}
+
+impl AudioStats {
+    pub fn clipping_ratio(&self) -> f64 {
+        if self.samples == 0 {
+            0.0
+        } else {
+            self.clipped as f64 / self.samples as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FrameShape {
+    zero_crossings: u32,
+    rms: f32,
+}
+
+impl FrameShape {
+    fn matches(self, other: Self) -> bool {
+        self.zero_crossings.abs_diff(other.zero_crossings) <= TONE_CROSSING_SLACK
+            && (self.rms - other.rms).abs() <= TONE_RMS_SLACK * self.rms.max(other.rms)
+    }
+}
+
+/// Classifies sound output fed one video frame of interleaved stereo samples at a time.
+#[derive(Debug, Clone, Default)]
+pub struct AudioMonitor {
+    samples: u64,
+    clipped: u64,
+    audible_frames: u64,
+    previous: Option<FrameShape>,
+    changed: bool,
+}
+
+impl AudioMonitor {
+    pub fn record_frame(&mut self, samples: &[f32]) {
+        self.samples += samples.len() as u64;
+        self.clipped += samples
+            .iter()
+            .filter(|sample| sample.abs() >= CLIPPING_LEVEL)
+            .count() as u64;
+
+        let mono: Vec<f32> = samples
+            .chunks_exact(2)
+            .map(|frame| (frame[0] + frame[1]) / 2.0)
+            .collect();
+        let peak = mono
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        if peak < SILENCE_PEAK {
+            // Sound stopping after it started is a change too.
+            self.changed |= self.audible_frames > 0;
+            return;
+        }
+        self.audible_frames += 1;
+        let shape = FrameShape {
+            zero_crossings: mono
+                .windows(2)
+                .filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0))
+                .count() as u32,
+            rms: (mono.iter().map(|sample| sample * sample).sum::<f32>() / mono.len() as f32)
+                .sqrt(),
+        };
+        // The frame sound starts in holds only part of the first note, so comparing begins
+        // with the one after it.
+        if self.audible_frames > 2 {
+            self.changed |= self
+                .previous
+                .is_some_and(|previous| !previous.matches(shape));
+        }
+        self.previous = Some(shape);
+    }
+
+    pub fn finish(&self) -> AudioStats {
+        let health = if self.audible_frames == 0 {
+            AudioHealth::Silent
+        } else if self.clipped * 1000 > self.samples * CLIPPING_LIMIT_PER_MILLE {
+            AudioHealth::Clipping
+        } else if !self.changed && self.audible_frames > 2 {
+            AudioHealth::ConstantTone
+        } else {
+            AudioHealth::Ok
+        };
+        AudioStats {
+            health,
+            samples: self.samples,
+            clipped: self.clipped,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One 1/60 s frame of a stereo square wave at 16 kHz.
+    fn square_frame(half_period: usize, amplitude: f32) -> Vec<f32> {
+        (0..266)
+            .flat_map(|index| {
+                let sample = if (index / half_period).is_multiple_of(2) {
+                    amplitude
+                } else {
+                    -amplitude
+                };
+                [sample, sample]
+            })
+            .collect()
+    }
+
+    fn classify(frames: &[Vec<f32>]) -> AudioStats {
+        let mut monitor = AudioMonitor::default();
+        for frame in frames {
+            monitor.record_frame(frame);
+        }
+        monitor.finish()
+    }
+
+    #[test]
+    fn classifies_silence_tones_changes_and_clipping() {
+        let silence = vec![0.001; 532];
+        assert_eq!(
+            classify(&[silence.clone(), silence.clone()]).health,
+            AudioHealth::Silent
+        );
+
+        let tone = square_frame(20, 0.5);
+        let stuck = [silence.clone(), tone.clone(), tone.clone(), tone.clone()];
+        assert_eq!(classify(&stuck).health, AudioHealth::ConstantTone);
+
+        let melody = [
+            tone.clone(),
+            tone.clone(),
+            square_frame(10, 0.5),
+            tone.clone(),
+        ];
+        assert_eq!(classify(&melody).health, AudioHealth::Ok);
+
+        let beep = [tone.clone(), tone.clone(), tone.clone(), silence];
+        assert_eq!(classify(&beep).health, AudioHealth::Ok);
+
+        let loud = classify(&[square_frame(20, 1.0), square_frame(10, 1.0)]);
+        assert_eq!(loud.health, AudioHealth::Clipping);
+        assert_eq!(loud.samples, 1064);
+        assert_eq!(loud.clipping_ratio(), 1.0);
+    }
+}
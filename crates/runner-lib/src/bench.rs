@@ -0,0 +1,90 @@
+use std::time::{Duration, Instant};
+use vibegb_core::{EmuError, GameBoy, CPU_CLOCK_HZ};
+
+/// Frames run untimed first, so the timed run starts with the LCD and APU warm.
+const WARMUP_FRAMES: u64 = 60;
+
+/// The pinned workload: LCD and sound on, then a WRAM block copy in a loop, so the CPU,
+/// PPU and APU all do steady work whatever ROMs the machine has on hand.
+pub fn builtin_workload() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x104].copy_from_slice(&[0xC3, 0x50, 0x01, 0x00]); // JP 0150
+    rom[0x150..0x168].copy_from_slice(&[
+        0x3E, 0x91, // LD A,91
+        0xE0, 0x40, // LDH (LCDC),A
+        0x3E, 0x80, // LD A,80
+        0xE0, 0x26, // LDH (NR52),A
+        0x21, 0x00, 0xC0, // loop: LD HL,C000
+        0x11, 0x00, 0xD0, // LD DE,D000
+        0x06, 0x00, // LD B,00
+        0x2A, // copy: LD A,(HL+)
+        0x12, // LD (DE),A
+        0x13, // INC DE
+        0x05, // DEC B
+        0x20, 0xFA, // JR NZ,copy
+        0x18, 0xF0, // JR loop
+    ]);
+    rom
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchResult {
+    pub frames: u64,
+    pub cycles: u64,
+    pub wall: Duration,
+}
+
+impl BenchResult {
+    pub fn emulated_seconds(&self) -> f64 {
+        self.cycles as f64 / f64::from(CPU_CLOCK_HZ)
+    }
+
+    /// Emulated seconds per wall-clock second; 1.0 is full speed.
+    pub fn speed(&self) -> f64 {
+        self.emulated_seconds() / self.wall.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Times `frames` frames of `gb` after a short warmup.
+pub fn run_bench(gb: &mut GameBoy, frames: u64) -> Result<BenchResult, EmuError> {
+    for _ in 0..WARMUP_FRAMES {
+        gb.run_frame()?;
+    }
+    let start_cycles = gb.cycle_count();
+    let start = Instant::now();
+    for _ in 0..frames {
+        gb.run_frame()?;
+    }
+    Ok(BenchResult {
+        frames,
+        cycles: gb.cycle_count() - start_cycles,
+        wall: start.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_whole_frames_of_the_builtin_workload() {
+        let mut gb = GameBoy::new();
+        gb.load_rom(&builtin_workload());
+        let result = run_bench(&mut gb, 3).expect("workload should run");
+        assert_eq!(result.frames, 3);
+        assert!(result.cycles >= 3 * 70_224);
+        assert!(result.speed() > 0.0);
+        assert!(gb.bus.read_byte(0xFF40) & 0x80 != 0, "the LCD is on");
+    }
+
+    #[test]
+    fn speed_is_emulated_time_over_wall_time() {
+        let result = BenchResult {
+            frames: 60,
+            cycles: u64::from(CPU_CLOCK_HZ) * 2,
+            wall: Duration::from_millis(500),
+        };
+        assert_eq!(result.emulated_seconds(), 2.0);
+        assert_eq!(result.speed(), 4.0);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::render_serial;
+use vibegb_core::{DebugAnnotation, GameBoy};
+
+/// Headroom added on top of the observed completion point.
+pub const BUDGET_MARGIN_PERCENT: usize = 25;
+const BUDGET_ROUNDING_STEPS: usize = 1000;
+
+/// Where a suite case first met its expectation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Completion {
+    pub steps: usize,
+    pub frames: u64,
+}
+
+/// An expectation that can be checked while the case runs, to find when it first held.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompletionCheck {
+    SerialContains(String),
+    MooneyePass,
+    MemoryPass,
+    DebugMessage(String),
+}
+
+#[derive(Debug)]
+pub struct CompletionTracker<'a> {
+    check: &'a CompletionCheck,
+    serial_bytes: u64,
+    annotations: usize,
+    completion: Option<Completion>,
+}
+
+impl<'a> CompletionTracker<'a> {
+    pub fn new(check: &'a CompletionCheck) -> Self {
+        Self {
+            check,
+            serial_bytes: 0,
+            annotations: 0,
+            completion: None,
+        }
+    }
+
+    /// Checks the machine after step `steps`; serial text is only re-read when a byte was sent,
+    /// and debug messages only when a new one was recorded.
+    pub fn observe(&mut self, gb: &GameBoy, steps: usize) {
+        if self.completion.is_some() {
+            return;
+        }
+        let met = match self.check {
+            CompletionCheck::SerialContains(expected) => {
+                let sent = gb.bus.serial_bytes_sent();
+                sent != self.serial_bytes && {
+                    self.serial_bytes = sent;
+                    render_serial(gb.bus.serial_output()).contains(expected.as_str())
+                }
+            }
+            CompletionCheck::MooneyePass => {
+                let regs = gb.cpu.regs;
+                regs.bc() == 0x0305 && regs.de() == 0x080D && regs.hl() == 0x1522
+            }
+            CompletionCheck::MemoryPass => gb.memory_test_status() == Some(0),
+            CompletionCheck::DebugMessage(expected) => {
+                let annotations = gb.debug_annotations();
+                let new = &annotations[self.annotations..];
+                self.annotations = annotations.len();
+                new.iter().any(|annotation| {
+                    matches!(annotation, DebugAnnotation::Message { text, .. } if text.contains(expected.as_str()))
+                })
+            }
+        };
+        if met {
+            self.completion = Some(Completion {
+                steps,
+                frames: gb.frame_count(),
+            });
+        }
+    }
+
+    pub fn completion(&self) -> Option<Completion> {
+        self.completion
+    }
+}
+
+/// `steps` plus the margin, rounded up to a whole thousand steps.
+pub fn suggested_max_steps(steps: usize) -> usize {
+    let padded = steps + steps * BUDGET_MARGIN_PERCENT / 100;
+    padded.div_ceil(BUDGET_ROUNDING_STEPS).max(1) * BUDGET_ROUNDING_STEPS
+}
+
+pub fn render_budget_line(label: &str, max_steps: usize, completion: Option<Completion>) -> String {
+    match completion {
+        Some(completion) => format!(
+            "BUDGET | {label} | max_steps={max_steps} -> {} | completed at step {} (frame {})",
+            suggested_max_steps(completion.steps),
+            completion.steps,
+            completion.frames
+        ),
+        None => format!("BUDGET | {label} | max_steps={max_steps} | no expectation to observe"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggestions_pad_and_round_the_completion_point() {
+        assert_eq!(suggested_max_steps(0), 1000);
+        assert_eq!(suggested_max_steps(24), 1000);
+        assert_eq!(suggested_max_steps(800_000), 1_000_000);
+        assert_eq!(suggested_max_steps(800_001), 1_001_000);
+
+        let completion = Completion {
+            steps: 1_600_000,
+            frames: 97,
+        };
+        assert_eq!(
+            render_budget_line("cpu-01", 9_000_000, Some(completion)),
+            "BUDGET | cpu-01 | max_steps=9000000 -> 2000000 | completed at step 1600000 (frame 97)"
+        );
+        assert_eq!(
+            render_budget_line("smoke", 500, None),
+            "BUDGET | smoke | max_steps=500 | no expectation to observe"
+        );
+    }
+}
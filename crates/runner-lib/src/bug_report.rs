@@ -0,0 +1,175 @@
+use std::fs;
+use std::path::Path;
+
+const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x0403_4B50;
+const CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0201_4B50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4B50;
+const ZIP_VERSION: u16 = 20;
+// Fixed 1980-01-01 00:00 DOS timestamp keeps archives byte-identical across runs.
+const DOS_TIME: u16 = 0x0000;
+const DOS_DATE: u16 = 0x0021;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BugReportEntry {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+impl BugReportEntry {
+    pub fn text(name: &str, text: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            data: text.into().into_bytes(),
+        }
+    }
+
+    pub fn binary(name: &str, data: Vec<u8>) -> Self {
+        Self {
+            name: name.to_string(),
+            data,
+        }
+    }
+}
+
+pub fn write_bug_report(path: &Path, entries: &[BugReportEntry]) -> Result<(), String> {
+    let archive = build_zip(entries)?;
+    fs::write(path, archive)
+        .map_err(|err| format!("failed to write bug report '{}': {err}", path.display()))
+}
+
+/// Builds a store-only (uncompressed) ZIP archive containing `entries`.
+pub fn build_zip(entries: &[BugReportEntry]) -> Result<Vec<u8>, String> {
+    let mut archive = Vec::new();
+    let mut central_directory = Vec::new();
+
+    for entry in entries {
+        let name = entry.name.as_bytes();
+        let name_len = u16::try_from(name.len())
+            .map_err(|_| format!("bug report entry name too long: '{}'", entry.name))?;
+        let size = u32::try_from(entry.data.len())
+            .map_err(|_| format!("bug report entry too large: '{}'", entry.name))?;
+        let offset = u32::try_from(archive.len())
+            .map_err(|_| "bug report archive exceeds 4 GiB".to_string())?;
+        let crc = crc32(&entry.data);
+
+        push_u32(&mut archive, LOCAL_FILE_HEADER_SIGNATURE);
+        push_u16(&mut archive, ZIP_VERSION);
+        push_u16(&mut archive, 0); // flags
+        push_u16(&mut archive, 0); // method: stored
+        push_u16(&mut archive, DOS_TIME);
+        push_u16(&mut archive, DOS_DATE);
+        push_u32(&mut archive, crc);
+        push_u32(&mut archive, size);
+        push_u32(&mut archive, size);
+        push_u16(&mut archive, name_len);
+        push_u16(&mut archive, 0); // extra field length
+        archive.extend_from_slice(name);
+        archive.extend_from_slice(&entry.data);
+
+        push_u32(&mut central_directory, CENTRAL_DIRECTORY_SIGNATURE);
+        push_u16(&mut central_directory, ZIP_VERSION);
+        push_u16(&mut central_directory, ZIP_VERSION);
+        push_u16(&mut central_directory, 0); // flags
+        push_u16(&mut central_directory, 0); // method: stored
+        push_u16(&mut central_directory, DOS_TIME);
+        push_u16(&mut central_directory, DOS_DATE);
+        push_u32(&mut central_directory, crc);
+        push_u32(&mut central_directory, size);
+        push_u32(&mut central_directory, size);
+        push_u16(&mut central_directory, name_len);
+        push_u16(&mut central_directory, 0); // extra field length
+        push_u16(&mut central_directory, 0); // comment length
+        push_u16(&mut central_directory, 0); // disk number
+        push_u16(&mut central_directory, 0); // internal attributes
+        push_u32(&mut central_directory, 0); // external attributes
+        push_u32(&mut central_directory, offset);
+        central_directory.extend_from_slice(name);
+    }
+
+    let entry_count =
+        u16::try_from(entries.len()).map_err(|_| "too many bug report entries".to_string())?;
+    let directory_offset =
+        u32::try_from(archive.len()).map_err(|_| "bug report archive exceeds 4 GiB".to_string())?;
+    let directory_size = u32::try_from(central_directory.len())
+        .map_err(|_| "bug report archive exceeds 4 GiB".to_string())?;
+    archive.extend_from_slice(&central_directory);
+
+    push_u32(&mut archive, END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+    push_u16(&mut archive, 0); // disk number
+    push_u16(&mut archive, 0); // disk with central directory
+    push_u16(&mut archive, entry_count);
+    push_u16(&mut archive, entry_count);
+    push_u32(&mut archive, directory_size);
+    push_u32(&mut archive, directory_offset);
+    push_u16(&mut archive, 0); // comment length
+
+    Ok(archive)
+}
+
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn push_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_matches_reference_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn builds_store_only_archive_with_central_directory() {
+        let entries = [
+            BugReportEntry::text("header.txt", "Title: TEST"),
+            BugReportEntry::binary("serial.bin", vec![0x00, 0xFF]),
+        ];
+        let archive = build_zip(&entries).expect("archive should build");
+
+        assert_eq!(&archive[..4], &LOCAL_FILE_HEADER_SIGNATURE.to_le_bytes());
+        let eocd = &archive[archive.len() - 22..];
+        assert_eq!(
+            &eocd[..4],
+            &END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes()
+        );
+        assert_eq!(u16::from_le_bytes([eocd[10], eocd[11]]), 2);
+
+        let directory_offset =
+            u32::from_le_bytes([eocd[16], eocd[17], eocd[18], eocd[19]]) as usize;
+        assert_eq!(
+            &archive[directory_offset..directory_offset + 4],
+            &CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes()
+        );
+
+        let first_name_end = 30 + "header.txt".len();
+        assert_eq!(&archive[30..first_name_end], b"header.txt");
+        assert_eq!(
+            &archive[first_name_end..first_name_end + 11],
+            b"Title: TEST"
+        );
+    }
+
+    #[test]
+    fn archives_are_deterministic() {
+        let entries = [BugReportEntry::text("version.txt", "0.1.0")];
+        assert_eq!(build_zip(&entries), build_zip(&entries));
+    }
+}
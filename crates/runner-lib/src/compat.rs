@@ -0,0 +1,462 @@
+use crate::audio_health::{AudioHealth, AudioMonitor, AudioStats, COMPAT_SAMPLE_RATE};
+use crate::json::json_string;
+use crate::{frame_step_budget, ExecOptions};
+use std::fmt::Write as _;
+use std::fs;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use vibegb_core::{GameBoy, HardwareQuirks, HeaderValidation, Rom};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompatOutcome {
+    /// Drew something and kept changing the screen afterwards.
+    Boots,
+    /// Completed every frame without ever drawing anything.
+    Blank,
+    /// Stopped producing frames, or the screen went static after first drawing.
+    Hang,
+    IllegalOpcode,
+    /// The file could not be read or its header was rejected.
+    LoadFailed,
+}
+
+impl CompatOutcome {
+    pub const ALL: [Self; 5] = [
+        Self::Boots,
+        Self::Blank,
+        Self::Hang,
+        Self::IllegalOpcode,
+        Self::LoadFailed,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Boots => "boots",
+            Self::Blank => "blank",
+            Self::Hang => "hang",
+            Self::IllegalOpcode => "illegal-opcode",
+            Self::LoadFailed => "load-failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatEntry {
+    pub file: String,
+    pub title: String,
+    pub outcome: CompatOutcome,
+    /// VBlanks reached before the run ended.
+    pub frames: u64,
+    /// Frames that changed any pixel; see `FrameDeltaStats`.
+    pub active_frames: u64,
+    pub detail: String,
+    /// `None` when the ROM never ran.
+    pub audio: Option<AudioStats>,
+    pub quirks: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatReport {
+    pub frames: u64,
+    pub entries: Vec<CompatEntry>,
+}
+
+/// Where `--mode compat` writes its report, chosen by the file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+    Html,
+}
+
+impl ReportFormat {
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Ok(Self::Json),
+            Some(ext) if ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm") => {
+                Ok(Self::Html)
+            }
+            _ => Err(format!(
+                "compat report '{}' must end in .json or .html",
+                path.display()
+            )),
+        }
+    }
+}
+
+/// `.gb` and `.gbc` files directly inside `dir`, sorted by name.
+pub fn rom_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|err| format!("failed to read ROM directory '{}': {err}", dir.display()))?;
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| {
+                        ext.eq_ignore_ascii_case("gb") || ext.eq_ignore_ascii_case("gbc")
+                    })
+        })
+        .collect();
+    if files.is_empty() {
+        return Err(format!("no .gb or .gbc files found in '{}'", dir.display()));
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Boots every ROM in `dir` for up to `frames` frames. Each ROM gets the settings in
+/// `options` plus its known-game quirks unless `game_quirks` is false.
+pub fn run_compat(
+    dir: &Path,
+    frames: u64,
+    options: &ExecOptions,
+    validation: HeaderValidation,
+    game_quirks: bool,
+) -> Result<CompatReport, String> {
+    let entries = rom_files(dir)?
+        .iter()
+        .map(|path| {
+            let file = path
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+            match Rom::from_file_with(path, validation) {
+                Ok(rom) => check_rom(file, &rom, frames, options, game_quirks),
+                Err(err) => CompatEntry {
+                    file,
+                    title: String::new(),
+                    outcome: CompatOutcome::LoadFailed,
+                    frames: 0,
+                    active_frames: 0,
+                    detail: err.to_string(),
+                    audio: None,
+                    quirks: Vec::new(),
+                },
+            }
+        })
+        .collect();
+    Ok(CompatReport { frames, entries })
+}
+
+fn check_rom(
+    file: String,
+    rom: &Rom,
+    frames: u64,
+    options: &ExecOptions,
+    game_quirks: bool,
+) -> CompatEntry {
+    let mut gb = GameBoy::with_seed(options.seed);
+    gb.set_rtc_mode(options.rtc_mode);
+    gb.set_ppu_renderer(options.ppu_renderer);
+    gb.set_hardware_quirks(HardwareQuirks {
+        oam_corruption: options.oam_corruption_bug,
+        window_glitches: options.window_glitches,
+        stat_write_bug: options.stat_write_bug,
+    });
+    let quirks = if game_quirks {
+        gb.apply_game_quirks(&rom.header)
+    } else {
+        &[]
+    };
+    gb.set_audio_sample_rate(COMPAT_SAMPLE_RATE);
+    gb.load_rom(&rom.data);
+
+    let mut first_drawn = None;
+    let mut audio = AudioMonitor::default();
+    let mut last_vblank = 0;
+    let result = gb.run_steps_with(frame_step_budget(frames), |gb, _| {
+        let vblanks = gb.bus.ppu().vblank_count();
+        if vblanks != last_vblank {
+            last_vblank = vblanks;
+            audio.record_frame(&gb.take_audio_samples());
+        }
+        if first_drawn.is_none() && vblanks > 0 {
+            let framebuffer = gb.framebuffer();
+            if framebuffer.iter().any(|&shade| shade != framebuffer[0]) {
+                first_drawn = Some(vblanks);
+            }
+        }
+        if vblanks >= frames {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+
+    let reached = gb.bus.ppu().vblank_count();
+    let delta = gb.frame_delta_stats();
+    let (outcome, detail) = match result {
+        Err(err) => (
+            CompatOutcome::IllegalOpcode,
+            format!("{err} at PC 0x{:04X} after {reached} frames", gb.cpu.pc),
+        ),
+        Ok(_) if reached < frames => (
+            CompatOutcome::Hang,
+            format!(
+                "stopped after {reached} of {frames} frames with the LCD {}",
+                if gb.lcd_enabled() { "on" } else { "off" }
+            ),
+        ),
+        Ok(_) => match first_drawn {
+            None => (
+                CompatOutcome::Blank,
+                format!("no non-blank frame in {frames} frames"),
+            ),
+            Some(drawn) if delta.static_frames >= reached - drawn => (
+                CompatOutcome::Hang,
+                format!("screen static since frame {drawn}"),
+            ),
+            Some(_) => (
+                CompatOutcome::Boots,
+                format!("{} changing frames", delta.active_frames),
+            ),
+        },
+    };
+    CompatEntry {
+        file,
+        title: rom.header.title.clone(),
+        outcome,
+        frames: reached,
+        active_frames: delta.active_frames,
+        detail,
+        audio: Some(audio.finish()),
+        quirks: quirks.iter().map(|quirk| quirk.name()).collect(),
+    }
+}
+
+impl CompatReport {
+    pub fn count(&self, outcome: CompatOutcome) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.outcome == outcome)
+            .count()
+    }
+
+    pub fn audio_count(&self, health: AudioHealth) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.audio.is_some_and(|audio| audio.health == health))
+            .count()
+    }
+
+    /// One line per ROM followed by the per-outcome and audio health totals.
+    pub fn render_text(&self) -> String {
+        let mut output = String::new();
+        for entry in &self.entries {
+            let _ = writeln!(
+                output,
+                "{} | {} | {} | {} | audio {}",
+                entry.outcome.name(),
+                entry.file,
+                entry.title,
+                entry.detail,
+                entry.audio.map_or("-", |audio| audio.health.name())
+            );
+        }
+        let totals: Vec<String> = CompatOutcome::ALL
+            .iter()
+            .map(|&outcome| format!("{}={}", outcome.name(), self.count(outcome)))
+            .collect();
+        let _ = write!(
+            output,
+            "Compat Summary: {} ROMs, {}",
+            self.entries.len(),
+            totals.join(" ")
+        );
+        let audio: Vec<String> = AudioHealth::ALL
+            .iter()
+            .map(|&health| format!("{}={}", health.name(), self.audio_count(health)))
+            .collect();
+        let _ = write!(output, "\nAudio Summary: {}", audio.join(" "));
+        output
+    }
+
+    pub fn render_json(&self) -> String {
+        let totals: Vec<String> = CompatOutcome::ALL
+            .iter()
+            .map(|&outcome| format!("{}:{}", json_string(outcome.name()), self.count(outcome)))
+            .collect();
+        let roms: Vec<String> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let quirks: Vec<String> = entry.quirks.iter().map(|name| json_string(name)).collect();
+                let audio = entry.audio.map_or_else(
+                    || "null".to_string(),
+                    |audio| {
+                        format!(
+                            "{{\"health\":{},\"samples\":{},\"clipped\":{},\"clipping_ratio\":{:.6}}}",
+                            json_string(audio.health.name()),
+                            audio.samples,
+                            audio.clipped,
+                            audio.clipping_ratio()
+                        )
+                    },
+                );
+                format!(
+                    "{{\"file\":{},\"title\":{},\"outcome\":{},\"frames\":{},\"active_frames\":{},\"detail\":{},\"audio\":{},\"quirks\":[{}]}}",
+                    json_string(&entry.file),
+                    json_string(&entry.title),
+                    json_string(entry.outcome.name()),
+                    entry.frames,
+                    entry.active_frames,
+                    json_string(&entry.detail),
+                    audio,
+                    quirks.join(",")
+                )
+            })
+            .collect();
+        let audio_totals: Vec<String> = AudioHealth::ALL
+            .iter()
+            .map(|&health| {
+                format!(
+                    "{}:{}",
+                    json_string(health.name()),
+                    self.audio_count(health)
+                )
+            })
+            .collect();
+        format!(
+            "{{\"frames\":{},\"summary\":{{{}}},\"audio_summary\":{{{}}},\"roms\":[{}]}}\n",
+            self.frames,
+            totals.join(","),
+            audio_totals.join(","),
+            roms.join(",")
+        )
+    }
+
+    pub fn render_html(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>VibeGB compatibility report</title>\n<style>\nbody { font-family: sans-serif; }\ntable { border-collapse: collapse; }\nth, td { border: 1px solid #999; padding: 4px 8px; text-align: left; }\n.boots { background: #cfc; }\n.blank, .hang { background: #ffc; }\n.illegal-opcode, .load-failed { background: #fcc; }\n</style>\n</head>\n<body>\n<h1>VibeGB compatibility report</h1>\n",
+        );
+        let _ = writeln!(
+            html,
+            "<p>{} ROMs, {} frames each.</p>\n<ul>",
+            self.entries.len(),
+            self.frames
+        );
+        for outcome in CompatOutcome::ALL {
+            let _ = writeln!(html, "<li>{}: {}</li>", outcome.name(), self.count(outcome));
+        }
+        for health in AudioHealth::ALL {
+            let _ = writeln!(
+                html,
+                "<li>audio {}: {}</li>",
+                health.name(),
+                self.audio_count(health)
+            );
+        }
+        html.push_str("</ul>\n<table>\n<tr><th>File</th><th>Title</th><th>Outcome</th><th>Frames</th><th>Detail</th><th>Audio</th><th>Quirks</th></tr>\n");
+        for entry in &self.entries {
+            let audio = entry.audio.map_or_else(
+                || "-".to_string(),
+                |audio| {
+                    format!(
+                        "{} ({:.1}% clipped)",
+                        audio.health.name(),
+                        audio.clipping_ratio() * 100.0
+                    )
+                },
+            );
+            let _ = writeln!(
+                html,
+                "<tr class=\"{outcome}\"><td>{}</td><td>{}</td><td>{outcome}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                html_escape(&entry.file),
+                html_escape(&entry.title),
+                entry.frames,
+                html_escape(&entry.detail),
+                audio,
+                entry.quirks.join(", "),
+                outcome = entry.outcome.name(),
+            );
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+}
+
+fn html_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(file: &str, outcome: CompatOutcome) -> CompatEntry {
+        CompatEntry {
+            file: file.to_string(),
+            title: "<TITLE>".to_string(),
+            outcome,
+            frames: 60,
+            active_frames: 2,
+            detail: "2 changing frames".to_string(),
+            audio: Some(AudioStats {
+                health: AudioHealth::ConstantTone,
+                samples: 1000,
+                clipped: 5,
+            }),
+            quirks: vec!["pixel-fifo-ppu"],
+        }
+    }
+
+    #[test]
+    fn report_format_follows_the_extension() {
+        assert_eq!(
+            ReportFormat::from_path(Path::new("out.JSON")),
+            Ok(ReportFormat::Json)
+        );
+        assert_eq!(
+            ReportFormat::from_path(Path::new("out.html")),
+            Ok(ReportFormat::Html)
+        );
+        assert!(ReportFormat::from_path(Path::new("out.txt")).is_err());
+    }
+
+    #[test]
+    fn renders_summary_json_and_escaped_html() {
+        let report = CompatReport {
+            frames: 60,
+            entries: vec![
+                entry("a.gb", CompatOutcome::Boots),
+                entry("b.gb", CompatOutcome::Hang),
+                entry("c.gb", CompatOutcome::Boots),
+            ],
+        };
+        assert_eq!(report.count(CompatOutcome::Boots), 2);
+
+        let text = report.render_text();
+        assert!(
+            text.starts_with("boots | a.gb | <TITLE> | 2 changing frames | audio constant-tone\n")
+        );
+        assert!(text.ends_with(
+            "Compat Summary: 3 ROMs, boots=2 blank=0 hang=1 illegal-opcode=0 load-failed=0\nAudio Summary: ok=0 silent=0 constant-tone=3 clipping=0"
+        ));
+
+        let json = report.render_json();
+        assert!(json.starts_with("{\"frames\":60,\"summary\":{\"boots\":2,\"blank\":0,"));
+        assert!(json.contains("\"file\":\"b.gb\",\"title\":\"<TITLE>\",\"outcome\":\"hang\""));
+        assert!(json.contains("\"quirks\":[\"pixel-fifo-ppu\"]"));
+        assert!(json.contains("\"audio_summary\":{\"ok\":0,\"silent\":0,\"constant-tone\":3,"));
+        assert!(json.contains(
+            "\"audio\":{\"health\":\"constant-tone\",\"samples\":1000,\"clipped\":5,\"clipping_ratio\":0.005000}"
+        ));
+
+        let html = report.render_html();
+        assert!(html.contains("<li>hang: 1</li>"));
+        assert!(html.contains("<td>constant-tone (0.5% clipped)</td>"));
+        assert!(html.contains("<tr class=\"boots\"><td>a.gb</td><td>&lt;TITLE&gt;</td>"));
+        assert!(!html.contains("<TITLE>"));
+    }
+}
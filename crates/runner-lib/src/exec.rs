@@ -0,0 +1,349 @@
+use crate::budget::{Completion, CompletionCheck, CompletionTracker};
+use crate::frame_hash::frame_hash;
+use crate::input_script::{InputPlayer, InputScript};
+use crate::render_serial;
+use std::any::Any;
+use std::fmt::Write as _;
+use std::ops::ControlFlow;
+use std::panic::{self, AssertUnwindSafe};
+use vibegb_core::{
+    Button, DebugAnnotation, GameBoy, GameBoyPrinter, GameQuirk, HardwareQuirks, InterruptLatency,
+    LoadConfig, MapperRegisters, MemoryTestResult, MobileAdapter, Model, Movie, PpuRenderer,
+    RtcMode, SerialClockProvider,
+};
+
+// Every step takes at least one M-cycle and an LCD-on frame is 17556 of them.
+const MAX_STEPS_PER_FRAME: usize = 17_556;
+const INPUT_TEST_PERIOD_FRAMES: u64 = 8;
+const INPUT_TEST_HOLD_FRAMES: u64 = 4;
+// Drained well before the APU's bounded sample buffer starts dropping frames.
+const AUDIO_DRAIN_FRAMES: usize = 4096;
+
+/// Enough steps for `frames` LCD-on frames plus the partial frame before the first VBlank,
+/// so a ROM that turns the LCD off still terminates.
+pub fn frame_step_budget(frames: u64) -> usize {
+    usize::try_from(frames.saturating_add(1))
+        .unwrap_or(usize::MAX)
+        .saturating_mul(MAX_STEPS_PER_FRAME)
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("non-string panic payload")
+}
+
+/// The core's ring of recent instructions, for failure messages.
+fn render_trace(gb: &GameBoy) -> String {
+    let mut trace = "\nLast Instructions (oldest first):".to_string();
+    for entry in gb.recent_instructions() {
+        let _ = write!(trace, "\n  {entry}");
+    }
+    trace
+}
+
+pub fn run_with_options(rom_data: &[u8], options: &ExecOptions) -> Result<ExecutionReport, String> {
+    let outcome = execute_rom(rom_data, options);
+    match outcome.error {
+        Some(err) => Err(err),
+        None => Ok(outcome.report()),
+    }
+}
+
+pub struct ExecOptions {
+    pub max_steps: usize,
+    pub rtc_mode: RtcMode,
+    pub ppu_renderer: PpuRenderer,
+    pub seed: u64,
+    pub input_latency_test: bool,
+    pub mobile_adapter: bool,
+    pub printer: bool,
+    pub serial_clock: Option<u32>,
+    pub serial_responses: Vec<u8>,
+    pub bus_stats: bool,
+    pub log_rom_writes: bool,
+    pub boot_rom: Option<Vec<u8>>,
+    pub model: Option<Model>,
+    pub input_script: Option<InputScript>,
+    pub record_movie: bool,
+    pub play_movie: Option<Movie>,
+    pub frames: Option<u64>,
+    pub oam_corruption_bug: bool,
+    pub window_glitches: bool,
+    pub stat_write_bug: bool,
+    pub irq_latency: Option<u32>,
+    pub record_gif: bool,
+    pub record_audio: bool,
+    pub audio_output: bool,
+    pub completion: Option<CompletionCheck>,
+    /// Polled after every step; an error ends the run with that message.
+    pub abort_check: Option<fn() -> Option<String>>,
+}
+
+impl ExecOptions {
+    pub fn new(max_steps: usize) -> Self {
+        Self {
+            max_steps,
+            rtc_mode: RtcMode::Emulated,
+            ppu_renderer: PpuRenderer::Scanline,
+            seed: 0,
+            input_latency_test: false,
+            mobile_adapter: false,
+            printer: false,
+            serial_clock: None,
+            serial_responses: Vec::new(),
+            bus_stats: false,
+            log_rom_writes: false,
+            boot_rom: None,
+            model: None,
+            input_script: None,
+            record_movie: false,
+            play_movie: None,
+            frames: None,
+            oam_corruption_bug: false,
+            window_glitches: false,
+            stat_write_bug: false,
+            irq_latency: None,
+            record_gif: false,
+            record_audio: false,
+            audio_output: true,
+            completion: None,
+            abort_check: None,
+        }
+    }
+
+    // Mirrors `GameBoy::apply_game_quirks`, but on the options so bug reports record the
+    // settings the run actually used.
+    pub fn apply_game_quirks(&mut self, quirks: &[GameQuirk]) {
+        for quirk in quirks {
+            match quirk {
+                GameQuirk::PixelFifoPpu => self.ppu_renderer = PpuRenderer::PixelFifo,
+                GameQuirk::OamCorruption => self.oam_corruption_bug = true,
+            }
+        }
+    }
+}
+
+pub fn execute_rom(rom_data: &[u8], options: &ExecOptions) -> RunOutcome {
+    let mut gb = GameBoy::with_seed(options.seed);
+    gb.set_rtc_mode(options.rtc_mode);
+    gb.set_ppu_renderer(options.ppu_renderer);
+    gb.set_input_latency_tracking(options.input_latency_test);
+    gb.set_audio_output_enabled(options.audio_output);
+    // Homebrew test ROMs report through `ld b,b`/`ld d,d`; record them so every run shows them.
+    gb.set_debug_annotations(true);
+    gb.bus.set_access_stats(options.bus_stats);
+    gb.set_rom_write_logging(options.log_rom_writes);
+    if options.mobile_adapter {
+        gb.bus.set_serial_device(Box::new(MobileAdapter::new()));
+    }
+    if options.printer {
+        gb.bus.set_serial_device(Box::new(GameBoyPrinter::new()));
+    }
+    gb.set_hardware_quirks(HardwareQuirks {
+        oam_corruption: options.oam_corruption_bug,
+        window_glitches: options.window_glitches,
+        stat_write_bug: options.stat_write_bug,
+    });
+    gb.set_interrupt_latency(options.irq_latency.map(|max_cycles| InterruptLatency {
+        max_cycles,
+        seed: options.seed,
+    }));
+    // Without a boot ROM, start from the state it would have left behind.
+    match (options.model, &options.boot_rom) {
+        (Some(model), Some(_)) => gb.set_model(model),
+        (model, None) => gb.apply_post_boot_state(model.unwrap_or(Model::Dmg)),
+        (None, Some(_)) => {}
+    }
+    // Mismatched headers get a best-guess mapper; `--strict-cartridge` refuses them earlier.
+    gb.load_rom_with_config(rom_data, LoadConfig::default())
+        .expect("permissive loads always boot");
+    if let Some(image) = &options.boot_rom {
+        gb.load_boot_rom(image)
+            .expect("the runner checks the boot ROM size");
+    }
+    if let Some(movie) = &options.play_movie {
+        if let Err(err) = gb.play_movie(movie) {
+            return RunOutcome {
+                gb,
+                audio: Vec::new(),
+                steps: 0,
+                cycles: 0,
+                error: Some(format!("movie playback failed: {err}")),
+                serial_clock: None,
+                completion: None,
+                scripted_inputs: 0,
+            };
+        }
+    }
+    if options.record_movie {
+        gb.start_movie_recording();
+    }
+    if options.record_gif {
+        gb.start_recording();
+    }
+    let mut audio = Vec::new();
+    let mut serial_clock = options
+        .serial_clock
+        .map(|hz| SerialClockProvider::new(hz, options.serial_responses.clone()));
+    if let Some(provider) = &serial_clock {
+        provider.attach(&mut gb);
+    }
+    let mut cycles = 0u64;
+    let mut steps = 0usize;
+    let mut scripted_frame = None;
+    let mut completion = options.completion.as_ref().map(CompletionTracker::new);
+    let mut aborted = None;
+    if options.input_latency_test {
+        scripted_frame = Some(gb.frame_count());
+        apply_input_test_script(&mut gb);
+    }
+    let mut input_player = options.input_script.as_ref().map(InputPlayer::new);
+    if let Some(player) = input_player.as_mut() {
+        player.apply(&mut gb);
+    }
+
+    // A core panic becomes a run error, so the failure still gets the instruction trace.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        gb.run_steps_with(options.max_steps, |gb, info| {
+            steps = info.steps;
+            cycles = info.total_cycles;
+            if let Some(provider) = serial_clock.as_mut() {
+                provider.poll(gb);
+            }
+            if options.record_audio && gb.audio_buffered_frames() >= AUDIO_DRAIN_FRAMES {
+                audio.append(&mut gb.take_audio_samples());
+            }
+            if options.input_latency_test && scripted_frame != Some(gb.frame_count()) {
+                scripted_frame = Some(gb.frame_count());
+                apply_input_test_script(gb);
+            }
+            if let Some(player) = input_player.as_mut() {
+                player.apply(gb);
+            }
+            if let Some(completion) = completion.as_mut() {
+                completion.observe(gb, info.steps);
+            }
+            aborted = options.abort_check.and_then(|check| check());
+            if aborted.is_some() {
+                return ControlFlow::Break(());
+            }
+            if options
+                .frames
+                .is_some_and(|frames| gb.bus.ppu().vblank_count() >= frames)
+            {
+                return ControlFlow::Break(());
+            }
+            ControlFlow::Continue(())
+        })
+    }));
+    let error = match (result, options.frames) {
+        (Ok(Ok(_)), _) if aborted.is_some() => aborted,
+        (Err(payload), _) => Some(format!(
+            "core panicked after step {steps}: {}{}",
+            panic_message(payload.as_ref()),
+            render_trace(&gb)
+        )),
+        (Ok(Err(err)), _) => Some(format!(
+            "emulation failed at step {steps}: {err}{}",
+            render_trace(&gb)
+        )),
+        (Ok(Ok(_)), Some(frames)) if gb.bus.ppu().vblank_count() < frames => Some(format!(
+            "reached max steps ({steps}) after {} of {frames} frames",
+            gb.bus.ppu().vblank_count()
+        )),
+        (Ok(Ok(_)), _) => None,
+    };
+
+    if options.record_audio {
+        audio.append(&mut gb.take_audio_samples());
+    }
+
+    RunOutcome {
+        gb,
+        audio,
+        steps,
+        cycles,
+        error,
+        serial_clock,
+        completion: completion.and_then(|completion| completion.completion()),
+        scripted_inputs: input_player.map_or(0, |player| player.applied()),
+    }
+}
+
+// Cycles through every button, pressing one at the start of each period and releasing it
+// halfway through, so both edges get a latency measurement.
+fn apply_input_test_script(gb: &mut GameBoy) {
+    let frame = gb.frame_count();
+    let button =
+        Button::ALL[((frame / INPUT_TEST_PERIOD_FRAMES) % Button::ALL.len() as u64) as usize];
+    match frame % INPUT_TEST_PERIOD_FRAMES {
+        0 => gb.set_button(button, true),
+        INPUT_TEST_HOLD_FRAMES => gb.set_button(button, false),
+        _ => {}
+    }
+}
+
+pub struct RunOutcome {
+    pub gb: GameBoy,
+    // Interleaved stereo samples, collected only with `ExecOptions::record_audio`.
+    pub audio: Vec<f32>,
+    pub steps: usize,
+    pub cycles: u64,
+    pub error: Option<String>,
+    pub serial_clock: Option<SerialClockProvider>,
+    pub completion: Option<Completion>,
+    // Input script events applied before the run ended.
+    pub scripted_inputs: usize,
+}
+
+impl RunOutcome {
+    pub fn report(&self) -> ExecutionReport {
+        let regs = self.gb.cpu.regs;
+        ExecutionReport {
+            seed: self.gb.seed(),
+            steps: self.steps,
+            cycles: self.cycles,
+            frames: self.gb.frame_count(),
+            scanline: self.gb.current_scanline(),
+            pc: self.gb.cpu.pc,
+            sp: self.gb.cpu.sp,
+            af: regs.af(),
+            bc: regs.bc(),
+            de: regs.de(),
+            hl: regs.hl(),
+            frame_hash: frame_hash(self.gb.framebuffer()),
+            serial_output: render_serial(self.gb.bus.serial_output()),
+            mapper: self
+                .gb
+                .bus
+                .cartridge()
+                .map(|cartridge| cartridge.registers()),
+            memory_result: self.gb.memory_test_result(),
+            debug_annotations: self.gb.debug_annotations().to_vec(),
+            completion: self.completion,
+        }
+    }
+}
+
+pub struct ExecutionReport {
+    pub seed: u64,
+    pub steps: usize,
+    pub cycles: u64,
+    pub frames: u64,
+    pub scanline: u8,
+    pub pc: u16,
+    pub sp: u16,
+    pub af: u16,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+    pub frame_hash: String,
+    pub serial_output: String,
+    pub mapper: Option<MapperRegisters>,
+    pub memory_result: Option<MemoryTestResult>,
+    pub debug_annotations: Vec<DebugAnnotation>,
+    pub completion: Option<Completion>,
+}
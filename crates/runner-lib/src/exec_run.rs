@@ -0,0 +1,263 @@
+use crate::bug_report::write_bug_report;
+use crate::input_script::InputScript;
+use crate::png::write_frame_png;
+use crate::wav::write_audio_wav;
+use crate::{
+    bug_report_entries, printer_export, render_bus_stats, render_cartridge_warnings,
+    render_exec_config, render_exec_report, render_game_quirks, render_input_latency,
+    render_rom_writes, vram_export, ExecOptions, Expectations, RunOutcome,
+};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use vibegb_core::{
+    check_boot_rom, check_cartridge, GameBoyPrinter, GameQuirk, Movie, Palette, Rom,
+};
+
+/// The files a single-ROM exec run reads its inputs from and writes its results to.
+#[derive(Debug, Clone, Default)]
+pub struct ExecFiles {
+    pub boot_rom: Option<PathBuf>,
+    pub input_script: Option<PathBuf>,
+    pub play_movie: Option<PathBuf>,
+    pub record_movie: Option<PathBuf>,
+    pub record_gif: Option<PathBuf>,
+    pub dump_audio: Option<PathBuf>,
+    pub dump_frame: Option<PathBuf>,
+    pub export_vram: Option<PathBuf>,
+    pub printer: Option<PathBuf>,
+    pub bug_report: Option<PathBuf>,
+}
+
+impl ExecFiles {
+    /// Reads the boot ROM, input script and movie into `options`, and turns on the
+    /// recordings and devices the outputs need.
+    pub fn load_into(&self, options: &mut ExecOptions) -> Result<(), String> {
+        options.boot_rom = self.boot_rom.as_deref().map(read_boot_rom).transpose()?;
+        options.input_script = self
+            .input_script
+            .as_deref()
+            .map(read_input_script)
+            .transpose()?;
+        options.play_movie = self.play_movie.as_deref().map(read_movie).transpose()?;
+        options.record_movie = self.record_movie.is_some();
+        options.record_gif = self.record_gif.is_some();
+        options.record_audio = self.dump_audio.is_some();
+        options.printer = self.printer.is_some();
+        Ok(())
+    }
+}
+
+fn read_boot_rom(path: &Path) -> Result<Vec<u8>, String> {
+    let image = fs::read(path)
+        .map_err(|err| format!("failed to read boot ROM '{}': {err}", path.display()))?;
+    check_boot_rom(&image).map_err(|err| format!("boot ROM load failed: {err}"))?;
+    Ok(image)
+}
+
+fn read_input_script(path: &Path) -> Result<InputScript, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| format!("failed to read input script '{}': {err}", path.display()))?;
+    InputScript::parse(&text)
+}
+
+fn read_movie(path: &Path) -> Result<Movie, String> {
+    let bytes = fs::read(path)
+        .map_err(|err| format!("failed to read movie '{}': {err}", path.display()))?;
+    Movie::from_bytes(&bytes)
+        .map_err(|err| format!("movie load failed for '{}': {err}", path.display()))
+}
+
+/// Checks a finished exec run against `expectations`, writes the files it asked for (the
+/// bug report even when the run failed) and renders its report.
+pub fn finish_exec(
+    rom_path: &Path,
+    rom: &Rom,
+    options: &ExecOptions,
+    game_quirks: &[GameQuirk],
+    mut outcome: RunOutcome,
+    files: &ExecFiles,
+    expectations: &Expectations,
+) -> Result<String, String> {
+    let report = outcome.report();
+    let expectation_result = match &outcome.error {
+        Some(err) => Err(err.clone()),
+        None => expectations.check(&report, "single ROM run"),
+    };
+
+    if let Some(archive_path) = &files.bug_report {
+        let config = render_exec_config(options, expectations);
+        let entries = bug_report_entries(
+            rom_path,
+            &rom.header,
+            &config,
+            &outcome,
+            &report,
+            expectation_result.as_ref().err(),
+        );
+        write_bug_report(archive_path, &entries)?;
+    }
+    if let Some(png_path) = &files.dump_frame {
+        let frame = outcome
+            .gb
+            .screenshot()
+            .map_err(|err| format!("failed to finish the frame for --dump-frame: {err}"))?;
+        write_frame_png(png_path, &frame)?;
+    }
+    let exported = files
+        .export_vram
+        .as_deref()
+        .map(|dir| vram_export::export_vram(&outcome.gb, dir))
+        .transpose()?;
+    let printed = files
+        .printer
+        .as_deref()
+        .map(|dir| {
+            let printouts = outcome
+                .gb
+                .bus
+                .serial_device::<GameBoyPrinter>()
+                .map(GameBoyPrinter::printouts)
+                .unwrap_or_default();
+            printer_export::export_printouts(printouts, dir)
+        })
+        .transpose()?;
+    if let Some(wav_path) = &files.dump_audio {
+        write_audio_wav(wav_path, outcome.gb.bus.apu().sample_rate(), &outcome.audio)?;
+    }
+    let recorded_inputs = files
+        .record_movie
+        .as_ref()
+        .map(|movie_path| {
+            let movie = outcome
+                .gb
+                .stop_movie_recording()
+                .expect("exec runs record when a movie path is given");
+            fs::write(movie_path, movie.to_bytes()).map_err(|err| {
+                format!("failed to write movie '{}': {err}", movie_path.display())
+            })?;
+            Ok::<_, String>(movie.inputs().len())
+        })
+        .transpose()?;
+    if let Some(gif_path) = &files.record_gif {
+        let recording = outcome.gb.stop_recording().unwrap_or_default();
+        if recording.is_empty() {
+            return Err(format!(
+                "no frames recorded for '{}': the PPU never reached VBlank",
+                gif_path.display()
+            ));
+        }
+        fs::write(gif_path, recording.encode_gif(&Palette::GRAYSCALE))
+            .map_err(|err| format!("failed to write recording '{}': {err}", gif_path.display()))?;
+    }
+
+    expectation_result?;
+    let mut output = render_exec_report(rom_path, &rom.header, &report);
+    output.push_str(&render_cartridge_warnings(&check_cartridge(
+        &rom.header,
+        rom.data.len(),
+    )));
+    output.push_str(&render_game_quirks(game_quirks));
+    if options.input_latency_test {
+        output.push_str(&render_input_latency(&outcome.gb));
+    }
+    if let Some(provider) = &outcome.serial_clock {
+        let _ = write!(
+            output,
+            "\nSerial Clock Transfers: {}",
+            provider.received().len()
+        );
+    }
+    if options.bus_stats {
+        output.push_str(&render_bus_stats(&outcome.gb));
+    }
+    if options.log_rom_writes {
+        output.push_str(&render_rom_writes(&outcome.gb));
+    }
+    if let (Some(path), Some(script)) = (&files.input_script, &options.input_script) {
+        let _ = write!(
+            output,
+            "\nInput Script: {} ({} of {} events applied)",
+            path.display(),
+            outcome.scripted_inputs,
+            script.events().len()
+        );
+    }
+    if let (Some(path), Some(inputs)) = (&files.record_movie, recorded_inputs) {
+        let _ = write!(
+            output,
+            "\nMovie Recorded: {} ({inputs} inputs)",
+            path.display()
+        );
+    }
+    if let (Some(path), Some((played, total))) = (&files.play_movie, outcome.gb.movie_progress()) {
+        let _ = write!(
+            output,
+            "\nMovie: {} ({played} of {total} inputs played)",
+            path.display()
+        );
+    }
+    if let Some(path) = &files.boot_rom {
+        let _ = write!(
+            output,
+            "\nBoot ROM: {} ({})",
+            path.display(),
+            if outcome.gb.boot_rom_mapped() {
+                "still mapped"
+            } else {
+                "unmapped"
+            }
+        );
+    }
+    if let Some(max_cycles) = options.irq_latency {
+        let injected = outcome.gb.injected_interrupt_latency();
+        let _ = write!(
+            output,
+            "\nIRQ Latency: max={max_cycles} delayed={} held_cycles={}",
+            injected.delayed, injected.cycles
+        );
+    }
+    if let (Some(dir), Some(written)) = (&files.printer, printed) {
+        let _ = write!(
+            output,
+            "\nPrinter: {} ({} printouts)",
+            dir.display(),
+            written.len()
+        );
+    }
+    if let (Some(dir), Some(written)) = (&files.export_vram, exported) {
+        let _ = write!(
+            output,
+            "\nVRAM Export: {} ({})",
+            dir.display(),
+            written.join(", ")
+        );
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outputs_turn_on_their_recordings_and_missing_inputs_fail() {
+        let files = ExecFiles {
+            record_gif: Some(PathBuf::from("run.gif")),
+            dump_audio: Some(PathBuf::from("run.wav")),
+            printer: Some(PathBuf::from("prints")),
+            ..ExecFiles::default()
+        };
+        let mut options = ExecOptions::new(10);
+        files.load_into(&mut options).expect("no inputs to read");
+        assert!(options.record_gif && options.record_audio && options.printer);
+        assert!(!options.record_movie);
+
+        let files = ExecFiles {
+            boot_rom: Some(PathBuf::from("/nonexistent/boot.bin")),
+            ..ExecFiles::default()
+        };
+        let err = files.load_into(&mut options).expect_err("no such boot ROM");
+        assert!(err.starts_with("failed to read boot ROM '/nonexistent/boot.bin'"));
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{render_memory_result, ExecutionReport};
+use vibegb_core::{DebugAnnotation, MemoryTestResult};
+
+/// What a single-ROM exec run must show once it ends.
+#[derive(Debug, Clone, Default)]
+pub struct Expectations {
+    pub serial: Option<String>,
+    pub mooneye_pass: bool,
+    pub frame_hash: Option<String>,
+    pub memory_pass: bool,
+    pub debug_message: Option<String>,
+}
+
+impl Expectations {
+    pub fn check(&self, report: &ExecutionReport, context: &str) -> Result<(), String> {
+        assert_expectations(
+            report,
+            self.serial.as_deref(),
+            self.mooneye_pass,
+            self.frame_hash.as_deref(),
+            self.memory_pass,
+            self.debug_message.as_deref(),
+            context,
+        )
+    }
+}
+
+pub fn assert_expectations(
+    report: &ExecutionReport,
+    expect_serial: Option<&str>,
+    expect_mooneye_pass: bool,
+    expect_frame_hash: Option<&str>,
+    expect_memory_pass: bool,
+    expect_debug_message: Option<&str>,
+    context: &str,
+) -> Result<(), String> {
+    if let Some(expected) = expect_serial {
+        if !report.serial_output.contains(expected) {
+            return Err(format!(
+                "{context}: serial expectation failed: expected output containing '{expected}', got '{}'",
+                report.serial_output
+            ));
+        }
+    }
+
+    if expect_mooneye_pass && !(report.bc == 0x0305 && report.de == 0x080D && report.hl == 0x1522) {
+        return Err(format!(
+            "{context}: mooneye pass signature failed: expected BC=0x0305 DE=0x080D HL=0x1522, got BC=0x{:04X} DE=0x{:04X} HL=0x{:04X}",
+            report.bc, report.de, report.hl
+        ));
+    }
+
+    if let Some(expected) = expect_frame_hash {
+        if report.frame_hash != expected {
+            return Err(format!(
+                "{context}: frame hash expectation failed: expected {expected}, got {}",
+                report.frame_hash
+            ));
+        }
+    }
+
+    if expect_memory_pass
+        && !report
+            .memory_result
+            .as_ref()
+            .is_some_and(MemoryTestResult::passed)
+    {
+        return Err(format!(
+            "{context}: memory test expectation failed: expected status 0x00, got {}",
+            render_memory_result(report.memory_result.as_ref())
+        ));
+    }
+
+    if let Some(expected) = expect_debug_message {
+        let found = report.debug_annotations.iter().any(|annotation| {
+            matches!(annotation, DebugAnnotation::Message { text, .. } if text.contains(expected))
+        });
+        if !found {
+            return Err(format!(
+                "{context}: debug message expectation failed: no ld d,d message containing '{expected}'"
+            ));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use vibegb_core::{Button, GameBoy};
+
+/// A press or release applied once the frame counter reaches `frame`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    pub frame: u64,
+    pub button: Button,
+    pub pressed: bool,
+}
+
+/// Button presses and releases keyed to frame numbers, written as
+/// `frame 120: press Start; frame 130: release Start`. Entries are separated by `;` or
+/// newlines and `#` starts a comment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputScript {
+    // Sorted by frame; entries for the same frame keep their written order.
+    events: Vec<InputEvent>,
+}
+
+impl InputScript {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut events = Vec::new();
+        let entries = text
+            .lines()
+            .flat_map(|line| line.split('#').next().unwrap_or_default().split(';'))
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty());
+        for (index, entry) in entries.enumerate() {
+            let event = parse_event(entry).ok_or_else(|| {
+                format!(
+                    "input script entry {} '{entry}' is not 'frame N: press|release BUTTON'",
+                    index + 1
+                )
+            })?;
+            events.push(event);
+        }
+        events.sort_by_key(|event| event.frame);
+        Ok(Self { events })
+    }
+
+    pub fn events(&self) -> &[InputEvent] {
+        &self.events
+    }
+}
+
+fn parse_event(entry: &str) -> Option<InputEvent> {
+    let (frame, action) = entry.strip_prefix("frame")?.split_once(':')?;
+    let frame = frame.trim().parse().ok()?;
+    let (verb, button) = action.trim().split_once(char::is_whitespace)?;
+    let pressed = match verb.to_ascii_lowercase().as_str() {
+        "press" => true,
+        "release" => false,
+        _ => return None,
+    };
+    Some(InputEvent {
+        frame,
+        button: parse_button(button.trim())?,
+        pressed,
+    })
+}
+
+pub fn parse_button(name: &str) -> Option<Button> {
+    let button = match name.to_ascii_lowercase().as_str() {
+        "right" => Button::Right,
+        "left" => Button::Left,
+        "up" => Button::Up,
+        "down" => Button::Down,
+        "a" => Button::A,
+        "b" => Button::B,
+        "select" => Button::Select,
+        "start" => Button::Start,
+        _ => return None,
+    };
+    Some(button)
+}
+
+/// Plays an `InputScript` into a running console.
+#[derive(Debug)]
+pub struct InputPlayer<'a> {
+    script: &'a InputScript,
+    applied: usize,
+}
+
+impl<'a> InputPlayer<'a> {
+    pub fn new(script: &'a InputScript) -> Self {
+        Self { script, applied: 0 }
+    }
+
+    /// Applies every event due by the console's current frame.
+    pub fn apply(&mut self, gb: &mut GameBoy) {
+        let frame = gb.frame_count();
+        for event in &self.script.events[self.applied..] {
+            if event.frame > frame {
+                break;
+            }
+            gb.set_button(event.button, event.pressed);
+            self.applied += 1;
+        }
+    }
+
+    /// How many events have been applied so far.
+    pub fn applied(&self) -> usize {
+        self.applied
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_entries_in_frame_order() {
+        let script = InputScript::parse(
+            "frame 130: release Start # back to the menu\nframe 120: press start; frame 120: press A\n\n",
+        )
+        .expect("script should parse");
+        let events: Vec<_> = script
+            .events()
+            .iter()
+            .map(|event| (event.frame, event.button, event.pressed))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                (120, Button::Start, true),
+                (120, Button::A, true),
+                (130, Button::Start, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_entries() {
+        for text in [
+            "frame x: press A",
+            "frame 1 press A",
+            "frame 1: hold A",
+            "frame 1: press C",
+        ] {
+            assert!(InputScript::parse(text).is_err(), "{text}");
+        }
+        assert_eq!(
+            InputScript::parse("frame 1: press A; wait 2"),
+            Err("input script entry 2 'wait 2' is not 'frame N: press|release BUTTON'".to_string())
+        );
+    }
+
+    #[test]
+    fn player_applies_events_once_their_frame_is_reached() {
+        let script = InputScript::parse("frame 0: press Right; frame 1: release Right")
+            .expect("script should parse");
+        let mut gb = GameBoy::with_program(0x0000, &[0x18, 0xFE]);
+        let mut player = InputPlayer::new(&script);
+        player.apply(&mut gb);
+        assert_eq!(player.applied(), 1);
+        gb.bus.write_byte(0xFF00, 0x20);
+        assert_eq!(gb.bus.read_byte(0xFF00) & 0x0F, 0x0E, "Right is held");
+
+        gb.bus.write_byte(0xFF40, 0x91);
+        while gb.frame_count() < 1 {
+            gb.step().expect("loop should run");
+        }
+        player.apply(&mut gb);
+        assert_eq!(player.applied(), 2);
+        assert_eq!(gb.bus.read_byte(0xFF00) & 0x0F, 0x0F);
+    }
+}
@@ -0,0 +1,24 @@
+pub mod acid2;
+pub mod audio_health;
+pub mod bench;
+pub mod budget;
+pub mod bug_report;
+pub mod compat;
+mod exec;
+mod exec_run;
+mod expectations;
+pub mod frame_hash;
+pub mod input_script;
+pub mod json;
+pub mod png;
+pub mod printer_export;
+mod report;
+mod suite;
+pub mod vram_export;
+pub mod wav;
+
+pub use exec::*;
+pub use exec_run::*;
+pub use expectations::*;
+pub use report::*;
+pub use suite::*;
@@ -0,0 +1,111 @@
+use crate::bug_report::crc32;
+use std::fs;
+use std::path::Path;
+use vibegb_core::{Palette, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+const BIT_DEPTH_8: u8 = 8;
+const COLOR_TYPE_GRAYSCALE: u8 = 0;
+const FILTER_NONE: u8 = 0;
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+pub fn write_frame_png(path: &Path, framebuffer: &[u8]) -> Result<(), String> {
+    fs::write(path, frame_png(framebuffer))
+        .map_err(|err| format!("failed to write frame dump '{}': {err}", path.display()))
+}
+
+/// The framebuffer as an 8-bit grayscale PNG, using the DMG shades FF/AA/55/00.
+pub fn frame_png(framebuffer: &[u8]) -> Vec<u8> {
+    let grays: Vec<u8> = framebuffer
+        .iter()
+        .map(|&shade| Palette::GRAYSCALE.rgba(shade & 0x03)[0])
+        .collect();
+    encode_grayscale(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, &grays)
+}
+
+/// Encodes 8-bit grayscale pixels as a PNG using stored (uncompressed) deflate blocks.
+pub fn encode_grayscale(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    let mut scanlines = Vec::with_capacity(pixels.len() + height as usize);
+    for row in pixels.chunks(width as usize) {
+        scanlines.push(FILTER_NONE);
+        scanlines.extend_from_slice(row);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[BIT_DEPTH_8, COLOR_TYPE_GRAYSCALE, 0, 0, 0]);
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    push_chunk(&mut png, b"IHDR", &header);
+    push_chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+    push_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let len = block.len() as u16;
+        stream.push(u8::from(blocks.peek().is_none()));
+        stream.extend_from_slice(&len.to_le_bytes());
+        stream.extend_from_slice(&(!len).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + u32::from(*byte)) % 65_521;
+        b = (b + a) % 65_521;
+    }
+    (b << 16) | a
+}
+
+fn push_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adler32_matches_reference_value() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn encodes_chunks_and_splits_long_data_into_stored_blocks() {
+        let pixels = vec![0x55; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let png = encode_grayscale(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, &pixels);
+        assert_eq!(&png[..8], &PNG_SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..20], &(SCREEN_WIDTH as u32).to_be_bytes());
+        assert_eq!(&png[20..24], &(SCREEN_HEIGHT as u32).to_be_bytes());
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+
+        let idat_len = u32::from_be_bytes([png[33], png[34], png[35], png[36]]) as usize;
+        assert_eq!(&png[37..41], b"IDAT");
+        let raw_len = (SCREEN_WIDTH + 1) * SCREEN_HEIGHT;
+        assert_eq!(idat_len, 2 + 5 + raw_len + 4);
+        assert_eq!(png[41 + 2], 0x01, "single stored block is final");
+
+        let stream = zlib_stored(&vec![0; MAX_STORED_BLOCK + 1]);
+        assert_eq!(stream.len(), 2 + 5 + MAX_STORED_BLOCK + 5 + 1 + 4);
+        assert_eq!(stream[2], 0x00, "first of two blocks is not final");
+        assert_eq!(stream[2 + 5 + MAX_STORED_BLOCK], 0x01);
+    }
+}
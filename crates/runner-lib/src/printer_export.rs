@@ -0,0 +1,53 @@
+use crate::png::encode_grayscale;
+use std::fs;
+use std::path::Path;
+use vibegb_core::{Palette, Printout, PRINTER_WIDTH};
+
+/// The printed image as a 160-pixel-wide grayscale strip, without the paper margins.
+pub fn printout_png(printout: &Printout) -> Vec<u8> {
+    let grays: Vec<u8> = printout
+        .pixels
+        .iter()
+        .map(|&shade| Palette::GRAYSCALE.rgba(shade)[0])
+        .collect();
+    encode_grayscale(PRINTER_WIDTH as u32, printout.height() as u32, &grays)
+}
+
+/// Writes each printout as `print01.png`, `print02.png`, ... into `dir`, creating it if
+/// needed. Returns the files written.
+pub fn export_printouts(printouts: &[Printout], dir: &Path) -> Result<Vec<String>, String> {
+    fs::create_dir_all(dir)
+        .map_err(|err| format!("failed to create printer dir '{}': {err}", dir.display()))?;
+    let mut files = Vec::new();
+    for (index, printout) in printouts.iter().enumerate() {
+        let name = format!("print{:02}.png", index + 1);
+        let path = dir.join(&name);
+        fs::write(&path, printout_png(printout))
+            .map_err(|err| format!("failed to write '{}': {err}", path.display()))?;
+        files.push(name);
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_are_160_wide_and_as_tall_as_the_print() {
+        let printout = Printout {
+            pixels: vec![3; PRINTER_WIDTH * 16],
+            margins: 0x13,
+            exposure: 0x40,
+        };
+        let png = printout_png(&printout);
+        assert_eq!(&png[16..24], &[0, 0, 0, 160, 0, 0, 0, 16]);
+
+        let dir = std::env::temp_dir().join(format!("vibegb-printer-{}", std::process::id()));
+        let files = export_printouts(&[printout.clone(), printout], &dir)
+            .expect("printouts should be written");
+        assert_eq!(files, ["print01.png", "print02.png"]);
+        assert_eq!(fs::read(dir.join("print02.png")).ok(), Some(png));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
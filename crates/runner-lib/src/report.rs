@@ -0,0 +1,333 @@
+use crate::bug_report::BugReportEntry;
+use crate::png::frame_png;
+use crate::{ExecOptions, ExecutionReport, Expectations, RunOutcome};
+use std::fmt::Write as _;
+use std::path::Path;
+use vibegb_core::{
+    build_info, input_latency_by_frame, CartridgeError, GameBoy, GameQuirk, MapperRegisters,
+    MemoryRegion, MemoryTestResult, PpuRenderer, RomHeader, RtcMode, MAX_ROM_WRITES,
+};
+
+pub fn render_input_latency(gb: &GameBoy) -> String {
+    let events = gb.input_events();
+    let observed: Vec<u64> = events
+        .iter()
+        .filter_map(|event| event.latency_cycles())
+        .collect();
+    let mut output = format!(
+        "\nInput Events: {} (observed {})",
+        events.len(),
+        observed.len()
+    );
+    if let Some(max) = observed.iter().max() {
+        let average = observed.iter().sum::<u64>() / observed.len() as u64;
+        let _ = write!(output, "\nInput Latency: avg={average} max={max} cycles");
+    }
+    for frame in input_latency_by_frame(&events) {
+        let max = frame
+            .max_latency_cycles
+            .map(|cycles| cycles.to_string())
+            .unwrap_or_else(|| "never polled".to_string());
+        let _ = write!(
+            output,
+            "\nFrame {}: events={} observed={} max_latency={}",
+            frame.frame, frame.events, frame.observed, max
+        );
+    }
+    output
+}
+
+pub fn render_rom_writes(gb: &GameBoy) -> String {
+    let writes = gb.rom_writes();
+    let mut output = format!("\nUnexpected ROM Writes: {}", writes.len());
+    if writes.len() == MAX_ROM_WRITES {
+        output.push_str(" (log full)");
+    }
+    for write in writes {
+        let _ = write!(output, "\n  {write}");
+    }
+    output
+}
+
+pub fn render_bus_stats(gb: &GameBoy) -> String {
+    let stats = gb.bus.access_stats();
+    let mut output = format!("\nBus Accesses: frames={}", stats.frames);
+    let frames = stats.frames.max(1);
+    for region in MemoryRegion::ALL {
+        let reads = stats.total.reads(region);
+        let writes = stats.total.writes(region);
+        let _ = write!(
+            output,
+            "\nBus {region}: reads={reads} writes={writes} per_frame={}",
+            (reads + writes) / frames
+        );
+    }
+    if let Some(cartridge) = gb.bus.cartridge() {
+        let banks = cartridge.stats();
+        let _ = write!(output, "\nCartridge Banks: {banks}");
+        let used: Vec<String> = banks
+            .rom_banks_used()
+            .iter()
+            .map(|bank| format!("{bank:02X}"))
+            .collect();
+        let _ = write!(output, "\nROM Banks Read: {}", used.join(","));
+    }
+    output
+}
+
+pub fn render_serial(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return "<empty>".to_string();
+    }
+    bytes
+        .iter()
+        .map(|byte| {
+            if (0x20..=0x7E).contains(byte) || *byte == b'\n' || *byte == b'\r' || *byte == b'\t' {
+                char::from(*byte).to_string()
+            } else {
+                format!("\\x{byte:02X}")
+            }
+        })
+        .collect::<String>()
+}
+
+pub fn render_exec_report(path: &Path, header: &RomHeader, report: &ExecutionReport) -> String {
+    let mut output = format!(
+        "ROM: {}\nMode: exec\nBuild: {}\nSeed: {}\nTitle: {}\nSteps: {}\nCycles: {}\nFrames: {}\nScanline: {}\nPC: 0x{:04X}\nSP: 0x{:04X}\nAF: 0x{:04X}\nBC: 0x{:04X}\nDE: 0x{:04X}\nHL: 0x{:04X}\nFrame Hash: {}\nSerial Output: {}",
+        path.display(),
+        build_info(),
+        report.seed,
+        header.title,
+        report.steps,
+        report.cycles,
+        report.frames,
+        report.scanline,
+        report.pc,
+        report.sp,
+        report.af,
+        report.bc,
+        report.de,
+        report.hl,
+        report.frame_hash,
+        report.serial_output
+    );
+    let _ = write!(output, "\nMapper: {}", render_mapper(report.mapper));
+    let _ = write!(
+        output,
+        "\nMemory Test: {}",
+        render_memory_result(report.memory_result.as_ref())
+    );
+    if !report.debug_annotations.is_empty() {
+        output.push_str("\nDebug Annotations:");
+        for annotation in &report.debug_annotations {
+            let _ = write!(output, "\n  {annotation}");
+        }
+    }
+    output.push_str(&render_header_warnings(header));
+    output
+}
+
+pub fn render_mapper(mapper: Option<MapperRegisters>) -> String {
+    mapper.map_or_else(|| "<none>".to_string(), |mapper| mapper.to_string())
+}
+
+pub fn render_memory_result(result: Option<&MemoryTestResult>) -> String {
+    match result {
+        None => "<none>".to_string(),
+        Some(result) if result.running() => "running".to_string(),
+        Some(result) if result.passed() => format!("passed ({})", result.text.trim_end()),
+        Some(result) => format!(
+            "failed (status 0x{:02X}): {}",
+            result.status,
+            result.text.trim_end()
+        ),
+    }
+}
+
+pub fn render_exec_config(options: &ExecOptions, expectations: &Expectations) -> String {
+    format!(
+        "Mode: exec\nMax Steps: {}\nFrames: {}\nSeed: {}\nRTC Mode: {}\nPPU Renderer: {}\nInput Latency Test: {}\nMobile Adapter: {}\nPrinter: {}\nSerial Clock: {}\nBus Stats: {}\nLog ROM Writes: {}\nBoot ROM: {}\nModel: {}\nInput Script: {}\nMovie: {}\nOAM Corruption Bug: {}\nWindow Glitches: {}\nSTAT Write Bug: {}\nIRQ Latency: {}\nExpect Serial: {}\nExpect Mooneye Pass: {}\nExpect Frame Hash: {}\nExpect Memory Pass: {}\nExpect Debug Message: {}",
+        options.max_steps,
+        options
+            .frames
+            .map_or_else(|| "<none>".to_string(), |frames| frames.to_string()),
+        options.seed,
+        match options.rtc_mode {
+            RtcMode::Emulated => "emulated",
+            RtcMode::WallClock => "wall-clock",
+        },
+        match options.ppu_renderer {
+            PpuRenderer::Scanline => "scanline",
+            PpuRenderer::PixelFifo => "pixel-fifo",
+        },
+        if options.input_latency_test { "yes" } else { "no" },
+        if options.mobile_adapter { "yes" } else { "no" },
+        if options.printer { "yes" } else { "no" },
+        options.serial_clock.map_or_else(
+            || "<none>".to_string(),
+            |hz| format!("{hz} Hz, responses {:02X?}", options.serial_responses)
+        ),
+        if options.bus_stats { "yes" } else { "no" },
+        if options.log_rom_writes { "yes" } else { "no" },
+        options.boot_rom.as_ref().map_or_else(
+            || "<none>".to_string(),
+            |image| format!("{} bytes", image.len())
+        ),
+        options
+            .model
+            .map_or_else(|| "<none>".to_string(), |model| model.to_string()),
+        options.input_script.as_ref().map_or_else(
+            || "<none>".to_string(),
+            |script| format!("{} events", script.events().len())
+        ),
+        match (&options.play_movie, options.record_movie) {
+            (Some(movie), _) => format!("play {} inputs", movie.inputs().len()),
+            (None, true) => "record".to_string(),
+            (None, false) => "<none>".to_string(),
+        },
+        if options.oam_corruption_bug {
+            "yes"
+        } else {
+            "no"
+        },
+        if options.window_glitches { "yes" } else { "no" },
+        if options.stat_write_bug { "yes" } else { "no" },
+        options.irq_latency.map_or_else(
+            || "<none>".to_string(),
+            |cycles| format!("0..={cycles} cycles")
+        ),
+        expectations.serial.as_deref().unwrap_or("<none>"),
+        if expectations.mooneye_pass {
+            "yes"
+        } else {
+            "no"
+        },
+        expectations.frame_hash.as_deref().unwrap_or("<none>"),
+        if expectations.memory_pass {
+            "yes"
+        } else {
+            "no"
+        },
+        expectations.debug_message.as_deref().unwrap_or("<none>")
+    )
+}
+
+pub fn bug_report_entries(
+    rom_path: &Path,
+    header: &RomHeader,
+    config: &str,
+    outcome: &RunOutcome,
+    report: &ExecutionReport,
+    failure: Option<&String>,
+) -> Vec<BugReportEntry> {
+    let mut entries = vec![
+        BugReportEntry::text("version.txt", render_version()),
+        BugReportEntry::text("header.txt", render_header(rom_path, header)),
+        BugReportEntry::text("config.txt", config),
+        BugReportEntry::text("state.txt", render_exec_report(rom_path, header, report)),
+        BugReportEntry::binary("serial.bin", outcome.gb.bus.serial_output().to_vec()),
+        BugReportEntry::text("trace.txt", render_trace_log(&outcome.gb)),
+        BugReportEntry::text("events.txt", render_event_log(&outcome.gb)),
+        BugReportEntry::binary("state.bin", outcome.gb.save_state()),
+        // A run that died mid-frame may not get to VBlank again; the partial frame still helps.
+        BugReportEntry::binary(
+            "screen.png",
+            frame_png(
+                &outcome
+                    .gb
+                    .screenshot()
+                    .unwrap_or_else(|_| outcome.gb.framebuffer().to_vec()),
+            ),
+        ),
+    ];
+    if let Some(failure) = failure {
+        entries.push(BugReportEntry::text("failure.txt", failure.as_str()));
+    }
+    entries
+}
+
+fn render_trace_log(gb: &GameBoy) -> String {
+    let mut log = String::new();
+    for entry in gb.recent_instructions() {
+        let _ = writeln!(log, "{entry}");
+    }
+    log
+}
+
+// Debug annotations (`ld b,b` / `ld d,d`) in the order the program hit them.
+fn render_event_log(gb: &GameBoy) -> String {
+    let mut log = String::new();
+    for annotation in gb.debug_annotations() {
+        let _ = writeln!(log, "{annotation}");
+    }
+    log
+}
+
+pub fn render_version() -> String {
+    format!(
+        "vibegb-runner {}\n{}",
+        env!("CARGO_PKG_VERSION"),
+        build_info()
+    )
+}
+
+pub fn render_header(path: &Path, header: &RomHeader) -> String {
+    let rom_size = header
+        .rom_size_bytes
+        .map(|bytes| format!("{} KiB", bytes / 1024))
+        .unwrap_or_else(|| format!("unknown code 0x{:02X}", header.rom_size_code));
+
+    let ram_size = header
+        .ram_size_bytes
+        .map(|bytes| format!("{} KiB", bytes / 1024))
+        .unwrap_or_else(|| format!("unknown code 0x{:02X}", header.ram_size_code));
+
+    let licensee = header
+        .new_licensee_code
+        .as_deref()
+        .map(|value| format!("new={value}"))
+        .unwrap_or_else(|| format!("old=0x{:02X}", header.old_licensee_code));
+
+    let mut output = format!(
+        "ROM: {}\nBuild: {}\nTitle: {}\nCGB Mode: {}\nSGB Support: {}\nCartridge: 0x{:02X} ({})\nROM Size: {}\nRAM Size: {}\nDestination Code: 0x{:02X}\nLicensee: {}\nMask ROM Version: {}\nHeader Checksum: 0x{:02X}\nGlobal Checksum: 0x{:04X}",
+        path.display(),
+        build_info(),
+        header.title,
+        header.cgb_mode,
+        if header.sgb_supported { "yes" } else { "no" },
+        header.cartridge_type,
+        header.cartridge_type_name(),
+        rom_size,
+        ram_size,
+        header.destination_code,
+        licensee,
+        header.mask_rom_version,
+        header.header_checksum,
+        header.global_checksum
+    );
+    output.push_str(&render_header_warnings(header));
+    output
+}
+
+pub fn render_cartridge_warnings(errors: &[CartridgeError]) -> String {
+    errors
+        .iter()
+        .map(|err| format!("\nCartridge Warning: {err} (booting with a best-guess mapper)"))
+        .collect()
+}
+
+pub fn render_game_quirks(quirks: &[GameQuirk]) -> String {
+    if quirks.is_empty() {
+        return String::new();
+    }
+    let names: Vec<&str> = quirks.iter().map(|quirk| quirk.name()).collect();
+    format!("\nGame Quirks: {}", names.join(", "))
+}
+
+pub fn render_header_warnings(header: &RomHeader) -> String {
+    header
+        .warnings
+        .iter()
+        .map(|warning| format!("\nHeader Warning: {warning}"))
+        .collect()
+}
@@ -0,0 +1,466 @@
+use crate::acid2::{acid2_expected_hash, Acid2Variant, ACID2_FRAMES};
+use crate::budget::{render_budget_line, Completion, CompletionCheck, BUDGET_MARGIN_PERCENT};
+use crate::frame_hash::parse_frame_hash;
+use crate::{assert_expectations, render_mapper, run_with_options, ExecOptions, ExecutionReport};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use vibegb_core::{build_info, Rom};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaseExpectation {
+    SerialContains(String),
+    MooneyePass,
+    MemoryPass,
+    DebugMessage(String),
+    FrameHash { hash: String, frames: Option<u64> },
+    Acid2(Acid2Variant),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuiteCase {
+    pub label: String,
+    pub rom_path: PathBuf,
+    pub max_steps: usize,
+    pub expectation: Option<CaseExpectation>,
+}
+
+/// Runs every case in the suite, in-process or, with `isolated_runner`, each in a child
+/// process of that runner executable.
+pub fn execute_suite(
+    suite_path: &Path,
+    rom_root: Option<&Path>,
+    default_max_steps: usize,
+    isolated_runner: Option<&Path>,
+    suggest_budgets: bool,
+) -> Result<String, String> {
+    let suite_text = fs::read_to_string(suite_path).map_err(|err| {
+        format!(
+            "failed to read suite file '{}': {err}",
+            suite_path.display()
+        )
+    })?;
+    let cases = parse_suite(&suite_text, default_max_steps)?;
+    let mut total = 0usize;
+    let mut passed = 0usize;
+    let mut failed = 0usize;
+    let mut report = format!("Suite: {}\nBuild: {}", suite_path.display(), build_info());
+    let mut budgets = Vec::new();
+
+    for (index, case) in cases.iter().enumerate() {
+        total += 1;
+        let result = match isolated_runner {
+            Some(runner) => {
+                let mut command = Command::new(runner);
+                command.arg("--suite").arg(suite_path);
+                if let Some(root) = rom_root {
+                    command.arg("--rom-root").arg(root);
+                }
+                command
+                    .arg("--max-steps")
+                    .arg(default_max_steps.to_string())
+                    .arg("--suite-case")
+                    .arg(index.to_string());
+                isolated_case_line(command, &case.label)
+            }
+            None => {
+                let rom_path = resolve_case_rom_path(&case.rom_path, suite_path, rom_root);
+                let result = run_suite_case(case, &rom_path);
+                if let (true, Ok(run_report)) = (suggest_budgets, &result) {
+                    budgets.push(render_budget_line(
+                        &case.label,
+                        case.max_steps,
+                        run_report.completion,
+                    ));
+                }
+                suite_case_line(case, result)
+            }
+        };
+        match result {
+            Ok(line) => {
+                passed += 1;
+                let _ = writeln!(report, "\n{line}");
+            }
+            Err(line) => {
+                failed += 1;
+                let _ = writeln!(report, "\n{line}");
+            }
+        }
+    }
+
+    let _ = writeln!(
+        report,
+        "\nSummary: total={total} passed={passed} failed={failed}"
+    );
+    if failed == 0 && suggest_budgets {
+        let _ = writeln!(
+            report,
+            "\nSuggested Budgets (completion + {BUDGET_MARGIN_PERCENT}%):"
+        );
+        for line in &budgets {
+            let _ = writeln!(report, "{line}");
+        }
+    }
+
+    if failed == 0 {
+        Ok(report)
+    } else {
+        Err(report)
+    }
+}
+
+/// Child side of `--isolate`: prints the case's PASS line, or fails with its FAIL line.
+pub fn execute_suite_case(
+    suite_path: &Path,
+    rom_root: Option<&Path>,
+    default_max_steps: usize,
+    index: usize,
+) -> Result<String, String> {
+    let suite_text = fs::read_to_string(suite_path).map_err(|err| {
+        format!(
+            "failed to read suite file '{}': {err}",
+            suite_path.display()
+        )
+    })?;
+    let cases = parse_suite(&suite_text, default_max_steps)?;
+    let case = cases.get(index).ok_or_else(|| {
+        format!(
+            "suite case {index} is out of range for '{}' ({} cases)",
+            suite_path.display(),
+            cases.len()
+        )
+    })?;
+    let rom_path = resolve_case_rom_path(&case.rom_path, suite_path, rom_root);
+    suite_case_line(case, run_suite_case(case, &rom_path))
+}
+
+fn suite_case_line(
+    case: &SuiteCase,
+    result: Result<ExecutionReport, String>,
+) -> Result<String, String> {
+    match result {
+        Ok(run_report) => Ok(format!(
+            "PASS | {} | steps={} | serial={}",
+            case.label,
+            case.max_steps,
+            summarize_serial(&run_report.serial_output)
+        )),
+        Err(reason) => Err(format!("FAIL | {} | {}", case.label, reason)),
+    }
+}
+
+/// Runs one `--suite-case` child. A child that exits 1 reports its own FAIL line; any
+/// other failure (panic, abort, kill) is reported as a crash with its stderr.
+fn isolated_case_line(mut command: Command, label: &str) -> Result<String, String> {
+    let output = command
+        .output()
+        .map_err(|err| format!("FAIL | {label} | failed to start isolated case: {err}"))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if output.status.success() {
+        return Ok(stdout.trim().to_string());
+    }
+    if output.status.code() == Some(1) && stderr.starts_with("FAIL | ") {
+        return Err(stderr.trim().to_string());
+    }
+    let mut line = format!("FAIL | {label} | isolated case crashed ({})", output.status);
+    for stderr_line in stderr.lines() {
+        let _ = write!(line, "\n  stderr: {stderr_line}");
+    }
+    Err(line)
+}
+
+pub fn run_suite_case(case: &SuiteCase, rom_path: &Path) -> Result<ExecutionReport, String> {
+    let rom = Rom::from_file(rom_path).map_err(|err| {
+        format!(
+            "{}: ROM load failed for '{}': {err}",
+            case.label,
+            rom_path.display()
+        )
+    })?;
+    let mut options = ExecOptions::new(case.max_steps);
+    let mut acid2_hash = None;
+    match &case.expectation {
+        Some(CaseExpectation::SerialContains(expected)) => {
+            options.completion = Some(CompletionCheck::SerialContains(expected.clone()));
+        }
+        Some(CaseExpectation::MooneyePass) => {
+            options.completion = Some(CompletionCheck::MooneyePass)
+        }
+        Some(CaseExpectation::MemoryPass) => options.completion = Some(CompletionCheck::MemoryPass),
+        Some(CaseExpectation::DebugMessage(expected)) => {
+            options.completion = Some(CompletionCheck::DebugMessage(expected.clone()));
+        }
+        Some(CaseExpectation::FrameHash { frames, .. }) => options.frames = *frames,
+        Some(CaseExpectation::Acid2(variant)) => {
+            let hash =
+                acid2_expected_hash(*variant).map_err(|err| format!("{}: {err}", case.label))?;
+            acid2_hash = Some(hash);
+            options.frames = Some(ACID2_FRAMES);
+        }
+        None => {}
+    }
+    let mut report = run_with_options(&rom.data, &options).map_err(|err| {
+        format!(
+            "{}: execution failed for '{}': {err}",
+            case.label,
+            rom_path.display()
+        )
+    })?;
+
+    if let Some(expectation) = &case.expectation {
+        let result = match expectation {
+            CaseExpectation::SerialContains(expected) => assert_expectations(
+                &report,
+                Some(expected),
+                false,
+                None,
+                false,
+                None,
+                &case.label,
+            ),
+            CaseExpectation::MooneyePass => {
+                assert_expectations(&report, None, true, None, false, None, &case.label)
+            }
+            CaseExpectation::MemoryPass => {
+                assert_expectations(&report, None, false, None, true, None, &case.label)
+            }
+            CaseExpectation::DebugMessage(expected) => assert_expectations(
+                &report,
+                None,
+                false,
+                None,
+                false,
+                Some(expected),
+                &case.label,
+            ),
+            CaseExpectation::FrameHash { hash, .. } => {
+                assert_expectations(&report, None, false, Some(hash), false, None, &case.label)
+            }
+            CaseExpectation::Acid2(_) => {
+                assert_expectations(&report, None, false, acid2_hash, false, None, &case.label)
+            }
+        };
+        // Banking bugs are the usual suspect when a case fails, so show the final mapper state.
+        result.map_err(|err| format!("{err}\n  Mapper: {}", render_mapper(report.mapper)))?;
+    }
+    // Frame-count cases stop as soon as the last frame is drawn, which is their completion.
+    if options.frames.is_some() {
+        report.completion = Some(Completion {
+            steps: report.steps,
+            frames: report.frames,
+        });
+    }
+
+    Ok(report)
+}
+
+pub fn resolve_case_rom_path(
+    case_path: &Path,
+    suite_path: &Path,
+    rom_root: Option<&Path>,
+) -> PathBuf {
+    if case_path.is_absolute() {
+        return case_path.to_path_buf();
+    }
+    if let Some(root) = rom_root {
+        return root.join(case_path);
+    }
+    match suite_path.parent() {
+        Some(parent) => parent.join(case_path),
+        None => case_path.to_path_buf(),
+    }
+}
+
+pub fn parse_suite(content: &str, default_max_steps: usize) -> Result<Vec<SuiteCase>, String> {
+    let mut cases = Vec::new();
+    for (index, raw_line) in content.lines().enumerate() {
+        let line_no = index + 1;
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let parts: Vec<&str> = line.split('|').map(str::trim).collect();
+        if !(2..=4).contains(&parts.len()) {
+            return Err(format!(
+                "invalid suite line {line_no}: expected 'label|rom_path|[max_steps]|[expectation]'"
+            ));
+        }
+
+        let label = parts[0];
+        let rom_path = parts[1];
+        if label.is_empty() || rom_path.is_empty() {
+            return Err(format!(
+                "invalid suite line {line_no}: label and rom_path are required"
+            ));
+        }
+
+        let max_steps = if parts.len() >= 3 && !parts[2].is_empty() {
+            parts[2].parse::<usize>().map_err(|_| {
+                format!("invalid suite line {line_no}: max_steps must be an integer")
+            })?
+        } else {
+            default_max_steps
+        };
+
+        let expectation = if parts.len() == 4 && !parts[3].is_empty() {
+            Some(
+                parse_expectation(parts[3])
+                    .map_err(|err| format!("invalid suite line {line_no}: {err}"))?,
+            )
+        } else {
+            None
+        };
+
+        cases.push(SuiteCase {
+            label: label.to_string(),
+            rom_path: PathBuf::from(rom_path),
+            max_steps,
+            expectation,
+        });
+    }
+
+    if cases.is_empty() {
+        return Err("suite file contains no runnable cases".to_string());
+    }
+
+    Ok(cases)
+}
+
+pub fn parse_expectation(raw: &str) -> Result<CaseExpectation, String> {
+    if let Some(serial) = raw.strip_prefix("serial:") {
+        if serial.is_empty() {
+            return Err("serial expectation cannot be empty".to_string());
+        }
+        return Ok(CaseExpectation::SerialContains(serial.to_string()));
+    }
+
+    if raw == "mooneye-pass" {
+        return Ok(CaseExpectation::MooneyePass);
+    }
+
+    if raw == "memory-pass" {
+        return Ok(CaseExpectation::MemoryPass);
+    }
+
+    if let Some(message) = raw.strip_prefix("debug-message:") {
+        if message.is_empty() {
+            return Err("debug-message expectation cannot be empty".to_string());
+        }
+        return Ok(CaseExpectation::DebugMessage(message.to_string()));
+    }
+
+    if let Some(spec) = raw.strip_prefix("frame-hash:") {
+        let (hash, frames) = match spec.split_once('@') {
+            Some((hash, frames)) => {
+                let frames = frames
+                    .parse::<u64>()
+                    .map_err(|_| "frame-hash frame count must be an integer".to_string())?;
+                (hash, Some(frames))
+            }
+            None => (spec, None),
+        };
+        return Ok(CaseExpectation::FrameHash {
+            hash: parse_frame_hash(hash)?,
+            frames,
+        });
+    }
+
+    if let Some(variant) = raw.strip_prefix("acid2") {
+        let variant = variant.strip_prefix(':').unwrap_or(variant);
+        return Acid2Variant::parse(variant).map(CaseExpectation::Acid2);
+    }
+
+    Err(
+        "expectation must be 'serial:<text>', 'mooneye-pass', 'memory-pass', 'debug-message:<text>', 'frame-hash:<sha256>[@frames]' or 'acid2[:dmg|cgb]'"
+            .to_string(),
+    )
+}
+
+fn summarize_serial(serial_output: &str) -> String {
+    if serial_output.len() <= 80 {
+        return serial_output.to_string();
+    }
+    format!("{}...", &serial_output[..80])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_suite_lines_with_defaults_and_expectations() {
+        let suite = "\
+# comment
+cpu-01|blargg/cpu01.gb|2000|serial:Passed
+timer-01|mooneye/timer.gb||mooneye-pass
+header-only|misc/smoke.gb
+acid|acid.gb||frame-hash:E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855@30
+dmg-acid2|dmg-acid2.gb||acid2
+cgb-acid2|cgb-acid2.gbc||acid2:cgb
+";
+        let parsed = parse_suite(suite, 555).expect("suite should parse");
+        assert_eq!(parsed.len(), 6);
+        assert_eq!(parsed[0].label, "cpu-01");
+        assert_eq!(parsed[0].max_steps, 2000);
+        assert_eq!(
+            parsed[0].expectation,
+            Some(CaseExpectation::SerialContains("Passed".to_string()))
+        );
+        assert_eq!(parsed[1].max_steps, 555);
+        assert_eq!(parsed[1].expectation, Some(CaseExpectation::MooneyePass));
+        assert_eq!(parsed[2].max_steps, 555);
+        assert_eq!(parsed[2].expectation, None);
+        assert_eq!(
+            parsed[3].expectation,
+            Some(CaseExpectation::FrameHash {
+                hash: "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+                    .to_string(),
+                frames: Some(30),
+            })
+        );
+        assert_eq!(
+            parsed[4].expectation,
+            Some(CaseExpectation::Acid2(Acid2Variant::Dmg))
+        );
+        assert_eq!(
+            parsed[5].expectation,
+            Some(CaseExpectation::Acid2(Acid2Variant::Cgb))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_suite_expectation() {
+        let suite = "bad|rom.gb|100|unknown";
+        let err = parse_suite(suite, 1000).expect_err("should reject unknown expectation");
+        assert!(err.contains("expectation must be 'serial:<text>', 'mooneye-pass'"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn isolated_cases_report_child_results_and_crashes() {
+        let shell = |script: &str| {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(script);
+            command
+        };
+        assert_eq!(
+            isolated_case_line(shell("echo 'PASS | ok | steps=1 | serial=<none>'"), "ok"),
+            Ok("PASS | ok | steps=1 | serial=<none>".to_string())
+        );
+        assert_eq!(
+            isolated_case_line(shell("echo 'FAIL | bad | no match' >&2; exit 1"), "bad"),
+            Err("FAIL | bad | no match".to_string())
+        );
+
+        let err = isolated_case_line(shell("echo 'core panicked' >&2; exit 101"), "boom")
+            .expect_err("a panicking child fails its case");
+        assert!(err.starts_with("FAIL | boom | isolated case crashed (exit status: 101)"));
+        assert!(err.contains("\n  stderr: core panicked"));
+        let err = isolated_case_line(shell("kill -ABRT $$"), "abort")
+            .expect_err("an aborted child fails its case");
+        assert!(err.contains("isolated case crashed (signal: 6"));
+    }
+}
@@ -0,0 +1,139 @@
+use crate::png::encode_grayscale;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use vibegb_core::{GameBoy, Palette, Tile, TileMap, TILE_COUNT, TILE_MAP_SIZE};
+
+// The tile sheet is 16 tiles wide, so the three 128-tile blocks stack as rows of eight.
+const SHEET_COLUMNS: usize = 16;
+const SHEET_ROWS: usize = TILE_COUNT / SHEET_COLUMNS;
+const TILE_SIZE: usize = 8;
+const SHEET_IMAGE: &str = "tiles.png";
+
+/// The 384 VRAM tiles in the Game Boy's own 2bpp format, as tile editors import it.
+pub fn tiles_2bpp(tiles: &[Tile]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(tiles.len() * 16);
+    for tile in tiles {
+        for row in &tile.pixels {
+            let plane = |bit: u8| {
+                row.iter()
+                    .fold(0u8, |byte, &pixel| byte << 1 | (pixel >> bit) & 1)
+            };
+            data.extend([plane(0), plane(1)]);
+        }
+    }
+    data
+}
+
+/// The tiles as a 128x192 grayscale sheet in address order. Pixels are color numbers
+/// shaded as on a DMG with the identity palette, not through BGP/OBP.
+pub fn tiles_png(tiles: &[Tile]) -> Vec<u8> {
+    let width = SHEET_COLUMNS * TILE_SIZE;
+    let mut grays = vec![0; width * SHEET_ROWS * TILE_SIZE];
+    for (index, tile) in tiles.iter().enumerate() {
+        let left = index % SHEET_COLUMNS * TILE_SIZE;
+        let top = index / SHEET_COLUMNS * TILE_SIZE;
+        for (row, pixels) in tile.pixels.iter().enumerate() {
+            for (column, &pixel) in pixels.iter().enumerate() {
+                grays[(top + row) * width + left + column] = Palette::GRAYSCALE.rgba(pixel)[0];
+            }
+        }
+    }
+    encode_grayscale(width as u32, (SHEET_ROWS * TILE_SIZE) as u32, &grays)
+}
+
+/// The map as 32 lines of 32 comma-separated tile indices into the tile sheet.
+pub fn map_csv(map: &TileMap) -> String {
+    let mut csv = String::new();
+    for row in map.entries.chunks(TILE_MAP_SIZE) {
+        let cells: Vec<String> = row
+            .iter()
+            .map(|entry| entry.tile_index.to_string())
+            .collect();
+        let _ = writeln!(csv, "{}", cells.join(","));
+    }
+    csv
+}
+
+/// The map as a Tiled JSON map with one tile layer over `tiles.png`.
+pub fn map_json(map: &TileMap, name: &str) -> String {
+    // Tiled reserves gid 0 for an empty cell.
+    let gids: Vec<String> = map
+        .entries
+        .iter()
+        .map(|entry| (entry.tile_index + 1).to_string())
+        .collect();
+    format!(
+        "{{\"type\":\"map\",\"version\":\"1.10\",\"orientation\":\"orthogonal\",\"renderorder\":\"right-down\",\"infinite\":false,\
+\"width\":{TILE_MAP_SIZE},\"height\":{TILE_MAP_SIZE},\"tilewidth\":{TILE_SIZE},\"tileheight\":{TILE_SIZE},\"nextlayerid\":2,\"nextobjectid\":1,\
+\"layers\":[{{\"id\":1,\"type\":\"tilelayer\",\"name\":\"{name}\",\"x\":0,\"y\":0,\"width\":{TILE_MAP_SIZE},\"height\":{TILE_MAP_SIZE},\"opacity\":1,\"visible\":true,\"data\":[{}]}}],\
+\"tilesets\":[{{\"firstgid\":1,\"name\":\"vram\",\"image\":\"{SHEET_IMAGE}\",\"imagewidth\":{},\"imageheight\":{},\"tilewidth\":{TILE_SIZE},\"tileheight\":{TILE_SIZE},\"tilecount\":{TILE_COUNT},\"columns\":{SHEET_COLUMNS},\"margin\":0,\"spacing\":0}}]}}",
+        gids.join(","),
+        SHEET_COLUMNS * TILE_SIZE,
+        SHEET_ROWS * TILE_SIZE,
+    )
+}
+
+/// Writes `tiles.2bpp`, `tiles.png` and `map0`/`map1` as `.csv` and Tiled `.json` into
+/// `dir`, creating it if needed. Returns the files written.
+pub fn export_vram(gb: &GameBoy, dir: &Path) -> Result<Vec<String>, String> {
+    fs::create_dir_all(dir)
+        .map_err(|err| format!("failed to create export dir '{}': {err}", dir.display()))?;
+    let tiles = gb.decode_tiles();
+    let mut files = vec![
+        ("tiles.2bpp".to_string(), tiles_2bpp(&tiles)),
+        (SHEET_IMAGE.to_string(), tiles_png(&tiles)),
+    ];
+    for index in 0..2 {
+        let map = gb.background_map(index).expect("maps 0 and 1 exist");
+        let name = format!("map{index}");
+        files.push((format!("{name}.csv"), map_csv(&map).into_bytes()));
+        files.push((format!("{name}.json"), map_json(&map, &name).into_bytes()));
+    }
+    for (name, contents) in &files {
+        let path = dir.join(name);
+        fs::write(&path, contents)
+            .map_err(|err| format!("failed to write '{}': {err}", path.display()))?;
+    }
+    Ok(files.into_iter().map(|(name, _)| name).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn striped_gb() -> GameBoy {
+        let mut gb = GameBoy::new();
+        // Tile 1: low plane set on row 0, both planes on row 1.
+        gb.bus.load_bytes(0x8010, &[0xFF, 0x00, 0xF0, 0xF0]);
+        gb.bus.load_bytes(0x9800, &[1, 2]);
+        gb
+    }
+
+    #[test]
+    fn round_trips_2bpp_and_lays_out_the_sheet() {
+        let gb = striped_gb();
+        let tiles = gb.decode_tiles();
+        let data = tiles_2bpp(&tiles);
+        assert_eq!(data.len(), TILE_COUNT * 16);
+        assert_eq!(&data[0x10..0x14], &[0xFF, 0x00, 0xF0, 0xF0]);
+
+        let png = tiles_png(&tiles);
+        assert_eq!(&png[16..24], &[0, 0, 0, 128, 0, 0, 0, 192], "128x192");
+    }
+
+    #[test]
+    fn writes_maps_as_csv_and_tiled_json() {
+        let gb = striped_gb();
+        let map = gb.background_map(0).expect("map 0 exists");
+        let csv = map_csv(&map);
+        assert_eq!(csv.lines().count(), TILE_MAP_SIZE);
+        // The power-on LCDC of 0 uses 0x8800 addressing, where tile 0 is sheet index 256.
+        assert!(csv.starts_with("257,258,256,"));
+
+        let json = map_json(&map, "map0");
+        assert!(json.contains("\"name\":\"map0\""));
+        assert!(json.contains("\"data\":[258,259,257,"));
+        assert!(json.contains("\"image\":\"tiles.png\",\"imagewidth\":128,\"imageheight\":192"));
+    }
+}
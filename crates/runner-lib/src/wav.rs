@@ -0,0 +1,72 @@
+use std::fs;
+use std::path::Path;
+
+const WAV_HEADER_LEN: usize = 44;
+const FORMAT_PCM: u16 = 1;
+const CHANNELS: u16 = 2;
+const BITS_PER_SAMPLE: u16 = 16;
+
+pub fn write_audio_wav(path: &Path, sample_rate: u32, samples: &[f32]) -> Result<(), String> {
+    fs::write(path, encode_wav(sample_rate, samples))
+        .map_err(|err| format!("failed to write audio dump '{}': {err}", path.display()))
+}
+
+/// Interleaved stereo `samples` in -1.0..=1.0 as a 16-bit PCM WAV file.
+pub fn encode_wav(sample_rate: u32, samples: &[f32]) -> Vec<u8> {
+    let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+    let data_len = (samples.len() * 2) as u32;
+    let mut wav = Vec::with_capacity(WAV_HEADER_LEN + samples.len() * 2);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&FORMAT_PCM.to_le_bytes());
+    wav.extend_from_slice(&CHANNELS.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * u32::from(block_align)).to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&BITS_PER_SAMPLE.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        let pcm = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)).round() as i16;
+        wav.extend_from_slice(&pcm.to_le_bytes());
+    }
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_a_stereo_pcm16_header_and_clamped_samples() {
+        let wav = encode_wav(48_000, &[0.0, 1.0, -1.0, 2.0, 0.5, -0.5]);
+        assert_eq!(wav.len(), WAV_HEADER_LEN + 12);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(
+            u32::from_le_bytes([wav[4], wav[5], wav[6], wav[7]]),
+            36 + 12
+        );
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u16::from_le_bytes([wav[22], wav[23]]), 2, "channels");
+        assert_eq!(
+            u32::from_le_bytes([wav[24], wav[25], wav[26], wav[27]]),
+            48_000
+        );
+        assert_eq!(
+            u32::from_le_bytes([wav[28], wav[29], wav[30], wav[31]]),
+            192_000,
+            "byte rate"
+        );
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(u32::from_le_bytes([wav[40], wav[41], wav[42], wav[43]]), 12);
+
+        let pcm: Vec<i16> = wav[WAV_HEADER_LEN..]
+            .chunks_exact(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect();
+        assert_eq!(pcm, [0, 32767, -32767, 32767, 16384, -16384]);
+    }
+}
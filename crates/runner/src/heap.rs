@@ -0,0 +1,164 @@
+//! Accounting global allocator, installed with `--features heap-accounting`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct AccountingAllocator;
+
+#[global_allocator]
+static GLOBAL: AccountingAllocator = AccountingAllocator;
+
+// Counters for the run on the current thread, so other threads (test harness workers,
+// serve connections) never show up in a run's numbers.
+#[derive(Debug, Clone, Copy)]
+struct RunCounters {
+    active: bool,
+    live_bytes: isize,
+    peak_bytes: usize,
+    allocations: usize,
+    limit_bytes: usize,
+    exceeded: bool,
+}
+
+impl RunCounters {
+    const IDLE: Self = Self {
+        active: false,
+        live_bytes: 0,
+        peak_bytes: 0,
+        allocations: 0,
+        limit_bytes: usize::MAX,
+        exceeded: false,
+    };
+}
+
+thread_local! {
+    // Const-initialized and `Copy`, so the allocator can touch it without allocating.
+    static RUN: Cell<RunCounters> = const { Cell::new(RunCounters::IDLE) };
+}
+
+/// Heap activity between `begin_run` and `HeapRun::finish`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapUsage {
+    /// Highest live heap the run's own allocations reached, on top of what the thread
+    /// already held.
+    pub peak_bytes: usize,
+    pub allocations: usize,
+    /// Whether the run's live heap passed the limit at some point.
+    pub exceeded: bool,
+}
+
+pub struct HeapRun(());
+
+/// Starts measuring allocations made on this thread. With `limit`, `limit_exceeded`
+/// reports once the run's live heap passes that many bytes.
+pub fn begin_run(limit: Option<usize>) -> HeapRun {
+    RUN.with(|run| {
+        run.set(RunCounters {
+            active: true,
+            limit_bytes: limit.unwrap_or(usize::MAX),
+            ..RunCounters::IDLE
+        })
+    });
+    HeapRun(())
+}
+
+/// The error a run should stop with once its heap limit is passed.
+pub fn limit_exceeded() -> Option<String> {
+    let run = RUN.with(Cell::get);
+    run.exceeded.then(|| {
+        format!(
+            "heap limit exceeded: the run needed more than {} bytes",
+            run.limit_bytes
+        )
+    })
+}
+
+impl HeapRun {
+    pub fn finish(self) -> HeapUsage {
+        let run = RUN.with(|run| run.replace(RunCounters::IDLE));
+        HeapUsage {
+            peak_bytes: run.peak_bytes,
+            allocations: run.allocations,
+            exceeded: run.exceeded,
+        }
+    }
+}
+
+fn record(grown: isize, allocation: bool) {
+    // `try_with` because the allocator also runs while thread locals are torn down.
+    let _ = RUN.try_with(|run| {
+        let mut counters = run.get();
+        if !counters.active {
+            return;
+        }
+        counters.live_bytes += grown;
+        counters.allocations += usize::from(allocation);
+        let live = counters.live_bytes.max(0) as usize;
+        counters.peak_bytes = counters.peak_bytes.max(live);
+        counters.exceeded |= live > counters.limit_bytes;
+        run.set(counters);
+    });
+}
+
+unsafe impl GlobalAlloc for AccountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            record(layout.size() as isize, true);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            record(layout.size() as isize, true);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        record(-(layout.size() as isize), false);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            record(new_size as isize - layout.size() as isize, true);
+        }
+        new_ptr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hint::black_box;
+
+    #[test]
+    fn reports_peak_and_allocations_for_a_run() {
+        let run = begin_run(None);
+        let buffer = black_box(vec![0u8; 4 * 1024 * 1024]);
+        drop(buffer);
+        let usage = run.finish();
+        assert!(usage.peak_bytes >= 4 * 1024 * 1024);
+        assert!(usage.allocations >= 1);
+        assert!(!usage.exceeded);
+    }
+
+    #[test]
+    fn ignores_other_threads_and_flags_the_limit() {
+        let run = begin_run(Some(1024 * 1024));
+        std::thread::spawn(|| black_box(vec![0u8; 8 * 1024 * 1024]).len())
+            .join()
+            .expect("allocating thread should finish");
+        assert_eq!(limit_exceeded(), None);
+        let buffer = black_box(vec![0u8; 2 * 1024 * 1024]);
+        assert!(limit_exceeded().is_some_and(|err| err.contains("heap limit exceeded")));
+        drop(buffer);
+        let usage = run.finish();
+        assert!(usage.exceeded);
+        assert!(usage.peak_bytes < 8 * 1024 * 1024, "{usage:?}");
+    }
+}
@@ -1,15 +1,141 @@
-use clap::{Parser, ValueEnum};
+#[cfg(feature = "heap-accounting")]
+mod heap;
+#[cfg(feature = "serve")]
+mod serve;
+
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{Arg, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use std::ffi::OsString;
 use std::fmt::Write as _;
 use std::fs;
 use std::path::{Path, PathBuf};
-use vibegb_core::{GameBoy, Rom, RomHeader};
+use vibegb_core::{
+    build_info, check_cartridge, game_quirks, GameBoy, HeaderValidation, Model, PpuRenderer, Rom,
+    RtcMode, MAX_INTERRUPT_LATENCY,
+};
+use vibegb_runner_lib::frame_hash::parse_frame_hash;
+use vibegb_runner_lib::{
+    bench, compat, execute_rom, execute_suite, execute_suite_case, finish_exec, frame_step_budget,
+    render_cartridge_warnings, render_header, ExecFiles, ExecOptions, Expectations,
+};
 
 const DEFAULT_MAX_STEPS: usize = 2_000_000;
+// About ten seconds of emulated time per ROM for `--mode compat`.
+const COMPAT_DEFAULT_FRAMES: u64 = 600;
+// Ten emulated seconds for `--mode bench`, long enough to swamp timer noise.
+const BENCH_DEFAULT_FRAMES: u64 = 600;
+
+const EXEC: &[RunnerMode] = &[RunnerMode::Exec];
+const EXEC_COMPAT: &[RunnerMode] = &[RunnerMode::Exec, RunnerMode::Compat];
+const EXEC_BENCH: &[RunnerMode] = &[RunnerMode::Exec, RunnerMode::Bench];
+const RUNS: &[RunnerMode] = &[RunnerMode::Exec, RunnerMode::Compat, RunnerMode::Bench];
+const LOADS: &[RunnerMode] = &[
+    RunnerMode::Header,
+    RunnerMode::Exec,
+    RunnerMode::Serve,
+    RunnerMode::Compat,
+    RunnerMode::Bench,
+];
+
+// The modes each mode-specific flag applies to; `parse_cli` refuses it in the others.
+const MODE_ARGS: &[(&str, &[RunnerMode])] = &[
+    ("expect_serial", EXEC),
+    ("expect_mooneye_pass", EXEC),
+    ("expect_memory_pass", EXEC),
+    ("expect_debug_message", EXEC),
+    ("expect_frame_hash", EXEC),
+    ("frames", EXEC),
+    ("max_frames", RUNS),
+    ("bug_report", EXEC),
+    ("dump_frame", EXEC),
+    ("export_vram", EXEC),
+    ("record_gif", EXEC),
+    ("dump_audio", EXEC),
+    ("no_audio", EXEC_BENCH),
+    ("rtc_mode", EXEC_COMPAT),
+    ("ppu_renderer", RUNS),
+    ("seed", RUNS),
+    ("input_latency_test", EXEC),
+    ("input_script", EXEC),
+    ("record_movie", EXEC),
+    ("play_movie", EXEC),
+    ("mobile_adapter", EXEC),
+    ("printer", EXEC),
+    ("serial_clock", EXEC),
+    ("lenient_header", LOADS),
+    ("strict_cartridge", &[RunnerMode::Header, RunnerMode::Exec]),
+    ("bus_stats", EXEC),
+    ("log_rom_writes", EXEC),
+    ("boot_rom", EXEC),
+    ("model", EXEC),
+    ("oam_corruption_bug", EXEC_COMPAT),
+    ("window_glitches", EXEC_COMPAT),
+    ("stat_write_bug", EXEC_COMPAT),
+    ("irq_latency", EXEC),
+    ("no_game_quirks", EXEC_COMPAT),
+    ("heap_limit", EXEC),
+    ("listen", &[RunnerMode::Serve]),
+    ("compat_report", &[RunnerMode::Compat]),
+    ("assert_min_speed", &[RunnerMode::Bench]),
+];
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
 enum RunnerMode {
     Header,
     Exec,
+    Serve,
+    /// Boot every ROM in the `--rom` directory and classify how far each gets.
+    Compat,
+    /// Time a pinned workload (or `--rom`) and report emulated seconds per second.
+    Bench,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RtcModeArg {
+    Emulated,
+    WallClock,
+}
+
+impl From<RtcModeArg> for RtcMode {
+    fn from(mode: RtcModeArg) -> Self {
+        match mode {
+            RtcModeArg::Emulated => Self::Emulated,
+            RtcModeArg::WallClock => Self::WallClock,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum PpuRendererArg {
+    Scanline,
+    PixelFifo,
+}
+
+impl From<PpuRendererArg> for PpuRenderer {
+    fn from(renderer: PpuRendererArg) -> Self {
+        match renderer {
+            PpuRendererArg::Scanline => Self::Scanline,
+            PpuRendererArg::PixelFifo => Self::PixelFifo,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ModelArg {
+    Dmg,
+    Mgb,
+    Cgb,
+}
+
+impl From<ModelArg> for Model {
+    fn from(model: ModelArg) -> Self {
+        match model {
+            ModelArg::Dmg => Self::Dmg,
+            ModelArg::Mgb => Self::Mgb,
+            ModelArg::Cgb => Self::Cgb,
+        }
+    }
 }
 
 #[derive(Debug, Parser)]
@@ -18,15 +144,41 @@ enum RunnerMode {
     about = "Headless ROM loader and validation runner for VibeGB"
 )]
 struct Cli {
-    #[arg(short, long, value_name = "PATH", required_unless_present = "suite")]
+    /// Required except with --suite, --mode serve, and --mode bench (which falls back to
+    /// its built-in workload).
+    #[arg(short, long, value_name = "PATH")]
     rom: Option<PathBuf>,
 
-    #[arg(long, value_name = "PATH")]
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = MODE_ARGS.iter().map(|&(id, _)| id)
+    )]
     suite: Option<PathBuf>,
 
     #[arg(long, value_name = "PATH", requires = "suite")]
     rom_root: Option<PathBuf>,
 
+    /// Run each suite case in its own runner process so a panic or abort fails only
+    /// that case.
+    #[arg(long, requires = "suite")]
+    isolate: bool,
+
+    // Set by `--isolate` on the child processes it spawns: run only this case (0-based).
+    #[arg(
+        long,
+        value_name = "INDEX",
+        requires = "suite",
+        conflicts_with = "isolate",
+        hide = true
+    )]
+    suite_case: Option<usize>,
+
+    /// After a passing suite run, suggest a tighter max_steps for each case from the step
+    /// it met its expectation at.
+    #[arg(long, requires = "suite", conflicts_with = "isolate")]
+    suggest_budgets: bool,
+
     #[arg(long, value_enum, default_value_t = RunnerMode::Header)]
     mode: RunnerMode,
 
@@ -38,24 +190,181 @@ struct Cli {
 
     #[arg(long)]
     expect_mooneye_pass: bool,
-}
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-enum CaseExpectation {
-    SerialContains(String),
-    MooneyePass,
-}
+    /// Expect the test result a ROM leaves in cartridge RAM (status byte at 0xA000 and the
+    /// DE B0 61 signature) to be a pass.
+    #[arg(long)]
+    expect_memory_pass: bool,
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-struct SuiteCase {
-    label: String,
-    rom_path: PathBuf,
-    max_steps: usize,
-    expectation: Option<CaseExpectation>,
+    /// Expect an `ld d,d` debug message containing TEXT.
+    #[arg(long, value_name = "TEXT")]
+    expect_debug_message: Option<String>,
+
+    /// Stop exec once the PPU has entered VBlank this many times (still bounded by --max-steps).
+    #[arg(long, value_name = "N")]
+    frames: Option<u64>,
+
+    /// Run exec until the PPU has completed N frames instead of bounding it by --max-steps.
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = ["max_steps", "frames"]
+    )]
+    max_frames: Option<u64>,
+
+    #[arg(long, value_name = "SHA256", value_parser = parse_frame_hash)]
+    expect_frame_hash: Option<String>,
+
+    #[arg(long, value_name = "PATH.zip")]
+    bug_report: Option<PathBuf>,
+
+    #[arg(long, value_name = "PATH.png")]
+    dump_frame: Option<PathBuf>,
+
+    /// Write the VRAM tiles (2bpp and PNG sheet) and both tile maps (CSV and Tiled JSON)
+    /// into this directory when the exec run ends.
+    #[arg(long, value_name = "DIR")]
+    export_vram: Option<PathBuf>,
+
+    /// Record every frame of the exec run as an animated GIF.
+    #[arg(long, value_name = "PATH.gif")]
+    record_gif: Option<PathBuf>,
+
+    /// Write the exec run's sound output as a 16-bit stereo WAV file.
+    #[arg(long, value_name = "PATH.wav")]
+    dump_audio: Option<PathBuf>,
+
+    /// Skip audio mixing and resampling for faster exec runs; sound registers still work.
+    #[arg(long, conflicts_with = "dump_audio")]
+    no_audio: bool,
+
+    #[arg(long, value_enum, default_value_t = RtcModeArg::Emulated)]
+    rtc_mode: RtcModeArg,
+
+    #[arg(long, value_enum, default_value_t = PpuRendererArg::Scanline)]
+    ppu_renderer: PpuRendererArg,
+
+    /// Seeds power-on RAM (0 leaves it cleared). Reports record the seed so the run can be
+    /// reproduced with it.
+    #[arg(long, value_name = "U64")]
+    seed: Option<u64>,
+
+    #[arg(long)]
+    input_latency_test: bool,
+
+    /// Press and release buttons on a schedule, e.g. `frame 120: press Start; frame 130:
+    /// release Start`, to reach in-game states in exec runs.
+    #[arg(long, value_name = "PATH", conflicts_with = "input_latency_test")]
+    input_script: Option<PathBuf>,
+
+    /// Record the exec run's starting state and every button change as a replayable movie.
+    #[arg(long, value_name = "PATH.vgbm")]
+    record_movie: Option<PathBuf>,
+
+    /// Replay a movie recorded with --record-movie against the same ROM.
+    #[arg(
+        long,
+        value_name = "PATH.vgbm",
+        conflicts_with_all = ["record_movie", "input_script", "input_latency_test"]
+    )]
+    play_movie: Option<PathBuf>,
+
+    #[arg(long)]
+    mobile_adapter: bool,
+
+    /// Plug a Game Boy Printer into the link port and write each printed sheet into this
+    /// directory as a PNG strip when the exec run ends.
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["mobile_adapter", "serial_clock"]
+    )]
+    printer: Option<PathBuf>,
+
+    /// Attach a link partner that clocks externally clocked transfers at this rate and
+    /// answers every byte with the --serial-response bytes in turn.
+    #[arg(long, value_name = "HZ", conflicts_with = "mobile_adapter")]
+    serial_clock: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "HEX,...",
+        value_delimiter = ',',
+        value_parser = parse_hex_byte,
+        requires = "serial_clock"
+    )]
+    serial_response: Vec<u8>,
+
+    #[arg(long)]
+    lenient_header: bool,
+
+    /// Refuse a ROM whose cartridge type does not fit the image or its RAM size, instead of
+    /// booting it with a best-guess mapper.
+    #[arg(long)]
+    strict_cartridge: bool,
+
+    #[arg(long)]
+    bus_stats: bool,
+
+    /// Log stores into 0x0000-0x7FFF that no mapper register decodes, with the PC of each.
+    #[arg(long)]
+    log_rom_writes: bool,
+
+    /// Boot through this DMG or CGB boot ROM image instead of starting at 0x0100.
+    #[arg(long, value_name = "PATH")]
+    boot_rom: Option<PathBuf>,
+
+    /// Emulate this model (DMG by default), starting from the register and IO values its
+    /// boot ROM leaves unless --boot-rom runs one.
+    #[arg(long, value_enum)]
+    model: Option<ModelArg>,
+
+    #[arg(long)]
+    oam_corruption_bug: bool,
+
+    /// Emulate DMG window bugs: the WX=166 line that fills the next line and the WX=0
+    /// window that shifts with SCX fine scroll.
+    #[arg(long)]
+    window_glitches: bool,
+
+    /// Emulate the DMG STAT write bug, where writing STAT can raise a spurious interrupt.
+    #[arg(long)]
+    stat_write_bug: bool,
+
+    /// Delay each interrupt dispatch by a random 0..=CYCLES (seeded by --seed) to see whether
+    /// a misbehaving game is sensitive to IRQ timing.
+    #[arg(
+        long,
+        value_name = "CYCLES",
+        value_parser = clap::value_parser!(u32).range(1..=i64::from(MAX_INTERRUPT_LATENCY))
+    )]
+    irq_latency: Option<u32>,
+
+    /// Skip the per-game settings the core's known-games table would apply in exec.
+    #[arg(long)]
+    no_game_quirks: bool,
+
+    /// Stop exec with an error once the run's heap exceeds this many bytes (heap-accounting
+    /// builds).
+    #[arg(long, value_name = "BYTES")]
+    heap_limit: Option<usize>,
+
+    /// Address for `--mode serve` to listen on, e.g. 127.0.0.1:8080.
+    #[arg(long, value_name = "ADDR", required_if_eq("mode", "serve"))]
+    listen: Option<String>,
+
+    /// Write the `--mode compat` results as JSON or HTML, chosen by the extension.
+    #[arg(long, value_name = "PATH.json|PATH.html")]
+    compat_report: Option<PathBuf>,
+
+    /// Fail `--mode bench` when it runs fewer emulated seconds per wall-clock second.
+    #[arg(long, value_name = "SPEED", value_parser = parse_min_speed)]
+    assert_min_speed: Option<f64>,
 }
 
 fn main() {
-    let cli = Cli::parse();
+    let cli = parse_cli(std::env::args_os()).unwrap_or_else(|err| err.exit());
     match execute(cli) {
         Ok(output) => {
             println!("{output}");
@@ -67,367 +376,338 @@ fn main() {
     }
 }
 
-fn execute(cli: Cli) -> Result<String, String> {
-    let Cli {
-        rom,
-        suite,
-        rom_root,
-        mode,
-        max_steps,
-        expect_serial,
-        expect_mooneye_pass,
-    } = cli;
-
-    if let Some(suite_path) = suite {
-        if expect_serial.is_some() || expect_mooneye_pass {
-            return Err(
-                "--expect-serial/--expect-mooneye-pass cannot be used with --suite".to_string(),
-            );
-        }
-        return execute_suite(&suite_path, rom_root.as_deref(), max_steps);
-    }
-
-    let rom_path = rom.ok_or_else(|| "missing required --rom argument".to_string())?;
-    let rom_data = Rom::from_file(&rom_path).map_err(|err| format!("ROM load failed: {err}"))?;
-
-    match mode {
-        RunnerMode::Header => {
-            if expect_serial.is_some() || expect_mooneye_pass {
-                return Err("--expect-serial/--expect-mooneye-pass require --mode exec".to_string());
-            }
-            Ok(render_header(&rom_path, &rom_data.header))
-        }
-        RunnerMode::Exec => {
-            let report = run_for_steps(&rom_data.data, max_steps)?;
-            assert_expectations(
-                &report,
-                expect_serial.as_deref(),
-                expect_mooneye_pass,
-                "single ROM run",
-            )?;
-            Ok(render_exec_report(&rom_path, &rom_data.header, &report))
-        }
-    }
-}
-
-fn execute_suite(
-    suite_path: &Path,
-    rom_root: Option<&Path>,
-    default_max_steps: usize,
-) -> Result<String, String> {
-    let suite_text = fs::read_to_string(suite_path).map_err(|err| {
-        format!(
-            "failed to read suite file '{}': {err}",
-            suite_path.display()
-        )
-    })?;
-    let cases = parse_suite(&suite_text, default_max_steps)?;
-    let mut total = 0usize;
-    let mut passed = 0usize;
-    let mut failed = 0usize;
-    let mut report = format!("Suite: {}", suite_path.display());
-
-    for case in cases {
-        total += 1;
-        let rom_path = resolve_case_rom_path(&case.rom_path, suite_path, rom_root);
-        match run_suite_case(&case, &rom_path) {
-            Ok(run_report) => {
-                passed += 1;
-                let _ = writeln!(
-                    report,
-                    "\nPASS | {} | steps={} | serial={}",
-                    case.label,
-                    case.max_steps,
-                    summarize_serial(&run_report.serial_output)
-                );
-            }
-            Err(reason) => {
-                failed += 1;
-                let _ = writeln!(report, "\nFAIL | {} | {}", case.label, reason);
-            }
-        }
-    }
-
-    let _ = writeln!(
-        report,
-        "\nSummary: total={total} passed={passed} failed={failed}"
-    );
-
-    if failed == 0 {
-        Ok(report)
-    } else {
-        Err(report)
+/// `Cli::try_parse_from`, then refuses flags the chosen `--mode` does not use: clap only
+/// relates flags to flags, not to a flag's value. With `--suite` the mode is unused and
+/// clap itself refuses every flag in `MODE_ARGS`.
+fn parse_cli<I, T>(args: I) -> Result<Cli, clap::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let mut command = Cli::command();
+    let matches = command.try_get_matches_from_mut(args)?;
+    let cli = Cli::from_arg_matches(&matches)?;
+    if cli.suite.is_some() {
+        return Ok(cli);
     }
-}
-
-fn run_suite_case(case: &SuiteCase, rom_path: &Path) -> Result<ExecutionReport, String> {
-    let rom = Rom::from_file(rom_path).map_err(|err| {
-        format!(
-            "{}: ROM load failed for '{}': {err}",
-            case.label,
-            rom_path.display()
-        )
-    })?;
-    let report = run_for_steps(&rom.data, case.max_steps).map_err(|err| {
-        format!(
-            "{}: execution failed for '{}': {err}",
-            case.label,
-            rom_path.display()
-        )
-    })?;
-
-    if let Some(expectation) = &case.expectation {
-        match expectation {
-            CaseExpectation::SerialContains(expected) => {
-                assert_expectations(&report, Some(expected), false, &case.label)?;
-            }
-            CaseExpectation::MooneyePass => {
-                assert_expectations(&report, None, true, &case.label)?;
-            }
+    for &(id, modes) in MODE_ARGS {
+        if matches.value_source(id) == Some(ValueSource::CommandLine) && !modes.contains(&cli.mode)
+        {
+            let flag = command
+                .get_arguments()
+                .find(|arg| arg.get_id() == id)
+                .and_then(Arg::get_long)
+                .unwrap_or(id);
+            let message = format!("--{flag} requires --mode {}", render_modes(modes));
+            return Err(command.error(ErrorKind::ArgumentConflict, message));
         }
     }
-
-    Ok(report)
+    Ok(cli)
 }
 
-fn resolve_case_rom_path(case_path: &Path, suite_path: &Path, rom_root: Option<&Path>) -> PathBuf {
-    if case_path.is_absolute() {
-        return case_path.to_path_buf();
-    }
-    if let Some(root) = rom_root {
-        return root.join(case_path);
-    }
-    match suite_path.parent() {
-        Some(parent) => parent.join(case_path),
-        None => case_path.to_path_buf(),
+fn render_modes(modes: &[RunnerMode]) -> String {
+    let names: Vec<_> = modes
+        .iter()
+        .filter_map(ValueEnum::to_possible_value)
+        .map(|value| value.get_name().to_string())
+        .collect();
+    match names.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} or {last}", rest.join(", ")),
+        _ => names.concat(),
     }
 }
 
-fn parse_suite(content: &str, default_max_steps: usize) -> Result<Vec<SuiteCase>, String> {
-    let mut cases = Vec::new();
-    for (index, raw_line) in content.lines().enumerate() {
-        let line_no = index + 1;
-        let line = raw_line.trim();
-        if line.is_empty() || line.starts_with('#') {
-            continue;
-        }
-
-        let parts: Vec<&str> = line.split('|').map(str::trim).collect();
-        if !(2..=4).contains(&parts.len()) {
-            return Err(format!(
-                "invalid suite line {line_no}: expected 'label|rom_path|[max_steps]|[expectation]'"
-            ));
-        }
-
-        let label = parts[0];
-        let rom_path = parts[1];
-        if label.is_empty() || rom_path.is_empty() {
-            return Err(format!(
-                "invalid suite line {line_no}: label and rom_path are required"
-            ));
+fn execute(cli: Cli) -> Result<String, String> {
+    let validation = if cli.lenient_header {
+        HeaderValidation::Lenient
+    } else {
+        HeaderValidation::Strict
+    };
+    if let Some(suite_path) = &cli.suite {
+        if let Some(index) = cli.suite_case {
+            return execute_suite_case(suite_path, cli.rom_root.as_deref(), cli.max_steps, index);
         }
-
-        let max_steps = if parts.len() >= 3 && !parts[2].is_empty() {
-            parts[2].parse::<usize>().map_err(|_| {
-                format!("invalid suite line {line_no}: max_steps must be an integer")
-            })?
-        } else {
-            default_max_steps
-        };
-
-        let expectation = if parts.len() == 4 && !parts[3].is_empty() {
+        let runner = if cli.isolate {
             Some(
-                parse_expectation(parts[3])
-                    .map_err(|err| format!("invalid suite line {line_no}: {err}"))?,
+                std::env::current_exe()
+                    .map_err(|err| format!("failed to locate the runner for --isolate: {err}"))?,
             )
         } else {
             None
         };
-
-        cases.push(SuiteCase {
-            label: label.to_string(),
-            rom_path: PathBuf::from(rom_path),
-            max_steps,
-            expectation,
-        });
-    }
-
-    if cases.is_empty() {
-        return Err("suite file contains no runnable cases".to_string());
+        return execute_suite(
+            suite_path,
+            cli.rom_root.as_deref(),
+            cli.max_steps,
+            runner.as_deref(),
+            cli.suggest_budgets,
+        );
     }
 
-    Ok(cases)
-}
-
-fn parse_expectation(raw: &str) -> Result<CaseExpectation, String> {
-    if let Some(serial) = raw.strip_prefix("serial:") {
-        if serial.is_empty() {
-            return Err("serial expectation cannot be empty".to_string());
+    match cli.mode {
+        RunnerMode::Serve => {
+            let listen = cli.listen.as_deref().expect("clap requires --listen");
+            let rom = cli
+                .rom
+                .map(|path| Rom::from_file_with(&path, validation))
+                .transpose()
+                .map_err(|err| format!("ROM load failed: {err}"))?;
+            execute_serve(listen, rom, validation)
+        }
+        RunnerMode::Compat => execute_compat(cli, validation),
+        RunnerMode::Bench => execute_bench(cli, validation),
+        RunnerMode::Header | RunnerMode::Exec => {
+            let rom_path = cli
+                .rom
+                .clone()
+                .ok_or_else(|| "missing required --rom argument".to_string())?;
+            let rom = Rom::from_file_with(&rom_path, validation)
+                .map_err(|err| format!("ROM load failed: {err}"))?;
+            let cartridge_errors = check_cartridge(&rom.header, rom.data.len());
+            if let (true, Some(err)) = (cli.strict_cartridge, cartridge_errors.first()) {
+                return Err(format!("ROM load failed: {err}"));
+            }
+            if cli.mode == RunnerMode::Exec {
+                return execute_exec(cli, &rom_path, &rom);
+            }
+            let mut output = render_header(&rom_path, &rom.header);
+            output.push_str(&render_cartridge_warnings(&cartridge_errors));
+            Ok(output)
         }
-        return Ok(CaseExpectation::SerialContains(serial.to_string()));
     }
+}
 
-    if raw == "mooneye-pass" {
-        return Ok(CaseExpectation::MooneyePass);
+fn execute_exec(cli: Cli, rom_path: &Path, rom: &Rom) -> Result<String, String> {
+    let (max_steps, frames) = match cli.max_frames {
+        Some(max_frames) => (frame_step_budget(max_frames), Some(max_frames)),
+        None => (cli.max_steps, cli.frames),
+    };
+    let mut options = ExecOptions::new(max_steps);
+    options.frames = frames;
+    options.rtc_mode = cli.rtc_mode.into();
+    options.ppu_renderer = cli.ppu_renderer.into();
+    options.seed = cli.seed.unwrap_or(0);
+    options.input_latency_test = cli.input_latency_test;
+    options.mobile_adapter = cli.mobile_adapter;
+    options.serial_clock = cli.serial_clock;
+    options.serial_responses = cli.serial_response;
+    options.bus_stats = cli.bus_stats;
+    options.log_rom_writes = cli.log_rom_writes;
+    options.model = cli.model.map(Model::from);
+    options.oam_corruption_bug = cli.oam_corruption_bug;
+    options.window_glitches = cli.window_glitches;
+    options.stat_write_bug = cli.stat_write_bug;
+    options.irq_latency = cli.irq_latency;
+    options.audio_output = !cli.no_audio;
+    options.abort_check = cli.heap_limit.and(heap_limit_check());
+    let files = ExecFiles {
+        boot_rom: cli.boot_rom,
+        input_script: cli.input_script,
+        play_movie: cli.play_movie,
+        record_movie: cli.record_movie,
+        record_gif: cli.record_gif,
+        dump_audio: cli.dump_audio,
+        dump_frame: cli.dump_frame,
+        export_vram: cli.export_vram,
+        printer: cli.printer,
+        bug_report: cli.bug_report,
+    };
+    files.load_into(&mut options)?;
+    let game_quirks = if cli.no_game_quirks {
+        &[]
+    } else {
+        game_quirks(&rom.header)
+    };
+    options.apply_game_quirks(game_quirks);
+    let expectations = Expectations {
+        serial: cli.expect_serial,
+        mooneye_pass: cli.expect_mooneye_pass,
+        frame_hash: cli.expect_frame_hash,
+        memory_pass: cli.expect_memory_pass,
+        debug_message: cli.expect_debug_message,
+    };
+
+    let (outcome, heap_usage) = measure_heap(cli.heap_limit, || execute_rom(&rom.data, &options))?;
+    let mut output = finish_exec(
+        rom_path,
+        rom,
+        &options,
+        game_quirks,
+        outcome,
+        &files,
+        &expectations,
+    )?;
+    if let Some(heap_usage) = heap_usage {
+        output.push_str(&heap_usage);
     }
-
-    Err("expectation must be 'serial:<text>' or 'mooneye-pass'".to_string())
+    Ok(output)
 }
 
-fn run_for_steps(rom_data: &[u8], max_steps: usize) -> Result<ExecutionReport, String> {
-    let mut gb = GameBoy::new();
-    gb.load_rom(rom_data);
-    let mut cycles = 0u64;
-
-    for step in 0..max_steps {
-        let step_cycles = gb
-            .step()
-            .map_err(|err| format!("emulation failed at step {step}: {err}"))?;
-        cycles += u64::from(step_cycles);
-    }
-
-    let regs = gb.cpu.regs;
-    Ok(ExecutionReport {
-        steps: max_steps,
-        cycles,
-        pc: gb.cpu.pc,
-        sp: gb.cpu.sp,
-        af: regs.af(),
-        bc: regs.bc(),
-        de: regs.de(),
-        hl: regs.hl(),
-        serial_output: render_serial(gb.bus.serial_output()),
-    })
+fn execute_compat(cli: Cli, validation: HeaderValidation) -> Result<String, String> {
+    let dir = cli
+        .rom
+        .ok_or_else(|| "--mode compat requires --rom <DIR>".to_string())?;
+    let format = cli
+        .compat_report
+        .as_deref()
+        .map(compat::ReportFormat::from_path)
+        .transpose()?;
+    let mut options = ExecOptions::new(0);
+    options.rtc_mode = cli.rtc_mode.into();
+    options.ppu_renderer = cli.ppu_renderer.into();
+    options.seed = cli.seed.unwrap_or(0);
+    options.oam_corruption_bug = cli.oam_corruption_bug;
+    options.window_glitches = cli.window_glitches;
+    options.stat_write_bug = cli.stat_write_bug;
+    let report = compat::run_compat(
+        &dir,
+        cli.max_frames.unwrap_or(COMPAT_DEFAULT_FRAMES),
+        &options,
+        validation,
+        !cli.no_game_quirks,
+    )?;
+    let mut output = format!(
+        "ROM Directory: {}\nMode: compat\nBuild: {}\nSeed: {}\nFrames: {}\n{}",
+        dir.display(),
+        build_info(),
+        options.seed,
+        report.frames,
+        report.render_text()
+    );
+    if let (Some(path), Some(format)) = (&cli.compat_report, format) {
+        let contents = match format {
+            compat::ReportFormat::Json => report.render_json(),
+            compat::ReportFormat::Html => report.render_html(),
+        };
+        fs::write(path, contents)
+            .map_err(|err| format!("failed to write compat report '{}': {err}", path.display()))?;
+        let _ = write!(output, "\nReport: {}", path.display());
+    }
+    Ok(output)
 }
 
-fn assert_expectations(
-    report: &ExecutionReport,
-    expect_serial: Option<&str>,
-    expect_mooneye_pass: bool,
-    context: &str,
-) -> Result<(), String> {
-    if let Some(expected) = expect_serial {
-        if !report.serial_output.contains(expected) {
+fn execute_bench(cli: Cli, validation: HeaderValidation) -> Result<String, String> {
+    let (workload, rom_data) = match &cli.rom {
+        Some(path) => (
+            path.display().to_string(),
+            Rom::from_file_with(path, validation)
+                .map_err(|err| format!("ROM load failed: {err}"))?
+                .data,
+        ),
+        None => ("builtin".to_string(), bench::builtin_workload()),
+    };
+    let mut gb = GameBoy::with_seed(cli.seed.unwrap_or(0));
+    gb.set_ppu_renderer(cli.ppu_renderer.into());
+    gb.set_audio_output_enabled(!cli.no_audio);
+    gb.load_rom(&rom_data);
+    let result = bench::run_bench(&mut gb, cli.max_frames.unwrap_or(BENCH_DEFAULT_FRAMES))
+        .map_err(|err| format!("emulation failed during bench: {err}"))?;
+    let output = format!(
+        "Workload: {workload}\nMode: bench\nBuild: {}\nFrames: {}\nEmulated Seconds: {:.3}\nWall Seconds: {:.3}\nSpeed: {:.2} emulated s/s",
+        build_info(),
+        result.frames,
+        result.emulated_seconds(),
+        result.wall.as_secs_f64(),
+        result.speed()
+    );
+    if let Some(floor) = cli.assert_min_speed {
+        if result.speed() < floor {
             return Err(format!(
-                "{context}: serial expectation failed: expected output containing '{expected}', got '{}'",
-                report.serial_output
+                "{output}\nspeed regression: {:.2} emulated s/s is below --assert-min-speed {floor}",
+                result.speed()
             ));
         }
     }
+    Ok(output)
+}
 
-    if expect_mooneye_pass && !(report.bc == 0x0305 && report.de == 0x080D && report.hl == 0x1522) {
-        return Err(format!(
-            "{context}: mooneye pass signature failed: expected BC=0x0305 DE=0x080D HL=0x1522, got BC=0x{:04X} DE=0x{:04X} HL=0x{:04X}",
-            report.bc, report.de, report.hl
-        ));
-    }
-
-    Ok(())
+#[cfg(feature = "serve")]
+fn execute_serve(
+    listen: &str,
+    rom: Option<Rom>,
+    validation: HeaderValidation,
+) -> Result<String, String> {
+    serve::serve(listen, rom, validation)
 }
 
-fn summarize_serial(serial_output: &str) -> String {
-    if serial_output.len() <= 80 {
-        return serial_output.to_string();
-    }
-    format!("{}...", &serial_output[..80])
+#[cfg(not(feature = "serve"))]
+fn execute_serve(
+    _listen: &str,
+    _rom: Option<Rom>,
+    _validation: HeaderValidation,
+) -> Result<String, String> {
+    Err("--mode serve requires building vibegb-runner with --features serve".to_string())
 }
 
-fn render_serial(bytes: &[u8]) -> String {
-    if bytes.is_empty() {
-        return "<empty>".to_string();
-    }
-    bytes
-        .iter()
-        .map(|byte| {
-            if (0x20..=0x7E).contains(byte) || *byte == b'\n' || *byte == b'\r' || *byte == b'\t' {
-                char::from(*byte).to_string()
-            } else {
-                format!("\\x{byte:02X}")
-            }
-        })
-        .collect::<String>()
+/// Runs `run` under the accounting allocator, returning its result and a rendered
+/// `Peak Heap:` line counting only the allocations `run` makes on this thread.
+#[cfg(feature = "heap-accounting")]
+fn measure_heap<T>(
+    limit: Option<usize>,
+    run: impl FnOnce() -> T,
+) -> Result<(T, Option<String>), String> {
+    let heap_run = heap::begin_run(limit);
+    let value = run();
+    let usage = heap_run.finish();
+    Ok((
+        value,
+        Some(format!(
+            "\nPeak Heap: {} bytes ({} allocations)",
+            usage.peak_bytes, usage.allocations
+        )),
+    ))
 }
 
-fn render_exec_report(path: &Path, header: &RomHeader, report: &ExecutionReport) -> String {
-    format!(
-        "ROM: {}\nMode: exec\nTitle: {}\nSteps: {}\nCycles: {}\nPC: 0x{:04X}\nSP: 0x{:04X}\nAF: 0x{:04X}\nBC: 0x{:04X}\nDE: 0x{:04X}\nHL: 0x{:04X}\nSerial Output: {}",
-        path.display(),
-        header.title,
-        report.steps,
-        report.cycles,
-        report.pc,
-        report.sp,
-        report.af,
-        report.bc,
-        report.de,
-        report.hl,
-        report.serial_output
-    )
+#[cfg(feature = "heap-accounting")]
+fn heap_limit_check() -> Option<fn() -> Option<String>> {
+    Some(heap::limit_exceeded)
 }
 
-fn render_header(path: &Path, header: &RomHeader) -> String {
-    let rom_size = header
-        .rom_size_bytes
-        .map(|bytes| format!("{} KiB", bytes / 1024))
-        .unwrap_or_else(|| format!("unknown code 0x{:02X}", header.rom_size_code));
+#[cfg(not(feature = "heap-accounting"))]
+fn heap_limit_check() -> Option<fn() -> Option<String>> {
+    None
+}
 
-    let ram_size = header
-        .ram_size_bytes
-        .map(|bytes| format!("{} KiB", bytes / 1024))
-        .unwrap_or_else(|| format!("unknown code 0x{:02X}", header.ram_size_code));
+#[cfg(not(feature = "heap-accounting"))]
+fn measure_heap<T>(
+    limit: Option<usize>,
+    run: impl FnOnce() -> T,
+) -> Result<(T, Option<String>), String> {
+    if limit.is_some() {
+        return Err(
+            "--heap-limit requires building vibegb-runner with --features heap-accounting"
+                .to_string(),
+        );
+    }
+    Ok((run(), None))
+}
 
-    let licensee = header
-        .new_licensee_code
-        .as_deref()
-        .map(|value| format!("new={value}"))
-        .unwrap_or_else(|| format!("old=0x{:02X}", header.old_licensee_code));
-
-    format!(
-        "ROM: {}\nTitle: {}\nCGB Mode: {}\nSGB Support: {}\nCartridge: 0x{:02X} ({})\nROM Size: {}\nRAM Size: {}\nDestination Code: 0x{:02X}\nLicensee: {}\nMask ROM Version: {}\nHeader Checksum: 0x{:02X}\nGlobal Checksum: 0x{:04X}",
-        path.display(),
-        header.title,
-        header.cgb_mode,
-        if header.sgb_supported { "yes" } else { "no" },
-        header.cartridge_type,
-        header.cartridge_type_name(),
-        rom_size,
-        ram_size,
-        header.destination_code,
-        licensee,
-        header.mask_rom_version,
-        header.header_checksum,
-        header.global_checksum
-    )
+fn parse_hex_byte(raw: &str) -> Result<u8, String> {
+    let digits = raw.trim_start_matches("0x");
+    u8::from_str_radix(digits, 16).map_err(|_| format!("'{raw}' is not a hex byte"))
 }
 
-struct ExecutionReport {
-    steps: usize,
-    cycles: u64,
-    pc: u16,
-    sp: u16,
-    af: u16,
-    bc: u16,
-    de: u16,
-    hl: u16,
-    serial_output: String,
+fn parse_min_speed(raw: &str) -> Result<f64, String> {
+    raw.parse::<f64>()
+        .ok()
+        .filter(|speed| speed.is_finite() && *speed > 0.0)
+        .ok_or_else(|| format!("'{raw}' is not a positive speed"))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::Path;
     use std::time::{SystemTime, UNIX_EPOCH};
-
-    const NINTENDO_LOGO: [u8; 48] = [
-        0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00,
-        0x0D, 0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD,
-        0xD9, 0x99, 0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB,
-        0xB9, 0x33, 0x3E,
-    ];
+    use vibegb_core::{
+        GameQuirk, CPU_CLOCK_HZ, DEFAULT_SAMPLE_RATE, SCREEN_HEIGHT, SCREEN_WIDTH, TEST_LOGO,
+    };
+    use vibegb_runner_lib::frame_hash::frame_hash;
 
     #[test]
     fn parses_required_rom_argument() {
-        let cli = Cli::try_parse_from(["vibegb-runner", "--rom", "Pokemon.gb"])
-            .expect("cli parse should succeed");
+        let cli =
+            parse_cli(["vibegb-runner", "--rom", "Pokemon.gb"]).expect("cli parse should succeed");
         assert_eq!(cli.rom, Some(PathBuf::from("Pokemon.gb")));
         assert_eq!(cli.mode, RunnerMode::Header);
         assert_eq!(cli.max_steps, DEFAULT_MAX_STEPS);
@@ -435,15 +715,15 @@ mod tests {
 
     #[test]
     fn supports_short_rom_flag() {
-        let cli = Cli::try_parse_from(["vibegb-runner", "-r", "Pokemon.gb"])
-            .expect("cli parse should succeed");
+        let cli =
+            parse_cli(["vibegb-runner", "-r", "Pokemon.gb"]).expect("cli parse should succeed");
         assert_eq!(cli.rom, Some(PathBuf::from("Pokemon.gb")));
     }
 
     #[test]
     fn prints_header_for_valid_rom() {
         let rom_path = write_rom_with_program("RUNNER TEST", &[]);
-        let cli = Cli::try_parse_from([
+        let cli = parse_cli([
             "vibegb-runner",
             "--rom",
             rom_path.to_str().expect("path should be utf8"),
@@ -452,6 +732,7 @@ mod tests {
 
         let output = execute(cli).expect("valid test ROM should load");
         assert!(output.contains("Title: RUNNER TEST"));
+        assert!(output.contains(&format!("Build: {}", build_info())));
         assert!(output.contains("Cartridge: 0x00 (ROM ONLY)"));
 
         fs::remove_file(rom_path).expect("temp ROM should be removable");
@@ -460,7 +741,7 @@ mod tests {
     #[test]
     fn executes_rom_and_matches_serial_expectation() {
         let rom_path = write_rom_with_program("RUN EXEC", &serial_emit_program(b"PASS"));
-        let cli = Cli::try_parse_from([
+        let cli = parse_cli([
             "vibegb-runner",
             "--rom",
             rom_path.to_str().expect("path should be utf8"),
@@ -475,38 +756,195 @@ mod tests {
 
         let output = execute(cli).expect("execution should succeed");
         assert!(output.contains("Mode: exec"));
+        assert!(output.contains("Build: vibegb-core"));
+        assert!(output.contains("Frames: 0"));
         assert!(output.contains("Serial Output: PASS"));
 
         fs::remove_file(rom_path).expect("temp ROM should be removable");
     }
 
     #[test]
-    fn executes_rom_and_matches_mooneye_signature() {
-        let rom_path = write_rom_with_program("MOONEYE", &mooneye_pass_program());
-        let cli = Cli::try_parse_from([
+    fn seed_reproduces_power_on_ram_and_is_reported() {
+        let program = [
+            0xFA, 0x00, 0xC0, // LD A,(C000)
+            0x47, // LD B,A
+            0xF0, 0x80, // LDH A,(FF80)
+            0x4F, // LD C,A
+            0x18, 0xFE, // JR -2
+        ];
+        let rom_path = write_rom_with_program("SEEDED", &program);
+        let run = |extra: &[&str]| {
+            let mut args = vec![
+                "vibegb-runner",
+                "--rom",
+                rom_path.to_str().expect("path should be utf8"),
+                "--mode",
+                "exec",
+                "--max-steps",
+                "16",
+            ];
+            args.extend_from_slice(extra);
+            execute(parse_cli(args).expect("cli parse should succeed"))
+                .expect("execution should succeed")
+        };
+
+        let cleared = run(&[]);
+        assert!(cleared.contains("Seed: 0\n"));
+        assert!(cleared.contains("BC: 0x0000"));
+
+        let seeded = run(&["--seed", "7"]);
+        assert!(seeded.contains("Seed: 7\n"));
+        assert!(!seeded.contains("BC: 0x0000"));
+        assert_eq!(run(&["--seed", "7"]), seeded);
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+    }
+
+    #[test]
+    fn serial_clock_partner_completes_externally_clocked_transfers() {
+        // LD A,42 ; LDH ($01),A ; LD A,80 ; LDH ($02),A
+        // wait: LDH A,($02) ; BIT 7,A ; JR NZ,wait
+        // LDH A,($01) ; LD B,A ; JR -2
+        let program = [
+            0x3E, 0x42, 0xE0, 0x01, 0x3E, 0x80, 0xE0, 0x02, 0xF0, 0x02, 0xCB, 0x7F, 0x20, 0xFA,
+            0xF0, 0x01, 0x47, 0x18, 0xFE,
+        ];
+        let rom_path = write_rom_with_program("EXT CLOCK", &program);
+        let rom = rom_path.to_str().expect("path should be utf8");
+        let cli = parse_cli([
             "vibegb-runner",
             "--rom",
-            rom_path.to_str().expect("path should be utf8"),
+            rom,
             "--mode",
             "exec",
             "--max-steps",
-            "64",
-            "--expect-mooneye-pass",
+            "2000",
+            "--serial-clock",
+            "8192",
+            "--serial-response",
+            "5A,0xA5",
         ])
         .expect("cli parse should succeed");
 
-        let output = execute(cli).expect("execution should satisfy mooneye signature");
-        assert!(output.contains("BC: 0x0305"));
-        assert!(output.contains("DE: 0x080D"));
-        assert!(output.contains("HL: 0x1522"));
+        let output = execute(cli).expect("execution should succeed");
+        assert!(output.contains("BC: 0x5A13"));
+        assert!(output.contains("Serial Clock Transfers: 1"));
+
+        assert!(parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom,
+            "--serial-clock",
+            "8192",
+            "--serial-response",
+            "5G",
+        ])
+        .is_err());
+        assert!(parse_cli(["vibegb-runner", "--rom", rom, "--serial-response", "5A"]).is_err());
 
         fs::remove_file(rom_path).expect("temp ROM should be removable");
     }
 
     #[test]
-    fn reports_error_when_serial_expectation_fails() {
-        let rom_path = write_rom_with_program("RUN EXEC", &serial_emit_program(b"PASS"));
-        let cli = Cli::try_parse_from([
+    fn applies_and_reports_known_game_quirks() {
+        let rom_path = write_rom_with_program("PREHISTORIK MAN", &serial_emit_program(b"OK"));
+        let run = |extra: &[&str]| {
+            let mut args = vec![
+                "vibegb-runner",
+                "--rom",
+                rom_path.to_str().expect("path should be utf8"),
+                "--mode",
+                "exec",
+                "--max-steps",
+                "128",
+            ];
+            args.extend_from_slice(extra);
+            execute(parse_cli(args).expect("cli parse should succeed"))
+                .expect("execution should succeed")
+        };
+
+        assert!(run(&[]).contains("\nGame Quirks: pixel-fifo-ppu"));
+        assert!(!run(&["--no-game-quirks"]).contains("Game Quirks:"));
+
+        let mut options = ExecOptions::new(128);
+        options.apply_game_quirks(&[GameQuirk::PixelFifoPpu, GameQuirk::OamCorruption]);
+        assert_eq!(options.ppu_renderer, PpuRenderer::PixelFifo);
+        assert!(options.oam_corruption_bug);
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+    }
+
+    #[test]
+    fn compat_mode_classifies_every_rom_in_a_directory() {
+        let dir = temp_dir("compat");
+        fs::create_dir_all(&dir).expect("compat dir should exist");
+        // Starts a square wave on channel 1 and dots tile 0 so the screen is not uniform,
+        // then cycles BGP forever.
+        let animated = [
+            0x3E, 0x80, 0xE0, 0x26, 0x3E, 0x77, 0xE0, 0x24, 0x3E, 0x11, 0xE0, 0x25, 0x3E, 0x80,
+            0xE0, 0x11, 0x3E, 0xF0, 0xE0, 0x12, 0x3E, 0x87, 0xE0, 0x14, 0x3E, 0xFF, 0xEA, 0x00,
+            0x80, 0x3E, 0x91, 0xE0, 0x40, 0x3C, 0xE0, 0x47, 0x18, 0xFB,
+        ];
+        write_rom_file(&dir.join("a-animated.gb"), "ANIMATED", &animated);
+        write_rom_file(
+            &dir.join("b-blank.gbc"),
+            "BLANK",
+            &[0x3E, 0x91, 0xE0, 0x40, 0x18, 0xFE],
+        );
+        write_rom_file(&dir.join("c-lcd-off.gb"), "LCD OFF", &[0x18, 0xFE]);
+        write_rom_file(&dir.join("d-illegal.gb"), "ILLEGAL", &[0xD3]);
+        fs::write(dir.join("e-garbage.gb"), [0u8; 16]).expect("bad ROM should be written");
+        fs::write(dir.join("notes.txt"), "not a ROM").expect("notes should be written");
+        let report_path = dir.join("report.json");
+
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--mode",
+            "compat",
+            "--rom",
+            dir.to_str().expect("path should be utf8"),
+            "--max-frames",
+            "8",
+            "--compat-report",
+            report_path.to_str().expect("path should be utf8"),
+        ])
+        .expect("cli parse should succeed");
+        let output = execute(cli).expect("compat run should succeed");
+
+        assert!(output.contains("Mode: compat\n"));
+        assert!(output.contains("\nboots | a-animated.gb | ANIMATED | "));
+        assert!(output.contains(" changing frames | audio constant-tone\n"));
+        assert!(output.contains(
+            "\nblank | b-blank.gbc | BLANK | no non-blank frame in 8 frames | audio silent\n"
+        ));
+        assert!(output.contains(
+            "\nhang | c-lcd-off.gb | LCD OFF | stopped after 0 of 8 frames with the LCD off"
+        ));
+        assert!(output.contains("\nillegal-opcode | d-illegal.gb | ILLEGAL | illegal opcode 0xD3"));
+        assert!(output.contains("\nload-failed | e-garbage.gb |  | ROM too small"));
+        assert!(output.contains("Audio Summary: ok=0 silent=3 constant-tone=1 clipping=0"));
+        assert!(!output.contains("notes.txt"));
+        assert!(output.contains(
+            "Compat Summary: 5 ROMs, boots=1 blank=1 hang=1 illegal-opcode=1 load-failed=1"
+        ));
+
+        let json = fs::read_to_string(&report_path).expect("report should be written");
+        assert!(json.starts_with("{\"frames\":8,\"summary\":{\"boots\":1,"));
+        assert!(json.contains("\"file\":\"d-illegal.gb\""));
+
+        let dir_arg = dir.to_str().expect("path should be utf8");
+        assert_eq!(
+            rejection(&["--rom", dir_arg, "--compat-report", "out.html"]),
+            "--compat-report requires --mode compat"
+        );
+
+        fs::remove_dir_all(&dir).expect("compat dir should be removable");
+    }
+
+    #[test]
+    fn heap_limit_depends_on_heap_accounting_builds() {
+        let rom_path = write_rom_with_program("HEAP", &serial_emit_program(b"OK"));
+        let cli = parse_cli([
             "vibegb-runner",
             "--rom",
             rom_path.to_str().expect("path should be utf8"),
@@ -514,70 +952,1095 @@ mod tests {
             "exec",
             "--max-steps",
             "128",
-            "--expect-serial",
-            "FAIL",
+            "--heap-limit",
+            "1073741824",
         ])
         .expect("cli parse should succeed");
 
-        let err = execute(cli).expect_err("mismatched serial expectation should fail");
-        assert!(err.contains("serial expectation failed"));
+        let result = execute(cli);
+        if cfg!(feature = "heap-accounting") {
+            let output = result.expect("a 1 GiB cap leaves room for the run");
+            assert!(output.contains("\nPeak Heap: "));
+
+            let cli = parse_cli([
+                "vibegb-runner",
+                "--rom",
+                rom_path.to_str().expect("path should be utf8"),
+                "--mode",
+                "exec",
+                "--max-steps",
+                "128",
+                "--heap-limit",
+                "1",
+            ])
+            .expect("cli parse should succeed");
+            let err = execute(cli).expect_err("the run allocates more than one byte");
+            assert!(err.contains("heap limit exceeded"), "{err}");
+        } else {
+            let err = result.expect_err("no accounting allocator to enforce the cap");
+            assert!(err.contains("--features heap-accounting"));
+        }
 
         fs::remove_file(rom_path).expect("temp ROM should be removable");
     }
 
     #[test]
-    fn parses_suite_lines_with_defaults_and_expectations() {
-        let suite = "\
-# comment
-cpu-01|blargg/cpu01.gb|2000|serial:Passed
-timer-01|mooneye/timer.gb||mooneye-pass
-header-only|misc/smoke.gb
-";
-        let parsed = parse_suite(suite, 555).expect("suite should parse");
-        assert_eq!(parsed.len(), 3);
-        assert_eq!(parsed[0].label, "cpu-01");
-        assert_eq!(parsed[0].max_steps, 2000);
-        assert_eq!(
-            parsed[0].expectation,
-            Some(CaseExpectation::SerialContains("Passed".to_string()))
-        );
-        assert_eq!(parsed[1].max_steps, 555);
-        assert_eq!(parsed[1].expectation, Some(CaseExpectation::MooneyePass));
-        assert_eq!(parsed[2].max_steps, 555);
-        assert_eq!(parsed[2].expectation, None);
-    }
+    fn mobile_adapter_answers_serial_probe() {
+        // LD A,$99 ; LDH ($01),A ; LD A,$81 ; LDH ($02),A
+        // wait: LDH A,($02) ; BIT 7,A ; JR NZ,wait ; LDH A,($01) ; LD B,A ; JR -2
+        let program = [
+            0x3E, 0x99, 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0xF0, 0x02, 0xCB, 0x7F, 0x20, 0xFA,
+            0xF0, 0x01, 0x47, 0x18, 0xFE,
+        ];
+        let rom_path = write_rom_with_program("MOBILE", &program);
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_path.to_str().expect("path should be utf8"),
+            "--mode",
+            "exec",
+            "--max-steps",
+            "2000",
+            "--mobile-adapter",
+        ])
+        .expect("cli parse should succeed");
 
-    #[test]
-    fn rejects_invalid_suite_expectation() {
-        let suite = "bad|rom.gb|100|unknown";
-        let err = parse_suite(suite, 1000).expect_err("should reject unknown expectation");
-        assert!(err.contains("expectation must be 'serial:<text>' or 'mooneye-pass'"));
+        let output = execute(cli).expect("execution should succeed");
+        assert!(output.contains("BC: 0xD2"));
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
     }
 
     #[test]
-    fn executes_suite_and_reports_failure_summary() {
-        let root = temp_dir("suite-root");
-        fs::create_dir_all(&root).expect("suite root dir should exist");
+    fn printer_writes_printed_sheets_as_png_strips() {
+        let packet = |command: u8, compressed: u8, data: &[u8]| {
+            let mut body = vec![command, compressed, data.len() as u8, 0];
+            body.extend_from_slice(data);
+            let sum = body.iter().map(|&byte| u16::from(byte)).sum::<u16>();
+            let mut bytes = vec![0x88, 0x33];
+            bytes.extend(body);
+            bytes.extend(sum.to_le_bytes());
+            bytes.extend([0x00, 0x00]);
+            bytes
+        };
+        // Two tile rows of black: four 129-byte runs and a 124-byte run of 0xFF.
+        let mut tiles = [0xFF, 0xFF].repeat(4);
+        tiles.extend([0xFA, 0xFF]);
+        let mut table = packet(0x01, 0, &[]);
+        table.extend(packet(0x04, 1, &tiles));
+        table.extend(packet(0x04, 0, &[]));
+        table.extend(packet(0x02, 0, &[1, 0x00, 0xE4, 0x40]));
+
+        // LD HL,table ; LD C,len
+        // loop: LD A,(HL+) ; LDH ($01),A ; LD A,$81 ; LDH ($02),A ; DEC C ; JR NZ,loop ; JR -2
+        let mut program = vec![0x21, 0x00, 0x00, 0x0E, table.len() as u8];
+        program.extend([
+            0x2A, 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0x0D, 0x20, 0xF6, 0x18, 0xFE,
+        ]);
+        let table_address = 0x150 + program.len() as u16;
+        program[1..3].copy_from_slice(&table_address.to_le_bytes());
+        program.extend(&table);
+        let rom_path = write_rom_with_program("PRINTER", &program);
+        let dir = temp_dir("printer");
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_path.to_str().expect("path should be utf8"),
+            "--mode",
+            "exec",
+            "--max-steps",
+            "2000",
+            "--printer",
+            dir.to_str().expect("path should be utf8"),
+        ])
+        .expect("cli parse should succeed");
 
-        let serial_rom = root.join("serial-pass.gb");
-        let mooneye_rom = root.join("mooneye-pass.gb");
-        write_rom_file(&serial_rom, "SERIAL", &serial_emit_program(b"Passed"));
-        write_rom_file(&mooneye_rom, "MOONEYE", &mooneye_pass_program());
+        let output = execute(cli).expect("execution should succeed");
+        assert!(output.contains(&format!("\nPrinter: {} (1 printouts)", dir.display())));
+        let png = fs::read(dir.join("print01.png")).expect("the sheet should be written");
+        assert_eq!(&png[16..24], &[0, 0, 0, 160, 0, 0, 0, 16]);
 
-        let suite_path = root.join("m1-suite.txt");
-        let suite = "\
-serial-case|serial-pass.gb|256|serial:Passed
-mooneye-case|mooneye-pass.gb|64|mooneye-pass
-failing-case|serial-pass.gb|256|serial:FAIL
-";
-        fs::write(&suite_path, suite).expect("suite file should be written");
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+        let _ = fs::remove_dir_all(dir);
+    }
 
-        let err = execute_suite(&suite_path, None, DEFAULT_MAX_STEPS)
+    #[test]
+    fn input_latency_test_reports_poll_latency_per_frame() {
+        // LD A,$20 ; LDH ($00),A ; loop: LDH A,($00) ; JR loop
+        let program = [0x3E, 0x20, 0xE0, 0x00, 0xF0, 0x00, 0x18, 0xFC];
+        let rom_path = write_rom_with_program("INPUT LAT", &program);
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_path.to_str().expect("path should be utf8"),
+            "--mode",
+            "exec",
+            "--max-steps",
+            "60000",
+            "--input-latency-test",
+        ])
+        .expect("cli parse should succeed");
+
+        let output = execute(cli).expect("execution should succeed");
+        assert!(output.contains("Input Events: 3 (observed 3)"));
+        assert!(output.contains("Frame 0: events=1 observed=1"));
+        assert!(output.contains("Frame 4: events=1 observed=1"));
+        assert!(output.contains("Frame 8: events=1 observed=1"));
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+    }
+
+    #[test]
+    fn bus_stats_counts_accesses_per_device() {
+        // loop: LDH A,($80) ; LD ($C000),A ; JR loop
+        let program = [0xF0, 0x80, 0xEA, 0x00, 0xC0, 0x18, 0xF9];
+        let rom_path = write_rom_with_program("BUS STATS", &program);
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_path.to_str().expect("path should be utf8"),
+            "--mode",
+            "exec",
+            "--max-steps",
+            "20000",
+            "--bus-stats",
+        ])
+        .expect("cli parse should succeed");
+
+        let output = execute(cli).expect("execution should succeed");
+        assert!(output.contains("Bus Accesses: frames=3"));
+        assert!(output.contains("Bus vram: reads=0 writes=0 per_frame=0"));
+        let line = |region: &str| {
+            output
+                .lines()
+                .find(|line| line.starts_with(&format!("Bus {region}:")))
+                .expect("region line should be present")
+                .to_string()
+        };
+        assert!(!line("hram").contains("reads=0 "));
+        assert!(!line("wram").contains("writes=0 "));
+        assert!(output.contains("\nCartridge Banks: rom_banks_used=1/2 ram_banks_used=0/0"));
+        assert!(output.lines().any(|line| line == "ROM Banks Read: 00"));
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+    }
+
+    #[test]
+    fn logs_rom_writes_only_when_asked() {
+        // LD A,$12 ; LD ($2000),A ; JR -2
+        let program = [0x3E, 0x12, 0xEA, 0x00, 0x20, 0x18, 0xFE];
+        let rom_path = write_rom_with_program("ROM WRITES", &program);
+        let rom_arg = rom_path.to_str().expect("path should be utf8");
+        let run = |extra: &[&str]| {
+            let mut args = vec!["vibegb-runner", "--rom", rom_arg, "--max-steps", "100"];
+            args.extend_from_slice(extra);
+            execute(parse_cli(args).expect("cli parse should succeed"))
+        };
+
+        let output = run(&["--mode", "exec", "--log-rom-writes"]).expect("run should succeed");
+        assert!(output.contains("\nUnexpected ROM Writes: 1\n  PC=0152 wrote 0x12 to 0x2000"));
+        let quiet = run(&["--mode", "exec"]).expect("run should succeed");
+        assert!(!quiet.contains("Unexpected ROM Writes"));
+        assert_eq!(
+            rejection(&["--rom", rom_arg, "--log-rom-writes"]),
+            "--log-rom-writes requires --mode exec"
+        );
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+    }
+
+    #[test]
+    fn boots_through_a_boot_rom_when_given_one() {
+        let rom_path = write_rom_with_program("BOOT ROM", &[0x18, 0xFE]);
+        let rom_arg = rom_path.to_str().expect("path should be utf8");
+        // NOPs up to the unmap write at 0x00FC, as the real boot ROMs end.
+        let mut boot = vec![0x00; 0x100];
+        boot[0xFC..].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x50]);
+        let boot_path = temp_dir("boot-rom");
+        fs::write(&boot_path, &boot).expect("temp boot ROM should be written");
+        let boot_arg = boot_path.to_str().expect("path should be utf8");
+        let run = |steps: &str| {
+            execute(
+                parse_cli([
+                    "vibegb-runner",
+                    "--rom",
+                    rom_arg,
+                    "--mode",
+                    "exec",
+                    "--max-steps",
+                    steps,
+                    "--boot-rom",
+                    boot_arg,
+                ])
+                .expect("cli parse should succeed"),
+            )
+        };
+
+        let output = run("300").expect("run should succeed");
+        assert!(output.contains(&format!("\nBoot ROM: {boot_arg} (unmapped)")));
+        let output = run("10").expect("run should succeed");
+        assert!(output.contains(&format!("\nBoot ROM: {boot_arg} (still mapped)")));
+
+        fs::write(&boot_path, [0; 0x200]).expect("temp boot ROM should be written");
+        assert_eq!(
+            run("10"),
+            Err(
+                "boot ROM load failed: boot ROM is 512 bytes; expected 256 (DMG) or 2304 (CGB)"
+                    .to_string()
+            )
+        );
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+        fs::remove_file(boot_path).expect("temp boot ROM should be removable");
+    }
+
+    #[test]
+    fn model_starts_from_its_post_boot_registers() {
+        let rom_path = write_rom_with_program("MODEL", &[0x18, 0xFE]);
+        let rom_arg = rom_path.to_str().expect("path should be utf8");
+        let run = |extra: &[&str]| {
+            let mut args = vec!["vibegb-runner", "--rom", rom_arg, "--max-steps", "10"];
+            args.extend_from_slice(extra);
+            execute(parse_cli(args).expect("cli parse should succeed"))
+        };
+
+        let output = run(&["--mode", "exec", "--model", "mgb"]).expect("run should succeed");
+        assert!(output.contains("\nAF: 0xFFB0\nBC: 0x0013\nDE: 0x00D8\nHL: 0x014D"));
+        assert_eq!(
+            rejection(&["--rom", rom_arg, "--model", "cgb"]),
+            "--model requires --mode exec"
+        );
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+    }
+
+    #[test]
+    fn exec_without_a_boot_rom_starts_from_dmg_post_boot_state() {
+        // LD B,A ; LDH A,($04) ; LD C,A ; JR -2
+        let rom_path = write_rom_with_program("POST BOOT", &[0x47, 0xF0, 0x04, 0x4F, 0x18, 0xFE]);
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_path.to_str().expect("path should be utf8"),
+            "--mode",
+            "exec",
+            "--max-steps",
+            "10",
+        ])
+        .expect("cli parse should succeed");
+
+        let output = execute(cli).expect("run should succeed");
+        assert!(
+            output.contains("\nBC: 0x01AB"),
+            "A=0x01 and DIV=0xAB: {output}"
+        );
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+    }
+
+    #[test]
+    fn input_script_presses_buttons_on_schedule() {
+        // Select the action buttons, then spin until Start reads pressed and load B.
+        let program = [
+            0x3E, 0x10, 0xE0, 0x00, 0xF0, 0x00, 0xCB, 0x5F, 0x20, 0xFA, 0x06, 0x42, 0x18, 0xFE,
+        ];
+        let rom_path = write_rom_with_program("INPUT SCRIPT", &program);
+        let rom_arg = rom_path.to_str().expect("path should be utf8");
+        let script_path = temp_dir("input-script");
+        fs::write(
+            &script_path,
+            "frame 2: press Start\nframe 900: release Start\n",
+        )
+        .expect("temp script should be written");
+        let script_arg = script_path.to_str().expect("path should be utf8");
+        let run = |extra: &[&str]| {
+            let mut args = vec!["vibegb-runner", "--rom", rom_arg, "--max-steps", "100000"];
+            args.extend_from_slice(extra);
+            execute(parse_cli(args).expect("cli parse should succeed"))
+        };
+
+        let output =
+            run(&["--mode", "exec", "--input-script", script_arg]).expect("run should succeed");
+        assert!(output.contains("\nBC: 0x42"));
+        assert!(output.contains(&format!(
+            "\nInput Script: {script_arg} (1 of 2 events applied)"
+        )));
+        let idle = run(&["--mode", "exec"]).expect("run should succeed");
+        assert!(idle.contains("\nBC: 0x00"));
+
+        fs::write(&script_path, "frame 2: hold Start").expect("temp script should be written");
+        assert_eq!(
+            run(&["--mode", "exec", "--input-script", script_arg]),
+            Err(
+                "input script entry 1 'frame 2: hold Start' is not 'frame N: press|release BUTTON'"
+                    .to_string()
+            )
+        );
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+        fs::remove_file(script_path).expect("temp script should be removable");
+    }
+
+    #[test]
+    fn played_movies_reproduce_the_recorded_run() {
+        // Spins until Start reads pressed, then loads B.
+        let program = [
+            0x3E, 0x10, 0xE0, 0x00, 0xF0, 0x00, 0xCB, 0x5F, 0x20, 0xFA, 0x06, 0x42, 0x18, 0xFE,
+        ];
+        let rom_path = write_rom_with_program("MOVIE", &program);
+        let rom_arg = rom_path.to_str().expect("path should be utf8");
+        let script_path = temp_dir("movie-script");
+        fs::write(&script_path, "frame 3: press Start").expect("temp script should be written");
+        let movie_path = temp_dir("movie");
+        let movie_arg = movie_path.to_str().expect("path should be utf8");
+        let run = |extra: &[&str]| {
+            let mut args = vec![
+                "vibegb-runner",
+                "--rom",
+                rom_arg,
+                "--mode",
+                "exec",
+                "--max-steps",
+                "100000",
+            ];
+            args.extend_from_slice(extra);
+            execute(parse_cli(args).expect("cli parse should succeed"))
+        };
+        let machine = |output: &str| {
+            let start = output.find("\nSteps:").expect("report has steps");
+            let end = output.find("\nSerial Output:").expect("report has serial");
+            output[start..end].to_string()
+        };
+
+        let recorded = run(&[
+            "--input-script",
+            script_path.to_str().expect("path should be utf8"),
+            "--record-movie",
+            movie_arg,
+        ])
+        .expect("recording run should succeed");
+        assert!(recorded.contains(&format!("\nMovie Recorded: {movie_arg} (1 inputs)")));
+        let played = run(&["--play-movie", movie_arg]).expect("playback should succeed");
+        assert!(played.contains(&format!("\nMovie: {movie_arg} (1 of 1 inputs played)")));
+        assert!(played.contains("\nBC: 0x42"));
+        assert_eq!(machine(&played), machine(&recorded));
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+        let other_rom = write_rom_with_program("OTHER", &program);
+        let other = execute(
+            parse_cli([
+                "vibegb-runner",
+                "--rom",
+                other_rom.to_str().expect("path should be utf8"),
+                "--mode",
+                "exec",
+                "--play-movie",
+                movie_arg,
+            ])
+            .expect("cli parse should succeed"),
+        );
+        assert!(other
+            .expect_err("a different ROM should not play the movie")
+            .starts_with("movie playback failed: movie was recorded with a different ROM"));
+
+        fs::remove_file(other_rom).expect("temp ROM should be removable");
+        fs::remove_file(script_path).expect("temp script should be removable");
+        fs::remove_file(movie_path).expect("temp movie should be removable");
+    }
+
+    #[test]
+    fn frame_hash_expectation_checks_framebuffer_after_frames() {
+        let rom_path = write_rom_with_program("FRAMEHASH", &solid_frame_program());
+        let black = frame_hash(&[3; SCREEN_WIDTH * SCREEN_HEIGHT]);
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_path.to_str().expect("path should be utf8"),
+            "--mode",
+            "exec",
+            "--frames",
+            "2",
+            "--expect-frame-hash",
+            &black.to_ascii_uppercase(),
+        ])
+        .expect("cli parse should succeed");
+        let output = execute(cli).expect("solid frame should match its hash");
+        assert!(output.contains(&format!("Frame Hash: {black}")));
+
+        let white = frame_hash(&[0; SCREEN_WIDTH * SCREEN_HEIGHT]);
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_path.to_str().expect("path should be utf8"),
+            "--mode",
+            "exec",
+            "--frames",
+            "2",
+            "--expect-frame-hash",
+            &white,
+        ])
+        .expect("cli parse should succeed");
+        let err = execute(cli).expect_err("white hash should not match");
+        assert!(err.contains("frame hash expectation failed"));
+
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_path.to_str().expect("path should be utf8"),
+            "--mode",
+            "exec",
+            "--max-steps",
+            "100",
+            "--frames",
+            "2",
+        ])
+        .expect("cli parse should succeed");
+        let err = execute(cli).expect_err("100 steps cannot reach two frames");
+        assert!(err.contains("after 0 of 2 frames"));
+
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_path.to_str().expect("path should be utf8"),
+            "--mode",
+            "exec",
+            "--max-frames",
+            "2",
+            "--expect-frame-hash",
+            &black,
+        ])
+        .expect("cli parse should succeed");
+        let output = execute(cli).expect("max frames runs until two frames complete");
+        assert!(output.contains(&format!("Frame Hash: {black}")));
+        for conflicting in [["--max-steps", "100"], ["--frames", "2"]] {
+            assert!(parse_cli([
+                "vibegb-runner",
+                "--rom",
+                "x.gb",
+                "--max-frames",
+                "2",
+                conflicting[0],
+                conflicting[1],
+            ])
+            .is_err());
+        }
+        assert!(parse_cli(["vibegb-runner", "--rom", "x.gb", "--max-frames", "0"]).is_err());
+
+        assert!(parse_cli([
+            "vibegb-runner",
+            "--rom",
+            "x.gb",
+            "--expect-frame-hash",
+            "1234",
+        ])
+        .is_err());
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+    }
+
+    #[test]
+    fn dumps_final_frame_as_grayscale_png() {
+        let rom_path = write_rom_with_program("DUMPFRAME", &solid_frame_program());
+        let png_path =
+            std::env::temp_dir().join(format!("vibegb-runner-test-frame-{}.png", unique_suffix()));
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_path.to_str().expect("path should be utf8"),
+            "--mode",
+            "exec",
+            "--frames",
+            "2",
+            "--dump-frame",
+            png_path.to_str().expect("path should be utf8"),
+        ])
+        .expect("cli parse should succeed");
+        execute(cli).expect("execution should succeed");
+
+        let png = fs::read(&png_path).expect("frame dump should be written");
+        assert_eq!(&png[1..4], b"PNG");
+        // First pixel of the first scanline, after the filter byte: shade 3 is black.
+        let first_pixel = 41 + 2 + 5 + 1;
+        assert_eq!(png[first_pixel], 0x00);
+
+        fs::remove_file(png_path).expect("frame dump should be removable");
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+    }
+
+    #[test]
+    fn dumps_exec_audio_as_wav() {
+        // Starts a square wave on channel 1, then turns the LCD on and loops.
+        let tone = [
+            0x3E, 0x80, 0xE0, 0x26, 0x3E, 0x77, 0xE0, 0x24, 0x3E, 0x11, 0xE0, 0x25, 0x3E, 0x80,
+            0xE0, 0x11, 0x3E, 0xF0, 0xE0, 0x12, 0x3E, 0x00, 0xE0, 0x13, 0x3E, 0x87, 0xE0, 0x14,
+            0x3E, 0x91, 0xE0, 0x40, 0x18, 0xFE,
+        ];
+        let rom_path = write_rom_with_program("DUMPAUDIO", &tone);
+        let wav_path =
+            std::env::temp_dir().join(format!("vibegb-runner-test-audio-{}.wav", unique_suffix()));
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_path.to_str().expect("path should be utf8"),
+            "--mode",
+            "exec",
+            "--max-frames",
+            "30",
+            "--dump-audio",
+            wav_path.to_str().expect("path should be utf8"),
+        ])
+        .expect("cli parse should succeed");
+        let output = execute(cli).expect("execution should succeed");
+        let cycles: u64 = output
+            .lines()
+            .find_map(|line| line.strip_prefix("Cycles: "))
+            .and_then(|cycles| cycles.parse().ok())
+            .expect("report should include cycles");
+
+        let wav = fs::read(&wav_path).expect("audio dump should be written");
+        assert_eq!(&wav[8..12], b"WAVE");
+        let frames = (wav.len() - 44) / 4;
+        let expected = cycles * u64::from(DEFAULT_SAMPLE_RATE) / u64::from(CPU_CLOCK_HZ);
+        assert!(frames.abs_diff(expected as usize) <= 1, "no frames dropped");
+        assert!(wav[44..]
+            .chunks_exact(2)
+            .any(|pcm| i16::from_le_bytes([pcm[0], pcm[1]]) > 3000));
+
+        fs::remove_file(wav_path).expect("audio dump should be removable");
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+    }
+
+    #[test]
+    fn no_audio_skips_mixing_without_changing_the_run() {
+        let tone = [
+            0x3E, 0x80, 0xE0, 0x26, 0x3E, 0x80, 0xE0, 0x11, 0x3E, 0xF0, 0xE0, 0x12, 0x3E, 0x87,
+            0xE0, 0x14, 0x18, 0xFE,
+        ];
+        let rom_path = write_rom_with_program("NOAUDIO", &tone);
+        let rom = fs::read(&rom_path).expect("temp ROM should be readable");
+        let mut options = ExecOptions::new(5000);
+        let audible = execute_rom(&rom, &options);
+        options.audio_output = false;
+        let silent = execute_rom(&rom, &options);
+        assert_eq!(silent.gb.audio_buffered_frames(), 0);
+        assert!(audible.gb.audio_buffered_frames() > 0);
+        assert_eq!(silent.gb.bus.apu().channel_status(), 0x01);
+        assert_eq!(silent.gb.cycle_count(), audible.gb.cycle_count());
+
+        let run = |extra: &[&str]| {
+            let mut args = vec![
+                "vibegb-runner",
+                "--rom",
+                rom_path.to_str().expect("path should be utf8"),
+                "--no-audio",
+            ];
+            args.extend_from_slice(extra);
+            execute(parse_cli(args).expect("cli parse should succeed"))
+        };
+        let rom_arg = rom_path.to_str().expect("path should be utf8");
+        assert_eq!(
+            rejection(&[
+                "--rom",
+                rom_arg,
+                "--mode",
+                "exec",
+                "--no-audio",
+                "--dump-audio",
+                "a.wav"
+            ]),
+            "the argument '--no-audio' cannot be used with '--dump-audio <PATH.wav>'"
+        );
+        assert_eq!(
+            rejection(&["--rom", rom_arg, "--no-audio"]),
+            "--no-audio requires --mode exec or bench"
+        );
+        assert!(run(&["--mode", "exec", "--max-steps", "100"]).is_ok());
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+    }
+
+    #[test]
+    fn records_exec_frames_as_animated_gif() {
+        let rom_path = write_rom_with_program("RECORDGIF", &solid_frame_program());
+        let gif_path =
+            std::env::temp_dir().join(format!("vibegb-runner-test-rec-{}.gif", unique_suffix()));
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_path.to_str().expect("path should be utf8"),
+            "--mode",
+            "exec",
+            "--frames",
+            "3",
+            "--record-gif",
+            gif_path.to_str().expect("path should be utf8"),
+        ])
+        .expect("cli parse should succeed");
+        execute(cli).expect("execution should succeed");
+
+        let gif = fs::read(&gif_path).expect("recording should be written");
+        assert!(gif.starts_with(b"GIF89a"));
+        let frames = gif
+            .windows(4)
+            .filter(|window| window == &[0x21, 0xF9, 0x04, 0x04])
+            .count();
+        assert_eq!(frames, 3);
+
+        fs::remove_file(gif_path).expect("recording should be removable");
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+    }
+
+    #[test]
+    fn executes_rom_and_matches_mooneye_signature() {
+        let rom_path = write_rom_with_program("MOONEYE", &mooneye_pass_program());
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_path.to_str().expect("path should be utf8"),
+            "--mode",
+            "exec",
+            "--max-steps",
+            "64",
+            "--expect-mooneye-pass",
+        ])
+        .expect("cli parse should succeed");
+
+        let output = execute(cli).expect("execution should satisfy mooneye signature");
+        assert!(output.contains("BC: 0x0305"));
+        assert!(output.contains("DE: 0x080D"));
+        assert!(output.contains("HL: 0x1522"));
+        assert!(output.contains("Mapper: ROM rom_bank=0x001 ram_bank=0x00 ram_enabled=no"));
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+    }
+
+    #[test]
+    fn checks_homebrew_memory_results_and_debug_messages() {
+        let root = temp_dir("homebrew");
+        fs::create_dir_all(&root).expect("homebrew dir should exist");
+        let rom_path = root.join("homebrew.gb");
+        write_rom_file(&rom_path, "HOMEBREW", &homebrew_test_program());
+        // ROM+RAM with 8 KiB, so the result has somewhere to go.
+        let mut rom = fs::read(&rom_path).expect("temp ROM should be readable");
+        rom[0x147] = 0x08;
+        rom[0x149] = 0x02;
+        rom[0x14D] = calculate_header_checksum(&rom);
+        fs::write(&rom_path, rom).expect("temp ROM should be written");
+        let rom_arg = rom_path.to_str().expect("path should be utf8");
+
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_arg,
+            "--mode",
+            "exec",
+            "--max-steps",
+            "64",
+            "--expect-memory-pass",
+            "--expect-debug-message",
+            "done",
+        ])
+        .expect("cli parse should succeed");
+        let output = execute(cli).expect("the ROM passes and says so");
+        assert!(output.contains("Memory Test: passed (ok)"));
+        assert!(output.contains("Debug Annotations:\n  PC=016B message: done"));
+
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_arg,
+            "--mode",
+            "exec",
+            "--max-steps",
+            "64",
+            "--expect-debug-message",
+            "missing",
+        ])
+        .expect("cli parse should succeed");
+        let err = execute(cli).expect_err("no such message");
+        assert!(err.contains("no ld d,d message containing 'missing'"));
+
+        let suite_path = root.join("homebrew-suite.txt");
+        fs::write(
+            &suite_path,
+            "memory|homebrew.gb|64|memory-pass\nmessage|homebrew.gb|64|debug-message:done\n",
+        )
+        .expect("suite file should be written");
+        let output = execute_suite(&suite_path, None, DEFAULT_MAX_STEPS, None, true)
+            .expect("both conventions should pass");
+        assert!(output.contains("BUDGET | memory | max_steps=64 -> 1000 | completed at step 18"));
+        assert!(output.contains("BUDGET | message | max_steps=64 -> 1000 | completed at step 19"));
+
+        fs::remove_dir_all(&root).expect("homebrew dir should be removable");
+    }
+
+    #[test]
+    fn reports_error_when_serial_expectation_fails() {
+        let rom_path = write_rom_with_program("RUN EXEC", &serial_emit_program(b"PASS"));
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_path.to_str().expect("path should be utf8"),
+            "--mode",
+            "exec",
+            "--max-steps",
+            "128",
+            "--expect-serial",
+            "FAIL",
+        ])
+        .expect("cli parse should succeed");
+
+        let err = execute(cli).expect_err("mismatched serial expectation should fail");
+        assert!(err.contains("serial expectation failed"));
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+    }
+
+    #[test]
+    fn emulation_errors_include_the_recent_instruction_trace() {
+        // LD B,$12 ; illegal $D3
+        let rom_path = write_rom_with_program("TRACE", &[0x06, 0x12, 0xD3]);
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_path.to_str().expect("path should be utf8"),
+            "--mode",
+            "exec",
+        ])
+        .expect("cli parse should succeed");
+
+        let err = execute(cli).expect_err("illegal opcode should fail the run");
+        assert!(err.contains("illegal opcode 0xD3\nLast Instructions (oldest first):\n"));
+        let trace: Vec<&str> = err
+            .lines()
+            .filter(|line| line.starts_with("  PC="))
+            .collect();
+        assert!(trace[trace.len() - 2].starts_with("  PC=0150 OP=06"));
+        assert!(trace[trace.len() - 1].starts_with("  PC=0152 OP=D3 AF="));
+        assert!(trace[trace.len() - 1].contains(" BC=12"));
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+    }
+
+    #[test]
+    fn writes_bug_report_archive_even_when_expectation_fails() {
+        let rom_path = write_rom_with_program("BUG REPORT", &serial_emit_program(b"PASS"));
+        let archive_path = temp_dir("bug-report").with_extension("zip");
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_path.to_str().expect("path should be utf8"),
+            "--mode",
+            "exec",
+            "--max-steps",
+            "128",
+            "--expect-serial",
+            "FAIL",
+            "--bug-report",
+            archive_path.to_str().expect("path should be utf8"),
+        ])
+        .expect("cli parse should succeed");
+
+        let err = execute(cli).expect_err("mismatched serial expectation should fail");
+        assert!(err.contains("serial expectation failed"));
+
+        let archive = fs::read(&archive_path).expect("bug report should be written");
+        for name in [
+            "version.txt",
+            "header.txt",
+            "config.txt",
+            "state.txt",
+            "serial.bin",
+            "trace.txt",
+            "events.txt",
+            "state.bin",
+            "screen.png",
+            "failure.txt",
+        ] {
+            assert!(
+                archive
+                    .windows(name.len())
+                    .any(|window| window == name.as_bytes()),
+                "bug report should contain {name}"
+            );
+        }
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+        fs::remove_file(archive_path).expect("bug report should be removable");
+    }
+
+    #[test]
+    fn suites_refuse_every_mode_flag() {
+        assert_eq!(
+            rejection(&["--suite", "suite.txt", "--bug-report", "report.zip"]),
+            "the argument '--suite <PATH>' cannot be used with '--bug-report <PATH.zip>'"
+        );
+        assert_eq!(
+            rejection(&["--suite", "suite.txt", "--oam-corruption-bug"]),
+            "the argument '--suite <PATH>' cannot be used with '--oam-corruption-bug'"
+        );
+        assert!(parse_cli(["vibegb-runner", "--suite", "suite.txt", "--max-steps", "9"]).is_ok());
+    }
+
+    #[test]
+    fn mode_flags_name_the_modes_they_need() {
+        let command = Cli::command();
+        for &(id, modes) in MODE_ARGS {
+            assert!(
+                command.get_arguments().any(|arg| arg.get_id() == id),
+                "{id} is a flag"
+            );
+            assert!(!modes.is_empty(), "{id} belongs to a mode");
+        }
+        assert_eq!(
+            rejection(&["--rom", "game.gb", "--max-frames", "9"]),
+            "--max-frames requires --mode exec, compat or bench"
+        );
+        assert_eq!(
+            rejection(&["--mode", "bench", "--strict-cartridge"]),
+            "--strict-cartridge requires --mode header or exec"
+        );
+        assert!(parse_cli(["vibegb-runner", "--mode", "bench", "--seed", "3"]).is_ok());
+    }
+
+    #[test]
+    fn bench_mode_reports_speed_and_gates_on_a_floor() {
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--mode",
+            "bench",
+            "--max-frames",
+            "2",
+            "--assert-min-speed",
+            "0.000001",
+        ])
+        .expect("cli parse should succeed");
+        let output = execute(cli).expect("any machine beats the floor");
+        assert!(output.starts_with("Workload: builtin\nMode: bench\n"));
+        assert!(output.contains("\nFrames: 2\n"));
+        assert!(output.contains(" emulated s/s"));
+
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--mode",
+            "bench",
+            "--max-frames",
+            "2",
+            "--assert-min-speed",
+            "1e12",
+        ])
+        .expect("cli parse should succeed");
+        let err = execute(cli).expect_err("no machine reaches the floor");
+        assert!(err.contains("speed regression:"));
+        assert!(err.contains("is below --assert-min-speed 1000000000000"));
+
+        assert_eq!(
+            rejection(&["--rom", "game.gb", "--assert-min-speed", "2"]),
+            "--assert-min-speed requires --mode bench"
+        );
+        assert!(parse_cli([
+            "vibegb-runner",
+            "--mode",
+            "bench",
+            "--assert-min-speed",
+            "0"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn exports_vram_after_an_exec_run() {
+        let root = temp_dir("export-vram");
+        fs::create_dir_all(&root).expect("export dir should exist");
+        let rom_path = root.join("vram.gb");
+        // LD A,FF ; LD (8010),A ; HALT
+        write_rom_file(&rom_path, "VRAM", &[0x3E, 0xFF, 0xEA, 0x10, 0x80, 0x76]);
+        let out_dir = root.join("assets");
+
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_path.to_str().expect("path should be utf8"),
+            "--mode",
+            "exec",
+            "--max-steps",
+            "8",
+            "--export-vram",
+            out_dir.to_str().expect("path should be utf8"),
+        ])
+        .expect("cli parse should succeed");
+        let output = execute(cli).expect("exec should run");
+        assert!(output.contains("tiles.2bpp, tiles.png, map0.csv, map0.json, map1.csv, map1.json)"));
+        let tiles = fs::read(out_dir.join("tiles.2bpp")).expect("tiles should be written");
+        assert_eq!(tiles[0x10], 0xFF);
+        let map = fs::read_to_string(out_dir.join("map1.json")).expect("map should be written");
+        assert!(map.contains("\"type\":\"tilelayer\",\"name\":\"map1\""));
+
+        fs::remove_dir_all(&root).expect("export dir should be removable");
+    }
+
+    #[test]
+    fn irq_latency_delays_dispatch_in_exec_only() {
+        let root = temp_dir("irq-latency");
+        fs::create_dir_all(&root).expect("irq latency dir should exist");
+        let rom_path = root.join("irq.gb");
+        let program = [
+            0x3E, 0x04, // LD A,04
+            0xE0, 0x0F, // LDH (IF),A
+            0xE0, 0xFF, // LDH (IE),A
+            0xFB, // EI
+            0x04, // INC B
+            0x18, 0xFD, // JR -3
+        ];
+        write_rom_file(&rom_path, "IRQ", &program);
+        let rom_arg = rom_path.to_str().expect("path should be utf8");
+
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            rom_arg,
+            "--mode",
+            "exec",
+            "--max-steps",
+            "40",
+            "--seed",
+            "7",
+            "--irq-latency",
+            "456",
+        ])
+        .expect("cli parse should succeed");
+        let output = execute(cli).expect("exec should run");
+        assert!(output.contains("IRQ Latency: max=456 delayed=1 held_cycles="));
+
+        assert_eq!(
+            rejection(&["--rom", rom_arg, "--irq-latency", "456"]),
+            "--irq-latency requires --mode exec"
+        );
+        assert!(
+            parse_cli(["vibegb-runner", "--rom", rom_arg, "--irq-latency", "457"]).is_err(),
+            "latency is bounded"
+        );
+
+        fs::remove_dir_all(&root).expect("irq latency dir should be removable");
+    }
+
+    #[test]
+    fn serve_mode_needs_listen_and_listen_needs_serve_mode() {
+        assert_eq!(
+            rejection(&["--mode", "serve", "--rom", "game.gb"]),
+            "the following required arguments were not provided:\n  --listen <ADDR>"
+        );
+        assert_eq!(
+            rejection(&["--listen", "127.0.0.1:0"]),
+            "--listen requires --mode serve"
+        );
+    }
+
+    #[test]
+    fn rtc_mode_defaults_to_emulated_and_rejects_wall_clock_suites() {
+        let cli =
+            parse_cli(["vibegb-runner", "--rom", "game.gb"]).expect("cli parse should succeed");
+        assert_eq!(cli.rtc_mode, RtcModeArg::Emulated);
+        assert_eq!(cli.ppu_renderer, PpuRendererArg::Scanline);
+
+        assert_eq!(
+            rejection(&["--suite", "suite.txt", "--rtc-mode", "wall-clock"]),
+            "the argument '--suite <PATH>' cannot be used with '--rtc-mode <RTC_MODE>'"
+        );
+    }
+
+    #[test]
+    fn strict_cartridge_refuses_oversized_rom_only_images() {
+        let rom_path = write_rom_with_program("OVERSIZED", &[0x18, 0xFE]);
+        let mut rom = fs::read(&rom_path).expect("temp ROM should be readable");
+        rom.resize(0x20000, 0);
+        fs::write(&rom_path, rom).expect("temp ROM should be writable");
+        let path = rom_path.to_string_lossy().to_string();
+        let args = ["vibegb-runner", "--rom", &path, "--mode", "exec"];
+
+        let cli =
+            parse_cli(args.iter().chain(&["--max-steps", "10"])).expect("cli parse should succeed");
+        let output = execute(cli).expect("permissive run should succeed");
+        assert!(output.contains(
+            "Cartridge Warning: cartridge type is ROM only but the image is 128 KiB (booting with a best-guess mapper)"
+        ));
+
+        let cli = parse_cli(args.iter().chain(&["--strict-cartridge"]))
+            .expect("cli parse should succeed");
+        let err = execute(cli).expect_err("strict load should refuse the ROM");
+        assert_eq!(
+            err,
+            "ROM load failed: cartridge type is ROM only but the image is 128 KiB"
+        );
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+    }
+
+    #[test]
+    fn lenient_header_runs_roms_with_a_custom_logo() {
+        let rom_path = write_rom_with_program("CUSTOMLOGO", &[0x18, 0xFE]);
+        let mut rom = fs::read(&rom_path).expect("temp ROM should be readable");
+        rom[0x104..0x134].fill(0xAA);
+        fs::write(&rom_path, rom).expect("temp ROM should be writable");
+        let path = rom_path.to_string_lossy().to_string();
+
+        let cli = parse_cli(["vibegb-runner", "--rom", &path]).expect("cli parse should succeed");
+        let err = execute(cli).expect_err("strict parse should reject the logo");
+        assert!(err.contains("invalid Nintendo logo"));
+
+        let cli = parse_cli([
+            "vibegb-runner",
+            "--rom",
+            &path,
+            "--mode",
+            "exec",
+            "--max-steps",
+            "10",
+            "--lenient-header",
+        ])
+        .expect("cli parse should succeed");
+        let output = execute(cli).expect("lenient run should succeed");
+        assert!(output.contains("Title: CUSTOMLOGO"));
+        assert!(output.contains("Header Warning: Nintendo logo does not match"));
+
+        fs::remove_file(rom_path).expect("temp ROM should be removable");
+    }
+
+    #[test]
+    fn executes_suite_and_reports_failure_summary() {
+        let root = temp_dir("suite-root");
+        fs::create_dir_all(&root).expect("suite root dir should exist");
+
+        let serial_rom = root.join("serial-pass.gb");
+        let mooneye_rom = root.join("mooneye-pass.gb");
+        write_rom_file(&serial_rom, "SERIAL", &serial_emit_program(b"Passed"));
+        write_rom_file(&mooneye_rom, "MOONEYE", &mooneye_pass_program());
+
+        let suite_path = root.join("m1-suite.txt");
+        let suite = "\
+serial-case|serial-pass.gb|256|serial:Passed
+mooneye-case|mooneye-pass.gb|64|mooneye-pass
+failing-case|serial-pass.gb|256|serial:FAIL
+";
+        fs::write(&suite_path, suite).expect("suite file should be written");
+
+        let err = execute_suite(&suite_path, None, DEFAULT_MAX_STEPS, None, false)
             .expect_err("suite should fail due to one failing case");
         assert!(err.contains("Summary: total=3 passed=2 failed=1"));
         assert!(err.contains("PASS | serial-case"));
         assert!(err.contains("PASS | mooneye-case"));
         assert!(err.contains("FAIL | failing-case"));
+        assert!(err.contains("\n  Mapper: ROM rom_bank=0x001"));
+
+        let suite_arg = suite_path.to_str().expect("path should be utf8");
+        let cli = parse_cli(["vibegb-runner", "--suite", suite_arg, "--suite-case", "1"])
+            .expect("cli parse should succeed");
+        let line = execute(cli).expect("mooneye case should pass on its own");
+        assert!(line.starts_with("PASS | mooneye-case | steps=64"));
+        let cli = parse_cli(["vibegb-runner", "--suite", suite_arg, "--suite-case", "3"])
+            .expect("cli parse should succeed");
+        let err = execute(cli).expect_err("there are only three cases");
+        assert!(err.contains("suite case 3 is out of range"));
 
         fs::remove_file(&suite_path).expect("suite should be removable");
         fs::remove_file(&serial_rom).expect("serial rom should be removable");
@@ -585,6 +2048,29 @@ failing-case|serial-pass.gb|256|serial:FAIL
         fs::remove_dir_all(&root).expect("suite root should be removable");
     }
 
+    #[test]
+    fn isolate_requires_a_suite() {
+        assert!(parse_cli(["vibegb-runner", "--rom", "x.gb", "--isolate"]).is_err());
+    }
+
+    #[test]
+    fn acid2_case_fails_fast_on_missing_rendering_features() {
+        let root = temp_dir("acid2-suite");
+        fs::create_dir_all(&root).expect("suite root dir should exist");
+        let rom_path = root.join("cgb-acid2.gbc");
+        write_rom_file(&rom_path, "CGB-ACID2", &solid_frame_program());
+        let suite_path = root.join("acid2.txt");
+        fs::write(&suite_path, "cgb-acid2|cgb-acid2.gbc||acid2:cgb\n")
+            .expect("suite file should be written");
+
+        let err = execute_suite(&suite_path, None, DEFAULT_MAX_STEPS, None, false)
+            .expect_err("CGB rendering is not implemented yet");
+        assert!(err.contains("FAIL | cgb-acid2 | cgb-acid2: cgb-acid2 needs rendering features"));
+        assert!(err.contains("cgb-ppu"));
+
+        fs::remove_dir_all(&root).expect("suite root should be removable");
+    }
+
     #[test]
     fn suite_uses_rom_root_for_relative_paths() {
         let suite_root = temp_dir("suite-root");
@@ -602,7 +2088,7 @@ failing-case|serial-pass.gb|256|serial:FAIL
         )
         .expect("suite file should be written");
 
-        let output = execute_suite(&suite_path, Some(&rom_root), DEFAULT_MAX_STEPS)
+        let output = execute_suite(&suite_path, Some(&rom_root), DEFAULT_MAX_STEPS, None, false)
             .expect("suite should pass with explicit rom root");
         assert!(output.contains("Summary: total=1 passed=1 failed=0"));
 
@@ -612,6 +2098,47 @@ failing-case|serial-pass.gb|256|serial:FAIL
         fs::remove_dir_all(&rom_root).expect("rom root should be removable");
     }
 
+    #[test]
+    fn passing_suite_suggests_budgets_from_completion_points() {
+        let root = temp_dir("budget-suite");
+        fs::create_dir_all(&root).expect("suite root dir should exist");
+        write_rom_file(
+            &root.join("serial.gb"),
+            "SERIAL",
+            &serial_emit_program(b"Passed"),
+        );
+        write_rom_file(&root.join("mooneye.gb"), "MOONEYE", &mooneye_pass_program());
+        let suite_path = root.join("budgets.txt");
+        fs::write(
+            &suite_path,
+            "serial|serial.gb|50000|serial:Passed\nmooneye|mooneye.gb|50000|mooneye-pass\nsmoke|mooneye.gb|100\n",
+        )
+        .expect("suite file should be written");
+
+        let output = execute_suite(&suite_path, None, DEFAULT_MAX_STEPS, None, true)
+            .expect("suite should pass");
+        assert!(output.contains("Suggested Budgets (completion + 25%):"));
+        assert!(output.contains("BUDGET | serial | max_steps=50000 -> 1000 | completed at step 25"));
+        assert!(output.contains(
+            "BUDGET | mooneye | max_steps=50000 -> 1000 | completed at step 7 (frame 0)"
+        ));
+        assert!(output.contains("BUDGET | smoke | max_steps=100 | no expectation to observe"));
+
+        let quiet = execute_suite(&suite_path, None, DEFAULT_MAX_STEPS, None, false)
+            .expect("suite should pass");
+        assert!(!quiet.contains("BUDGET"));
+        assert!(parse_cli([
+            "vibegb-runner",
+            "--suite",
+            "s.txt",
+            "--isolate",
+            "--suggest-budgets"
+        ])
+        .is_err());
+
+        fs::remove_dir_all(&root).expect("suite root should be removable");
+    }
+
     fn serial_emit_program(text: &[u8]) -> Vec<u8> {
         let mut program = Vec::with_capacity((text.len() * 10) + 2);
         for byte in text {
@@ -624,6 +2151,16 @@ failing-case|serial-pass.gb|256|serial:FAIL
         program
     }
 
+    fn solid_frame_program() -> Vec<u8> {
+        vec![
+            0x3E, 0xFF, // LD A,FF
+            0xE0, 0x47, // LDH (BGP),A
+            0x3E, 0x91, // LD A,91
+            0xE0, 0x40, // LDH (LCDC),A
+            0x18, 0xFE, // JR -2
+        ]
+    }
+
     fn mooneye_pass_program() -> Vec<u8> {
         vec![
             0x06, 0x03, // LD B,03
@@ -636,6 +2173,34 @@ failing-case|serial-pass.gb|256|serial:FAIL
         ]
     }
 
+    // Marks the test running in cartridge RAM, leaves "ok" and a passing status there, then
+    // prints "done" with ld d,d.
+    fn homebrew_test_program() -> Vec<u8> {
+        let mut program = vec![0x21, 0x00, 0xA0]; // LD HL,A000
+        for byte in [0x80, 0xDE, 0xB0, 0x61, b'o', b'k'] {
+            program.extend([0x3E, byte, 0x22]); // LD A,byte ; LD (HL+),A
+        }
+        program.extend([
+            0xAF, // XOR A
+            0x22, // LD (HL+),A
+            0x21, 0x00, 0xA0, // LD HL,A000
+            0x77, // LD (HL),A
+            0x52, 0x18, 0x08, 0x64, 0x64, 0x00, 0x00, // LD D,D ; JR +8 ; magic
+        ]);
+        program.extend(b"done");
+        program.extend([0x18, 0xFE]); // JR -2
+        program
+    }
+
+    // Clap's message for a refused command line, without the `error: ` prefix or usage.
+    fn rejection(args: &[&str]) -> String {
+        let err = parse_cli(["vibegb-runner"].iter().chain(args))
+            .expect_err("the command line should be refused");
+        let rendered = err.render().to_string();
+        let message = rendered.trim_start_matches("error: ");
+        message.split("\n\n").next().unwrap_or(message).to_string()
+    }
+
     fn write_rom_with_program(title: &str, program: &[u8]) -> PathBuf {
         let path = std::env::temp_dir().join(format!(
             "vibegb-runner-test-{}-{}.gb",
@@ -649,7 +2214,7 @@ failing-case|serial-pass.gb|256|serial:FAIL
     fn write_rom_file(path: &Path, title: &str, program: &[u8]) {
         let mut rom = vec![0; 0x8000];
         rom[0x100..0x104].copy_from_slice(&[0xC3, 0x50, 0x01, 0x00]); // JP 0x0150
-        rom[0x104..0x134].copy_from_slice(&NINTENDO_LOGO);
+        rom[0x104..0x134].copy_from_slice(&TEST_LOGO);
 
         let title_bytes = title.as_bytes();
         let title_len = title_bytes.len().min(16);
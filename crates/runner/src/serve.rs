@@ -0,0 +1,554 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+use vibegb_core::{GameBoy, HeaderValidation, MapperRegisters, Rom};
+use vibegb_runner_lib::input_script::parse_button;
+use vibegb_runner_lib::json::json_string;
+use vibegb_runner_lib::png::frame_png;
+use vibegb_runner_lib::vram_export::{map_csv, map_json, tiles_2bpp, tiles_png};
+
+const MAX_BODY_BYTES: usize = 8 * 1024 * 1024;
+const MAX_LINE_BYTES: usize = 8 * 1024;
+const MAX_HEADER_LINES: usize = 100;
+// Requests are served one at a time, so a client that stalls must not hold the API.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_STEP_FRAMES: u64 = 3_600;
+
+/// Serves the HTTP control API on `listen` until the process is killed. Requests are
+/// handled one at a time on a single emulator instance:
+///
+/// - `GET /status`, including the mapper registers
+/// - `POST /rom` with the ROM image as the body
+/// - `POST /step?frames=N` (default 1)
+/// - `GET /frame.png`
+/// - `GET /tiles.2bpp` and `GET /tiles.png` (the 384 VRAM tiles)
+/// - `GET /map.csv?index=0` and `GET /map.json?index=0` (tile map 0 or 1; JSON is Tiled's
+///   format over `tiles.png`)
+/// - `GET /memory?address=C000&length=16` (hex address, CPU view of the bus)
+/// - `POST /button?name=start&pressed=true`
+/// - `GET /metrics` (Prometheus text format; counters restart when a ROM is loaded)
+pub fn serve(
+    listen: &str,
+    rom: Option<Rom>,
+    validation: HeaderValidation,
+) -> Result<String, String> {
+    let listener = TcpListener::bind(listen)
+        .map_err(|err| format!("failed to listen on '{listen}': {err}"))?;
+    let address = listener
+        .local_addr()
+        .map_err(|err| format!("failed to read listen address: {err}"))?;
+    let mut state = ServeState::new(validation);
+    if let Some(rom) = rom {
+        state.load(rom);
+    }
+    eprintln!("Serving VibeGB control API on http://{address}");
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(err) = handle_connection(&mut state, &stream) {
+                    eprintln!("request failed: {err}");
+                }
+            }
+            Err(err) => eprintln!("connection failed: {err}"),
+        }
+    }
+    Ok(String::new())
+}
+
+fn handle_connection(state: &mut ServeState, stream: &TcpStream) -> Result<(), String> {
+    stream
+        .set_read_timeout(Some(READ_TIMEOUT))
+        .map_err(|err| format!("failed to set read timeout: {err}"))?;
+    let mut reader = BufReader::new(stream);
+    let response = match read_request(&mut reader) {
+        Ok(request) => state.handle(&request),
+        Err(err) => Response::error(400, &err),
+    };
+    let mut writer = stream;
+    write_response(&mut writer, &response).map_err(|err| format!("failed to respond: {err}"))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn json(body: String) -> Self {
+        Self {
+            status: 200,
+            content_type: "application/json",
+            body: body.into_bytes(),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: format!("{{\"error\":{}}}", json_string(message)).into_bytes(),
+        }
+    }
+}
+
+/// Reads one line of at most `MAX_LINE_BYTES`, so a client cannot grow it without limit.
+fn read_line(reader: &mut impl BufRead, what: &str) -> Result<String, String> {
+    let mut line = String::new();
+    reader
+        .take(MAX_LINE_BYTES as u64 + 1)
+        .read_line(&mut line)
+        .map_err(|err| format!("failed to read {what}: {err}"))?;
+    if line.len() > MAX_LINE_BYTES {
+        return Err(format!("{what} exceeds the {MAX_LINE_BYTES} byte limit"));
+    }
+    Ok(line)
+}
+
+fn read_request(reader: &mut impl BufRead) -> Result<Request, String> {
+    let request_line = read_line(reader, "request line")?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(format!("malformed request line '{}'", request_line.trim()));
+    };
+
+    let mut content_length = 0usize;
+    for header in 0.. {
+        if header == MAX_HEADER_LINES {
+            return Err(format!("more than {MAX_HEADER_LINES} header lines"));
+        }
+        let line = read_line(reader, "header line")?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid Content-Length '{}'", value.trim()))?;
+            }
+        }
+    }
+    if content_length > MAX_BODY_BYTES {
+        return Err(format!(
+            "body of {content_length} bytes exceeds the {MAX_BODY_BYTES} byte limit"
+        ));
+    }
+    let mut body = vec![0; content_length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|err| format!("failed to read body: {err}"))?;
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (key.to_string(), value.to_string())
+        })
+        .collect();
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query,
+        body,
+    })
+}
+
+fn write_response(writer: &mut impl Write, response: &Response) -> std::io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    };
+    write!(
+        writer,
+        "HTTP/1.1 {} {reason}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    )?;
+    writer.write_all(&response.body)?;
+    writer.flush()
+}
+
+struct ServeState {
+    gb: Option<GameBoy>,
+    title: String,
+    validation: HeaderValidation,
+}
+
+type ApiResult = Result<Response, (u16, String)>;
+
+impl ServeState {
+    fn new(validation: HeaderValidation) -> Self {
+        Self {
+            gb: None,
+            title: String::new(),
+            validation,
+        }
+    }
+
+    fn load(&mut self, rom: Rom) {
+        let mut gb = GameBoy::new();
+        gb.load_rom(&rom.data);
+        self.gb = Some(gb);
+        self.title = rom.header.title;
+    }
+
+    fn handle(&mut self, request: &Request) -> Response {
+        let result = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/status") => Ok(Response::json(self.status_json())),
+            ("POST", "/rom") => self.load_rom(request),
+            ("POST", "/step") => self.step(request),
+            ("GET", "/frame.png") => self.frame(),
+            ("GET", "/tiles.2bpp") => self.tiles(false),
+            ("GET", "/tiles.png") => self.tiles(true),
+            ("GET", "/map.csv") => self.map(request, false),
+            ("GET", "/map.json") => self.map(request, true),
+            ("GET", "/memory") => self.memory(request),
+            ("POST", "/button") => self.button(request),
+            ("GET", "/metrics") => self.metrics(),
+            (method, path) => Err((404, format!("no route for {method} {path}"))),
+        };
+        result.unwrap_or_else(|(status, message)| Response::error(status, &message))
+    }
+
+    fn gb(&mut self) -> Result<&mut GameBoy, (u16, String)> {
+        self.gb
+            .as_mut()
+            .ok_or_else(|| (409, "no ROM loaded; POST one to /rom".to_string()))
+    }
+
+    fn status_json(&self) -> String {
+        match &self.gb {
+            Some(gb) => format!(
+                "{{\"loaded\":true,\"title\":{},\"frames\":{},\"cycles\":{},\"pc\":{},\"mapper\":{}}}",
+                json_string(&self.title),
+                gb.bus.ppu().vblank_count(),
+                gb.cycle_count(),
+                gb.cpu.pc,
+                mapper_json(gb.bus.cartridge().map(|cartridge| cartridge.registers()))
+            ),
+            None => "{\"loaded\":false}".to_string(),
+        }
+    }
+
+    fn load_rom(&mut self, request: &Request) -> ApiResult {
+        let rom = Rom::from_bytes_with(request.body.clone(), self.validation)
+            .map_err(|err| (400, format!("ROM load failed: {err}")))?;
+        self.load(rom);
+        Ok(Response::json(self.status_json()))
+    }
+
+    fn step(&mut self, request: &Request) -> ApiResult {
+        let frames = match request.param("frames") {
+            Some(raw) => raw
+                .parse::<u64>()
+                .ok()
+                .filter(|frames| (1..=MAX_STEP_FRAMES).contains(frames))
+                .ok_or_else(|| {
+                    (
+                        400,
+                        format!("frames must be between 1 and {MAX_STEP_FRAMES}, got '{raw}'"),
+                    )
+                })?,
+            None => 1,
+        };
+        let gb = self.gb()?;
+        for _ in 0..frames {
+            gb.run_frame()
+                .map_err(|err| (500, format!("emulation failed: {err}")))?;
+        }
+        Ok(Response::json(self.status_json()))
+    }
+
+    fn frame(&mut self) -> ApiResult {
+        let frame = self
+            .gb()?
+            .screenshot()
+            .map_err(|err| (500, format!("emulation failed: {err}")))?;
+        Ok(Response {
+            status: 200,
+            content_type: "image/png",
+            body: frame_png(&frame),
+        })
+    }
+
+    fn tiles(&mut self, png: bool) -> ApiResult {
+        let tiles = self.gb()?.decode_tiles();
+        Ok(if png {
+            Response {
+                status: 200,
+                content_type: "image/png",
+                body: tiles_png(&tiles),
+            }
+        } else {
+            Response {
+                status: 200,
+                content_type: "application/octet-stream",
+                body: tiles_2bpp(&tiles),
+            }
+        })
+    }
+
+    fn map(&mut self, request: &Request, json: bool) -> ApiResult {
+        let raw = request.param("index").unwrap_or("0");
+        let index = raw
+            .parse::<usize>()
+            .ok()
+            .filter(|index| *index < 2)
+            .ok_or_else(|| (400, format!("index must be 0 or 1, got '{raw}'")))?;
+        let map = self
+            .gb()?
+            .background_map(index)
+            .expect("maps 0 and 1 exist");
+        Ok(if json {
+            Response::json(map_json(&map, &format!("map{index}")))
+        } else {
+            Response {
+                status: 200,
+                content_type: "text/csv",
+                body: map_csv(&map).into_bytes(),
+            }
+        })
+    }
+
+    fn metrics(&mut self) -> ApiResult {
+        let text = self.gb()?.counters().prometheus_text();
+        Ok(Response {
+            status: 200,
+            content_type: "text/plain; version=0.0.4",
+            body: text.into_bytes(),
+        })
+    }
+
+    fn memory(&mut self, request: &Request) -> ApiResult {
+        let raw_address = request
+            .param("address")
+            .ok_or_else(|| (400, "missing address parameter".to_string()))?;
+        let address = u16::from_str_radix(raw_address.trim_start_matches("0x"), 16)
+            .map_err(|_| (400, format!("invalid hex address '{raw_address}'")))?;
+        let length = match request.param("length") {
+            Some(raw) => raw
+                .parse::<usize>()
+                .ok()
+                .filter(|length| usize::from(address) + length <= 0x10000)
+                .ok_or_else(|| (400, format!("invalid length '{raw}' at 0x{address:04X}")))?,
+            None => 1,
+        };
+        let gb = self.gb()?;
+        let bytes: Vec<String> = (0..length)
+            .map(|offset| gb.bus.read_byte(address + offset as u16).to_string())
+            .collect();
+        Ok(Response::json(format!(
+            "{{\"address\":{address},\"bytes\":[{}]}}",
+            bytes.join(",")
+        )))
+    }
+
+    fn button(&mut self, request: &Request) -> ApiResult {
+        let raw_name = request
+            .param("name")
+            .ok_or_else(|| (400, "missing name parameter".to_string()))?;
+        let button =
+            parse_button(raw_name).ok_or_else(|| (400, format!("unknown button '{raw_name}'")))?;
+        let pressed = match request.param("pressed").unwrap_or("true") {
+            "true" | "1" => true,
+            "false" | "0" => false,
+            other => return Err((400, format!("pressed must be true or false, got '{other}'"))),
+        };
+        self.gb()?.set_button(button, pressed);
+        Ok(Response::json(format!(
+            "{{\"button\":{},\"pressed\":{pressed}}}",
+            json_string(raw_name)
+        )))
+    }
+}
+
+fn mapper_json(mapper: Option<MapperRegisters>) -> String {
+    let Some(mapper) = mapper else {
+        return "null".to_string();
+    };
+    format!(
+        "{{\"type\":{},\"rom_bank\":{},\"ram_bank\":{},\"ram_enabled\":{},\"mode\":{}}}",
+        json_string(mapper.mapper),
+        mapper.rom_bank,
+        mapper.ram_bank,
+        mapper.ram_enabled,
+        mapper
+            .mode
+            .map_or_else(|| "null".to_string(), |mode| mode.to_string())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Read};
+    use std::thread;
+
+    fn request(method: &str, target: &str, body: &[u8]) -> Request {
+        let mut raw = format!(
+            "{method} {target} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        raw.extend_from_slice(body);
+        read_request(&mut Cursor::new(raw)).expect("request should parse")
+    }
+
+    fn test_rom() -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]); // JR -2
+        rom[0x134..0x138].copy_from_slice(b"HTTP");
+        rom
+    }
+
+    fn body(response: &Response) -> &str {
+        std::str::from_utf8(&response.body).expect("utf8 body")
+    }
+
+    #[test]
+    fn parses_request_line_query_and_body() {
+        let parsed = request("POST", "/button?name=a&pressed=false", b"xyz");
+        assert_eq!(parsed.method, "POST");
+        assert_eq!(parsed.path, "/button");
+        assert_eq!(parsed.param("name"), Some("a"));
+        assert_eq!(parsed.param("pressed"), Some("false"));
+        assert_eq!(parsed.body, b"xyz");
+        assert!(read_request(&mut Cursor::new(b"\r\n".to_vec())).is_err());
+    }
+
+    #[test]
+    fn caps_request_and_header_lines() {
+        let long_target = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE_BYTES));
+        assert_eq!(
+            read_request(&mut Cursor::new(long_target.into_bytes())),
+            Err(format!(
+                "request line exceeds the {MAX_LINE_BYTES} byte limit"
+            ))
+        );
+
+        let mut endless_header = b"GET /status HTTP/1.1\r\nX-Pad: ".to_vec();
+        endless_header.resize(endless_header.len() + 4 * MAX_LINE_BYTES, b'a');
+        assert_eq!(
+            read_request(&mut Cursor::new(endless_header)),
+            Err(format!(
+                "header line exceeds the {MAX_LINE_BYTES} byte limit"
+            ))
+        );
+
+        let many_headers = format!(
+            "GET /status HTTP/1.1\r\n{}\r\n",
+            "X-Pad: a\r\n".repeat(MAX_HEADER_LINES)
+        );
+        assert_eq!(
+            read_request(&mut Cursor::new(many_headers.into_bytes())),
+            Err(format!("more than {MAX_HEADER_LINES} header lines"))
+        );
+    }
+
+    #[test]
+    fn drives_an_emulator_through_the_api() {
+        let mut state = ServeState::new(HeaderValidation::Lenient);
+        let response = state.handle(&request("POST", "/step", b""));
+        assert_eq!(response.status, 409);
+
+        let response = state.handle(&request("POST", "/rom", &test_rom()));
+        assert_eq!(response.status, 200, "{}", body(&response));
+        assert!(body(&response).contains("\"title\":\"HTTP\""));
+
+        let response = state.handle(&request("POST", "/step?frames=2", b""));
+        assert_eq!(response.status, 200);
+        assert!(body(&response).contains("\"pc\":256"));
+        assert!(body(&response).ends_with(
+            "\"mapper\":{\"type\":\"ROM\",\"rom_bank\":1,\"ram_bank\":0,\"ram_enabled\":false,\"mode\":null}}"
+        ));
+        assert_eq!(
+            state.handle(&request("POST", "/step?frames=0", b"")).status,
+            400
+        );
+
+        let response = state.handle(&request("GET", "/memory?address=0100&length=2", b""));
+        assert_eq!(body(&response), "{\"address\":256,\"bytes\":[24,254]}");
+
+        let response = state.handle(&request("POST", "/button?name=Start", b""));
+        assert_eq!(body(&response), "{\"button\":\"Start\",\"pressed\":true}");
+        assert_eq!(
+            state
+                .handle(&request("POST", "/button?name=turbo", b""))
+                .status,
+            400
+        );
+
+        let response = state.handle(&request("GET", "/frame.png", b""));
+        assert_eq!(response.content_type, "image/png");
+        assert_eq!(&response.body[1..4], b"PNG");
+
+        let response = state.handle(&request("GET", "/tiles.2bpp", b""));
+        assert_eq!(response.body.len(), 384 * 16);
+        let response = state.handle(&request("GET", "/map.csv?index=1", b""));
+        assert_eq!(response.content_type, "text/csv");
+        assert_eq!(
+            state
+                .handle(&request("GET", "/map.json?index=2", b""))
+                .status,
+            400
+        );
+
+        let response = state.handle(&request("GET", "/metrics", b""));
+        assert_eq!(response.content_type, "text/plain; version=0.0.4");
+        assert!(body(&response).contains("# TYPE vibegb_frames_total counter\n"));
+        assert!(!body(&response).contains("vibegb_instructions_total 0\n"));
+        assert!(body(&response).contains("vibegb_interrupts_total 0\n"));
+
+        assert_eq!(state.handle(&request("GET", "/nope", b"")).status, 404);
+    }
+
+    #[test]
+    fn serves_over_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("loopback should bind");
+        let address = listener.local_addr().expect("bound address");
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("client should connect");
+            let mut state = ServeState::new(HeaderValidation::Lenient);
+            handle_connection(&mut state, &stream).expect("request should be served");
+        });
+
+        let mut client = TcpStream::connect(address).expect("client should connect");
+        client
+            .write_all(b"GET /status HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .expect("request should send");
+        let mut reply = String::new();
+        client
+            .read_to_string(&mut reply)
+            .expect("response should arrive");
+        server.join().expect("server thread");
+        assert!(reply.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(reply.ends_with("{\"loaded\":false}"));
+    }
+}